@@ -0,0 +1,7 @@
+; Non-tail recursion and integer arithmetic.
+(define (fib n)
+  (if (< n 2)
+      n
+      (+ (fib (- n 1)) (fib (- n 2)))))
+
+(define (bench) (fib 20))
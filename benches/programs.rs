@@ -0,0 +1,47 @@
+// The programs in this directory, both tree-walked and compiled to the vm.
+// Each program is loaded once and its result checked, then criterion measures evaluating (bench).
+
+#[macro_use] extern crate criterion;
+extern crate flip;
+
+use criterion::Criterion;
+use flip::interpreter::Interpreter;
+use flip::grammar;
+use flip::value::Value;
+
+fn parse(interpreter: &mut Interpreter, name: &str, src: &str) -> Vec<Value> {
+    match grammar::parse_with_macros(src, &mut interpreter.interner, &mut interpreter.macros) {
+        Ok(parsed) => parsed,
+        Err(err) => panic!("{}: {:?}", name, err),
+    }
+}
+
+fn bench_program(c: &mut Criterion, name: &str, program: &str, expected: &str) {
+    for &use_vm in &[false, true] {
+        let mut interpreter = Interpreter::new();
+        interpreter.use_vm = use_vm;
+        for x in parse(&mut interpreter, name, program) {
+            if let Err(condition) = interpreter.evaluate(&x) {
+                panic!("{}: {}", name, condition.to_string(&interpreter.interner));
+            }
+        }
+
+        let call = parse(&mut interpreter, name, "(bench)").remove(0);
+        let result = interpreter.evaluate(&call).map(|x| x.to_string(&interpreter.interner));
+        assert_eq!(result.ok(), Some(expected.to_string()), "{} returned something else", name);
+
+        let id = if use_vm { format!("{} vm", name) } else { name.to_string() };
+        c.bench_function(&id, move |b| b.iter(|| interpreter.evaluate(&call).unwrap()));
+    }
+}
+
+fn programs(c: &mut Criterion) {
+    bench_program(c, "fib", include_str!("fib.lisp"), "6765");
+    bench_program(c, "ackermann", include_str!("ackermann.lisp"), "123");
+    bench_program(c, "tak", include_str!("tak.lisp"), "7");
+    bench_program(c, "sort", include_str!("sort.lisp"), "0");
+    bench_program(c, "strings", include_str!("strings.lisp"), "3893");
+}
+
+criterion_group!(benches, programs);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+; Allocating and walking lists: a merge sort of pseudo random numbers.
+(define (random-list n seed acc)
+  (if (= n 0)
+      acc
+      (let ((next (remainder (+ (* seed 1103515245) 12345) 2147483648)))
+        (random-list (- n 1) next (cons (remainder next 1000) acc)))))
+
+(define (split list left right)
+  (if (null? list)
+      (cons left right)
+      (split (cdr list) right (cons (car list) left))))
+
+(define (merge a b acc)
+  (cond ((null? a) (append (reverse acc) b))
+        ((null? b) (append (reverse acc) a))
+        ((< (car b) (car a)) (merge a (cdr b) (cons (car b) acc)))
+        (else (merge (cdr a) b (cons (car a) acc)))))
+
+(define (sort list)
+  (if (or (null? list) (null? (cdr list)))
+      list
+      (let ((halves (split list '() '())))
+        (merge (sort (car halves)) (sort (cdr halves)) '()))))
+
+(define numbers (random-list 1000 42 '()))
+
+(define (bench) (car (sort numbers)))
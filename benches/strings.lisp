@@ -0,0 +1,7 @@
+; Building strings piece by piece.
+(define (build n acc)
+  (if (= n 0)
+      acc
+      (build (- n 1) (string-append acc (number->string n) " "))))
+
+(define (bench) (string-length (build 1000 "")))
@@ -0,0 +1,9 @@
+; Lots of calls with little work in between.
+(define (tak x y z)
+  (if (not (< y x))
+      z
+      (tak (tak (- x 1) y z)
+           (tak (- y 1) z x)
+           (tak (- z 1) x y))))
+
+(define (bench) (tak 18 12 6))
@@ -1,6 +1,4 @@
 use std::path::Path;
-use std::fs::File;
-use std::process::exit;
 
 fn file_is_present(val: String) -> Result<(), String> {
     let path = Path::new(&val);
@@ -11,18 +9,49 @@ fn file_is_present(val: String) -> Result<(), String> {
     }
 }
 
-pub fn get_args() -> Option<File> {
+fn dir_is_present(val: String) -> Result<(), String> {
+    if Path::new(&val).is_dir() {
+        Result::Ok(())
+    } else {
+        Result::Err("Path is no directory".into())
+    }
+}
+
+pub struct Options {
+    // the script to run followed by the arguments passed to it, None starts the repl
+    pub script: Option<Vec<String>>,
+    // see Interpreter::use_vm
+    pub use_vm: bool,
+    // the file the lcov coverage of the script is written to
+    pub coverage: Option<String>,
+    // the directory whose tests are run instead of a script
+    pub test: Option<String>,
+}
+
+pub fn get_args() -> Options {
     let matches = clap_app!(myapp =>
         (version: "0.1")
         (author: "Florian Lackner <lacknerflo@gmail.com>")
         (about: "Interprets Scheme code")
+        (@setting TrailingVarArg)
+        (@arg VM: --vm "Runs procedures with the bytecode vm")
+        (@arg COVERAGE: --coverage +takes_value "Writes the line coverage of the script to this file in the lcov format")
+        (@arg TEST: --test +takes_value {dir_is_present} "Loads the .lisp files in this directory and runs their tests")
         (@arg INPUT: {file_is_present} "File to interpret")
+        // they can look like options, e.g. for scripts that start with #!/usr/bin/env flip
+        (@arg ARGS: ... +allow_hyphen_values "Arguments for the script, see (command-line)")
     ).get_matches();
 
-    matches.value_of("INPUT")
-    .map(File::open)
-    .map(|x| x.unwrap_or_else(|err| {
-        println!("Error opening file: {}", err);
-        exit(-1);
-    }))
+    let script = matches.value_of("INPUT").map(|input| {
+        let mut args = vec![input.to_string()];
+        args.extend(matches.values_of("ARGS").into_iter().flat_map(|x| x).map(Into::into));
+        args
+    });
+
+    Options {
+        script: script,
+        use_vm: matches.is_present("VM"),
+        coverage: matches.value_of("COVERAGE").map(Into::into),
+        test: matches.value_of("TEST").map(Into::into),
+    }
 }
@@ -0,0 +1,263 @@
+// A cycle collector for the reference counted values and scopes.
+//
+// Arc frees everything but cycles, like a procedure bound in the scope it captures
+// or a list whose cdr was set to itself. Cycles can only be created by capturing a scope
+// or by changing a mutable value, so these are registered as candidates. collect looks at
+// everything reachable from the candidates: objects with more strong references than
+// are found in there are referenced from outside, e.g. by the interpreter, and they keep
+// alive what they reach. The rest is garbage, their references are cleared so Arc frees them.
+//
+// Every interpreter has its own Heap with the candidates and the bytes it allocated. A value remembers
+// the heap it was allocated in, so it's counted and registered there no matter which thread changes
+// or drops it. Only allocating has to know which interpreter is running, Heap::enter tells the thread.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use ::value::{Value, WeakValue};
+use ::scope::{Scope, WeakScope};
+
+// collect runs once there are this many candidates, or twice as many as survived the last collection
+const MIN_THRESHOLD: usize = 10000;
+
+// An object that can hold strong references to others.
+pub enum Object {
+    Value(Value),
+    Scope(Scope),
+}
+
+impl Object {
+    fn address(&self) -> usize {
+        match self {
+            &Object::Value(ref x) => x.id(),
+            &Object::Scope(ref x) => x.address(),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            &Object::Value(ref x) => x.strong_count(),
+            &Object::Scope(ref x) => x.strong_count(),
+        }
+    }
+
+    // None if they can't be found out right now, because the object is borrowed
+    fn references(&self) -> Option<Vec<Object>> {
+        match self {
+            &Object::Value(ref x) => x.references(),
+            &Object::Scope(ref x) => x.references().map(|(values, parent)| {
+                let mut references: Vec<Object> = values.into_iter().map(Object::Value).collect();
+                references.extend(parent.map(Object::Scope));
+                references
+            }),
+        }
+    }
+
+    fn clear(&self) {
+        match self {
+            &Object::Value(ref x) => x.clear_references(),
+            &Object::Scope(ref x) => x.clear(),
+        }
+    }
+}
+
+enum Candidate {
+    Value(WeakValue),
+    Scope(WeakScope),
+}
+
+impl Candidate {
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            &Candidate::Value(ref x) => x.upgrade().map(Object::Value),
+            &Candidate::Scope(ref x) => x.upgrade().map(Object::Scope),
+        }
+    }
+}
+
+// The candidates and the bytes of one interpreter, see Interpreter::heap. Clones are handles to the same heap.
+#[derive(Clone)]
+pub struct Heap {
+    data: Arc<HeapData>,
+}
+
+// shows the bytes only, the candidates may be part of cycles
+impl fmt::Debug for Heap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Heap {{ allocated: {} }}", self.allocated())
+    }
+}
+
+struct HeapData {
+    candidates: Mutex<Candidates>,
+    // the estimated bytes of the values alive, see Interpreter::set_memory_limit
+    allocated: AtomicUsize,
+}
+
+struct Candidates {
+    list: Vec<Candidate>,
+    threshold: usize,
+}
+
+thread_local! {
+    // the heap of the interpreter running on this thread
+    static RUNNING: RefCell<Option<Heap>> = RefCell::new(None);
+}
+
+// the heap new values are allocated in, None if no interpreter is running on this thread
+pub fn running() -> Option<Heap> {
+    // the thread local is gone when values are dropped while the thread exits
+    RUNNING.try_with(|running| running.borrow().clone()).unwrap_or(None)
+}
+
+// Makes the heap that ran before Heap::enter the running one again when it's dropped.
+pub struct Running {
+    // None if the heap was running already
+    previous: Option<Option<Heap>>,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            let _ = RUNNING.try_with(|running| *running.borrow_mut() = previous);
+        }
+    }
+}
+
+// for values that were changed, e.g. by set-cdr!, in the heap they were allocated in
+pub fn register_value(value: &Value) {
+    // values that aren't on the heap hold no references
+    if let (Some(weak), Some(heap)) = (value.downgrade(), value.heap()) {
+        heap.register(Candidate::Value(weak));
+    }
+}
+
+struct Entry {
+    object: Object,
+    // the references from other objects that were found
+    internal: usize,
+    references: Option<Vec<usize>>,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        let candidates = Candidates { list: vec![], threshold: MIN_THRESHOLD };
+        Heap { data: Arc::new(HeapData { candidates: Mutex::new(candidates), allocated: AtomicUsize::new(0) }) }
+    }
+
+    // Values allocated on this thread belong to this heap until the returned guard is dropped.
+    // An interpreter enters its heap whenever it starts running, so one that's called from another one enters its own.
+    pub fn enter(&self) -> Running {
+        RUNNING.with(|running| {
+            let mut running = running.borrow_mut();
+            if running.as_ref().map_or(false, |heap| Arc::ptr_eq(&heap.data, &self.data)) {
+                return Running { previous: None };
+            }
+            Running { previous: Some(mem::replace(&mut *running, Some(self.clone()))) }
+        })
+    }
+
+    pub fn allocated(&self) -> usize {
+        self.data.allocated.load(Ordering::Relaxed)
+    }
+
+    pub fn track_allocation(&self, bytes: usize) {
+        self.data.allocated.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    // the sizes are estimated when values are dropped, e.g. a vector can have grown since, so this stops at 0
+    pub fn track_free(&self, bytes: usize) {
+        let mut allocated = self.allocated();
+        loop {
+            match self.data.allocated.compare_exchange_weak(allocated, allocated.saturating_sub(bytes), Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => allocated = current,
+            }
+        }
+    }
+
+    // for scopes that were captured by a procedure or an environment
+    pub fn register_scope(&self, scope: &Scope) {
+        self.register(Candidate::Scope(scope.downgrade()));
+    }
+
+    fn register(&self, candidate: Candidate) {
+        let collect_now = {
+            let mut candidates = self.data.candidates.lock().unwrap();
+            candidates.list.push(candidate);
+            candidates.list.len() >= candidates.threshold
+        };
+        if collect_now { self.collect(); }
+    }
+
+    // frees the cycles that aren't referenced from outside and returns the number of objects freed with them
+    pub fn collect(&self) -> usize {
+        let candidates = self.data.candidates.lock().unwrap().list.split_off(0);
+
+        // every object reachable from the candidates, by address
+        let mut entries: HashMap<usize, Entry> = HashMap::new();
+        let mut unvisited = vec![];
+        for object in candidates.iter().filter_map(Candidate::upgrade) {
+            let address = object.address();
+            if !entries.contains_key(&address) {
+                entries.insert(address, Entry { object: object, internal: 0, references: None });
+                unvisited.push(address);
+            }
+        }
+        let candidate_addresses = unvisited.clone();
+        drop(candidates);
+
+        while let Some(address) = unvisited.pop() {
+            let references = entries[&address].object.references();
+            let references = references.map(|references| references.into_iter().map(|object| {
+                let address = object.address();
+                let entry = entries.entry(address).or_insert_with(|| {
+                    unvisited.push(address);
+                    Entry { object: object, internal: 0, references: None }
+                });
+                entry.internal += 1;
+                address
+            }).collect());
+            // safe because the entry was inserted before it was unvisited
+            entries.get_mut(&address).unwrap().references = references;
+        }
+
+        // entries hold a reference themselves, borrowed objects are referenced from outside anyway
+        let mut live: Vec<usize> = entries.iter()
+        .filter(|&(_, entry)| entry.references.is_none() || entry.object.strong_count() - 1 > entry.internal)
+        .map(|(&address, _)| address)
+        .collect();
+
+        let mut reached: HashSet<usize> = live.iter().cloned().collect();
+        while let Some(address) = live.pop() {
+            for &reference in entries[&address].references.iter().flat_map(|x| x) {
+                if reached.insert(reference) { live.push(reference); }
+            }
+        }
+
+        let garbage: Vec<&Entry> = entries.iter().filter(|&(address, _)| !reached.contains(address)).map(|(_, entry)| entry).collect();
+        for entry in &garbage {
+            entry.object.clear();
+        }
+        let freed = garbage.len();
+
+        // the candidates that survived can still become part of a cycle
+        {
+            let mut candidates = self.data.candidates.lock().unwrap();
+            for address in candidate_addresses.into_iter().filter(|address| reached.contains(address)) {
+                let candidate = match entries[&address].object {
+                    // safe because only values on the heap are registered
+                    Object::Value(ref x) => Candidate::Value(x.downgrade().unwrap()),
+                    Object::Scope(ref x) => Candidate::Scope(x.downgrade()),
+                };
+                candidates.list.push(candidate);
+            }
+            let survivors = candidates.list.len();
+            candidates.threshold = MIN_THRESHOLD.max(2 * survivors);
+        }
+        freed
+    }
+}
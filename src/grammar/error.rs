@@ -1,7 +1,7 @@
 #[derive(Debug)]
 pub enum Error {
-    // pos of invalid char
-    NonAsciiChar(usize),
+    // pos of a char that can't be written there, e.g. a control char after #\
+    InvalidChar(usize),
 
     // start..end
     InvalidEscape(usize, usize),
@@ -9,7 +9,11 @@ pub enum Error {
     // start
     UnexpectedEofString(usize),
     UnexpectedEofChar(usize),
+    UnexpectedEofComment(usize),
+    UnexpectedEofSymbol(usize),
 
     // TODO: add more info
     RecurInNonTailPosition,
+    // description of what's wrong
+    InvalidSyntax(String),
 }
@@ -1,83 +1,91 @@
 use ::lalrpop_util::ParseError;
-use super::lexer::Token;
+use super::lexer::{Token, Tokenizer};
 use super::error::Error;
 use std::iter;
 
-pub fn create_error_message(input: &str, err: &ParseError<usize, Token, Error>) -> String {
-    match err {
-        &ParseError::InvalidToken { location } => invalid_token(input, location),
-        &ParseError::UnrecognizedToken {
-            token: Some((left, _token, right)),
-            expected: _,
-        } => unrecognized_token(input, left, right),
-        &ParseError::UnrecognizedToken {
-            token: None,
-            expected: _,
-        } => unexpected_eof(&input),
-        &ParseError::ExtraToken {
-            token: (left, _token, right),
-        } => extra_token(input, left, right),
-        &ParseError::User {
-            ref error,
-        } => user_error(input, error),
-    }
+// An error in the input, with the span that caused it if there's one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub span: Option<(usize, usize)>,
+    pub message: String,
+    pub hint: String,
 }
 
-fn print_line_with_pos(input: &str, start: usize, mut end: usize) -> String {
-    // protect against start == end
-    if start >= end { end = start + 1; }
-
-    let mut line_no = 0;
-    let mut line_start = 0;
-    let mut line_end = input.len();
-    let mut finished = false;
-    for (pos, c) in input.char_indices() {
-        if pos >= start { finished = true; }
-        if c == '\n' {
-            if finished {
-                line_end = pos;
-                break;
-            }
-            line_start = pos+1;
-            line_no += 1;
-        }
+impl Diagnostic {
+    fn new(start: usize, end: usize, message: &str, hint: &str) -> Self {
+        Diagnostic { span: Some((start, end)), message: message.into(), hint: hint.into() }
     }
 
-    let span: String = iter::repeat('^').take(end-start).collect();
+    // the line and column of the start of the span, both start at 1
+    pub fn line_column(&self, input: &str) -> Option<(usize, usize)> {
+        self.span.map(|(start, _)| {
+            let line_start = line_start(input, start);
+            (input[..line_start].matches('\n').count() + 1, input[line_start..start].chars().count() + 1)
+        })
+    }
+}
 
-    format!(
-"{} | {}
-{:ws_pipe$} | {:ws_err$}{} ",
-        line_no,
-        &input[line_start..line_end],
-        "", "", span,
-        ws_pipe = line_no.to_string().len(),
-        ws_err = input[line_start..start].chars().count(),
-    )
+// every error message in input, err is the one the parser stopped at
+pub fn create_error_message(input: &str, err: &ParseError<usize, Token, Error>) -> String {
+    let diagnostics = diagnostics(input, err);
+    let mut messages: Vec<String> = diagnostics.iter().map(|x| print_diagnostic(input, x)).collect();
+    if messages.len() > 1 {
+        messages.push(format!("{} errors", messages.len()));
+    }
+    messages.join("\n")
 }
 
-fn invalid_token(input: &str, start: usize) -> String {
-    concat_strings(&[
-        print_line_with_pos(input, start, start),
-        print_error_msg("invalid token"),
-    ])
+// The parser stops at the first error, so the tokens of the whole input are scanned for more of them:
+// invalid tokens, a ) without a ( and parens that are never closed. err is only added if it isn't one of these.
+pub fn diagnostics(input: &str, err: &ParseError<usize, Token, Error>) -> Vec<Diagnostic> {
+    let mut diagnostics = scan(input);
+    let diagnostic = parse_error(input, err);
+    let found = match err {
+        // the unclosed parens tell where the EOF came from
+        &ParseError::UnrecognizedToken { token: None, .. } => diagnostics.iter().any(|x| x.message.starts_with("unclosed")),
+        _ => diagnostics.iter().any(|x| x.span.is_some() && x.span.map(|x| x.0) == diagnostic.span.map(|x| x.0)),
+    };
+    if !found { diagnostics.push(diagnostic); }
+    diagnostics.sort_by_key(|x| x.span.map_or(input.len(), |(start, _)| start));
+    diagnostics
 }
 
-fn unrecognized_token(input: &str, start: usize, end: usize) -> String {
-    concat_strings(&[
-        print_line_with_pos(input, start, end),
-        print_error_msg("unrecognized token"),
-    ])
+fn scan(input: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    // where the parens that are still open start
+    let mut open = vec![];
+    let mut tokens = Tokenizer::new(input);
+    while let Some(token) = tokens.next() {
+        match token {
+            Ok((start, Token::OpenParen, _)) | Ok((start, Token::VectorParen, _)) => open.push(start),
+            Ok((start, Token::ClosingParen, end)) => if open.pop().is_none() {
+                diagnostics.push(Diagnostic::new(start, end, "unexpected ), there's no ( for it", "remove it or add the missing ( before it"));
+            },
+            Ok(_) => {},
+            Err(ref err) => {
+                diagnostics.push(lexer_error(input, err));
+                tokens.recover();
+            },
+        }
+    }
+    for start in open {
+        let paren = if input[start..].starts_with('#') { "#(" } else { "(" };
+        diagnostics.push(Diagnostic::new(start, start + paren.len(), &format!("unclosed {}", paren), "missing ), it's never closed"));
+    }
+    diagnostics
 }
 
-fn extra_token(input: &str, start: usize, end: usize) -> String {
-    concat_strings(&[
-        print_line_with_pos(input, start, end),
-        print_error_msg("extra token"),
-    ])
+fn parse_error(input: &str, err: &ParseError<usize, Token, Error>) -> Diagnostic {
+    match err {
+        &ParseError::InvalidToken { location } => Diagnostic::new(location, location, "invalid token", ""),
+        &ParseError::UnrecognizedToken { token: Some((left, _, right)), .. } => Diagnostic::new(left, right, "unrecognized token", ""),
+        &ParseError::UnrecognizedToken { token: None, .. } => unexpected_eof(input),
+        &ParseError::ExtraToken { token: (left, _, right) } => Diagnostic::new(left, right, "extra token", ""),
+        &ParseError::User { ref error } => lexer_error(input, error),
+    }
 }
 
-fn unexpected_eof(input: &str) -> String {
+fn unexpected_eof(input: &str) -> Diagnostic {
     //check if there are imbalanced parens
     let missing_parens = input.chars().fold(0i32, |x, c| {
         match c {
@@ -93,55 +101,87 @@ fn unexpected_eof(input: &str) -> String {
     } else {
         "".into()
     };
-
-    concat_strings(&[
-        print_line_with_pos(input, input.len(), input.len()),
-        print_error_msg("unexpected EOF"),
-        print_hint_msg(&parens_hint),
-    ])
+    Diagnostic::new(input.len(), input.len(), "unexpected EOF", &parens_hint)
 }
 
-fn user_error(input: &str, err: &Error) -> String {
-    let strs = match err {
-        &Error::NonAsciiChar(pos) => {
+fn lexer_error(input: &str, err: &Error) -> Diagnostic {
+    match err {
+        &Error::InvalidChar(pos) => {
             // find char at position
             let c = input[pos..].chars().next().expect("internal error");
-            vec![
-                print_line_with_pos(input, pos, pos),
-                print_error_msg(&format!("invalid character: {} is not ASCII", c)),
-                "".into(),
-            ]
+            Diagnostic::new(pos, pos + c.len_utf8(), &format!("invalid character: {}", c.escape_default()), "")
         },
-        &Error::UnexpectedEofString(start) => vec![
-            print_line_with_pos(input, start, input.len()),
-            print_error_msg(&format!("unexpected EOF\n")),
-            print_hint_msg("missing closing \", did you forget to terminate a string literal?")
-        ],
-        &Error::UnexpectedEofChar(start) => vec![
-            print_line_with_pos(input, start, input.len()),
-            print_error_msg(&format!("unexpected EOF\n")),
-            print_hint_msg("missing a character, did you forget to finish a char literal?")
-        ],
-        &Error::InvalidToken(start, end) => {
-            vec![
-                print_line_with_pos(input, start, end),
-                print_error_msg(&format!("invalid token")),
-            ]
-        },
-        &Error::InvalidEscape(start, end) => {
-            vec![
-                print_line_with_pos(input, start, end),
-                print_error_msg(&format!("invalid escape sequence")),
-            ]
-        },
-        &Error::RecurInNonTailPosition => {
-            vec![
-                print_error_msg(&format!("recur in non-tail position")),
-            ]
-        }
+        &Error::UnexpectedEofString(start) => Diagnostic::new(start, input.len(), "unexpected EOF",
+                                                              "missing closing \", did you forget to terminate a string literal?"),
+        &Error::UnexpectedEofChar(start) => Diagnostic::new(start, input.len(), "unexpected EOF",
+                                                            "missing a character, did you forget to finish a char literal?"),
+        &Error::UnexpectedEofComment(start) => Diagnostic::new(start, start + 2, "unexpected EOF",
+                                                               "missing closing |#, did you forget to end a block comment?"),
+        &Error::UnexpectedEofSymbol(start) => Diagnostic::new(start, input.len(), "unexpected EOF",
+                                                              "missing closing |, did you forget to end a |symbol|?"),
+        &Error::InvalidToken(start, end) => Diagnostic::new(start, end, &invalid_item(input, start), ""),
+        &Error::InvalidEscape(start, end) => Diagnostic::new(start, end, "invalid escape sequence", ""),
+        &Error::RecurInNonTailPosition => Diagnostic { span: None, message: "recur in non-tail position".into(), hint: "".into() },
+        &Error::InvalidSyntax(ref msg) => Diagnostic { span: None, message: msg.clone(), hint: "".into() },
+    }
+}
+
+// what kind of literal the item around pos was meant to be, e.g. invalid number: 1.5a
+fn invalid_item(input: &str, pos: usize) -> String {
+    let start = input[..pos].rfind(delimiter).map_or(0, |i| i + 1);
+    let end = input[pos..].find(delimiter).map_or(input.len(), |i| pos + i);
+    let item = &input[start..end];
+    let kind = if item.starts_with("#\\") {
+        "char"
+    } else if item.starts_with('#') && item[1..].starts_with(|c: char| c.is_digit(10)) {
+        "datum label"
+    } else if item.starts_with(|c: char| c.is_digit(10) || c == '-' || c == '.') || item.starts_with('#') {
+        "number"
+    } else {
+        "token"
     };
+    format!("invalid {}: {}", kind, item)
+}
+
+fn delimiter(c: char) -> bool {
+    c == ' ' || c == '\n' || c == '\t' || c == '(' || c == ')' || c == ';'
+}
 
-    concat_strings(&strs)
+fn line_start(input: &str, pos: usize) -> usize {
+    input[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+// line:column: error: message
+// line | the line
+//      |    ^^^
+// hint: hint
+fn print_diagnostic(input: &str, diagnostic: &Diagnostic) -> String {
+    let (start, mut end) = match diagnostic.span {
+        Some(span) => span,
+        None => return format!("{}{}", print_error_msg(&diagnostic.message), print_hint_msg(&diagnostic.hint)),
+    };
+    // protect against start == end
+    if start >= end { end = start + 1; }
+
+    // safe because there's a span
+    let (line_no, column) = diagnostic.line_column(input).unwrap();
+    let line_start = line_start(input, start);
+    let line_end = input[start..].find('\n').map_or(input.len(), |i| start + i);
+    // the span is cut off at the end of the line
+    let width = input[start..line_end].chars().count().min(end - start).max(1);
+    let span: String = iter::repeat('^').take(width).collect();
+
+    format!(
+"{}:{}: {}
+{} | {}
+{:ws_pipe$} | {:ws_err$}{}{}",
+        line_no, column, print_error_msg(&diagnostic.message),
+        line_no,
+        &input[line_start..line_end],
+        "", "", span, print_hint_msg(&diagnostic.hint),
+        ws_pipe = line_no.to_string().len(),
+        ws_err = input[line_start..start].chars().count(),
+    )
 }
 
 fn print_error_msg(msg: &str) -> String {
@@ -155,7 +195,3 @@ fn print_hint_msg(hint: &str) -> String {
         "".into()
     }
 }
-
-fn concat_strings(strings: &[String]) -> String {
-    strings.into_iter().fold(String::new(), |mut res, s| {res.push_str(s); res})
-}
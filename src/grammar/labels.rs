@@ -0,0 +1,92 @@
+use std::collections::{HashMap, HashSet};
+use ::value::{Value, Userdata};
+use super::error::Error;
+
+// Datum labels like #0=(a . #0#), the list whose cdr is itself. The parser can't build such data,
+// so it wraps the labeled items and the references in userdata, resolve replaces them afterwards.
+
+struct Label(u64, Value);
+struct Reference(u64);
+
+pub fn label(n: u64, x: Value) -> Value {
+    Value::new_userdata(Userdata::new(Label(n, x)).with_type_name("datum label"))
+}
+
+pub fn reference(n: u64) -> Value {
+    Value::new_userdata(Userdata::new(Reference(n)).with_type_name("datum reference"))
+}
+
+// only input with a # followed by a digit can contain labels, everything else isn't walked
+pub fn may_contain_labels(input: &str) -> bool {
+    input.split('#').skip(1).any(|x| x.starts_with(|c: char| c.is_digit(10)))
+}
+
+// The data with the labels replaced, a label can only be referenced in the datum it's in.
+// A reference to a label that's still being read makes the datum contain itself,
+// that's only allowed if cycles is true, code that contains itself can't be compiled.
+pub fn resolve(data: Vec<Value>, cycles: bool) -> Result<Vec<Value>, Error> {
+    data.into_iter().map(|x| -> Result<Value, Error> {
+        let mut resolver = Resolver { labels: HashMap::new(), active: HashSet::new(), cycles: cycles };
+        Ok(resolver.resolve(&x)?.unwrap_or(x))
+    }).collect()
+}
+
+struct Resolver {
+    labels: HashMap<u64, Value>,
+    // the labels whose items are being resolved
+    active: HashSet<u64>,
+    cycles: bool,
+}
+
+impl Resolver {
+    // what x has to be replaced with, None if it stays, pairs and vectors are changed in place
+    fn resolve(&mut self, x: &Value) -> Result<Option<Value>, Error> {
+        if let Some(userdata) = x.get_userdata() {
+            if let Some(&Label(n, ref item)) = userdata.downcast_ref::<Label>() {
+                if self.labels.contains_key(&n) {
+                    return Err(Error::InvalidSyntax(format!("datum label #{}= is defined twice", n)));
+                }
+                self.labels.insert(n, item.clone());
+                self.active.insert(n);
+                let item = self.resolve(item)?.unwrap_or_else(|| item.clone());
+                self.active.remove(&n);
+                self.labels.insert(n, item.clone());
+                return Ok(Some(item));
+            }
+            if let Some(&Reference(n)) = userdata.downcast_ref::<Reference>() {
+                return match self.labels.get(&n) {
+                    None => Err(Error::InvalidSyntax(format!("undefined datum label #{}#", n))),
+                    Some(_) if self.active.contains(&n) && !self.cycles => {
+                        Err(Error::InvalidSyntax(format!("#{}# makes code contain itself, only read accepts that", n)))
+                    },
+                    Some(item) => Ok(Some(item.clone())),
+                };
+            }
+            return Ok(None);
+        }
+
+        if x.get_pair().is_some() {
+            // iterative for the cdrs, so long lists don't overflow the stack
+            let mut current = x.clone();
+            loop {
+                let (car, cdr) = { let pair = current.get_pair().unwrap(); (pair.car(), pair.cdr()) };
+                if let Some(car) = self.resolve(&car)? { current.get_pair().unwrap().set_car(car); }
+                if cdr.get_pair().is_some() {
+                    current = cdr;
+                    continue;
+                }
+                if let Some(cdr) = self.resolve(&cdr)? { current.get_pair().unwrap().set_cdr(cdr); }
+                return Ok(None);
+            }
+        }
+
+        if let Some(elements) = x.get_vector() {
+            let len = elements.read().unwrap().len();
+            for i in 0..len {
+                let element = elements.read().unwrap()[i].clone();
+                if let Some(element) = self.resolve(&element)? { elements.write().unwrap()[i] = element; }
+            }
+        }
+        Ok(None)
+    }
+}
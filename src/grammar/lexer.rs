@@ -1,8 +1,8 @@
 use std::str::CharIndices;
-use std::ascii::AsciiExt;
 use std::iter::Peekable;
 
 use super::error::Error;
+use super::numbers::parse_prefixed;
 
 // use ascii end of transmission as EOF
 const EOF: char = 0x4 as char;
@@ -19,24 +19,40 @@ macro_rules! my_try {
 #[derive(Clone, Copy, Debug)]
 pub enum Token<'input> {
     OpenParen,
+    // #(
+    VectorParen,
     ClosingParen,
     Dot,
     QuoteTick,
+    QuasiQuoteTick,
+    Unquote,
+    UnquoteSplicing,
     WhiteSpace,
+    // #;, the next item is skipped
+    DatumComment,
+    // #0=, labels the next item
+    DatumLabel(u64),
+    // #0#, the item labeled with it
+    DatumReference(u64),
     Char(char),
     Integer(i64),
+    // too big for an i64
+    BigInteger(&'input str),
+    // with a radix or exactness prefix, e.g. #xff, including the prefix
+    PrefixedNumber(&'input str),
+    // numerator/denominator
+    Rational(&'input str),
+    Float(f64),
     String(&'input str),
+    // #"no \ escapes"#, between the quotes
+    RawString(&'input str),
     Symbol(&'input str),
+    // |hello world|, between the pipes
+    PipeSymbol(&'input str),
+    // #:name or :name, without the prefix
+    Keyword(&'input str),
     True,
     False,
-    Begin,
-    Define,
-    If,
-    Let,
-    Loop,
-    Lambda,
-    Recur,
-    Quote,
 }
 
 // Tokenzer state
@@ -47,15 +63,43 @@ enum State<'input> {
     Finished(Spanned<Token<'input>>),
     // start
     EatInteger(usize),
+    // after the /
+    Denominator(usize),
+    EatDenominator(usize),
+    // after the decimal point
+    EatFloat(usize),
+    // after e, before the sign
+    ExponentStart(usize),
+    // after the sign, at least one digit is needed
+    ExponentSign(usize),
+    EatExponent(usize),
     WhiteSpace(usize),
+    Comment(usize),
+    // start and the depth of #| |# comments, after a # the next | nests another one, after a | the next # ends one
+    BlockComment(usize, usize),
+    BlockCommentPound(usize, usize),
+    BlockCommentBar(usize, usize),
     Minus(usize),
     Symbol(usize),
+    PipeSymbol(usize),
+    PipeSymbolBackslash(usize),
+    // start and the start of the name
+    Keyword(usize, usize),
     StringStart(usize),
     StringBackslash(usize),
+    RawString(usize),
+    // after a " in a raw string, a # ends it
+    RawStringQuote(usize),
     Pound(usize),
+    PrefixedNumber(usize),
+    // #0= or #0#, a # followed by anything else is a symbol like before
+    DatumLabel(usize),
+    Comma(usize),
+    Dot(usize),
     CharBegin(usize),
     EscapedChar(usize),
     FinishedChar(usize, char),
+    CharName(usize),
 }
 use self::State::*;
 
@@ -84,6 +128,19 @@ impl<'input> Tokenizer<'input> {
     fn peek_next(&mut self) -> Option<&(usize, char)> {
         self.chars.peek()
     }
+
+    // continues with the next item after an error, so more than one error can be found
+    pub fn recover(&mut self) {
+        self.state = NewToken;
+        while self.peek_next().map_or(false, |&(_, c)| !end_of_item(c)) {
+            self.next_char();
+        }
+    }
+
+    fn finish_float(&self, start: usize, end: usize) -> State<'input> {
+        // safe because the tokenizer checked that text[start..end] is a valid float
+        Finished((start, Token::Float(self.text[start..end].parse().unwrap()), end))
+    }
 }
 
 impl<'input> Iterator for Tokenizer<'input> {
@@ -103,10 +160,18 @@ impl<'input> Iterator for Tokenizer<'input> {
                 (NewToken, pos, '(') => { self.next_char(); Finished((pos, Token::OpenParen, pos+1)) },
                 (NewToken, pos, ')') => { self.next_char(); Finished((pos, Token::ClosingParen, pos+1)) },
                 (NewToken, pos, '\'') => { self.next_char(); Finished((pos, Token::QuoteTick, pos+1)) },
-                (NewToken, pos, '.') => { self.next_char(); Finished((pos, Token::Dot, pos+1)) },
+                (NewToken, pos, '`') => { self.next_char(); Finished((pos, Token::QuasiQuoteTick, pos+1)) },
+                (NewToken, pos, ',') => Comma(pos),
+                (NewToken, pos, '.') => Dot(pos),
                 (NewToken, pos, '-') => Minus(pos),
                 (NewToken, pos, '"') => StringStart(pos),
+                (NewToken, pos, '|') => PipeSymbol(pos),
+                // the #! line at the start of scripts, e.g. #!/usr/bin/env flip
+                (NewToken, 0, '#') if self.text.starts_with("#!") => Comment(0),
+                (NewToken, pos, '#') if self.text[pos..].starts_with("#|") => BlockCommentPound(pos, 0),
                 (NewToken, pos, '#') => Pound(pos),
+                (NewToken, pos, ';') => Comment(pos),
+                (NewToken, pos, ':') => Keyword(pos, pos+1),
                 (NewToken, _, EOF) => return None,
                 (NewToken, pos, c) if whitespace(c) => WhiteSpace(pos),
                 (NewToken, pos, c) if numeric(c) => EatInteger(pos),
@@ -117,72 +182,195 @@ impl<'input> Iterator for Tokenizer<'input> {
                 (Minus(start), end, c) if end_of_item(c) => Finished((start, Token::Symbol(&self.text[start..end]), end)),
                 (Minus(start), _, _) => Symbol(start),
 
+                // a dot on its own, or symbols like ...
+                (Dot(start), _, c) if end_of_item(c) => Finished((start, Token::Dot, start+1)),
+                (Dot(start), _, c) if numeric(c) => EatFloat(start),
+                (Dot(start), _, _) => Symbol(start),
+
+                // , or ,@
+                (Comma(start), _, '@') => { self.next_char(); Finished((start, Token::UnquoteSplicing, start+2)) },
+                (Comma(start), _, _) => Finished((start, Token::Unquote, start+1)),
+
                 // whitespace
                 (WhiteSpace(pos), _, c) if whitespace(c) => WhiteSpace(pos),
+                (WhiteSpace(pos), _, ';') => Comment(pos),
+                (WhiteSpace(start), pos, '#') if self.text[pos..].starts_with("#|") => BlockCommentPound(start, 0),
                 (WhiteSpace(start), end, _) => Finished((start, Token::WhiteSpace, end)),
 
+                // comments are whitespace, they end at the end of the line
+                (Comment(pos), _, '\n') => WhiteSpace(pos),
+                (Comment(start), end, EOF) => Finished((start, Token::WhiteSpace, end)),
+                (Comment(pos), _, _) => Comment(pos),
+
+                // block comments nest, they're whitespace too
+                (BlockCommentPound(start, depth), _, '|') => BlockComment(start, depth + 1),
+                (BlockCommentPound(start, depth), _, '#') => BlockCommentPound(start, depth),
+                (BlockCommentBar(start, 1), _, '#') => WhiteSpace(start),
+                (BlockCommentBar(start, depth), _, '#') => BlockComment(start, depth - 1),
+                (BlockCommentBar(start, depth), _, '|') => BlockCommentBar(start, depth),
+                (BlockComment(start, _), _, EOF) | (BlockCommentPound(start, _), _, EOF) | (BlockCommentBar(start, _), _, EOF) => {
+                    return Some(Err(Error::UnexpectedEofComment(start)))
+                },
+                (BlockComment(start, depth), _, '|') => BlockCommentBar(start, depth),
+                (BlockComment(start, depth), _, '#') => BlockCommentPound(start, depth),
+                (BlockComment(start, depth), _, _) | (BlockCommentPound(start, depth), _, _) | (BlockCommentBar(start, depth), _, _) => {
+                    BlockComment(start, depth)
+                },
+
                 // chars
                 (Pound(pos), _, '\\') => CharBegin(pos),
+                (Pound(start), _, '(') => { self.next_char(); Finished((start, Token::VectorParen, start+2)) },
+                (Pound(start), _, ':') => Keyword(start, start+2),
+                (Pound(start), _, '"') => RawString(start),
+                (Pound(start), _, c) if "xXoObBdDeEiI".contains(c) => PrefixedNumber(start),
+                (Pound(start), _, c) if numeric(c) => DatumLabel(start),
+                (Pound(start), _, ';') => { self.next_char(); Finished((start, Token::DatumComment, start+2)) },
                 (Pound(start), end, c) if end_of_item(c) => Finished((start, Token::Symbol(&self.text[start..end]), end)),
                 (Pound(pos), _, _) => Symbol(pos),
 
                 (CharBegin(pos), _, '\\') => EscapedChar(pos),
-                (CharBegin(_), _, EOF) => return Some(Err(Error::UnexpectedEofChar(self.text.len()))),
+                (CharBegin(start), _, EOF) => return Some(Err(Error::UnexpectedEofChar(start))),
                 (CharBegin(pos), _, c) if printable_char(c) => FinishedChar(pos, c),
-                (CharBegin(_), pos, _) => return Some(Err(Error::NonAsciiChar(pos))),
+                (CharBegin(_), pos, _) => return Some(Err(Error::InvalidChar(pos))),
 
                 (EscapedChar(pos), _, c) if unescape_char(c).is_some() => FinishedChar(pos, unescape_char(c).unwrap()),
                 (EscapedChar(start), end, EOF) => Finished((start, Token::Char('\\'), end)),
                 (EscapedChar(start), end, _) => return Some(Err(Error::InvalidEscape(start+2, next_char(&self.text, end)))), // +2 bc #\ is 2 bytes
 
                 (FinishedChar(start, x), end, c) if end_of_item(c) => Finished((start, Token::Char(x), end)),
+                // e.g. #\space, but not #\\s
+                (FinishedChar(start, _), _, c) if printable_char(c) && !self.text[start..].starts_with("#\\\\") => CharName(start),
+
+                (CharName(start), end, c) if end_of_item(c) => match named_char(&self.text[start+2..end]) {
+                    Some(x) => Finished((start, Token::Char(x), end)),
+                    None => return Some(Err(Error::InvalidToken(start, end))),
+                },
+                (CharName(start), _, c) if printable_char(c) => CharName(start),
+                (CharName(_), pos, _) => return Some(Err(Error::InvalidChar(pos))),
                 (FinishedChar(_, _), end, _) => return Some(Err(Error::InvalidToken(end, end))),
 
+                // numbers with prefixes, parse_prefixed checks the rest
+                (PrefixedNumber(start), end, c) if end_of_item(c) => match parse_prefixed(&self.text[start..end], 10) {
+                    Some(_) => Finished((start, Token::PrefixedNumber(&self.text[start..end]), end)),
+                    None => return Some(Err(Error::InvalidToken(start, end))),
+                },
+                (PrefixedNumber(start), _, _) => PrefixedNumber(start),
+
                 // integers
                 (EatInteger(start), _, c) if numeric(c) => EatInteger(start),
-                (EatInteger(start), pos, c) if end_of_item(c) =>
-                    // safe because we checked that text[start..pos] is a valid number
-                    Finished((start, Token::Integer(self.text[start..pos].parse().unwrap()), pos)),
+                (EatInteger(start), pos, c) if end_of_item(c) => {
+                    // we checked that text[start..pos] is a valid number, so this only fails if it's too big
+                    let text = &self.text[start..pos];
+                    let token = text.parse().map(Token::Integer).unwrap_or(Token::BigInteger(text));
+                    Finished((start, token, pos))
+                },
+                (EatInteger(start), _, '.') => EatFloat(start),
+                (EatInteger(start), _, '/') => Denominator(start),
+                (EatInteger(start), _, 'e') | (EatInteger(start), _, 'E') => ExponentStart(start),
                 (EatInteger(_), pos, _) => return Some(Err(Error::InvalidToken(pos, pos))),
 
-                // strings
+                // rationals, e.g. 1/3
+                (Denominator(start), _, c) if numeric(c) => EatDenominator(start),
+                (Denominator(_), pos, _) => return Some(Err(Error::InvalidToken(pos, pos))),
+                (EatDenominator(start), _, c) if numeric(c) => EatDenominator(start),
+                (EatDenominator(start), pos, c) if end_of_item(c) => {
+                    let text = &self.text[start..pos];
+                    let denominator = &text[text.find('/').unwrap()+1..];
+                    if denominator.bytes().all(|b| b == b'0') {
+                        return Some(Err(Error::InvalidToken(start, pos)));
+                    }
+                    Finished((start, Token::Rational(text), pos))
+                },
+                (EatDenominator(_), pos, _) => return Some(Err(Error::InvalidToken(pos, pos))),
+
+                // floats, e.g. 1.5, .5, 1., 1e10, -1.5E-3
+                (EatFloat(start), _, c) if numeric(c) => EatFloat(start),
+                (EatFloat(start), _, 'e') | (EatFloat(start), _, 'E') => ExponentStart(start),
+                (EatFloat(start), pos, c) if end_of_item(c) => self.finish_float(start, pos),
+                (EatFloat(_), pos, _) => return Some(Err(Error::InvalidToken(pos, pos))),
+
+                (ExponentStart(start), _, '+') | (ExponentStart(start), _, '-') => ExponentSign(start),
+                (ExponentStart(start), _, c) | (ExponentSign(start), _, c) if numeric(c) => EatExponent(start),
+                (ExponentStart(_), pos, _) | (ExponentSign(_), pos, _) => return Some(Err(Error::InvalidToken(pos, pos))),
+
+                (EatExponent(start), _, c) if numeric(c) => EatExponent(start),
+                (EatExponent(start), pos, c) if end_of_item(c) => self.finish_float(start, pos),
+                (EatExponent(_), pos, _) => return Some(Err(Error::InvalidToken(pos, pos))),
+
+                // strings, the escapes are checked once the string is complete
                 (StringStart(start), end, '"') => {
                     self.next_char(); // bump
                     let string = &self.text[start+1..end];
-                    for (i, c) in string.char_indices() {
-                        if !c.is_ascii() {
-                            return Some(Err(Error::NonAsciiChar(i+1))) // +1 bc start+1 above
-                        }
+                    if let Err((from, to)) = unescape(string) {
+                        return Some(Err(Error::InvalidEscape(start+1+from, start+1+to))); // +1 bc of the "
                     }
                     Finished((start, Token::String(string), end+1))
                 },
-                (StringStart(_), _, EOF) => return Some(Err(Error::UnexpectedEofString(self.text.len()))),
+                (StringStart(start), _, EOF) => return Some(Err(Error::UnexpectedEofString(start))),
                 (StringStart(start), _, '\\') => StringBackslash(start),
                 (StringStart(start), _, _) => StringStart(start),
-                (StringBackslash(start), _, '"') => StringStart(start),
-                (StringBackslash(start), _, c) if unescape_char(c).is_some() => StringStart(start),
-                (StringBackslash(_), _, EOF) => return Some(Err(Error::UnexpectedEofString(self.text.len()))),
-                (StringBackslash(_), pos, _) => return Some(Err(Error::InvalidEscape(pos-1, next_char(&self.text, pos)))), // -1 bc \ is 1 byte
+                (StringBackslash(start), _, EOF) => return Some(Err(Error::UnexpectedEofString(start))),
+                (StringBackslash(start), _, _) => StringStart(start),
+
+                // raw strings end at the first "#
+                (RawString(start), _, EOF) | (RawStringQuote(start), _, EOF) => return Some(Err(Error::UnexpectedEofString(start))),
+                (RawString(start), _, '"') | (RawStringQuote(start), _, '"') => RawStringQuote(start),
+                (RawStringQuote(start), end, '#') => {
+                    self.next_char(); // bump
+                    let string = &self.text[start+2..end-1];
+                    Finished((start, Token::RawString(string), end+1))
+                },
+                (RawString(start), _, _) | (RawStringQuote(start), _, _) => RawString(start),
+
+                // datum labels
+                (DatumLabel(start), _, c) if numeric(c) => DatumLabel(start),
+                (DatumLabel(start), end, c) if c == '=' || c == '#' => {
+                    self.next_char();
+                    match self.text[start+1..end].parse() {
+                        Ok(n) if c == '=' => Finished((start, Token::DatumLabel(n), end+1)),
+                        Ok(n) => Finished((start, Token::DatumReference(n), end+1)),
+                        // too big for a u64
+                        Err(_) => return Some(Err(Error::InvalidToken(start, end+1))),
+                    }
+                },
+                (DatumLabel(start), end, c) if end_of_item(c) => Finished((start, Token::Symbol(&self.text[start..end]), end)),
+                (DatumLabel(start), _, _) => Symbol(start),
+
+                // symbols in pipes can contain anything, with the escapes of strings
+                (PipeSymbol(start), end, '|') => {
+                    self.next_char(); // bump
+                    let name = &self.text[start+1..end];
+                    if let Err((from, to)) = unescape(name) {
+                        return Some(Err(Error::InvalidEscape(start+1+from, start+1+to))); // +1 bc of the |
+                    }
+                    Finished((start, Token::PipeSymbol(name), end+1))
+                },
+                (PipeSymbol(start), _, EOF) | (PipeSymbolBackslash(start), _, EOF) => return Some(Err(Error::UnexpectedEofSymbol(start))),
+                (PipeSymbol(start), _, '\\') => PipeSymbolBackslash(start),
+                (PipeSymbol(start), _, _) | (PipeSymbolBackslash(start), _, _) => PipeSymbol(start),
 
                 // symbols
                 (Symbol(start), end, c) if end_of_item(c) => {
                     let token = match &self.text[start..end] {
-                        "begin" => Token::Begin,
-                        "define" => Token::Define,
-                        "if" => Token::If,
-                        "let" => Token::Let,
-                        "loop" => Token::Loop,
-                        "lambda" => Token::Lambda,
-                        "recur" => Token::Recur,
-                        "quote" => Token::Quote,
                         "true" => Token::True,
                         "false" => Token::False,
+                        "+inf.0" => Token::Float(::std::f64::INFINITY),
+                        "-inf.0" => Token::Float(::std::f64::NEG_INFINITY),
+                        "+nan.0" | "-nan.0" => Token::Float(::std::f64::NAN),
                         x => Token::Symbol(x),
                     };
 
                     Finished((start, token, end))
                 },
                 (Symbol(start), _, _) => Symbol(start),
+
+                // keywords, a : on its own is a symbol
+                (Keyword(start, name), end, c) if end_of_item(c) && name == end => {
+                    if name - start == 2 { return Some(Err(Error::InvalidToken(start, end))); }
+                    Finished((start, Token::Symbol(&self.text[start..end]), end))
+                },
+                (Keyword(start, name), end, c) if end_of_item(c) => Finished((start, Token::Keyword(&self.text[name..end]), end)),
+                (Keyword(start, name), _, _) => Keyword(start, name),
                 (Finished(_), _, _) => unreachable!(),
             };
 
@@ -207,6 +395,7 @@ fn whitespace(x: char) -> bool {
 fn end_of_item(x: char) -> bool {
     whitespace(x) ||
     x == ')' ||
+    x == ';' ||
     x == EOF
 }
 
@@ -230,42 +419,124 @@ fn unescape_char(x: char) -> Option<char> {
     }
 }
 
+// the char written as #\name, e.g. space or x41
+fn named_char(name: &str) -> Option<char> {
+    match name {
+        "space" => Some(' '),
+        "newline" => Some('\n'),
+        "tab" => Some('\t'),
+        "return" => Some('\r'),
+        "null" | "nul" => Some('\0'),
+        "alarm" => Some('\x07'),
+        "backspace" => Some('\x08'),
+        "delete" => Some('\x7F'),
+        "escape" => Some('\x1B'),
+        _ if name.starts_with('x') && name[1..].chars().all(|c| c.is_digit(16)) => u32::from_str_radix(&name[1..], 16).ok().and_then(::std::char::from_u32),
+        _ => None,
+    }
+}
+
+// where the char at pos ends
 fn next_char(text: &str, pos: usize) -> usize {
-    pos + char::len_utf8(text.as_bytes()[pos] as char)
+    text[pos..].chars().next().map_or(pos, |c| pos + c.len_utf8())
 }
 
 fn numeric(x: char) -> bool {
     '0' <= x && x <= '9'
 }
 
+// everything but whitespace and control chars, e.g. a, ( or λ
 fn printable_char(x: char) -> bool {
-    x >= '!' &&
-    x <= '~'
+    !x.is_whitespace() && !x.is_control()
 }
 
-#[allow(dead_code)]
+// the inverse of unescape_string, so written strings can be read back in
+// whitespace except for spaces and control chars are written as escapes, everything else as it is
+pub fn escape_string(input: &str) -> String {
+    escape(input, '"')
+}
+
+// The name of a symbol like it has to be written to be read back, in pipes with escapes
+// unless the lexer reads it as that symbol anyway, e.g. |hello world|, |1| or |true|.
+pub fn escape_symbol(name: &str) -> String {
+    let mut tokens = Tokenizer::new(name);
+    let plain = match (tokens.next(), tokens.next()) {
+        (Some(Ok((_, Token::Symbol(x), _))), None) => x == name,
+        _ => false,
+    };
+    if plain && name.chars().all(printable_char) { name.into() } else { format!("|{}|", escape(name, '|')) }
+}
+
+// quote is the char that ends the literal, it's escaped with a \
+fn escape(input: &str, quote: char) -> String {
+    let mut s = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '\n' => s.push_str("\\n"),
+            '\t' => s.push_str("\\t"),
+            '\r' => s.push_str("\\r"),
+            x if x == quote => { s.push('\\'); s.push(x); },
+            '\\' => s.push_str("\\\\"),
+            ' ' => s.push(' '),
+            x if printable_char(x) => s.push(x),
+            x => s.push_str(&format!("\\x{:x};", x as u32)),
+        }
+    }
+    s
+}
+
+// the contents of a string literal, the lexer checked its escapes
 pub fn unescape_string(input: &str) -> String {
-    let mut chars = input.chars();
-    let mut s = String::with_capacity(input.chars().count());
-
-    while let Some(c) = chars.next() {
-        if c == '\\' {
-            if let Some(next) = chars.next() {
-                match next {
-                    'n' => s.push('\n'),
-                    't' => s.push('\t'),
-                    '"' => s.push('"'),
-                    's' => s.push(' '),
-                    '\\' => s.push('\\'),
-                    x => {
-                        s.push('\\');
-                        s.push(x);
+    unescape(input).expect("internal error: invalid escape in string")
+}
+
+// \n, \t, \r, \a, \b, \0, \s for a space, \", \\, \| and \x41; for the char with that hex code,
+// a \ at the end of a line continues the string on the next one without the whitespace around the line break.
+// Err has the start and end of an invalid escape.
+fn unescape(input: &str) -> Result<String, (usize, usize)> {
+    let mut s = String::with_capacity(input.len());
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        if c != '\\' {
+            s.push(c);
+            continue;
+        }
+        // safe because the lexer doesn't end a string after a \
+        let (_, next) = chars.next().unwrap();
+        match next {
+            'n' => s.push('\n'),
+            't' => s.push('\t'),
+            'r' => s.push('\r'),
+            'a' => s.push('\x07'),
+            'b' => s.push('\x08'),
+            '0' => s.push('\0'),
+            's' => s.push(' '),
+            '"' | '\\' | '|' => s.push(next),
+            'x' => {
+                let digits = &input[pos+2..];
+                let end = pos + 2 + digits.find(|c: char| !c.is_digit(16)).unwrap_or(digits.len());
+                let code = u32::from_str_radix(&input[pos+2..end], 16).ok().and_then(::std::char::from_u32);
+                match code {
+                    Some(x) if input[end..].starts_with(';') => s.push(x),
+                    _ => return Err((pos, end)),
+                }
+                while chars.peek().map_or(false, |&(i, _)| i <= end) { chars.next(); }
+            },
+            ' ' | '\t' | '\n' => {
+                let mut newline = next == '\n';
+                while let Some(&(_, c)) = chars.peek() {
+                    match c {
+                        ' ' | '\t' => {},
+                        '\n' if !newline => newline = true,
+                        _ => break,
                     }
+                    chars.next();
                 }
-                continue;
-            }
+                if !newline { return Err((pos, pos+2)); }
+            },
+            x => return Err((pos, pos + 1 + x.len_utf8())),
         }
-        s.push(c);
     }
-    s
+    Ok(s)
 }
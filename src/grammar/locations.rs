@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use ::value::{Value, WeakValue};
+
+// Where a parsed value was written, lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub source: Arc<String>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn to_string(&self) -> String {
+        format!("{}:{}:{}", self.source, self.line, self.column)
+    }
+}
+
+// A side table from parsed values to their locations.
+// Values are identified by their address, so entries don't keep them alive
+// and are removed once their value is dropped.
+pub struct Locations {
+    entries: HashMap<usize, (WeakValue, Location)>,
+    // entries are pruned when there are twice as many as after the last pruning
+    prune_at: usize,
+}
+
+impl Locations {
+    pub fn new() -> Self {
+        Locations {
+            entries: HashMap::new(),
+            prune_at: 1024,
+        }
+    }
+
+    // values that aren't on the heap can't be told apart, they get no location
+    pub fn add(&mut self, value: &Value, location: Location) {
+        let weak = match value.downgrade() {
+            Some(weak) => weak,
+            None => return,
+        };
+        self.entries.insert(value.id(), (weak, location));
+
+        if self.entries.len() >= self.prune_at {
+            self.entries.retain(|_, &mut (ref weak, _)| weak.is_alive());
+            self.prune_at = ::std::cmp::max(1024, self.entries.len() * 2);
+        }
+    }
+
+    pub fn get(&self, value: &Value) -> Option<&Location> {
+        match self.entries.get(&value.id()) {
+            Some(&(ref weak, ref location)) if weak.refers_to(value) => Some(location),
+            _ => None,
+        }
+    }
+
+    // the locations of the values that are still alive, e.g. the code of procedures, in no particular order
+    pub fn live(&self) -> Vec<&Location> {
+        self.entries.values().filter(|&&(ref weak, _)| weak.is_alive()).map(|&(_, ref location)| location).collect()
+    }
+
+    // " at source:line:column" or nothing, for appending to condition messages
+    pub fn describe(&self, value: &Value) -> String {
+        self.get(value).map(|location| format!(" at {}", location.to_string())).unwrap_or_default()
+    }
+
+    // positions are byte offsets into input, which starts at line first_line of source
+    pub fn add_positions(&mut self, input: &str, source: &str, first_line: usize, positions: Vec<(Value, usize)>) {
+        let source = Arc::new(source.to_string());
+        let line_starts: Vec<usize> = Some(0).into_iter()
+        .chain(input.char_indices().filter(|&(_, c)| c == '\n').map(|(pos, _)| pos + 1))
+        .collect();
+
+        for (value, pos) in positions {
+            let line = match line_starts.binary_search(&pos) {
+                Ok(line) => line,
+                Err(next_line) => next_line - 1,
+            };
+            let location = Location {
+                source: source.clone(),
+                line: first_line + line,
+                column: input[line_starts[line]..pos].chars().count() + 1,
+            };
+            self.add(&value, location);
+        }
+    }
+}
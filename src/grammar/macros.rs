@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use ::value::Value;
+use ::string_interner::StringInterner;
+use ::grammar::error::Error;
+use ::grammar::syntax::is_parameter_list;
+
+static EXPECTED_DEFINE_SYNTAX: &'static str = "(define-syntax name (syntax-rules (literals*) (pattern template)*))";
+
+// All macros defined with define-syntax, they are expanded in the syntax pass.
+pub struct Macros {
+    macros: HashMap<u64, SyntaxRules>,
+    // names defined at the top level, they shadow special forms like lexical bindings do
+    globals: HashSet<u64>,
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Macros {
+            macros: HashMap::new(),
+            globals: HashSet::new(),
+        }
+    }
+
+    pub fn get(&self, name: u64) -> Option<&SyntaxRules> {
+        self.macros.get(&name)
+    }
+
+    pub fn define(&mut self, name: u64, rules: SyntaxRules) {
+        self.macros.insert(name, rules);
+    }
+
+    pub fn define_global(&mut self, name: u64) {
+        self.globals.insert(name);
+    }
+
+    pub fn is_global(&self, name: u64) -> bool {
+        self.globals.contains(&name)
+    }
+
+    pub fn names<'a>(&'a self) -> impl Iterator<Item=u64> + 'a {
+        self.macros.keys().cloned()
+    }
+}
+
+// what a pattern variable matched
+#[derive(Clone)]
+enum Binding {
+    Single(Value),
+    // matched by a pattern followed by an ellipsis
+    Sequence(Vec<Binding>),
+}
+
+type Bindings = HashMap<u64, Binding>;
+
+struct Rule {
+    // without the leading macro keyword, which is ignored
+    pattern: Vec<Value>,
+    template: Value,
+    // identifiers the template binds itself, they are renamed on every expansion
+    introduced: Vec<u64>,
+}
+
+pub struct SyntaxRules {
+    name: u64,
+    ellipsis: u64,
+    underscore: u64,
+    literals: Vec<u64>,
+    rules: Vec<Rule>,
+}
+
+impl SyntaxRules {
+    // creates the macro from the (syntax-rules ...) part of define-syntax
+    pub fn new(name: u64, spec: &Value, interner: &mut StringInterner) -> Result<Self, Error> {
+        let spec = spec.get_list().unwrap_or(vec![]);
+        if spec.len() < 2 || spec[0].get_symbol().and_then(|id| interner.lookup(id)) != Some("syntax-rules") {
+            return Err(Error::InvalidSyntax(format!("invalid define-syntax, expected: {}", EXPECTED_DEFINE_SYNTAX)));
+        }
+
+        let literals: Option<Vec<u64>> = spec[1].get_list().and_then(|list| list.iter().map(|x| x.get_symbol()).collect());
+        let literals = match literals {
+            Some(literals) => literals,
+            None => return Err(Error::InvalidSyntax("invalid syntax-rules literals, expected: (literals*)".into())),
+        };
+
+        let mut syntax_rules = SyntaxRules {
+            name: name,
+            ellipsis: interner.intern("..."),
+            underscore: interner.intern("_"),
+            literals: literals,
+            rules: vec![],
+        };
+
+        for rule in &spec[2..] {
+            let rule = rule.get_list().unwrap_or(vec![]);
+            let pattern = match rule.get(0).and_then(|x| x.get_list()) {
+                Some(ref pattern) if rule.len() == 2 && !pattern.is_empty() => pattern[1..].to_vec(),
+                _ => return Err(Error::InvalidSyntax("invalid syntax rule, expected: ((_ pattern*) template)".into())),
+            };
+
+            let mut variables = vec![];
+            syntax_rules.pattern_variables(&Value::new_list(&pattern), &mut variables);
+
+            let mut introduced = vec![];
+            syntax_rules.binders(&rule[1], interner, &mut introduced);
+            introduced.retain(|x| !variables.contains(x));
+
+            syntax_rules.rules.push(Rule {
+                pattern: pattern,
+                template: rule[1].clone(),
+                introduced: introduced,
+            });
+        }
+
+        Ok(syntax_rules)
+    }
+
+    // expands form with the first matching rule
+    pub fn expand(&self, form: &[Value], interner: &mut StringInterner) -> Result<Value, Error> {
+        for rule in &self.rules {
+            let mut bindings = HashMap::new();
+            if !self.match_list(&rule.pattern, &form[1..], &mut bindings) { continue }
+
+            // introduced identifiers get a fresh name, so they can't capture the macro user's identifiers
+            let renames = rule.introduced.iter()
+            .map(|&id| {
+                let name = interner.lookup(id).unwrap_or("").to_string();
+                (id, interner.gensym(&name))
+            })
+            .collect();
+
+            return self.instantiate(&rule.template, &bindings, &renames);
+        }
+
+        let name = interner.lookup(self.name).unwrap_or("").to_string();
+        Err(Error::InvalidSyntax(format!("no rule of macro {} matches: {}", name, Value::new_list(form).to_string(interner))))
+    }
+
+    fn match_pattern(&self, pattern: &Value, form: &Value, bindings: &mut Bindings) -> bool {
+        if let Some(id) = pattern.get_symbol() {
+            if id == self.underscore {
+                true
+            } else if self.literals.contains(&id) {
+                form.get_symbol() == Some(id)
+            } else {
+                bindings.insert(id, Binding::Single(form.clone()));
+                true
+            }
+        } else if let Some(pattern) = pattern.get_list() {
+            match form.get_list() {
+                Some(form) => self.match_list(&pattern, &form, bindings),
+                None => false,
+            }
+        } else {
+            match (pattern.get_string(), form.get_string()) {
+                (Some(a), Some(b)) => a == b,
+                _ => pattern.eqv(form),
+            }
+        }
+    }
+
+    fn match_list(&self, pattern: &[Value], form: &[Value], bindings: &mut Bindings) -> bool {
+        let ellipsis = pattern.iter().position(|x| x.get_symbol() == Some(self.ellipsis));
+        let (before, repeated, after) = match ellipsis {
+            Some(pos) if pos > 0 => (&pattern[..pos-1], Some(&pattern[pos-1]), &pattern[pos+1..]),
+            _ => (pattern, None, &[][..]),
+        };
+
+        let enough = match repeated {
+            Some(_) => form.len() >= before.len() + after.len(),
+            None => form.len() == before.len(),
+        };
+        if !enough { return false }
+
+        let repeated_end = form.len() - after.len();
+        let singles = before.iter().zip(&form[..before.len()])
+        .chain(after.iter().zip(&form[repeated_end..]));
+        for (pattern, form) in singles {
+            if !self.match_pattern(pattern, form, bindings) { return false }
+        }
+
+        if let Some(repeated) = repeated {
+            let mut variables = vec![];
+            self.pattern_variables(repeated, &mut variables);
+
+            let mut matches = vec![];
+            for form in &form[before.len()..repeated_end] {
+                let mut item_bindings = HashMap::new();
+                if !self.match_pattern(repeated, form, &mut item_bindings) { return false }
+                matches.push(item_bindings);
+            }
+
+            for variable in variables {
+                let sequence = matches.iter_mut().map(|b| b.remove(&variable).unwrap()).collect();
+                bindings.insert(variable, Binding::Sequence(sequence));
+            }
+        }
+        true
+    }
+
+    fn instantiate(&self, template: &Value, bindings: &Bindings, renames: &HashMap<u64, u64>) -> Result<Value, Error> {
+        if let Some(id) = template.get_symbol() {
+            match bindings.get(&id) {
+                Some(&Binding::Single(ref value)) => Ok(value.clone()),
+                Some(&Binding::Sequence(_)) => Err(Error::InvalidSyntax("pattern variable used without ellipsis".into())),
+                None => Ok(Value::new_symbol(*renames.get(&id).unwrap_or(&id))),
+            }
+        } else if let Some(list) = template.get_list() {
+            let mut values = vec![];
+            let mut i = 0;
+            while i < list.len() {
+                let followed_by_ellipsis = list.get(i+1).and_then(|x| x.get_symbol()) == Some(self.ellipsis);
+                if followed_by_ellipsis {
+                    for bindings in self.unroll(&list[i], bindings)? {
+                        values.push(self.instantiate(&list[i], &bindings, renames)?);
+                    }
+                    i += 2;
+                } else {
+                    values.push(self.instantiate(&list[i], bindings, renames)?);
+                    i += 1;
+                }
+            }
+            Ok(Value::new_list(&values))
+        } else if let Some((values, tail)) = template.get_dotted_list() {
+            // (a b . c) is instantiated like (a b), the tail is added afterwards
+            let values = self.instantiate(&Value::new_list(&values), bindings, renames)?;
+            let tail = self.instantiate(&tail, bindings, renames)?;
+            Ok(Value::new_dotted_list(values.get_list().unwrap_or(vec![]), tail))
+        } else {
+            Ok(template.clone())
+        }
+    }
+
+    // creates the bindings for every repetition of a template followed by an ellipsis
+    fn unroll(&self, template: &Value, bindings: &Bindings) -> Result<Vec<Bindings>, Error> {
+        let mut symbols = vec![];
+        self.pattern_variables(template, &mut symbols);
+
+        let sequences: Vec<(u64, &Vec<Binding>)> = symbols.iter()
+        .filter_map(|id| match bindings.get(id) {
+            Some(&Binding::Sequence(ref sequence)) => Some((*id, sequence)),
+            _ => None,
+        })
+        .collect();
+
+        let len = match sequences.first() {
+            Some(&(_, sequence)) => sequence.len(),
+            None => return Err(Error::InvalidSyntax("no pattern variable before ellipsis in template".into())),
+        };
+        if sequences.iter().any(|&(_, sequence)| sequence.len() != len) {
+            return Err(Error::InvalidSyntax("pattern variables before ellipsis matched different lengths".into()));
+        }
+
+        Ok((0..len).map(|i| {
+            let mut bindings = bindings.clone();
+            for &(id, sequence) in &sequences {
+                bindings.insert(id, sequence[i].clone());
+            }
+            bindings
+        }).collect())
+    }
+
+    // all symbols in pattern which aren't literals, _ or ...
+    fn pattern_variables(&self, pattern: &Value, variables: &mut Vec<u64>) {
+        if let Some(id) = pattern.get_symbol() {
+            if id != self.ellipsis && id != self.underscore && !self.literals.contains(&id) {
+                variables.push(id);
+            }
+        } else if let Some(list) = pattern.get_list() {
+            for x in &list {
+                self.pattern_variables(x, variables);
+            }
+        }
+    }
+
+    // symbols bound by lambdas and lets in template
+    fn binders(&self, template: &Value, interner: &StringInterner, binders: &mut Vec<u64>) {
+        let list = match template.get_list() {
+            Some(list) => list,
+            None => return,
+        };
+
+        let keyword = list.get(0).and_then(|x| x.get_symbol()).and_then(|id| interner.lookup(id));
+        let bound: Vec<Value> = match (keyword, list.get(1)) {
+            (Some("lambda"), Some(args)) => {
+                // same rules as in syntax.rs
+                let args = match list.get(2) {
+                    Some(named_args) if args.get_symbol().is_some() && list.len() >= 4 && is_parameter_list(named_args) => named_args,
+                    _ => args,
+                };
+                match args.get_dotted_list() {
+                    Some((args, rest)) => args.into_iter().chain(Some(rest)).collect(),
+                    None => args.get_list().unwrap_or(vec![args.clone()]),
+                }
+            },
+            (Some("let"), Some(bindings)) | (Some("let*"), Some(bindings)) |
+            (Some("letrec"), Some(bindings)) | (Some("loop"), Some(bindings)) => {
+                let bindings = bindings.get_list().unwrap_or(vec![]);
+                if bindings.first().and_then(|x| x.get_symbol()).is_some() {
+                    bindings.iter().enumerate().filter(|&(i, _)| i % 2 == 0).map(|(_, x)| x.clone()).collect()
+                } else {
+                    bindings.iter().filter_map(|x| x.get_list().and_then(|b| b.first().cloned())).collect()
+                }
+            },
+            _ => vec![],
+        };
+
+        for x in bound {
+            if let Some(id) = x.get_symbol() {
+                if id != self.ellipsis && !binders.contains(&id) { binders.push(id); }
+            }
+        }
+
+        for x in &list {
+            self.binders(x, interner, binders);
+        }
+    }
+}
@@ -4,26 +4,106 @@ mod tests;
 mod parser;
 mod lexer;
 mod error;
+mod syntax;
+mod macros;
+mod locations;
+mod numbers;
+mod labels;
+mod reader;
 pub mod error_printing;
 
 use std::mem;
+use ::lalrpop_util::ParseError;
 use ::value::Value;
 use ::string_interner::StringInterner;
 
-pub use self::lexer::escape_char;
+pub use self::lexer::{escape_char, escape_string, escape_symbol};
+pub use self::macros::Macros;
+pub use self::syntax::KEYWORDS;
+pub use self::locations::Locations;
+pub use self::reader::Reader;
+pub use self::numbers::{parse_prefixed, exact_to_string};
 
 static NO_INTERNER_ERROR_STRING: &'static str = "internal error: interner not set";
 
+// parses input and turns it into code, without macros from earlier inputs
 pub fn parse<'input>(input: &'input str, interner: &mut StringInterner)
--> Result<Vec<Value>, ::lalrpop_util::ParseError<usize, lexer::Token<'input>, error::Error>> {
+-> Result<Vec<Value>, ParseError<usize, lexer::Token<'input>, error::Error>> {
+    parse_with_macros(input, interner, &mut Macros::new())
+}
+
+// same as parse, but macros defined in input are added to macros and can be used afterwards
+pub fn parse_with_macros<'input>(input: &'input str, interner: &mut StringInterner, macros: &mut Macros)
+-> Result<Vec<Value>, ParseError<usize, lexer::Token<'input>, error::Error>> {
+    let tokenizer = lexer::Tokenizer::new(input);
+    let data = parser::parse_TopLevelItem(input, true, interner, &mut vec![], tokenizer)?;
+    let data = resolve_labels(input, data, false)?;
+    compile_all(&data, interner, macros)
+}
+
+// same as parse_with_macros, but the locations of symbols are added to locations
+// input starts at line first_line of source, e.g. a file name
+pub fn parse_with_locations<'input>(input: &'input str, source: &str, first_line: usize,
+                                    interner: &mut StringInterner, macros: &mut Macros, locations: &mut Locations)
+-> Result<Vec<Value>, ParseError<usize, lexer::Token<'input>, error::Error>> {
+    let tokenizer = lexer::Tokenizer::new(input);
+    let mut positions = vec![];
+    let data = parser::parse_TopLevelItem(input, true, interner, &mut positions, tokenizer)?;
+    let data = resolve_labels(input, data, false)?;
+    locations.add_positions(input, source, first_line, positions);
+    compile_all(&data, interner, macros)
+}
+
+fn compile_all<'input>(data: &[Value], interner: &mut StringInterner, macros: &mut Macros)
+-> Result<Vec<Value>, ParseError<usize, lexer::Token<'input>, error::Error>> {
+    data.iter()
+    .map(|x| syntax::compile(x, interner, macros).map_err(|err| ParseError::User { error: err }))
+    .collect()
+}
+
+// turns a datum into code like parse_with_macros does, used by eval
+pub fn compile(datum: &Value, interner: &mut StringInterner, macros: &mut Macros) -> Result<Value, String> {
+    syntax::compile(datum, interner, macros).map_err(|err| match err {
+        error::Error::RecurInNonTailPosition => "recur in non-tail position".into(),
+        error::Error::InvalidSyntax(msg) => msg,
+        // the other errors are only raised by the lexer
+        err => format!("{:?}", err),
+    })
+}
+
+// parses input as data without turning it into code, used by read
+// an input of only whitespace contains no data
+pub fn parse_data<'input>(input: &'input str, interner: &mut StringInterner)
+-> Result<Vec<Value>, ParseError<usize, lexer::Token<'input>, error::Error>> {
+    if input.trim().is_empty() { return Ok(vec![]); }
     let tokenizer = lexer::Tokenizer::new(input);
-    parser::parse_TopLevelItem(input, true, interner, tokenizer)
+    let data = parser::parse_TopLevelItem(input, true, interner, &mut vec![], tokenizer)?;
+    resolve_labels(input, data, true)
+}
+
+// replaces datum labels like #0=(a . #0#), data that contains itself is only allowed if cycles is true
+fn resolve_labels<'input>(input: &str, data: Vec<Value>, cycles: bool)
+-> Result<Vec<Value>, ParseError<usize, lexer::Token<'input>, error::Error>> {
+    if !labels::may_contain_labels(input) { return Ok(data); }
+    labels::resolve(data, cycles).map_err(|err| ParseError::User { error: err })
+}
+
+// true if err happened because input ended too early, so more input could fix it
+pub fn is_incomplete(err: &ParseError<usize, lexer::Token, error::Error>) -> bool {
+    match err {
+        &ParseError::UnrecognizedToken { token: None, .. } => true,
+        &ParseError::User { error: error::Error::UnexpectedEofString(_) } => true,
+        &ParseError::User { error: error::Error::UnexpectedEofChar(_) } => true,
+        &ParseError::User { error: error::Error::UnexpectedEofComment(_) } => true,
+        &ParseError::User { error: error::Error::UnexpectedEofSymbol(_) } => true,
+        _ => false,
+    }
 }
 
-pub fn parse_integer<'input>(input: &'input str)
--> Result<Value, ::lalrpop_util::ParseError<usize, lexer::Token<'input>, error::Error>> {
-    // safe because we give parse_Integer false, so it knows the pointer is invalid
+pub fn parse_number<'input>(input: &'input str)
+-> Result<Value, ParseError<usize, lexer::Token<'input>, error::Error>> {
+    // safe because we give parse_Number false, so it knows the pointer is invalid
     let fake_interner: &mut StringInterner = unsafe { mem::transmute(0usize) };
     let tokenizer = lexer::Tokenizer::new(input);
-    parser::parse_Integer(input, false, fake_interner, tokenizer)
+    parser::parse_Number(input, false, fake_interner, &mut vec![], tokenizer)
 }
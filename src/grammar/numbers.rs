@@ -0,0 +1,98 @@
+use ::value::{Value, BigInt, Rational};
+
+// Numbers with prefixes, #x, #o, #b and #d for the radix and #e and #i for the exactness, e.g. #xff or #e1.5.
+// There's at most one of each kind, in any order. Decimals can only be written in radix 10,
+// #e makes them exact rationals and #i makes exact numbers floats.
+
+// exact decimals with exponents above this are too big to be useful
+const MAX_EXPONENT: i64 = 10000;
+
+// None if text isn't a number, radix is used if text has no radix prefix
+pub fn parse_prefixed(text: &str, radix: u32) -> Option<Value> {
+    let mut body = text;
+    let mut radix_prefix = None;
+    let mut exactness = None;
+    while body.starts_with('#') {
+        match body[1..].chars().next()? {
+            'x' | 'X' if radix_prefix.is_none() => radix_prefix = Some(16),
+            'o' | 'O' if radix_prefix.is_none() => radix_prefix = Some(8),
+            'b' | 'B' if radix_prefix.is_none() => radix_prefix = Some(2),
+            'd' | 'D' if radix_prefix.is_none() => radix_prefix = Some(10),
+            'e' | 'E' if exactness.is_none() => exactness = Some(true),
+            'i' | 'I' if exactness.is_none() => exactness = Some(false),
+            _ => return None,
+        }
+        body = &body[2..];
+    }
+    let radix = radix_prefix.unwrap_or(radix);
+
+    if let Some(slash) = body.find('/') {
+        let numerator = BigInt::parse_radix(&body[..slash], radix)?;
+        // no second sign
+        if body[slash+1..].starts_with('-') { return None; }
+        let denominator = BigInt::parse_radix(&body[slash+1..], radix)?;
+        let x = Rational::new(numerator, denominator)?;
+        return Some(if exactness == Some(false) { Value::new_float(x.to_f64()) } else { Value::new_rational(x) });
+    }
+
+    if let Some(x) = BigInt::parse_radix(body, radix) {
+        return Some(if exactness == Some(false) { Value::new_float(x.to_f64()) } else { Value::new_bigint(x) });
+    }
+
+    if radix != 10 { return None; }
+    let (mantissa, exponent) = decimal(body)?;
+    if exactness != Some(true) {
+        // safe because decimal checked the syntax
+        return Some(Value::new_float(body.parse().unwrap()));
+    }
+    if exponent.abs() > MAX_EXPONENT { return None; }
+    let mut power = BigInt::from(1);
+    for _ in 0..exponent.abs() { power = power * BigInt::from(10); }
+    if exponent >= 0 {
+        Some(Value::new_bigint(mantissa * power))
+    } else {
+        // safe because power isn't zero
+        Some(Value::new_rational(Rational::new(mantissa, power).unwrap()))
+    }
+}
+
+// a decimal like the lexer reads them, e.g. -1.5e3, as its digits and the exponent of 10 they're multiplied with
+fn decimal(text: &str) -> Option<(BigInt, i64)> {
+    let (mantissa, exponent) = match text.find(|c| c == 'e' || c == 'E') {
+        Some(i) => (&text[..i], Some(&text[i+1..])),
+        None => (text, None),
+    };
+
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], &mantissa[i+1..]),
+        None => (mantissa, ""),
+    };
+    let (negative, integer) = if integer.starts_with('-') { (true, &integer[1..]) } else { (false, integer) };
+    let digits = format!("{}{}", integer, fraction);
+    if digits.is_empty() || !digits.bytes().all(|b| b >= b'0' && b <= b'9') { return None; }
+
+    let exponent = match exponent {
+        Some(x) => {
+            let digits = if x.starts_with('+') || x.starts_with('-') { &x[1..] } else { x };
+            if digits.is_empty() || !digits.bytes().all(|b| b >= b'0' && b <= b'9') { return None; }
+            x.parse::<i64>().ok()?
+        },
+        None => 0,
+    };
+
+    let digits = BigInt::parse(&digits)?;
+    Some((if negative { -digits } else { digits }, exponent - fraction.len() as i64))
+}
+
+// x written in radix, None if it isn't an exact number
+pub fn exact_to_string(x: &Value, radix: u32) -> Option<String> {
+    if let Some(i) = x.get_integer() {
+        Some(BigInt::from(i).to_string_radix(radix))
+    } else if let Some(x) = x.get_bigint() {
+        Some(x.to_string_radix(radix))
+    } else if let Some(x) = x.get_rational() {
+        Some(format!("{}/{}", x.numerator().to_string_radix(radix), x.denominator().to_string_radix(radix)))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use ::value::Value;
+use ::string_interner::StringInterner;
+use super::lexer::{Token, Tokenizer};
+use super::error_printing;
+
+// Reads top level data incrementally, from text fed to it piece by piece or from a BufRead like a file or a socket.
+// A datum is returned as soon as it's complete, e.g. after "(a) (b" is fed (a) is, (b waits for the rest of its list.
+// Before the end of the input an item at the very end of the text isn't complete yet, the next piece could continue it.
+pub struct Reader {
+    // text that doesn't hold a complete datum yet
+    buffer: String,
+    // the line buffer starts at
+    line: usize,
+    // data parsed from the text of one complete datum, datum comments in front of it leave only it
+    pending: VecDeque<Value>,
+    // no more text is fed, the buffer is all that's left
+    finished: bool,
+    scan: Scan,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Reader { buffer: String::new(), line: 1, pending: VecDeque::new(), finished: false, scan: Scan::new() }
+    }
+
+    pub fn feed(&mut self, text: &str) {
+        self.buffer.push_str(text);
+    }
+
+    // there's no more text, what's left in the buffer is complete or an error
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    // true if no datum has been started, e.g. for a repl to show its prompt for new input.
+    // Whitespace and complete comments left after the last datum don't start one.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty() && whitespace_end(&self.buffer) == self.buffer.len()
+    }
+
+    // the fed text of the datum that isn't complete yet
+    pub fn buffered(&self) -> &str {
+        &self.buffer
+    }
+
+    // throws away the fed text that wasn't returned yet
+    pub fn clear(&mut self) {
+        self.line += self.buffer.matches('\n').count();
+        self.buffer.clear();
+        self.pending.clear();
+        self.scan = Scan::new();
+    }
+
+    // The text of the next complete datum and the line it starts at, None if more text is needed.
+    // Text the parser can't make sense of is returned too, so its error is reported.
+    pub fn next_source(&mut self) -> Option<(String, usize)> {
+        let (start, end) = match self.scan.datum_end(&self.buffer, self.finished) {
+            Some(span) => span,
+            None => {
+                // whitespace and comments aren't kept, so only a started datum is
+                let end = whitespace_end(&self.buffer);
+                self.line += self.buffer[..end].matches('\n').count();
+                self.buffer.drain(..end);
+                self.scan.drain(end);
+                return None;
+            },
+        };
+        self.line += self.buffer[..start].matches('\n').count();
+        let line = self.line;
+        let source: String = self.buffer[start..end].to_string();
+        self.line += source.matches('\n').count();
+        self.buffer.drain(..end);
+        Some((source, line))
+    }
+
+    // the next complete datum, like next_source but parsed, the error message if it can't be parsed
+    pub fn next(&mut self, interner: &mut StringInterner) -> Result<Option<Value>, String> {
+        loop {
+            if let Some(x) = self.pending.pop_front() { return Ok(Some(x)); }
+            let (source, _) = match self.next_source() {
+                Some(x) => x,
+                None => return Ok(None),
+            };
+            match super::parse_data(&source, interner) {
+                Ok(data) => self.pending.extend(data),
+                Err(ref err) => return Err(error_printing::create_error_message(&source, err)),
+            }
+        }
+    }
+
+    // reads lines of input until there's a complete datum, None at the end of input
+    pub fn read_from<R: BufRead>(&mut self, input: &mut R, interner: &mut StringInterner) -> Result<Option<Value>, String> {
+        loop {
+            if let Some(x) = self.next(interner)? { return Ok(Some(x)); }
+            if self.finished { return Ok(None); }
+
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) => self.finish(),
+                Ok(_) => self.feed(&line),
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+    }
+}
+
+// How far datum_end got in the buffer, so text that's fed later is scanned from there instead of from the start.
+struct Scan {
+    // where the next token starts, a token at the end of the buffer is scanned again with the text after it
+    pos: usize,
+    start: Option<usize>,
+    depth: usize,
+    // the data commented out by #; that are still to come
+    skipped: usize,
+}
+
+impl Scan {
+    fn new() -> Self {
+        Scan { pos: 0, start: None, depth: 0, skipped: 0 }
+    }
+
+    // for when the first bytes of the buffer are thrown away
+    fn drain(&mut self, bytes: usize) {
+        self.pos = self.pos.saturating_sub(bytes);
+        self.start = self.start.map(|start| start - bytes);
+    }
+
+    // Where the first datum in text starts and ends, None if text ends before it's complete.
+    // Quotes, datum comments and datum labels are part of the datum they're in front of. Lexer errors end the datum
+    // at the end of text, a ) that closes nothing ends it right away.
+    // text is the buffer, it only grew since the last call, the scan starts over once a datum was returned.
+    fn datum_end(&mut self, text: &str, finished: bool) -> Option<(usize, usize)> {
+        let span = self.scan(text, finished);
+        if span.is_some() { *self = Scan::new(); }
+        span
+    }
+
+    fn scan(&mut self, text: &str, finished: bool) -> Option<(usize, usize)> {
+        let offset = self.pos;
+        for token in Tokenizer::new(&text[offset..]) {
+            let (left, token, right) = match token {
+                Ok((left, token, right)) => (left + offset, token, right + offset),
+                Err(ref err) if is_eof(err) && !finished => return None,
+                Err(_) => return Some((self.start.unwrap_or(0), text.len())),
+            };
+            match token {
+                Token::String(_) | Token::RawString(_) | Token::PipeSymbol(_) | Token::ClosingParen => {},
+                // might go on in the next text, e.g. a symbol, a comment or , before @
+                _ if right == text.len() && !finished => return None,
+                _ => {},
+            }
+            self.pos = right;
+            match token {
+                Token::WhiteSpace => continue,
+                _ if self.start.is_none() => self.start = Some(left),
+                _ => {},
+            }
+            let complete = match token {
+                Token::OpenParen | Token::VectorParen => { self.depth += 1; false },
+                Token::ClosingParen if self.depth == 0 => return Some((self.start.unwrap(), right)),
+                Token::ClosingParen => { self.depth -= 1; self.depth == 0 },
+                Token::DatumComment if self.depth == 0 => { self.skipped += 1; false },
+                Token::QuoteTick | Token::QuasiQuoteTick | Token::Unquote | Token::UnquoteSplicing | Token::DatumComment
+                | Token::DatumLabel(_) => false,
+                _ => self.depth == 0,
+            };
+            if complete {
+                // safe because start was set by this token at the latest
+                if self.skipped == 0 { return Some((self.start.unwrap(), right)); }
+                self.skipped -= 1;
+            }
+        }
+        // only whitespace, or the end of text after quotes or datum comments
+        match self.start {
+            Some(start) if finished => Some((start, text.len())),
+            _ => None,
+        }
+    }
+}
+
+// where the whitespace and comments at the start of text end
+fn whitespace_end(text: &str) -> usize {
+    let mut end = 0;
+    for token in Tokenizer::new(text) {
+        match token {
+            // a comment at the end could go on in the next text
+            Ok((_, Token::WhiteSpace, right)) if right < text.len() || text.ends_with('\n') => end = right,
+            _ => break,
+        }
+    }
+    end
+}
+
+fn is_eof(err: &super::error::Error) -> bool {
+    use super::error::Error::*;
+    match *err {
+        UnexpectedEofString(_) | UnexpectedEofChar(_) | UnexpectedEofComment(_) | UnexpectedEofSymbol(_) => true,
+        _ => false,
+    }
+}
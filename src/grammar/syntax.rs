@@ -0,0 +1,821 @@
+use std::collections::HashSet;
+use ::native;
+use ::value::{Value, Condition, ClauseBody, CondClause, CaseClause, Template, RecordType};
+use ::interpreter::Interpreter;
+use ::string_interner::StringInterner;
+use ::grammar::error::Error;
+use ::grammar::macros::{Macros, SyntaxRules};
+
+// turns data as returned by the parser into code, i.e. macros are expanded,
+// special forms are created and tail calls are checked
+pub fn compile(datum: &Value, interner: &mut StringInterner, macros: &mut Macros) -> Result<Value, Error> {
+    let mut compiler = Compiler { interner: interner, macros: macros, scopes: vec![] };
+    compiler.compile(datum, false)
+}
+
+// everything compiled to a special form, for completion in the repl
+pub static KEYWORDS: &'static [&'static str] = &[
+    "begin", "define", "set!", "if", "and", "or", "when", "unless", "lambda", "let", "let*", "letrec",
+    "loop", "do", "recur", "quote", "define-syntax", "syntax-rules", "quasiquote", "unquote", "unquote-splicing",
+    "cond", "case", "else", "guard", "reset", "shift", "delay", "time", "define-module", "export", "import",
+    "define-record-type", "let-values", "let*-values",
+    "parameterize", "doc", "define-test", "assert-raises",
+];
+
+// the special forms others are turned into, a binding named like them doesn't shadow them, see Compiler::is_shadowed
+static CORE_FORMS: &'static [&'static str] = &[
+    "begin", "define", "set!", "if", "lambda", "let", "let*", "letrec", "loop", "recur",
+    "quote", "quasiquote", "unquote", "unquote-splicing", "cond",
+];
+
+// the names and unevaluated expressions of a binding list, either clojure style: (name expr name expr ..)
+// or scheme style: ((name expr) (name expr) ..)
+fn binding_pairs(datum: &Value) -> Option<Vec<(u64, Value)>> {
+    let list = match datum.get_list() {
+        Some(list) => list,
+        None => return None,
+    };
+    if list.is_empty() { return Some(vec![]); }
+
+    let pairs: Vec<Vec<Value>> = if list[0].get_symbol().is_some() {
+        if list.len() % 2 != 0 { return None; }
+        list.chunks(2).map(|pair| pair.to_vec()).collect()
+    } else {
+        match list.iter().map(|x| x.get_list()).collect() {
+            Some(pairs) => pairs,
+            None => return None,
+        }
+    };
+
+    pairs.into_iter().map(|pair| match (pair.len(), pair.get(0).and_then(|x| x.get_symbol())) {
+        (2, Some(name)) => Some((name, pair[1].clone())),
+        _ => None,
+    }).collect()
+}
+
+fn invalid_syntax<T>(form: &str, expected: &str) -> Result<T, Error> {
+    Err(Error::InvalidSyntax(format!("invalid {}, expected: {}", form, expected)))
+}
+
+// (args), (args . rest) but not a plain symbol, the symbols aren't checked
+pub fn is_parameter_list(datum: &Value) -> bool {
+    datum.get_list().is_some() || datum.get_dotted_list().is_some()
+}
+
+struct Compiler<'a> {
+    interner: &'a mut StringInterner,
+    macros: &'a mut Macros,
+    // the names bound by the lambdas and lets around the datum compiled, innermost last
+    scopes: Vec<HashSet<u64>>,
+}
+
+impl<'a> Compiler<'a> {
+    // recur_allowed is true if datum is in tail position of a lambda or loop
+    fn compile(&mut self, datum: &Value, recur_allowed: bool) -> Result<Value, Error> {
+        let list = match datum.get_list() {
+            Some(ref list) if !list.is_empty() => list.clone(),
+            _ if datum.get_dotted_list().is_some() => return invalid_syntax("application", "proper list"),
+            _ => return Ok(datum.clone()),
+        };
+
+        if let Some(expanded) = self.expand_macro(&list)? {
+            return self.compile(&expanded, recur_allowed);
+        }
+
+        let keyword = match list[0].get_symbol() {
+            Some(id) if !self.is_shadowed(id) => self.interner.lookup(id).map(String::from),
+            _ => None,
+        };
+        let rest = &list[1..];
+        match keyword.as_ref().map(|x| &**x) {
+            Some("begin") => Ok(Value::new_begin(self.compile_body(rest, recur_allowed)?)),
+            Some("define") => self.compile_define(rest),
+            Some("set!") => self.compile_set(rest),
+            Some("if") => self.compile_if(rest, recur_allowed),
+            // only the last operand of and & or is in tail position, like in a body
+            Some("and") => Ok(Value::new_and(self.compile_body(rest, recur_allowed)?)),
+            Some("or") => Ok(Value::new_or(self.compile_body(rest, recur_allowed)?)),
+            Some("when") => self.compile_when(rest, "when", recur_allowed),
+            Some("unless") => self.compile_when(rest, "unless", recur_allowed),
+            Some("lambda") => self.compile_lambda(rest),
+            Some("let") if rest.len() > 2 && rest[0].get_symbol().is_some() => self.compile_named_let(rest, recur_allowed),
+            Some("let") => self.compile_let(rest, "let", recur_allowed).map(|(b, c)| Value::new_let(b, c)),
+            Some("let*") => self.compile_let(rest, "let*", recur_allowed).map(|(b, c)| Value::new_let_star(b, c)),
+            Some("letrec") => self.compile_let(rest, "letrec", recur_allowed).map(|(b, c)| Value::new_letrec(b, c)),
+            Some("loop") => self.compile_let(rest, "loop", true).map(|(b, c)| Value::new_loop(b, c)),
+            Some("do") => self.compile_do(rest, recur_allowed),
+            Some("recur") => self.compile_recur(rest, recur_allowed),
+            Some("quote") => self.compile_quote(rest),
+            Some("define-syntax") => self.compile_define_syntax(rest),
+            Some("quasiquote") => {
+                if rest.len() != 1 { return invalid_syntax("quasiquote", "(quasiquote template)"); }
+                match self.compile_template(&rest[0], 0)? {
+                    Template::UnquoteSplicing(_) => Err(Error::InvalidSyntax("unquote-splicing outside of list".into())),
+                    template => Ok(Value::new_quasiquote(template)),
+                }
+            },
+            Some("unquote") | Some("unquote-splicing") => {
+                Err(Error::InvalidSyntax(format!("{} outside of quasiquote", keyword.unwrap())))
+            },
+            Some("cond") => self.compile_cond(rest, recur_allowed),
+            Some("case") => self.compile_case(rest, recur_allowed),
+            Some("guard") => self.compile_guard(rest),
+            Some("reset") => self.compile_reset(rest),
+            Some("shift") => self.compile_shift(rest),
+            Some("delay") => self.compile_delay(rest),
+            Some("time") => self.compile_time(rest),
+            Some("define-module") => self.compile_define_module(rest),
+            Some("define-record-type") => self.compile_define_record_type(rest),
+            Some("let-values") => self.compile_let_values(rest, "let-values"),
+            Some("let*-values") => self.compile_let_values(rest, "let*-values"),
+            Some("parameterize") => self.compile_parameterize(rest),
+            Some("doc") => self.compile_doc(rest),
+            Some("define-test") => self.compile_define_test(rest),
+            Some("assert-raises") => self.compile_assert_raises(rest),
+            Some("export") => self.compile_names_form(rest, "export", native::export),
+            Some("import") => self.compile_names_form(rest, "import", native::import),
+            _ => {
+                let values = self.compile_all(&list)?;
+                Ok(Value::new_list(&values))
+            },
+        }
+    }
+
+    // true if a binding named like the special form id is in scope, so (id args) is a call
+    fn is_shadowed(&self, id: u64) -> bool {
+        if self.interner.lookup(id).map_or(false, |name| CORE_FORMS.contains(&name)) { return false; }
+        self.scopes.iter().any(|scope| scope.contains(&id)) || self.macros.is_global(id)
+    }
+
+    // a define binds name in the innermost scope, or globally at the top level
+    fn bind(&mut self, name: u64) {
+        match self.scopes.last_mut() {
+            Some(scope) => { scope.insert(name); },
+            None => self.macros.define_global(name),
+        }
+    }
+
+    // returns the expansion if list is a macro use
+    fn expand_macro(&mut self, list: &[Value]) -> Result<Option<Value>, Error> {
+        let macros = &*self.macros;
+        let macro_rules = match list[0].get_symbol().and_then(|id| macros.get(id)) {
+            Some(macro_rules) => macro_rules,
+            None => return Ok(None),
+        };
+        macro_rules.expand(list, self.interner).map(Some)
+    }
+
+    // macros are defined at compile time, at runtime this only returns the name
+    fn compile_define_syntax(&mut self, data: &[Value]) -> Result<Value, Error> {
+        let name = match (data.len(), data.get(0).and_then(|x| x.get_symbol())) {
+            (2, Some(name)) => name,
+            _ => return invalid_syntax("define-syntax", "(define-syntax name (syntax-rules (literals*) (pattern template)*))"),
+        };
+
+        let rules = SyntaxRules::new(name, &data[1], self.interner)?;
+        self.macros.define(name, rules);
+        Ok(Value::new_quote(Value::new_symbol(name)))
+    }
+
+    // compiles every value in non tail position
+    fn compile_all(&mut self, data: &[Value]) -> Result<Vec<Value>, Error> {
+        data.iter().map(|x| self.compile(x, false)).collect()
+    }
+
+    // only the last expression of a body is in tail position
+    fn compile_body(&mut self, data: &[Value], recur_allowed: bool) -> Result<Vec<Value>, Error> {
+        if data.is_empty() { return Ok(vec![]); }
+
+        let (last, init) = data.split_last().unwrap();
+        let mut code = self.compile_all(init)?;
+        code.push(self.compile(last, recur_allowed)?);
+        Ok(code)
+    }
+
+    // a string starting a procedure body that has more forms is its documentation, the define is turned into
+    // (begin (%symbol-put! 'name 'doc "doc") (define name ...)), see Interpreter::documentation
+    fn compile_define(&mut self, data: &[Value]) -> Result<Value, Error> {
+        let define = self.compile_definition(data)?;
+        match self.docstring(data) {
+            Some((name, doc)) => {
+                let key = self.symbol("doc");
+                let put = vec![Value::new_native_proc(native::symbol_put), self.quote(&Value::new_symbol(name)), self.quote(&key), doc];
+                let put = self.compile(&Value::new_list(&put), false)?;
+                Ok(Value::new_begin(vec![put, define]))
+            },
+            None => Ok(define),
+        }
+    }
+
+    // the name and the documentation of (define (name args) "doc" body) or (define name (lambda args "doc" body))
+    fn docstring(&self, data: &[Value]) -> Option<(u64, Value)> {
+        let (name, body) = match data.get(0).and_then(|x| x.get_symbol()) {
+            Some(name) => {
+                let lambda = match data.get(1).and_then(|x| x.get_list()) {
+                    Some(ref lambda) if lambda.len() >= 3 && lambda[0].get_symbol().and_then(|id| self.interner.lookup(id)) == Some("lambda") => lambda.clone(),
+                    _ => return None,
+                };
+                // the lambda can have a name before its parameters
+                let named = lambda[1].get_symbol().is_some() && lambda.len() >= 4 && is_parameter_list(&lambda[2]);
+                (name, lambda[if named { 3 } else { 2 }..].to_vec())
+            },
+            None => match data.get(0).and_then(|x| x.get_list()).and_then(|list| list.get(0).and_then(|x| x.get_symbol())) {
+                Some(name) => (name, data[1..].to_vec()),
+                None => return None,
+            },
+        };
+        match body.get(0) {
+            Some(doc) if body.len() >= 2 && doc.get_string().is_some() => Some((name, doc.clone())),
+            _ => None,
+        }
+    }
+
+    fn compile_definition(&mut self, data: &[Value]) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(define name expression) or (define (name args) body)";
+        if let Some(name) = data.get(0).and_then(|x| x.get_symbol()) {
+            if data.len() != 2 { return invalid_syntax("define", EXPECTED); }
+            self.bind(name);
+            return Ok(Value::new_define(name, self.compile(&data[1], false)?));
+        }
+
+        // (define (name args) body) is short for (define name (lambda name (args) body))
+        if data.len() < 2 { return invalid_syntax("define", EXPECTED); }
+        if let Some(list) = data.get(0).and_then(|x| x.get_list()) {
+            if let Some(name) = list.get(0).and_then(|x| x.get_symbol()) {
+                self.bind(name);
+                let procedure_name = self.interner.lookup(name).map(String::from);
+                if let Some(lambda) = self.compile_optional_parameters(procedure_name, &list[1..], &data[1..], "define")? {
+                    return Ok(Value::new_define(name, lambda));
+                }
+            }
+        }
+        let (name, args, rest) = match data.get(0).and_then(|x| self.parameters(x)) {
+            Some((ref args, rest)) if !args.is_empty() => (args[0], args[1..].to_vec(), rest),
+            _ => return invalid_syntax("define", EXPECTED),
+        };
+
+        self.bind(name);
+        let code = self.compile_procedure_body(&args, rest, &data[1..])?;
+        let lambda = Value::new_lambda(self.interner.lookup(name).map(String::from), args, rest, code);
+        Ok(Value::new_define(name, lambda))
+    }
+
+    fn compile_set(&mut self, data: &[Value]) -> Result<Value, Error> {
+        match (data.len(), data.get(0).and_then(|x| x.get_symbol())) {
+            (2, Some(name)) => Ok(Value::new_set(name, self.compile(&data[1], false)?)),
+            _ => invalid_syntax("set!", "(set! name expression)"),
+        }
+    }
+
+    fn compile_if(&mut self, data: &[Value], recur_allowed: bool) -> Result<Value, Error> {
+        if data.len() != 3 { return invalid_syntax("if", "(if condition then else)"); }
+
+        let condition = self.compile(&data[0], false)?;
+        let then = self.compile(&data[1], recur_allowed)?;
+        let or_else = self.compile(&data[2], recur_allowed)?;
+        Ok(Value::new_if(condition, then, or_else))
+    }
+
+    // (when test body) is short for (if test (begin body) '()), unless swaps the branches
+    fn compile_when(&mut self, data: &[Value], keyword: &str, recur_allowed: bool) -> Result<Value, Error> {
+        if data.len() < 2 { return invalid_syntax(keyword, &format!("({} test body)", keyword)); }
+
+        let test = self.compile(&data[0], false)?;
+        let body = Value::new_begin(self.compile_body(&data[1..], recur_allowed)?);
+        let nothing = Value::new_quote(Value::empty_list());
+        if keyword == "when" {
+            Ok(Value::new_if(test, body, nothing))
+        } else {
+            Ok(Value::new_if(test, nothing, body))
+        }
+    }
+
+    // (guard (var clause*) body) is turned into
+    // (with-exception-handler (lambda (var) (cond clause* (else (raise var)))) (lambda () body))
+    // the natives are inserted as values, so shadowing their names doesn't change guard
+    fn compile_guard(&mut self, data: &[Value]) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(guard (var clause*) body)";
+        if data.len() < 2 { return invalid_syntax("guard", EXPECTED); }
+        let spec = match data[0].get_list() {
+            Some(ref spec) if !spec.is_empty() && spec[0].get_symbol().is_some() => spec.clone(),
+            _ => return invalid_syntax("guard", EXPECTED),
+        };
+
+        let var = spec[0].clone();
+        let mut clauses = spec[1..].to_vec();
+        let has_else = clauses.last().and_then(Value::get_list)
+        .map_or(false, |clause| !clause.is_empty() && self.is_keyword(&clause[0], "else"));
+        if !has_else {
+            let reraise = Value::new_list(&[Value::new_native_proc(native::raise), var.clone()]);
+            clauses.push(Value::new_list(&[self.symbol("else"), reraise]));
+        }
+
+        let mut cond = vec![self.symbol("cond")];
+        cond.extend(clauses);
+        let handler = Value::new_list(&[self.symbol("lambda"), Value::new_list(&[var]), Value::new_list(&cond)]);
+        let mut thunk = vec![self.symbol("lambda"), Value::empty_list()];
+        thunk.extend(data[1..].iter().cloned());
+
+        let expanded = Value::new_list(&[Value::new_native_proc(native::with_exception_handler), handler, Value::new_list(&thunk)]);
+        self.compile(&expanded, false)
+    }
+
+    // (reset body) is turned into (%reset (lambda () body)), with the native inserted as value like in guard
+    fn compile_reset(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.is_empty() { return invalid_syntax("reset", "(reset body)"); }
+        let mut thunk = vec![self.symbol("lambda"), Value::empty_list()];
+        thunk.extend(data.iter().cloned());
+        let expanded = Value::new_list(&[Value::new_native_proc(native::reset), Value::new_list(&thunk)]);
+        self.compile(&expanded, false)
+    }
+
+    // (shift k body) is turned into (%shift (lambda (k) body))
+    fn compile_shift(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.len() < 2 || data[0].get_symbol().is_none() { return invalid_syntax("shift", "(shift k body)"); }
+        let mut f = vec![self.symbol("lambda"), Value::new_list(&data[..1])];
+        f.extend(data[1..].iter().cloned());
+        let expanded = Value::new_list(&[Value::new_native_proc(native::shift), Value::new_list(&f)]);
+        self.compile(&expanded, false)
+    }
+
+    // (delay expr) is turned into (%delay (lambda () expr))
+    fn compile_delay(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.len() != 1 { return invalid_syntax("delay", "(delay expr)"); }
+        let thunk = Value::new_list(&[self.symbol("lambda"), Value::empty_list(), data[0].clone()]);
+        let expanded = Value::new_list(&[Value::new_native_proc(native::delay), thunk]);
+        self.compile(&expanded, false)
+    }
+
+    // (time expr) is turned into (%time (lambda () expr))
+    fn compile_time(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.len() != 1 { return invalid_syntax("time", "(time expr)"); }
+        let thunk = Value::new_list(&[self.symbol("lambda"), Value::empty_list(), data[0].clone()]);
+        let expanded = Value::new_list(&[Value::new_native_proc(native::time), thunk]);
+        self.compile(&expanded, false)
+    }
+
+    // (define-module name body) is turned into (%define-module 'name body), body isn't evaluated up front by the native
+    fn compile_define_module(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.is_empty() { return invalid_syntax("define-module", "(define-module name body)"); }
+        let mut expanded = vec![Value::new_native_proc(native::define_module), self.quote(&data[0])];
+        expanded.extend(data[1..].iter().cloned());
+        self.compile(&Value::new_list(&expanded), false)
+    }
+
+    // (define-record-type point (make-point x y) point? (x point-x set-point-x!) (y point-y)) is turned into
+    // defines of the type and procedures calling the record natives, the type is created here,
+    // so it's the same every time the form is evaluated
+    fn compile_define_record_type(&mut self, data: &[Value]) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(define-record-type name (constructor field*) predicate (field accessor modifier?)*)";
+        let (name, constructor, predicate) = match (data.get(0).and_then(|x| x.get_symbol()), data.get(1).and_then(|x| self.symbols(x)), data.get(2)) {
+            (Some(name), Some(ref constructor), Some(predicate)) if !constructor.is_empty() && predicate.get_symbol().is_some() => {
+                (name, constructor.clone(), predicate.clone())
+            },
+            _ => return invalid_syntax("define-record-type", EXPECTED),
+        };
+        let specs = match data[3..].iter().map(|x| self.symbols(x)).collect::<Option<Vec<_>>>() {
+            Some(ref specs) if specs.iter().all(|spec| spec.len() == 2 || spec.len() == 3) => specs.clone(),
+            _ => return invalid_syntax("define-record-type", EXPECTED),
+        };
+
+        let fields: Vec<u64> = specs.iter().map(|spec| spec[0]).collect();
+        if let Some(&unknown) = constructor[1..].iter().find(|x| !fields.contains(x)) {
+            let unknown = self.interner.lookup(unknown).unwrap_or("?").to_string();
+            return Err(Error::InvalidSyntax(format!("invalid define-record-type, constructor has unknown field: {}", unknown)));
+        }
+        let record_type = self.quote(&Value::new_record_type(RecordType::new(name, fields.clone())));
+
+        let mut defines = vec![self.symbol("begin")];
+        defines.push(Value::new_list(&[self.symbol("define"), Value::new_symbol(name), record_type.clone()]));
+
+        // fields that aren't arguments of the constructor are false
+        let mut make = vec![Value::new_native_proc(native::make_record), record_type.clone()];
+        make.extend(fields.iter().map(|x| if constructor.contains(x) { Value::new_symbol(*x) } else { Value::new_bool(false) }));
+        let signature: Vec<Value> = constructor.iter().map(|&x| Value::new_symbol(x)).collect();
+        defines.push(Value::new_list(&[self.symbol("define"), Value::new_list(&signature), Value::new_list(&make)]));
+
+        let x = self.symbol("x");
+        let test = Value::new_list(&[Value::new_native_proc(native::is_record), record_type.clone(), x.clone()]);
+        defines.push(Value::new_list(&[self.symbol("define"), Value::new_list(&[predicate, x.clone()]), test]));
+
+        let value = self.symbol("value");
+        for (index, spec) in specs.iter().enumerate() {
+            let index = Value::new_integer(index as i64);
+            let accessor = Value::new_symbol(spec[1]);
+            let get = Value::new_list(&[Value::new_native_proc(native::record_ref), record_type.clone(), self.quote(&accessor), index.clone(), x.clone()]);
+            defines.push(Value::new_list(&[self.symbol("define"), Value::new_list(&[accessor, x.clone()]), get]));
+
+            if let Some(&modifier) = spec.get(2) {
+                let modifier = Value::new_symbol(modifier);
+                let set = Value::new_list(&[Value::new_native_proc(native::record_set), record_type.clone(), self.quote(&modifier), index, x.clone(), value.clone()]);
+                defines.push(Value::new_list(&[self.symbol("define"), Value::new_list(&[modifier, x.clone(), value.clone()]), set]));
+            }
+        }
+        // like define-syntax, the name of the type is returned
+        defines.push(self.quote(&Value::new_symbol(name)));
+        self.compile(&Value::new_list(&defines), false)
+    }
+
+    // (let-values (((a b) expr) (rest expr)) body) is turned into
+    // (%call-with-values (lambda () expr) (lambda (a b) (let-values ((rest expr)) body))),
+    // so the formals can be anything lambda accepts. Every expr sees the bindings before it, i.e. it works like let*-values.
+    fn compile_let_values(&mut self, data: &[Value], keyword: &str) -> Result<Value, Error> {
+        let expected = format!("({} ((formals expr)*) body)", keyword);
+        let bindings = match data.get(0).and_then(|x| x.get_list()) {
+            Some(bindings) if data.len() >= 2 => bindings,
+            _ => return invalid_syntax(keyword, &expected),
+        };
+
+        let mut expanded = vec![self.symbol("let"), Value::empty_list()];
+        expanded.extend(data[1..].iter().cloned());
+        for binding in bindings.iter().rev() {
+            let (formals, expr) = match binding.get_list() {
+                Some(ref binding) if binding.len() == 2 => (binding[0].clone(), binding[1].clone()),
+                _ => return invalid_syntax(keyword, &expected),
+            };
+            let producer = Value::new_list(&[self.symbol("lambda"), Value::empty_list(), expr]);
+            let consumer = Value::new_list(&[self.symbol("lambda"), formals, Value::new_list(&expanded)]);
+            expanded = vec![Value::new_native_proc(native::call_with_values), producer, consumer];
+        }
+        self.compile(&Value::new_list(&expanded), false)
+    }
+
+    // (parameterize ((p value) ...) body) is turned into (%parameterize (lambda () body) p value ...)
+    fn compile_parameterize(&mut self, data: &[Value]) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(parameterize ((parameter value)*) body)";
+        let bindings = match data.get(0).and_then(|x| x.get_list()) {
+            Some(bindings) if data.len() >= 2 => bindings,
+            _ => return invalid_syntax("parameterize", EXPECTED),
+        };
+
+        let mut thunk = vec![self.symbol("lambda"), Value::empty_list()];
+        thunk.extend(data[1..].iter().cloned());
+        let mut expanded = vec![Value::new_native_proc(native::parameterize), Value::new_list(&thunk)];
+        for binding in bindings {
+            match binding.get_list() {
+                Some(ref binding) if binding.len() == 2 => expanded.extend(binding.iter().cloned()),
+                _ => return invalid_syntax("parameterize", EXPECTED),
+            }
+        }
+        self.compile(&Value::new_list(&expanded), false)
+    }
+
+    // (doc name) is turned into (%doc 'name)
+    fn compile_doc(&mut self, data: &[Value]) -> Result<Value, Error> {
+        match (data.len(), data.get(0).and_then(|x| x.get_symbol())) {
+            (1, Some(_)) => {
+                let expanded = vec![Value::new_native_proc(native::doc), self.quote(&data[0])];
+                self.compile(&Value::new_list(&expanded), false)
+            },
+            _ => invalid_syntax("doc", "(doc name)"),
+        }
+    }
+
+    // (define-test name body) is turned into (%define-test 'name (lambda () body))
+    fn compile_define_test(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.len() < 2 || data[0].get_symbol().is_none() { return invalid_syntax("define-test", "(define-test name body)"); }
+        let mut thunk = vec![self.symbol("lambda"), Value::empty_list()];
+        thunk.extend(data[1..].iter().cloned());
+        let expanded = vec![Value::new_native_proc(native::define_test), self.quote(&data[0]), Value::new_list(&thunk)];
+        self.compile(&Value::new_list(&expanded), false)
+    }
+
+    // (assert-raises expr) is turned into (%assert-raises 'expr (lambda () expr)), the failure shows expr
+    fn compile_assert_raises(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.len() != 1 { return invalid_syntax("assert-raises", "(assert-raises expr)"); }
+        let thunk = Value::new_list(&[self.symbol("lambda"), Value::empty_list(), data[0].clone()]);
+        let expanded = vec![Value::new_native_proc(native::assert_raises), self.quote(&data[0]), thunk];
+        self.compile(&Value::new_list(&expanded), false)
+    }
+
+    // (export names) and (import names) are turned into calls of their natives with quoted names
+    fn compile_names_form(&mut self, data: &[Value], form: &str, f: fn(&mut Interpreter, &mut [Value]) -> Result<Value, Condition>) -> Result<Value, Error> {
+        if data.is_empty() { return invalid_syntax(form, &format!("({} name+)", form)); }
+        let mut expanded = vec![Value::new_native_proc(f)];
+        for x in data {
+            expanded.push(self.quote(x));
+        }
+        self.compile(&Value::new_list(&expanded), false)
+    }
+
+    fn quote(&mut self, datum: &Value) -> Value {
+        Value::new_list(&[self.symbol("quote"), datum.clone()])
+    }
+
+    fn symbol(&mut self, name: &str) -> Value {
+        Value::new_symbol(self.interner.intern(name))
+    }
+
+    fn compile_lambda(&mut self, data: &[Value]) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(lambda name? (args) body), (lambda name? (args . rest) body) or (lambda rest body)";
+
+        // optional name, only if followed by a parameter list and a body, (lambda args body) binds all args to args
+        let (name, data) = match data.get(0).and_then(|x| x.get_symbol()) {
+            Some(id) if data.len() >= 3 && is_parameter_list(&data[1]) => (self.interner.lookup(id).map(String::from), &data[1..]),
+            _ => (None, data),
+        };
+
+        if data.len() < 2 { return invalid_syntax("lambda", EXPECTED); }
+
+        if let Some(list) = data[0].get_list() {
+            if let Some(lambda) = self.compile_optional_parameters(name.clone(), &list, &data[1..], "lambda")? {
+                return Ok(lambda);
+            }
+        }
+        let (args, rest) = match data[0].get_symbol() {
+            Some(rest) => (vec![], Some(rest)),
+            None => match self.parameters(&data[0]) {
+                Some(parameters) => parameters,
+                None => return invalid_syntax("lambda", EXPECTED),
+            },
+        };
+
+        let code = self.compile_procedure_body(&args, rest, &data[1..])?;
+        Ok(Value::new_lambda(name, args, rest, code))
+    }
+
+    // the body of a lambda, which binds its parameters
+    fn compile_procedure_body(&mut self, args: &[u64], rest: Option<u64>, body: &[Value]) -> Result<Vec<Value>, Error> {
+        self.scopes.push(args.iter().cloned().chain(rest).collect());
+        let code = self.compile_body(body, true)?;
+        self.scopes.pop();
+        Ok(code)
+    }
+
+    // Parameters after #:optional and #:key are written as name or (name default), defaults are false if left out.
+    // The procedure gets all args as a rest parameter instead, which %lambda-arguments checks and splits up:
+    // (lambda f (a #:optional (b 1) #:key c) body) is turned into
+    // (lambda f rest (let* ((args (%lambda-arguments 'f rest 1 1 '(#:c)))
+    //                       (a (vector-ref args 0))
+    //                       (b (if (null? (vector-ref args 1)) 1 (car (vector-ref args 1))))
+    //                       (c (if (null? (vector-ref args 2)) false (car (vector-ref args 2)))))
+    //                  body))
+    // None if there are neither optional nor keyword parameters.
+    fn compile_optional_parameters(&mut self, name: Option<String>, parameters: &[Value], body: &[Value], form: &str) -> Result<Option<Value>, Error> {
+        const EXPECTED: &'static str = "(args #:optional (arg default)* #:key (arg default)*)";
+        if !parameters.iter().any(|x| self.is_marker(x, "optional") || self.is_marker(x, "key")) {
+            return Ok(None);
+        }
+
+        let mut required = vec![];
+        // name, default and whether it's a keyword parameter
+        let mut defaulted: Vec<(u64, Value, bool)> = vec![];
+        let (mut optional, mut key) = (false, false);
+        for x in parameters {
+            if self.is_marker(x, "optional") && !optional && !key {
+                optional = true;
+                continue;
+            }
+            if self.is_marker(x, "key") && !key {
+                key = true;
+                continue;
+            }
+
+            match (x.get_symbol(), x.get_list()) {
+                (Some(id), _) if optional || key => defaulted.push((id, Value::new_bool(false), key)),
+                (Some(id), _) => required.push(id),
+                (None, Some(ref spec)) if spec.len() == 2 && spec[0].get_symbol().is_some() && (optional || key) => {
+                    defaulted.push((spec[0].get_symbol().unwrap(), spec[1].clone(), key));
+                },
+                _ => return invalid_syntax(form, EXPECTED),
+            }
+        }
+
+        let rest = Value::new_symbol(self.interner.gensym("rest"));
+        let args = Value::new_symbol(self.interner.gensym("args"));
+        let keywords: Vec<Value> = defaulted.iter().filter(|x| x.2).map(|x| Value::new_keyword(x.0)).collect();
+        let optional_count = defaulted.len() - keywords.len();
+        let procedure_name = self.symbol(name.as_ref().map(|x| &**x).unwrap_or("lambda"));
+        let split = Value::new_list(&[
+            Value::new_native_proc(native::lambda_arguments), self.quote(&procedure_name), rest.clone(),
+            Value::new_integer(required.len() as i64), Value::new_integer(optional_count as i64), self.quote(&Value::new_list(&keywords)),
+        ]);
+
+        let mut bindings = vec![Value::new_list(&[args.clone(), split])];
+        for (i, &id) in required.iter().enumerate() {
+            let element = Value::new_list(&[Value::new_native_proc(native::vector_ref), args.clone(), Value::new_integer(i as i64)]);
+            bindings.push(Value::new_list(&[Value::new_symbol(id), element]));
+        }
+        for (i, &(id, ref default, _)) in defaulted.iter().enumerate() {
+            let index = required.len() + i;
+            let element = Value::new_list(&[Value::new_native_proc(native::vector_ref), args.clone(), Value::new_integer(index as i64)]);
+            let missing = Value::new_list(&[Value::new_native_proc(native::null_), element.clone()]);
+            let passed = Value::new_list(&[Value::new_native_proc(native::car), element]);
+            let value = Value::new_list(&[self.symbol("if"), missing, default.clone(), passed]);
+            bindings.push(Value::new_list(&[Value::new_symbol(id), value]));
+        }
+        let mut let_star = vec![self.symbol("let*"), Value::new_list(&bindings)];
+        let_star.extend(body.iter().cloned());
+
+        let code = self.compile_procedure_body(&[], rest.get_symbol(), &[Value::new_list(&let_star)])?;
+        Ok(Some(Value::new_lambda(name, vec![], rest.get_symbol(), code)))
+    }
+
+    // returns the bindings and the body of let, let*, letrec and loop
+    fn compile_let(&mut self, data: &[Value], keyword: &str, recur_allowed: bool) -> Result<(Vec<(u64, Value)>, Vec<Value>), Error> {
+        let expected = format!("({} (bindings) body)", keyword);
+        if data.len() < 2 { return invalid_syntax(keyword, &expected); }
+
+        let pairs = match binding_pairs(&data[0]) {
+            Some(pairs) => pairs,
+            None => return invalid_syntax(keyword, &expected),
+        };
+
+        // the names are in scope after all exprs for let, after their own expr for let* and in every expr otherwise
+        let names: Vec<u64> = pairs.iter().map(|&(name, _)| name).collect();
+        self.scopes.push(if keyword == "letrec" || keyword == "loop" { names.iter().cloned().collect() } else { HashSet::new() });
+        let mut bindings = vec![];
+        for (name, expr) in pairs {
+            bindings.push((name, self.compile(&expr, false)?));
+            if keyword == "let*" { self.bind(name); }
+        }
+        // safe because the scope was pushed above
+        self.scopes.last_mut().unwrap().extend(names);
+
+        let code = self.compile_body(&data[1..], recur_allowed)?;
+        self.scopes.pop();
+        Ok((bindings, code))
+    }
+
+    // (let name (bindings) body) is turned into ((letrec (name (lambda name (names) body)) name) exprs)
+    fn compile_named_let(&mut self, data: &[Value], recur_allowed: bool) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(let name (bindings) body)";
+        let pairs = match binding_pairs(&data[1]) {
+            Some(pairs) => pairs,
+            None => return invalid_syntax("let", EXPECTED),
+        };
+
+        let names: Vec<Value> = pairs.iter().map(|&(name, _)| Value::new_symbol(name)).collect();
+        let mut lambda = vec![self.symbol("lambda"), data[0].clone(), Value::new_list(&names)];
+        lambda.extend(data[2..].iter().cloned());
+        let binding = Value::new_list(&[data[0].clone(), Value::new_list(&lambda)]);
+        let letrec = Value::new_list(&[self.symbol("letrec"), binding, data[0].clone()]);
+
+        let mut call = vec![letrec];
+        call.extend(pairs.into_iter().map(|(_, expr)| expr));
+        self.compile(&Value::new_list(&call), recur_allowed)
+    }
+
+    // (do ((name init step?)*) (test result*) command*) is turned into
+    // (loop (name init ..) (if test (begin result*) (begin command* (recur step ..))))
+    // a name without step keeps its value
+    fn compile_do(&mut self, data: &[Value], recur_allowed: bool) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(do ((name init step?)*) (test result*) command*)";
+        let specs = match data.get(0).and_then(Value::get_list) {
+            Some(specs) => specs,
+            None => return invalid_syntax("do", EXPECTED),
+        };
+        let exit = match data.get(1).and_then(Value::get_list) {
+            Some(ref exit) if !exit.is_empty() => exit.clone(),
+            _ => return invalid_syntax("do", EXPECTED),
+        };
+
+        let (mut bindings, mut steps) = (vec![], vec![self.symbol("recur")]);
+        for spec in specs {
+            match spec.get_list() {
+                Some(ref spec) if (spec.len() == 2 || spec.len() == 3) && spec[0].get_symbol().is_some() => {
+                    bindings.push(Value::new_list(&spec[..2]));
+                    steps.push(spec.get(2).unwrap_or(&spec[0]).clone());
+                },
+                _ => return invalid_syntax("do", EXPECTED),
+            }
+        }
+
+        let mut result = vec![self.symbol("begin")];
+        result.extend(exit[1..].iter().cloned());
+        let mut body = vec![self.symbol("begin")];
+        body.extend(data[2..].iter().cloned());
+        body.push(Value::new_list(&steps));
+
+        let test = Value::new_list(&[self.symbol("if"), exit[0].clone(), Value::new_list(&result), Value::new_list(&body)]);
+        let expanded = Value::new_list(&[self.symbol("loop"), Value::new_list(&bindings), test]);
+        self.compile(&expanded, recur_allowed)
+    }
+
+    fn compile_recur(&mut self, data: &[Value], recur_allowed: bool) -> Result<Value, Error> {
+        if !recur_allowed { return Err(Error::RecurInNonTailPosition); }
+        Ok(Value::new_recur_form(self.compile_all(data)?))
+    }
+
+    fn compile_quote(&mut self, data: &[Value]) -> Result<Value, Error> {
+        if data.len() != 1 { return invalid_syntax("quote", "(quote datum)"); }
+        Ok(Value::new_quote(data[0].clone()))
+    }
+
+    // Depth is the number of quasiquotes the template is nested in, minus one.
+    // Only unquotes at depth 0 are evaluated, the others stay data.
+    fn compile_template(&mut self, datum: &Value, depth: usize) -> Result<Template, Error> {
+        let list = match datum.get_list() {
+            Some(ref list) if !list.is_empty() => list.clone(),
+            _ => return Ok(Template::Datum(datum.clone())),
+        };
+
+        let keyword = list[0].get_symbol().and_then(|id| self.interner.lookup(id)).map(String::from);
+        let depth = match (keyword.as_ref().map(|x| &**x), list.len()) {
+            (Some("unquote"), 2) if depth == 0 => return Ok(Template::Unquote(self.compile(&list[1], false)?)),
+            (Some("unquote-splicing"), 2) if depth == 0 => return Ok(Template::UnquoteSplicing(self.compile(&list[1], false)?)),
+            (Some("unquote"), 2) | (Some("unquote-splicing"), 2) => depth - 1,
+            (Some("quasiquote"), 2) => depth + 1,
+            _ => depth,
+        };
+
+        let templates = list.iter().map(|x| self.compile_template(x, depth)).collect::<Result<Vec<_>, _>>()?;
+
+        // lists without unquotes don't need to be rebuilt
+        if templates.iter().all(|x| if let &Template::Datum(_) = x { true } else { false }) {
+            Ok(Template::Datum(datum.clone()))
+        } else {
+            Ok(Template::List(templates))
+        }
+    }
+
+    fn compile_cond(&mut self, data: &[Value], recur_allowed: bool) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(cond (test body) .. (else body)?)";
+
+        let mut clauses = vec![];
+        for (i, clause) in data.iter().enumerate() {
+            let clause = match clause.get_list() {
+                Some(ref clause) if !clause.is_empty() => clause.clone(),
+                _ => return invalid_syntax("cond", EXPECTED),
+            };
+
+            let test = if self.is_keyword(&clause[0], "else") {
+                // else has to be the last clause
+                if i != data.len() - 1 { return invalid_syntax("cond", EXPECTED); }
+                None
+            } else {
+                Some(self.compile(&clause[0], false)?)
+            };
+
+            let body = self.clause_body(&clause[1..], "cond", EXPECTED, recur_allowed)?;
+            clauses.push(CondClause::new(test, body));
+        }
+        Ok(Value::new_cond(clauses))
+    }
+
+    fn compile_case(&mut self, data: &[Value], recur_allowed: bool) -> Result<Value, Error> {
+        const EXPECTED: &'static str = "(case key ((datum ..) body) .. (else body)?)";
+        if data.is_empty() { return invalid_syntax("case", EXPECTED); }
+
+        let key = self.compile(&data[0], false)?;
+        let data = &data[1..];
+
+        let mut clauses = vec![];
+        for (i, clause) in data.iter().enumerate() {
+            let clause = match clause.get_list() {
+                Some(ref clause) if clause.len() > 1 => clause.clone(),
+                _ => return invalid_syntax("case", EXPECTED),
+            };
+
+            let data = if self.is_keyword(&clause[0], "else") {
+                if i != data.len() - 1 { return invalid_syntax("case", EXPECTED); }
+                None
+            } else {
+                match clause[0].get_list() {
+                    Some(data) => Some(data),
+                    None => return invalid_syntax("case", EXPECTED),
+                }
+            };
+
+            let body = self.clause_body(&clause[1..], "case", EXPECTED, recur_allowed)?;
+            clauses.push(CaseClause::new(data, body));
+        }
+        Ok(Value::new_case(key, clauses))
+    }
+
+    // the part of a cond or case clause after the test
+    fn clause_body(&mut self, data: &[Value], form: &str, expected: &str, recur_allowed: bool) -> Result<ClauseBody, Error> {
+        match data.get(0) {
+            Some(x) if self.is_keyword(x, "=>") => {
+                if data.len() != 2 { return invalid_syntax(form, expected); }
+                Ok(ClauseBody::Receiver(self.compile(&data[1], false)?))
+            },
+            _ => Ok(ClauseBody::Code(self.compile_body(data, recur_allowed)?)),
+        }
+    }
+
+    fn is_keyword(&self, datum: &Value, keyword: &str) -> bool {
+        datum.get_symbol().and_then(|id| self.interner.lookup(id)) == Some(keyword)
+    }
+
+    // #:optional or #:key in a parameter list
+    fn is_marker(&self, datum: &Value, marker: &str) -> bool {
+        datum.get_keyword().and_then(|id| self.interner.lookup(id)) == Some(marker)
+    }
+
+    // a list of symbols, e.g. lambda args
+    fn symbols(&self, datum: &Value) -> Option<Vec<u64>> {
+        datum.get_list().and_then(|list| list.iter().map(|x| x.get_symbol()).collect())
+    }
+
+    // a list of symbols, optionally with a rest parameter after a dot, e.g. (a b . rest)
+    fn parameters(&self, datum: &Value) -> Option<(Vec<u64>, Option<u64>)> {
+        match datum.get_dotted_list() {
+            Some((values, tail)) => {
+                let symbols: Option<Vec<u64>> = values.iter().map(|x| x.get_symbol()).collect();
+                symbols.and_then(|symbols| tail.get_symbol().map(|rest| (symbols, Some(rest))))
+            },
+            None => self.symbols(datum).map(|symbols| (symbols, None)),
+        }
+    }
+}
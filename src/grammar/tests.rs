@@ -1,8 +1,9 @@
 use std::usize;
 use lalrpop_util::ParseError;
-use ::value::Value;
+use ::value::{Value, BigInt, Rational};
+use ::interpreter::Random;
 use super::parse;
-use super::lexer::Token;
+use super::lexer::{Token, escape_string, escape_symbol};
 use super::error::Error;
 use ::string_interner::StringInterner;
 
@@ -15,7 +16,10 @@ fn whatever(x: &ParseError<usize, Token, Error>) -> usize {
         &ParseError::User{error: Error::InvalidToken(_, end)} => end,
         &ParseError::User{error: Error::UnexpectedEofString(_)} => EOF,
         &ParseError::User{error: Error::UnexpectedEofChar(_)} => EOF,
-        &ParseError::User{error: Error::NonAsciiChar(x)} => x,
+        &ParseError::User{error: Error::UnexpectedEofComment(_)} => EOF,
+        &ParseError::User{error: Error::UnexpectedEofSymbol(_)} => EOF,
+        &ParseError::User{error: Error::InvalidChar(x)} => x,
+        &ParseError::User{error: Error::InvalidEscape(start, _)} => start,
         x => panic!("got: {:?}", x),
     }
 }
@@ -107,6 +111,23 @@ fn char() {
     expect_ok!(parse, interner, r"#\\n", Value::new_char('\n'));
     expect_ok!(parse, interner, r"#\\", Value::new_char('\\'));
 
+    // named chars
+    expect_ok!(parse, interner, r"#\space", Value::new_char(' '));
+    expect_ok!(parse, interner, r"#\newline", Value::new_char('\n'));
+    expect_ok!(parse, interner, r"#\tab", Value::new_char('\t'));
+    expect_ok!(parse, interner, r"#\null", Value::new_char('\0'));
+    expect_ok!(parse, interner, r"#\x41", Value::new_char('A'));
+    expect_ok!(parse, interner, r"#\x3bb", Value::new_char('λ'));
+    expect_ok!(parse, interner, r"#\x", Value::new_char('x'));
+    // any char that isn't whitespace or a control char can be written as it is
+    expect_ok!(parse, interner, r"#\λ", Value::new_char('λ'));
+    expect_ok!(parse, interner, "(#\\❤)", Value::new_list(&[Value::new_char('❤')]));
+    expect_error!(parse, r"#\λλ");
+    expect_error!(parse, "#\\\u{3000}", 2);
+    expect_error!(parse, r"#\spaces");
+    expect_error!(parse, r"#\xZZ");
+    expect_error!(parse, r"#\\ss");
+
     expect_error!(parse, r"#\", EOF);
     expect_error!(parse, "#\\\0", 2);
 }
@@ -118,6 +139,9 @@ fn integer() {
     expect_ok!(parse, interner, "-007", Value::new_integer(-7));
     expect_ok!(parse, interner, "123456789", Value::new_integer(123456789));
     expect_ok!(parse, interner, "-123456789", Value::new_integer(-123456789));
+    expect_ok!(parse, interner, "9223372036854775807", Value::new_integer(9223372036854775807));
+    expect_ok!(parse, interner, "9223372036854775808", Value::new_bigint(BigInt::parse("9223372036854775808").unwrap()));
+    expect_ok!(parse, interner, "-99999999999999999999", Value::new_bigint(BigInt::parse("-99999999999999999999").unwrap()));
 
     expect_error!(parse, "123b456789", 3);
     expect_error!(parse, "123456789c", 9);
@@ -126,6 +150,81 @@ fn integer() {
     expect_ok!(parse, interner, "--7", Value::new_symbol(interner.intern("--7")));
 }
 
+#[test]
+fn float() {
+    let interner = &mut StringInterner::new();
+    expect_ok!(parse, interner, "1.5", Value::new_float(1.5));
+    expect_ok!(parse, interner, "-1.5", Value::new_float(-1.5));
+    expect_ok!(parse, interner, ".5", Value::new_float(0.5));
+    expect_ok!(parse, interner, "1.", Value::new_float(1.0));
+    expect_ok!(parse, interner, "1e3", Value::new_float(1000.0));
+    expect_ok!(parse, interner, "1.5E-3", Value::new_float(0.0015));
+    expect_ok!(parse, interner, "-2e+2", Value::new_float(-200.0));
+
+    expect_error!(parse, "1.5.", 3);
+    expect_error!(parse, "1e", 2);
+    expect_error!(parse, "1e+", 3);
+    expect_error!(parse, "1ea", 2);
+    expect_error!(parse, "1.5a", 3);
+    expect_ok!(parse, interner, ".a", Value::new_symbol(interner.intern(".a")));
+
+    // infinities and nan are written like in scheme
+    expect_ok!(parse, interner, "+inf.0", Value::new_float(::std::f64::INFINITY));
+    expect_ok!(parse, interner, "-inf.0", Value::new_float(::std::f64::NEG_INFINITY));
+    expect_ok!(parse, interner, "-nan.0", Value::new_float(::std::f64::NAN));
+    expect_ok!(parse, interner, "+inf", Value::new_symbol(interner.intern("+inf")));
+}
+
+#[test]
+fn rational() {
+    let interner = &mut StringInterner::new();
+    let rational = |s: &str| Value::new_rational(Rational::parse(s).unwrap());
+    expect_ok!(parse, interner, "1/3", rational("1/3"));
+    expect_ok!(parse, interner, "-2/4", rational("-1/2"));
+    expect_ok!(parse, interner, "4/2", Value::new_integer(2));
+    expect_ok!(parse, interner, "1/99999999999999999999", rational("1/99999999999999999999"));
+
+    expect_error!(parse, "1/", 2);
+    expect_error!(parse, "1/0", 3);
+    expect_error!(parse, "1/a", 2);
+    expect_error!(parse, "1/2/3", 3);
+    expect_error!(parse, "1/-2", 2);
+}
+
+#[test]
+fn prefixed_number() {
+    let interner = &mut StringInterner::new();
+    let rational = |s: &str| Value::new_rational(Rational::parse(s).unwrap());
+    expect_ok!(parse, interner, "#xff", Value::new_integer(255));
+    expect_ok!(parse, interner, "#XFF", Value::new_integer(255));
+    expect_ok!(parse, interner, "#x-1a", Value::new_integer(-26));
+    expect_ok!(parse, interner, "#o17", Value::new_integer(15));
+    expect_ok!(parse, interner, "#b-101", Value::new_integer(-5));
+    expect_ok!(parse, interner, "#d99", Value::new_integer(99));
+    expect_ok!(parse, interner, "#b1/10", rational("1/2"));
+    expect_ok!(parse, interner, "#x10000000000000000", Value::new_bigint(BigInt::parse("18446744073709551616").unwrap()));
+
+    expect_ok!(parse, interner, "#e1.5", rational("3/2"));
+    expect_ok!(parse, interner, "#e-1.25e-1", rational("-1/8"));
+    expect_ok!(parse, interner, "#e1e3", Value::new_integer(1000));
+    expect_ok!(parse, interner, "#e2", Value::new_integer(2));
+    expect_ok!(parse, interner, "#i3", Value::new_float(3.0));
+    expect_ok!(parse, interner, "#i1/4", Value::new_float(0.25));
+    expect_ok!(parse, interner, "#i1.5", Value::new_float(1.5));
+    expect_ok!(parse, interner, "#e#xa", Value::new_integer(10));
+    expect_ok!(parse, interner, "#x#i10", Value::new_float(16.0));
+    expect_ok!(parse, interner, "(#xa #b1)", Value::new_list(&vec![Value::new_integer(10), Value::new_integer(1)]));
+
+    expect_error!(parse, "#x", 2);
+    expect_error!(parse, "#xg", 3);
+    expect_error!(parse, "#b102", 5);
+    expect_error!(parse, "#x1.5", 5); // decimals only in radix 10
+    expect_error!(parse, "#x#o1", 5);
+    expect_error!(parse, "#e#i1", 5);
+    expect_error!(parse, "#b1/0", 5);
+    expect_error!(parse, "#d1/-2", 6);
+}
+
 #[test]
 fn symbol() {
     let interner = &mut StringInterner::new();
@@ -135,9 +234,43 @@ fn symbol() {
     expect_ok!(parse, interner, "a1a", Value::new_symbol(interner.intern("a1a")));
     expect_ok!(parse, interner, "num->str", Value::new_symbol(interner.intern("num->str")));
     expect_ok!(parse, interner, "//", Value::new_symbol(interner.intern("//")));
+    expect_ok!(parse, interner, "...", Value::new_symbol(interner.intern("...")));
+    expect_ok!(parse, interner, "λ", Value::new_symbol(interner.intern("λ")));
+    expect_ok!(parse, interner, "größe->日本", Value::new_symbol(interner.intern("größe->日本")));
 
     // error is at 1 bc lexer tries to lex integer
     expect_error!(parse, "1a", 1);
+
+    // anything can be written in pipes, with the escapes of strings
+    expect_ok!(parse, interner, "|hello world|", Value::new_symbol(interner.intern("hello world")));
+    expect_ok!(parse, interner, "|abc|", Value::new_symbol(interner.intern("abc")));
+    expect_ok!(parse, interner, r"|a\|b\x41;\n|", Value::new_symbol(interner.intern("a|bA\n")));
+    expect_ok!(parse, interner, "||", Value::new_symbol(interner.intern("")));
+    expect_ok!(parse, interner, "(|1||(|)", Value::new_list(&[Value::new_symbol(interner.intern("1")), Value::new_symbol(interner.intern("("))]));
+    expect_ok!(parse, interner, "a|b|", Value::new_symbol(interner.intern("a|b|")));
+    expect_error!(parse, "|abc", EOF);
+    expect_error!(parse, r"|a\qb|", 2);
+}
+
+#[test]
+fn escape_symbol_in_pipes() {
+    for name in &["abc", "+", "...", "a|b", "a:b", "#foo"] {
+        assert_eq!(escape_symbol(name), *name);
+    }
+    // the lexer would read these as something else
+    assert_eq!(escape_symbol("hello world"), "|hello world|");
+    assert_eq!(escape_symbol("1"), "|1|");
+    assert_eq!(escape_symbol("-1.5"), "|-1.5|");
+    assert_eq!(escape_symbol("true"), "|true|");
+    assert_eq!(escape_symbol("+inf.0"), "|+inf.0|");
+    assert_eq!(escape_symbol("."), "|.|");
+    assert_eq!(escape_symbol(""), "||");
+    assert_eq!(escape_symbol("a(b)"), "|a(b)|");
+    assert_eq!(escape_symbol("a;b"), "|a;b|");
+    assert_eq!(escape_symbol(":key"), "|:key|");
+    assert_eq!(escape_symbol("#0#"), "|#0#|");
+    assert_eq!(escape_symbol("|a"), r"|\|a|");
+    assert_eq!(escape_symbol("a\tb\x01"), r"|a\tb\x1;|");
 }
 
 #[test]
@@ -166,8 +299,41 @@ fn string() {
     expect_str_ok!(r"\\\\", "\\\\");
     expect_str_ok!(r#"Hi there: \" \\ \n \t"#, "Hi there: \" \\ \n \t");
 
+    expect_str_ok!(r"\r\a\b\0\s\|", "\r\x07\x08\0 |");
+    expect_str_ok!(r"\x41;\x3b;\x0a;", "A;\n");
+    expect_str_ok!("❤");
+    expect_str_ok!("grüß λ 日本語");
+    expect_str_ok!(r"\x3bb;\x1f600;", "λ😀");
+    expect_str_ok!("one \\\n    two", "one two");
+    expect_str_ok!("one \\  \t\n\t two", "one two");
+
     expect_error!(parse, "\"", EOF);
-    expect_error!(parse, "\"❤\"", 1); // non ascii
+    expect_error!(parse, "\"\\", EOF);
+    expect_error!(parse, r#""a\q""#, 2);
+    expect_error!(parse, r#""a\x41""#, 2); // missing ;
+    expect_error!(parse, r#""\x;""#, 1);
+    expect_error!(parse, r#""\xd800;""#, 1); // not a char
+    expect_error!(parse, r#""a \ b""#, 3); // no line break
+
+    let interner = &mut StringInterner::new();
+    expect_ok!(parse, interner, r##"#"C:\dir\n"#"##, Value::new_string(r"C:\dir\n"));
+    expect_ok!(parse, interner, r###"#"say "hi"#"###, Value::new_string("say \"hi"));
+    expect_ok!(parse, interner, r##"#""#"##, Value::new_string(""));
+    expect_ok!(parse, interner, r##"(#"a"# "b")"##, Value::new_list(&vec![Value::new_string("a"), Value::new_string("b")]));
+    expect_error!(parse, r##"#"abc"##, EOF);
+    expect_ok!(parse, interner, r##"#"ab❤"#"##, Value::new_string("ab❤"));
+}
+
+#[test]
+fn escape_string_round_trip() {
+    let interner = &mut StringInterner::new();
+    for s in &["", "plain", "a \"quoted\" \\ path", "tab\tnew\nline\r", "bell\x07 nul\0 esc\x1b"] {
+        let written = format!("\"{}\"", escape_string(s));
+        assert!(written.is_ascii());
+        expect_ok!(parse, interner, written, Value::new_string(*s));
+    }
+    assert_eq!(escape_string("\x07\x7f"), r"\x7;\x7f;");
+    assert_eq!(escape_string("λ\u{3000}❤"), r"λ\x3000;❤");
 }
 
 #[test]
@@ -180,6 +346,85 @@ fn list() {
     expect_error!(parse, "(( ())");
 }
 
+#[test]
+fn comments() {
+    let interner = &mut StringInterner::new();
+    let one_two = Value::new_list(&vec![Value::new_integer(1), Value::new_integer(2)]);
+    expect_ok!(parse, interner, "; a comment, with (parens\n(1 2)", one_two);
+    expect_ok!(parse, interner, "(1 ; one\n 2) ; the end", one_two);
+    expect_ok!(parse, interner, "(1;one\n2)", one_two);
+    expect_ok!(parse, interner, "(1 \"; not a comment\")", Value::new_list(&vec![Value::new_integer(1), Value::new_string("; not a comment")]));
+    expect_ok!(parse, interner, r"#\;", Value::new_char(';'));
+
+    // block comments nest
+    expect_ok!(parse, interner, "#| a block\n(comment |#(1 2)", one_two);
+    expect_ok!(parse, interner, "(1 #| nested #| block |# comment |# 2)", one_two);
+    expect_ok!(parse, interner, "(1 #||# ; line\n#|# | |#2)", one_two);
+
+    // only a #! line at the start is a comment
+    expect_ok!(parse, interner, "#!/usr/bin/env flip\n(1 2)", one_two);
+    expect_ok!(parse, interner, " #!x", Value::new_symbol(interner.intern("#!x")));
+    // datum comments skip the next item
+    expect_ok!(parse, interner, "(1 #;(3 4) 2)", one_two);
+    expect_ok!(parse, interner, "(1 #; 3 2 #;4)", one_two);
+    expect_ok!(parse, interner, "(#; #; 3 4 1 2)", one_two);
+    expect_ok!(parse, interner, "#;(unused) (1 2)", one_two);
+
+    expect_error!(parse, "(1 ; 2)");
+    expect_error!(parse, "(1 #| 2)", EOF);
+    expect_error!(parse, "(1 #| #| 2 |# 3)", EOF);
+    expect_error!(parse, "(1 #;)");
+}
+
+#[test]
+fn dotted_list() {
+    let interner = &mut StringInterner::new();
+    let a = Value::new_symbol(interner.intern("a"));
+    let b = Value::new_symbol(interner.intern("b"));
+    let dotted = |values: &[&Value], tail: &Value| Value::new_quote(Value::new_dotted_list(values.iter().map(|&x| x.clone()).collect(), tail.clone()));
+    expect_ok!(parse, interner, "'(a . b)", dotted(&[&a], &b));
+    expect_ok!(parse, interner, "'( a b  .  b )", dotted(&[&a, &b], &b));
+    // a list as tail makes a proper list
+    expect_ok!(parse, interner, "'(a . (b))", Value::new_quote(Value::new_list(&[a.clone(), b.clone()])));
+    expect_ok!(parse, interner, "'(a . ())", Value::new_quote(Value::new_list(&[a.clone()])));
+    expect_ok!(parse, interner, "'(a . (b . a))", dotted(&[&a, &b], &a));
+
+    expect_error!(parse, "'(. a)");
+    expect_error!(parse, "'(a .)");
+    expect_error!(parse, "'(a . b c)");
+    expect_error!(parse, "'(a . b . c)");
+
+    // improper lists can't be evaluated
+    expect_error!(parse, "(a . b)");
+}
+
+#[test]
+fn vector() {
+    let interner = &mut StringInterner::new();
+    expect_ok!(parse, interner, "#()", Value::new_vector(vec![]));
+    expect_ok!(parse, interner, "#( 1 \"2\" )", Value::new_vector(vec![Value::new_integer(1), Value::new_string("2")]));
+    expect_ok!(parse, interner, "#(#(a)(b))", Value::new_vector(vec![
+        Value::new_vector(vec![Value::new_symbol(interner.intern("a"))]),
+        Value::new_list(&[Value::new_symbol(interner.intern("b"))]),
+    ]));
+
+    expect_error!(parse, "#(1 2");
+}
+
+#[test]
+fn keyword() {
+    let interner = &mut StringInterner::new();
+    let name = interner.intern("name");
+    expect_ok!(parse, interner, "#:name", Value::new_keyword(name));
+    expect_ok!(parse, interner, ":name", Value::new_keyword(name));
+    expect_ok!(parse, interner, "(f #:name)", Value::new_list(&[Value::new_symbol(interner.intern("f")), Value::new_keyword(name)]));
+    // a colon on its own or inside a symbol isn't a keyword
+    expect_ok!(parse, interner, ":", Value::new_symbol(interner.intern(":")));
+    expect_ok!(parse, interner, "a:b", Value::new_symbol(interner.intern("a:b")));
+
+    expect_error!(parse, "#:", 2);
+}
+
 #[test]
 fn quote() {
     fn quoted(v: Value, interner: &mut StringInterner) -> Value {
@@ -196,7 +441,7 @@ fn quote() {
     expect_ok!(parse, interner, "'(1 2)", quoted(Value::new_list(&[Value::new_integer(1), Value::new_integer(2)]), interner));
 
     // quoted lists should parse even if they contain invalid special forms
-    let special_forms = &["define", "quote", "if", "lambda", "let", "loop", "recur", "begin"];
+    let special_forms = &["define", "quote", "if", "lambda", "let", "let*", "letrec", "loop", "recur", "begin", "cond", "case", "quasiquote", "unquote", "unquote-splicing"];
     for &special in special_forms {
         let special_symbol = Value::new_symbol(interner.intern(special));
         expect_ok!(parse, interner, format!("'({})", special), quoted(Value::new_list(&[special_symbol.clone()]), interner));
@@ -208,6 +453,26 @@ fn quote() {
     }
 }
 
+#[test]
+fn quasiquote() {
+    let interner = &mut StringInterner::new();
+    let sym = |name: &str, interner: &mut StringInterner| Value::new_symbol(interner.intern(name));
+
+    let x = sym("x", interner);
+    let quasiquoted = Value::new_list(&[sym("quasiquote", interner), x.clone()]);
+    let unquoted = Value::new_list(&[sym("unquote", interner), x.clone()]);
+    let spliced = Value::new_list(&[sym("unquote-splicing", interner), x.clone()]);
+
+    expect_ok!(parse, interner, "`x", quasiquoted.clone());
+    expect_ok!(parse, interner, "'`x", Value::new_list(&[sym("quote", interner), quasiquoted]));
+    expect_ok!(parse, interner, "'(,x ,@x)", Value::new_list(&[sym("quote", interner), Value::new_list(&[unquoted, spliced])]));
+
+    expect_error!(parse, ",x");
+    expect_error!(parse, ",@x");
+    expect_error!(parse, "`,@x");
+    expect_error!(parse, "`");
+}
+
 #[test]
 fn recur() {
     let interner = &mut StringInterner::new();
@@ -238,6 +503,16 @@ fn tail_calls() {
     // let
     expect_error!(parse, "(let () (recur))");
     expect_error!(parse, "(let () bla (recur))");
+    expect_error!(parse, "(let* () (recur))");
+    expect_error!(parse, "(letrec () (recur))");
+
+    // cond and case
+    expect_error!(parse, "(cond (true (recur)))");
+    expect_error!(parse, "(case 1 (else (recur)))");
+    expect_error!(parse, "(loop () (cond ((recur) 1)))");
+    expect_error!(parse, "(loop () (case (recur) (else 1)))");
+    expect_error!(parse, "(loop () (cond (true => (recur))))");
+    expect_error!(parse, "(loop () `,(recur))");
 
     // loop
     expect_ok!(parse, interner, "(loop () (recur))", Value::new_list(&[Value::new_symbol(interner.intern("loop")), Value::empty_list(), Value::new_list(&[Value::new_symbol(interner.intern("recur"))])]));
@@ -263,6 +538,260 @@ fn tail_calls() {
     }
 }
 
+#[test]
+fn invalid_syntax() {
+    expect_error!(parse, "(if)");
+    expect_error!(parse, "(if true 1)");
+    expect_error!(parse, "(when true)");
+    expect_error!(parse, "(unless)");
+    expect_error!(parse, "(lambda (x) (and (recur x) true))");
+    expect_error!(parse, "(define 1 2)");
+    expect_error!(parse, "(define (f))");
+    expect_error!(parse, "(define () 1)");
+    expect_error!(parse, "(define (f 1) 1)");
+    expect_error!(parse, "(define (f . 1) 1)");
+    expect_error!(parse, "(define x 1 2)");
+    expect_error!(parse, "(set! x)");
+    expect_error!(parse, "(set! 1 2)");
+    expect_error!(parse, "(lambda)");
+    expect_error!(parse, "(lambda (1) 1)");
+    expect_error!(parse, "(let (a) a)");
+    expect_error!(parse, "(let ((a 1) b) a)");
+    expect_error!(parse, "(quote 1 2)");
+    expect_error!(parse, "(cond 1)");
+    expect_error!(parse, "(cond (else 1) (true 2))");
+    expect_error!(parse, "(case)");
+    expect_error!(parse, "(case 1 (2 3))");
+}
+
+#[test]
+fn diagnostics() {
+    use super::error_printing::{diagnostics, create_error_message};
+
+    fn found(input: &str) -> Vec<((usize, usize), String)> {
+        let interner = &mut StringInterner::new();
+        match parse(input, interner) {
+            Err(ref err) => diagnostics(input, err).iter().map(|x| (x.line_column(input).unwrap(), x.message.clone())).collect(),
+            Ok(_) => panic!("expected error for: {:?}", input),
+        }
+    }
+
+    // the parser stops at the first one, the rest are found anyway
+    assert_eq!(found("(define (f x)\n  (g 1.5a \"\\q\"))\n)\n(h #\\foo"), vec![
+        ((2, 9), "invalid number: 1.5a".to_string()),
+        ((2, 12), "invalid escape sequence".to_string()),
+        ((3, 1), "unexpected ), there's no ( for it".to_string()),
+        ((4, 1), "unclosed (".to_string()),
+        ((4, 4), "invalid char: #\\foo".to_string()),
+    ]);
+    assert_eq!(found("(a\n  #(b (c)"), vec![((1, 1), "unclosed (".to_string()), ((2, 3), "unclosed #(".to_string())]);
+    assert_eq!(found("\"ab\ncd"), vec![((1, 1), "unexpected EOF".to_string())]);
+    assert_eq!(found("(1 . )"), vec![((1, 6), "unrecognized token".to_string())]);
+    assert_eq!(found("(x #\\\x01 #xz)"), vec![((1, 6), "invalid character: \\u{1}".to_string()), ((1, 8), "invalid number: #xz".to_string())]);
+    assert_eq!(found("(λ \"❤\" #\\λλ)"), vec![((1, 8), "invalid char: #\\λλ".to_string())]);
+
+    let interner = &mut StringInterner::new();
+    let input = "(1 2)\n(3 4";
+    match parse(input, interner) {
+        Err(ref err) => assert_eq!(create_error_message(input, err), "2:1: error: unclosed (\n2 | (3 4\n  | ^\nhint: missing ), it's never closed"),
+        Ok(_) => panic!("expected error"),
+    }
+    let input = "1.5a)";
+    match parse(input, interner) {
+        Err(ref err) => assert_eq!(create_error_message(input, err), concat!(
+            "1:4: error: invalid number: 1.5a\n1 | 1.5a)\n  |    ^\n",
+            "1:5: error: unexpected ), there's no ( for it\n1 | 1.5a)\n  |     ^\nhint: remove it or add the missing ( before it\n",
+            "2 errors")),
+        Ok(_) => panic!("expected error"),
+    }
+}
+
+#[test]
+fn datum_labels() {
+    use super::parse_data;
+    let interner = &mut StringInterner::new();
+    let a = Value::new_symbol(interner.intern("a"));
+
+    // a label can be referenced in the rest of its datum
+    let shared = Value::new_list(&[a.clone()]);
+    expect_ok!(parse_data, interner, "(#0=(a) #0#)", Value::new_list(&[shared.clone(), shared.clone()]));
+    expect_ok!(parse_data, interner, "#(#12=a #12#)", Value::new_vector(vec![a.clone(), a.clone()]));
+    let data = parse_data("#0=(a . #0#)", interner).unwrap();
+    assert_eq!(data[0].get_pair().unwrap().cdr().id(), data[0].id());
+    assert_eq!(data[0].to_string(interner), "#0=(a . #0#)");
+    let data = parse_data("#0=#(a #1=(#0# #1#))", interner).unwrap();
+    assert_eq!(data[0].to_string(interner), "#0=#(a #1=(#0# #1#))");
+
+    expect_error!(parse_data, "#0#");
+    expect_error!(parse_data, "(#0# #0=a)");
+    expect_error!(parse_data, "(#0=a #0=b)");
+    expect_error!(parse_data, "#0=(a) #0#");
+    expect_error!(parse_data, "#99999999999999999999=a", 22);
+    // code can share, but it can't contain itself
+    expect_ok!(parse, interner, "'(#0=a #0#)", Value::new_quote(Value::new_list(&[a.clone(), a.clone()])));
+    expect_error!(parse, "'#0=(a . #0#)");
+
+    // a # and digits without = or # is a symbol
+    expect_ok!(parse, interner, "#1x", Value::new_symbol(interner.intern("#1x")));
+    expect_ok!(parse, interner, "#12", Value::new_symbol(interner.intern("#12")));
+}
+
+// Random data that write can write, at most depth lists or vectors deep.
+// Hash tables, records and procedures are written for humans, read can't give them back.
+fn random_datum(random: &mut Random, interner: &mut StringInterner, depth: usize) -> Value {
+    let kinds = if depth == 0 { 9 } else { 12 };
+    match random.below(kinds) {
+        0 => Value::new_bool(random.below(2) == 1),
+        1 => Value::new_char(random_char(random)),
+        2 => Value::new_integer(random.next_u64() as i64 >> random.below(64)),
+        3 => {
+            // more than 20 digits are too big for an i64
+            let digits: String = (0..20 + random.below(20)).map(|_| (b'0' + random.below(10) as u8) as char).collect();
+            let sign = if random.below(2) == 1 { "-" } else { "" };
+            Value::new_bigint(BigInt::parse(&format!("{}9{}", sign, digits)).unwrap())
+        },
+        4 => {
+            let numerator = BigInt::from(random.next_u64() as i64 >> random.below(64));
+            let denominator = BigInt::from(random.below(1000) as i64 + 1);
+            Value::new_rational(Rational::new(numerator, denominator).unwrap())
+        },
+        5 => {
+            // any bits, but nan isn't equal to itself
+            let x = f64::from_bits(random.next_u64());
+            Value::new_float(if x.is_nan() { random.next_f64() } else { x })
+        },
+        6 if random.below(2) == 0 => Value::new_symbol(interner.intern(&random_name(random))),
+        // any name, in pipes if needed
+        6 => Value::new_symbol(interner.intern((0..random.below(6)).map(|_| random_char(random)).collect::<String>())),
+        7 => Value::new_keyword(interner.intern(&random_name(random))),
+        8 => Value::new_string((0..random.below(10)).map(|_| random_char(random)).collect::<String>()),
+        kind => {
+            let elements: Vec<Value> = (0..random.below(5)).map(|_| random_datum(random, interner, depth - 1)).collect();
+            match kind {
+                9 => if elements.is_empty() { Value::empty_list() } else { Value::new_list(&elements) },
+                10 if !elements.is_empty() => Value::new_dotted_list(elements, random_datum(random, interner, depth - 1)),
+                _ => Value::new_vector(elements),
+            }
+        },
+    }
+}
+
+// mostly printable ascii, but also control chars and anything else unicode has
+fn random_char(random: &mut Random) -> char {
+    match random.below(4) {
+        0 => ['\n', '\t', ' ', '\r', '\0', '\x07', '\x7f', '\\', '"', '|', ';', '(', ')'][random.below(13) as usize],
+        1 => ::std::char::from_u32(random.below(0x110000) as u32).unwrap_or('x'),
+        _ => (b'!' + random.below(94) as u8) as char,
+    }
+}
+
+// a name that's read as symbol, never as a number or a boolean
+fn random_name(random: &mut Random) -> String {
+    let first = "abcdefghijklmnopqrstuvwxyzABCXYZ!$%&*/<=>?^_~";
+    let rest = "abcdefghijklmnopqrstuvwxyz0123456789+-.!?*<=>";
+    let mut name: String = first.chars().nth(random.below(first.len() as u64) as usize).into_iter().collect();
+    for _ in 0..random.below(8) {
+        name.push(rest.chars().nth(random.below(rest.len() as u64) as usize).unwrap());
+    }
+    if name == "true" || name == "false" { name.push('?'); }
+    name
+}
+
+#[test]
+fn write_read_round_trip() {
+    use super::parse_data;
+    let interner = &mut StringInterner::new();
+    let mut random = Random::new(42);
+    for _ in 0..2000 {
+        let x = random_datum(&mut random, interner, 3);
+        let written = x.to_string(interner);
+        let read = match parse_data(&written, interner) {
+            Ok(ref data) if data.len() == 1 => data[0].clone(),
+            result => panic!("{} was read as {:?}", written, result),
+        };
+        assert!(read.equal(&x), "{} was read as {}", written, read.to_string(interner));
+        assert_eq!(read.to_string(interner), written);
+    }
+
+    // nan isn't equal to itself, but it's still read back
+    let nan = Value::new_float(::std::f64::NAN).to_string(interner);
+    assert!(parse_data(&nan, interner).unwrap()[0].get_float().map_or(false, |x| x.is_nan()));
+}
+
+#[test]
+fn reader() {
+    use super::Reader;
+    let interner = &mut StringInterner::new();
+    let mut reader = Reader::new();
+    let next = |reader: &mut Reader, interner: &mut StringInterner| reader.next(interner).unwrap().map(|x| x.to_string(interner));
+
+    // data are returned once they're complete
+    reader.feed("(a) (b");
+    assert_eq!(next(&mut reader, interner), Some("(a)".into()));
+    assert_eq!(next(&mut reader, interner), None);
+    assert!(!reader.is_empty());
+    reader.feed("\n c) 'd \"e\" f");
+    assert_eq!(next(&mut reader, interner), Some("(b c)".into()));
+    assert_eq!(next(&mut reader, interner), Some("(quote d)".into()));
+    assert_eq!(next(&mut reader, interner), Some("\"e\"".into()));
+    // f could go on
+    assert_eq!(next(&mut reader, interner), None);
+    reader.feed("g #;(h) ; i\n");
+    assert_eq!(next(&mut reader, interner), Some("fg".into()));
+    assert_eq!(next(&mut reader, interner), None);
+    reader.feed("j\n");
+    assert_eq!(next(&mut reader, interner), Some("j".into()));
+    assert!(reader.is_empty());
+    reader.feed("; k\n");
+    assert!(reader.is_empty());
+
+    // the end of the input completes the last datum
+    reader.feed("#\\a");
+    assert_eq!(next(&mut reader, interner), None);
+    reader.finish();
+    assert_eq!(next(&mut reader, interner), Some("#\\a".into()));
+    assert_eq!(next(&mut reader, interner), None);
+
+    // the lines data start at
+    let mut reader = Reader::new();
+    reader.feed("; comment\n(a\n b)\n\n  c\n");
+    assert_eq!(reader.next_source(), Some(("(a\n b)".to_string(), 2)));
+    assert_eq!(reader.next_source(), Some(("c".to_string(), 5)));
+    assert_eq!(reader.next_source(), None);
+
+    // errors are returned for their datum, the data after them can still be read
+    let mut reader = Reader::new();
+    reader.feed("(1 . ) 2 ) 3\n");
+    assert!(reader.next(interner).is_err());
+    assert_eq!(next(&mut reader, interner), Some("2".into()));
+    assert!(reader.next(interner).is_err());
+    assert_eq!(next(&mut reader, interner), Some("3".into()));
+
+    // tokens at the end of a piece go on in the next one
+    let mut reader = Reader::new();
+    for piece in &["(1 `(a ,", "@b) ; c", " d)\n"] {
+        reader.feed(piece);
+        assert_eq!(next(&mut reader, interner), None);
+    }
+    reader.feed(")");
+    assert_eq!(next(&mut reader, interner), Some("(1 (quasiquote (a (unquote-splicing b))))".into()));
+    for piece in &[" (2 \"e", "f\" gh", "i #|j", "|# k"] {
+        reader.feed(piece);
+        assert_eq!(next(&mut reader, interner), None);
+    }
+    reader.feed(" . (l))\n");
+    assert_eq!(next(&mut reader, interner), Some("(2 \"ef\" ghi k l)".into()));
+
+    // from a BufRead, e.g. a file or a socket
+    let mut input = ::std::io::Cursor::new("(define x\n  1) x\n(unclosed".as_bytes());
+    let mut reader = Reader::new();
+    let mut read = |reader: &mut Reader, interner: &mut StringInterner| reader.read_from(&mut input, interner).map(|x| x.map(|x| x.to_string(interner)));
+    assert_eq!(read(&mut reader, interner), Ok(Some("(define x 1)".into())));
+    assert_eq!(read(&mut reader, interner), Ok(Some("x".into())));
+    assert!(read(&mut reader, interner).is_err());
+    assert_eq!(read(&mut reader, interner), Ok(None));
+}
+
 // TODO add tests for special forms
 
 #[test]
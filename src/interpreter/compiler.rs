@@ -0,0 +1,284 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use ::value::Value;
+use ::scope::{Address, LookupCache};
+
+// An instruction of the vm, see vm::run for what they do.
+// Targets of jumps are indices into Chunk::code, the other usizes index Chunk::constants.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Op {
+    Const(usize),
+    // the constant is the symbol as written, for the location in the error message,
+    // the last usize indexes Chunk::caches
+    Lookup(u64, usize, usize),
+    // locals are looked up by their address, see Compiler::resolve
+    LookupLocal(u64, Address, usize),
+    Define(u64),
+    Set(u64),
+    SetLocal(u64, Address),
+    Pop,
+    Swap,
+    Jump(usize),
+    // pops a bool and jumps if it's false, the str is the form for the error message
+    JumpUnless(usize, &'static str),
+    // and & or, jumps if the top is the short circuit value, pops it otherwise
+    ShortCircuit(bool, usize, &'static str),
+    // cond clauses with receivers, pops the top and jumps if it's false
+    JumpIfFalse(usize),
+    // case clauses, jumps if none of the data in the constant is eqv to the top
+    JumpUnlessCase(usize, usize),
+    SaveScope,
+    NewScope,
+    Bind(u64),
+    RestoreScope,
+    // the number of args and the operator as written, for backtraces
+    Call(usize, usize),
+    TailCall(usize, usize),
+    Return,
+    // forms without instructions are evaluated by the interpreter
+    Eval(usize),
+    TailEval(usize),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Value>,
+    // for the lookups of names without lexical address
+    pub caches: Vec<LookupCache>,
+}
+
+// The body of the procedure can't be compiled, e.g. because it recurs.
+// It's evaluated by the interpreter instead.
+#[derive(Debug)]
+pub struct Unsupported;
+
+// The names bound in a scope the compiled code creates, in the order they're bound.
+// Lambdas in the code share them, so they're complete once the lambdas are compiled.
+pub type StaticScope = Arc<RwLock<Vec<u64>>>;
+
+// Turns code as returned by the syntax pass into a chunk, special forms compile themselves.
+// Code in tail position ends with Return, TailCall or TailEval.
+pub struct Compiler {
+    code: Vec<Op>,
+    constants: Vec<Value>,
+    caches: Vec<LookupCache>,
+    // the scopes the code runs in, the innermost is last
+    scopes: Vec<StaticScope>,
+    // how many scopes there were at each SaveScope
+    saved_scopes: Vec<usize>,
+}
+
+impl Compiler {
+    // env are the scopes of the code the procedure was created in, if it was compiled
+    pub fn compile_procedure(body: &[Value], env: &[StaticScope], params: Vec<u64>) -> Result<Chunk, Unsupported> {
+        let mut scopes = env.to_vec();
+        scopes.push(Arc::new(RwLock::new(params)));
+        let mut compiler = Compiler { code: vec![], constants: vec![], caches: vec![], scopes: scopes, saved_scopes: vec![] };
+        compiler.compile_body(body, true)?;
+        Ok(Chunk { code: compiler.code, constants: compiler.constants, caches: compiler.caches })
+    }
+
+    pub fn compile(&mut self, value: &Value, tail: bool) -> Result<(), Unsupported> {
+        if let Some(special_form) = value.get_special_form() {
+            return special_form.compile(self, value, tail);
+        }
+
+        if let Some(list) = value.get_list() {
+            // the syntax pass inserts natives like %define-module, which need their args unevaluated
+            if list.is_empty() || list[0].get_native_fn_ptr().is_some() { return Ok(self.fallback(value, tail)); }
+
+            // the args are evaluated here, natives get them quoted like from apply
+            for x in list.iter() {
+                self.compile(x, false)?;
+            }
+            let site = self.constant(list[0].clone());
+            self.emit(if tail { Op::TailCall(list.len() - 1, site) } else { Op::Call(list.len() - 1, site) });
+        } else if let Some(id) = value.get_symbol() {
+            let symbol = self.constant(value.clone());
+            match self.resolve(id) {
+                Some(address) => self.emit(Op::LookupLocal(id, address, symbol)),
+                None => {
+                    // the name isn't bound in the scopes of the compiled code, unless define adds it
+                    self.caches.push(LookupCache::new(self.scopes.len()));
+                    let cache = self.caches.len() - 1;
+                    self.emit(Op::Lookup(id, symbol, cache))
+                },
+            };
+            self.finish(tail);
+        } else {
+            self.push_constant(value.clone());
+            self.finish(tail);
+        }
+        Ok(())
+    }
+
+    // all but the last value are popped, an empty body is ()
+    pub fn compile_body(&mut self, code: &[Value], tail: bool) -> Result<(), Unsupported> {
+        match code.split_last() {
+            Some((last, init)) => {
+                for x in init {
+                    self.compile(x, false)?;
+                    self.emit(Op::Pop);
+                }
+                self.compile(last, tail)
+            },
+            None => {
+                self.push_constant(Value::empty_list());
+                self.finish(tail);
+                Ok(())
+            },
+        }
+    }
+
+    pub fn set(&mut self, id: u64) {
+        match self.resolve(id) {
+            Some(address) => self.emit(Op::SetLocal(id, address)),
+            None => self.emit(Op::Set(id)),
+        };
+    }
+
+    // The address of the innermost binding of id in the scopes created by compiled code.
+    // The bindings of the global scope and of scopes created by the interpreter have none.
+    pub fn resolve(&self, id: u64) -> Option<Address> {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(index) = scope.read().unwrap().iter().position(|&name| name == id) {
+                return Some(Address { depth: depth, index: index });
+            }
+        }
+        None
+    }
+
+    // The following functions emit the instructions for scopes and keep track of the names bound in them.
+
+    pub fn save_scope(&mut self) {
+        self.saved_scopes.push(self.scopes.len());
+        self.emit(Op::SaveScope);
+    }
+
+    pub fn new_scope(&mut self) {
+        self.scopes.push(Arc::new(RwLock::new(vec![])));
+        self.emit(Op::NewScope);
+    }
+
+    // binds the value on top of the stack in the innermost scope
+    pub fn bind(&mut self, id: u64) {
+        {
+            let mut names = self.scopes.last().expect("internal error: bind without scope").write().unwrap();
+            if !names.contains(&id) { names.push(id); }
+        }
+        self.emit(Op::Bind(id));
+    }
+
+    // in tail position the scope is restored by returning, the code after it needs the saved scopes though
+    pub fn restore_scope(&mut self, tail: bool) {
+        let len = self.saved_scopes.pop().expect("internal error: restore without saved scope");
+        self.scopes.truncate(len);
+        if !tail { self.emit(Op::RestoreScope); }
+    }
+
+    // the scopes lambdas in the code are created in
+    pub fn scopes(&self) -> Vec<StaticScope> {
+        self.scopes.clone()
+    }
+
+    // leaves the evaluation of value to the interpreter
+    pub fn fallback(&mut self, value: &Value, tail: bool) {
+        let form = self.constant(value.clone());
+        self.emit(if tail { Op::TailEval(form) } else { Op::Eval(form) });
+    }
+
+    pub fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    pub fn constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn push_constant(&mut self, value: Value) {
+        let index = self.constant(value);
+        self.emit(Op::Const(index));
+    }
+
+    // the index of the next instruction, for jumps
+    pub fn position(&self) -> usize {
+        self.code.len()
+    }
+
+    // sets the target of the jump at index
+    pub fn patch(&mut self, index: usize, target: usize) {
+        self.code[index] = match self.code[index] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpUnless(_, form) => Op::JumpUnless(target, form),
+            Op::ShortCircuit(value, _, form) => Op::ShortCircuit(value, target, form),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+            Op::JumpUnlessCase(data, _) => Op::JumpUnlessCase(data, target),
+            op => panic!("internal error: tried to patch {:?}", op),
+        };
+    }
+
+    // values in tail position are returned
+    pub fn finish(&mut self, tail: bool) {
+        if tail { self.emit(Op::Return); }
+    }
+}
+
+enum State {
+    // the scopes of the code the lambda is in, see Compiler::compile_procedure
+    NotCompiled(Vec<StaticScope>),
+    Unsupported,
+    Compiled(Arc<Chunk>),
+}
+
+// The chunk of a lambda, shared by all procedures it creates and compiled when one of them is called the first time.
+#[derive(Clone)]
+pub struct CompiledBody {
+    state: Arc<RwLock<State>>,
+}
+
+impl CompiledBody {
+    pub fn new() -> Self {
+        CompiledBody { state: Arc::new(RwLock::new(State::NotCompiled(vec![]))) }
+    }
+
+    // called when the code containing the lambda is compiled
+    pub fn set_env(&self, env: Vec<StaticScope>) {
+        if let State::NotCompiled(ref mut old_env) = *self.state.write().unwrap() {
+            *old_env = env;
+        }
+    }
+
+    pub fn get(&self, body: &[Value], params: Vec<u64>) -> Option<Arc<Chunk>> {
+        let compiled = match *self.state.read().unwrap() {
+            State::NotCompiled(ref env) => Some(Compiler::compile_procedure(body, env, params)),
+            _ => None,
+        };
+        if let Some(compiled) = compiled {
+            *self.state.write().unwrap() = match compiled {
+                Ok(chunk) => State::Compiled(Arc::new(chunk)),
+                Err(Unsupported) => State::Unsupported,
+            };
+        }
+
+        match *self.state.read().unwrap() {
+            State::Compiled(ref chunk) => Some(chunk.clone()),
+            _ => None,
+        }
+    }
+}
+
+// the chunk is derived from the code, so it doesn't change what a procedure is
+impl PartialEq for CompiledBody {
+    fn eq(&self, _: &CompiledBody) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for CompiledBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CompiledBody")
+    }
+}
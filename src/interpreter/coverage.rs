@@ -0,0 +1,81 @@
+// Line coverage of flip code, from (coverage-start) on.
+//
+// Only symbols have locations, so a line is covered once a symbol written on it is evaluated, quoted symbols
+// are evaluated with their quote. The lines of a source are the ones with symbols that are still part of code,
+// e.g. of procedures, or that were evaluated. The prelude is left out of the reports.
+
+use std::collections::{HashMap, BTreeMap};
+use ::value::Value;
+use ::grammar::Locations;
+use super::Interpreter;
+
+pub struct Coverage {
+    // how often symbols on each line were evaluated, by source and line
+    hits: HashMap<String, HashMap<usize, u64>>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage { hits: HashMap::new() }
+    }
+
+    // the lines of each source with their hits, sorted by source and line
+    fn lines(&self, locations: &Locations) -> BTreeMap<String, BTreeMap<usize, u64>> {
+        let mut sources: BTreeMap<String, BTreeMap<usize, u64>> = BTreeMap::new();
+        for location in locations.live() {
+            sources.entry(location.source.to_string()).or_insert_with(BTreeMap::new).entry(location.line).or_insert(0);
+        }
+        for (source, lines) in &self.hits {
+            let entry = sources.entry(source.clone()).or_insert_with(BTreeMap::new);
+            for (&line, &hits) in lines { entry.insert(line, hits); }
+        }
+        sources.remove("prelude");
+        sources
+    }
+
+    // a line per source with the covered lines and the ones that aren't:
+    // lib.lisp: 4 of 5 lines (80.0%), not covered: 7
+    pub fn report(&self, locations: &Locations) -> String {
+        let mut s = String::new();
+        for (source, lines) in self.lines(locations) {
+            let missed: Vec<String> = lines.iter().filter(|&(_, &hits)| hits == 0).map(|(line, _)| line.to_string()).collect();
+            let covered = lines.len() - missed.len();
+            s.push_str(&format!("{}: {} of {} lines ({:.1}%)", source, covered, lines.len(), covered as f64 * 100.0 / lines.len() as f64));
+            if !missed.is_empty() { s.push_str(&format!(", not covered: {}", missed.join(", "))); }
+            s.push('\n');
+        }
+        s
+    }
+
+    // the lcov tracefile format read by genhtml and most coverage services
+    pub fn lcov(&self, locations: &Locations) -> String {
+        let mut s = String::new();
+        for (source, lines) in self.lines(locations) {
+            s.push_str(&format!("TN:\nSF:{}\n", source));
+            for (line, hits) in &lines { s.push_str(&format!("DA:{},{}\n", line, hits)); }
+            let hit = lines.values().filter(|&&hits| hits > 0).count();
+            s.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit));
+        }
+        s
+    }
+}
+
+impl Interpreter {
+    // called when symbol is evaluated
+    pub fn cover(&mut self, symbol: &Value) {
+        if let Some(ref mut coverage) = self.coverage {
+            if let Some(location) = self.locations.get(symbol) {
+                let lines = coverage.hits.entry(location.source.to_string()).or_insert_with(HashMap::new);
+                *lines.entry(location.line).or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub fn cover_quoted(&mut self, datum: &Value) {
+        if datum.get_symbol().is_some() {
+            self.cover(datum);
+        } else if let Some(elements) = datum.get_list() {
+            for x in &elements { self.cover_quoted(x); }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// Breaking into a debugger, e.g. the one of the repl.
+//
+// The debugger is called at (break), at calls of names with a breakpoint, when a condition is raised and
+// while stepping. It runs with the scope of the code that was interrupted as current scope, so it can show
+// its bindings and evaluate expressions in it. Stepping only stops in code run by the evaluator,
+// procedures run by the vm are stepped over. Watched names only write their changes by set!.
+
+use ::value::{Value, Condition};
+use ::native;
+use super::Interpreter;
+
+// What the program does after the debugger returns.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DebugAction {
+    // breaks before the next evaluation step
+    Step,
+    // breaks before the next step that isn't inside a procedure called from here
+    Next,
+    // runs until the next (break) or condition
+    Continue,
+}
+
+// gets what the program was doing when it was interrupted, e.g. "condition: undefined ident: x"
+pub type Debugger = dyn FnMut(&mut Interpreter, &str) -> DebugAction + Send;
+
+impl Interpreter {
+    // calls the debugger, if there is one
+    pub fn debug_break(&mut self, reason: &str) {
+        let mut debugger = match self.debugger.take() {
+            Some(debugger) => debugger,
+            None => return,
+        };
+        // the code the debugger evaluates doesn't break itself
+        self.stepping = None;
+        let depth = self.call_stack.len();
+        let action = debugger(self, reason);
+        self.debugger = Some(debugger);
+        self.stepping = match action {
+            DebugAction::Continue => None,
+            action => Some((action, depth)),
+        };
+    }
+
+    // for when the program the debugger stepped through has finished
+    pub fn stop_stepping(&mut self) {
+        self.stepping = None;
+    }
+
+    // true if operator is the name of a procedure with a breakpoint, see set-breakpoint
+    pub fn has_breakpoint(&self, operator: &Value) -> bool {
+        !self.breakpoints.is_empty() && operator.get_symbol().map_or(false, |id| self.breakpoints.contains(&id))
+    }
+
+    // called by set! before the binding of id is changed, writes the change if id is watched
+    pub fn report_set(&mut self, id: u64, value: &Value) -> Result<(), Condition> {
+        if !self.watched.contains(&id) { return Ok(()); }
+        if let Some(old) = self.current_scope.lookup_symbol(id) {
+            let line = format!("watch: {} changed from {} to {}\n", Value::new_symbol(id).to_string(&self.interner),
+                               old.to_string(&self.interner), value.to_string(&self.interner));
+            native::output("watch", &line, None, self)?;
+        }
+        Ok(())
+    }
+
+    // called before every evaluation step while stepping
+    pub fn break_at_step(&mut self, value: &Value) {
+        let breaks = match self.stepping {
+            Some((DebugAction::Next, depth)) => self.call_stack.len() <= depth,
+            Some(_) => true,
+            None => false,
+        };
+        if breaks {
+            let reason = format!("step: {}", value.to_string(&self.interner));
+            self.debug_break(&reason);
+        }
+    }
+}
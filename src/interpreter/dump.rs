@@ -0,0 +1,86 @@
+// Writing the global scope to a file and reading it back, for keeping the bindings of a session.
+//
+// The file holds a (define name expr) form per binding that was added or changed after Interpreter::init,
+// sorted by name, so dumping the same bindings always writes the same file.
+// Data is written quoted and procedures as their source. Only procedures defined in the global scope are
+// written, because the scopes closures capture are lost. Values that can't be read back in like ports,
+// natives or continuations are skipped.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::mem;
+use ::value::{Value, Condition};
+use ::grammar;
+use super::Interpreter;
+
+impl Interpreter {
+    // returns the number of bindings that were written
+    pub fn dump_scope(&mut self, path: PathBuf) -> Result<usize, Condition> {
+        let mut forms = vec![];
+        for id in self.global_scope.symbol_ids() {
+            // safe because the ids are bound in the global scope
+            let value = self.global_scope.lookup_symbol(id).unwrap();
+            if self.initial_bindings.get(&id) == Some(&value.id()) { continue; }
+
+            let name = Value::new_symbol(id).to_string(&self.interner);
+            let form = match self.expression(&value) {
+                Some(expr) => format!("(define {} {})", name, expr),
+                None => continue,
+            };
+            // e.g. symbols made by string->symbol can't always be read back in
+            let readable = grammar::parse(&form, &mut self.interner).map_or(false, |parsed| parsed.len() == 1);
+            if readable { forms.push((name, form)); }
+        }
+        forms.sort();
+
+        let written = File::create(&path).and_then(|mut file| {
+            for &(_, ref form) in &forms {
+                writeln!(file, "{}", form)?;
+            }
+            Ok(())
+        });
+        if let Err(err) = written {
+            raise_condition!(format!("dump-scope could not write {:?}: {}", path, err));
+        }
+        Ok(forms.len())
+    }
+
+    // evaluates a file written by dump_scope in the global scope
+    pub fn load_scope(&mut self, path: PathBuf) -> Result<Value, Condition> {
+        let scope = mem::replace(&mut self.current_scope, self.global_scope.clone());
+        let res = self.load(path);
+        self.current_scope = scope;
+        res
+    }
+
+    // code that evaluates to value, None if there's none
+    fn expression(&self, value: &Value) -> Option<String> {
+        if let Some(procedure) = value.get_proc() {
+            if procedure.parent_scope() != &self.global_scope { return None; }
+            return Some(procedure.source(&self.interner));
+        }
+        if let Some(table) = value.get_hash_table() {
+            let mut entries = vec![];
+            for (key, x) in table.read().unwrap().entries() {
+                if !is_datum(key) || !is_datum(x) { return None; }
+                entries.push(Value::new_list(&[key.clone(), x.clone()]));
+            }
+            return Some(format!("(make-hash '{})", Value::new_list(&entries).to_string(&self.interner)));
+        }
+        if is_datum(value) {
+            return Some(format!("'{}", value.to_string(&self.interner)));
+        }
+        None
+    }
+}
+
+// true if value is written the way it's read back in, floats are written like +inf.0 even if they aren't finite
+fn is_datum(value: &Value) -> bool {
+    if let Some(elements) = value.get_list() { return elements.iter().all(is_datum); }
+    if let Some((elements, tail)) = value.get_dotted_list() { return elements.iter().all(is_datum) && is_datum(&tail); }
+    if let Some(elements) = value.get_vector() { return elements.read().unwrap().iter().all(is_datum); }
+    value.get_bool().is_some() || value.get_char().is_some() || value.get_integer().is_some() ||
+    value.get_bigint().is_some() || value.get_rational().is_some() || value.get_float().is_some() || value.get_string().is_some() ||
+    value.get_symbol().is_some() || value.get_keyword().is_some()
+}
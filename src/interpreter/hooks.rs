@@ -0,0 +1,46 @@
+// Closures the application calls around evaluations, for profilers, tracers and coverage tools written in rust.
+//
+// They're called for the forms evaluated by Interpreter::evaluate, i.e. top level forms, the operators and
+// args of calls and the forms of bodies that aren't in tail position. Forms in tail position are part of the
+// evaluation of the form they're in, and the vm only evaluates the forms it doesn't compile.
+// Hooks aren't called for what they evaluate themselves.
+
+use ::value::{Value, Condition};
+use super::Interpreter;
+
+// gets the form that is about to be evaluated
+pub type PreEvalHook = dyn FnMut(&mut Interpreter, &Value) + Send;
+// gets the form and what evaluating it returned
+pub type PostEvalHook = dyn FnMut(&mut Interpreter, &Value, &Result<Value, Condition>) + Send;
+
+impl Interpreter {
+    pub fn set_pre_eval_hook<F>(&mut self, hook: F)
+    where F: FnMut(&mut Interpreter, &Value) + Send + 'static {
+        self.pre_eval_hook = Some(Box::new(hook));
+    }
+
+    pub fn set_post_eval_hook<F>(&mut self, hook: F)
+    where F: FnMut(&mut Interpreter, &Value, &Result<Value, Condition>) + Send + 'static {
+        self.post_eval_hook = Some(Box::new(hook));
+    }
+
+    pub fn remove_eval_hooks(&mut self) {
+        self.pre_eval_hook = None;
+        self.post_eval_hook = None;
+    }
+
+    // the hooks are taken out while they run, a hook that sets a new one replaces itself
+    pub fn before_eval(&mut self, form: &Value) {
+        if let Some(mut hook) = self.pre_eval_hook.take() {
+            hook(self, form);
+            if self.pre_eval_hook.is_none() { self.pre_eval_hook = Some(hook); }
+        }
+    }
+
+    pub fn after_eval(&mut self, form: &Value, res: &Result<Value, Condition>) {
+        if let Some(mut hook) = self.post_eval_hook.take() {
+            hook(self, form, res);
+            if self.post_eval_hook.is_none() { self.post_eval_hook = Some(hook); }
+        }
+    }
+}
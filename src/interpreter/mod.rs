@@ -0,0 +1,838 @@
+#[cfg(test)]
+mod tests;
+
+mod compiler;
+mod dump;
+mod trace;
+mod profile;
+mod coverage;
+mod debug;
+mod hooks;
+mod random;
+pub use self::compiler::{Compiler, CompiledBody, Chunk, Op, Unsupported};
+pub use self::random::Random;
+pub use self::debug::{DebugAction, Debugger};
+pub use self::profile::Profile;
+pub use self::coverage::Coverage;
+pub use self::hooks::{PreEvalHook, PostEvalHook};
+
+pub mod vm;
+
+use ::value::{Value, Condition, ConditionKind, RecursionPoint, Port, Parameter, HostProc, Args};
+use ::scope::Scope;
+use ::native;
+use ::gc;
+use ::string_interner::StringInterner;
+use ::grammar::{self, error_printing, Macros, Locations};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::mem;
+use std::fs::File;
+use std::io::Read;
+use std::time::Instant;
+
+// Tells the interpreter what to do next after a step of evaluation.
+// Everything but Return is evaluated in tail position.
+pub enum Trampoline {
+    Return(Value),
+    Eval(Value),
+    RecursionPoint(RecursionPoint),
+    Recur(Vec<Value>),
+    // the body of a procedure called while profiling, named like the procedure, see apply_profiled
+    Profiled(String, Box<Trampoline>),
+}
+
+// A procedure call that hasn't returned yet, see Interpreter::backtrace.
+// Tail calls replace the frame of their caller.
+struct Frame {
+    procedure: Value,
+    // the operator of the call, if it has a location
+    call_site: Option<Value>,
+}
+
+// What went wrong in Interpreter::eval_str.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // the message points at the error in the source
+    Parse(String),
+    Condition(Condition),
+}
+
+impl Error {
+    pub fn to_string(&self, interner: &StringInterner) -> String {
+        match self {
+            &Error::Parse(ref message) => message.clone(),
+            &Error::Condition(ref condition) => condition.to_string_with_backtrace(interner),
+        }
+    }
+}
+
+// The innermost reset is last in Interpreter::resets, see shift.
+pub struct ResetContext {
+    // calling func with args evaluates the body of the reset
+    pub func: Value,
+    pub args: Vec<Value>,
+    // results of the shifts in the body, when replaying for a composable continuation
+    pub log: Vec<Value>,
+    pub cursor: usize,
+    // escapes to this reset use this value as continuation
+    pub marker: Value,
+}
+
+// A module defined by define-module, the bindings of its body are in scope.
+// import binds the exported names in the importing scope.
+pub struct Module {
+    pub scope: Scope,
+    pub exports: Vec<u64>,
+}
+
+// Interpreters are Send, so a server can hand them to a pool of worker threads, but they aren't shared:
+// each one has its own heap, which counts the bytes and collects the cycles of the values it allocated, see gc.
+// Values can be sent to other threads too, they stay charged to the heap of the interpreter that allocated them.
+pub struct Interpreter {
+    pub interner: StringInterner,
+    pub macros: Macros,
+    pub current_scope: Scope,
+    // the top level scope, returned by interaction-environment
+    pub global_scope: Scope,
+    // the default port of read
+    pub stdin: Value,
+    // the parameter of the default port of write and display, bound to stdout unless it's parameterized
+    pub current_output_port: Value,
+    // the parameters bound by parameterize and their values, the innermost is last
+    pub parameterized: Vec<(Value, Value)>,
+    // the keys and values put with symbol-put!, by symbol
+    pub symbol_properties: HashMap<u64, Vec<(u64, Value)>>,
+    // the procedures marked by trace, see Interpreter::apply_traced
+    pub traced: Vec<Value>,
+    // the number of traced calls that haven't returned yet, for indenting their lines
+    trace_depth: usize,
+    // the calls counted since profile-start, None if nothing is profiled
+    pub profile: Option<Profile>,
+    // the lines evaluated since coverage-start, None if coverage isn't tracked
+    pub coverage: Option<Coverage>,
+    // the names and thunks of the tests defined by define-test, in the order they were defined
+    pub tests: Vec<(u64, Value)>,
+    // called at (break), when a condition is raised and while stepping, see debug
+    pub debugger: Option<Box<Debugger>>,
+    // the names that call the debugger when they're applied and the names whose set!s are written, see debug
+    pub breakpoints: Vec<u64>,
+    pub watched: Vec<u64>,
+    // the action of the last break unless it was continue, and the depth of the call stack it was chosen at
+    stepping: Option<(DebugAction, usize)>,
+    // called before and after Interpreter::evaluate, see hooks
+    pre_eval_hook: Option<Box<PreEvalHook>>,
+    post_eval_hook: Option<Box<PostEvalHook>>,
+    // the files currently being loaded, the innermost is last
+    pub loading: Vec<PathBuf>,
+    // modules by their written name, e.g. "(my lib)"
+    pub modules: HashMap<String, Module>,
+    // the exports of the modules whose bodies are being evaluated, the innermost is last
+    pub exports: Vec<Vec<u64>>,
+    // the script and its arguments, empty in the repl
+    pub command_line: Vec<String>,
+    // where parsed code came from, for condition messages
+    pub locations: Locations,
+    call_stack: Vec<Frame>,
+    // frames above this index belong to the innermost trampoline
+    frame_base: usize,
+    // the maximum number of frames in a backtrace, see set-backtrace-limit!
+    pub backtrace_limit: usize,
+    pub resets: Vec<ResetContext>,
+    // procedure bodies are run by the bytecode vm instead of being evaluated, when they can be compiled
+    pub use_vm: bool,
+    // the evaluation steps left before a fuel condition is raised, None for no limit, see set_fuel
+    fuel: Option<u64>,
+    // the bytes allocated when the limit was set and the limit, see set_memory_limit
+    memory_limit: Option<(usize, usize)>,
+    // import only loads modules from files if this is set, see Builder::without_io
+    pub file_access: bool,
+    // the values allocated while this interpreter runs, see gc::Heap::enter
+    pub heap: gc::Heap,
+    // the ids of the values bound in the global scope by init, see dump_scope
+    initial_bindings: HashMap<u64, usize>,
+    // used by random, seeded from the time unless random-seed! is called
+    pub random: Random,
+    // when the interpreter was created, see runtime
+    pub created: Instant,
+}
+
+// Chooses the families of natives that are installed, for running untrusted code.
+// Interpreter::builder().without_io().build() can't touch files or the process.
+#[derive(Debug, Clone, Copy)]
+pub struct Builder {
+    io: bool,
+    eval: bool,
+}
+
+impl Builder {
+    // leaves out ports of files, load, command-line and exit, import doesn't load modules from files
+    pub fn without_io(self) -> Self {
+        Builder { io: false, ..self }
+    }
+
+    // leaves out eval and interaction-environment
+    pub fn without_eval(self) -> Self {
+        Builder { eval: false, ..self }
+    }
+
+    pub fn build(self) -> Interpreter {
+        Interpreter::with_builder(self)
+    }
+}
+
+impl Interpreter {
+    // everything is installed
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> Builder {
+        Builder { io: true, eval: true }
+    }
+
+    fn with_builder(builder: Builder) -> Self {
+        let heap = gc::Heap::new();
+        let _running = heap.enter();
+        let scope = Scope::new();
+        let mut interpreter = Interpreter {
+            interner: StringInterner::new(),
+            macros: Macros::new(),
+            current_scope: scope.clone(),
+            global_scope: scope,
+            stdin: Value::new_port(Port::stdin()),
+            current_output_port: Value::new_parameter(Parameter::new(Value::new_port(Port::stdout()), None)),
+            parameterized: vec![],
+            symbol_properties: HashMap::new(),
+            traced: vec![],
+            trace_depth: 0,
+            profile: None,
+            coverage: None,
+            tests: vec![],
+            debugger: None,
+            breakpoints: vec![],
+            watched: vec![],
+            stepping: None,
+            pre_eval_hook: None,
+            post_eval_hook: None,
+            loading: vec![],
+            modules: HashMap::new(),
+            exports: vec![],
+            command_line: vec![],
+            locations: Locations::new(),
+            call_stack: vec![],
+            frame_base: 0,
+            backtrace_limit: 10,
+            resets: vec![],
+            use_vm: false,
+            fuel: None,
+            memory_limit: None,
+            file_access: builder.io,
+            heap: heap.clone(),
+            initial_bindings: HashMap::new(),
+            random: Random::from_time(),
+            created: Instant::now(),
+        };
+        interpreter.init(builder);
+        interpreter
+    }
+
+    fn init(&mut self, builder: Builder) {
+        self.add_str_to_current_scope("eq?", Value::new_native_proc(native::poly_eq));
+        self.add_str_to_current_scope("eqv?", Value::new_native_proc(native::eqv));
+        self.add_str_to_current_scope("equal?", Value::new_native_proc(native::equal));
+        self.add_str_to_current_scope("not", Value::new_native_proc(native::not));
+
+        self.add_str_to_current_scope("null?", Value::new_native_proc(native::null_));
+        self.add_str_to_current_scope("boolean?", Value::new_native_proc(native::boolean_));
+        self.add_str_to_current_scope("symbol?", Value::new_native_proc(native::symbol_));
+        self.add_str_to_current_scope("keyword?", Value::new_native_proc(native::keyword_));
+        self.add_str_to_current_scope("integer?", Value::new_native_proc(native::integer_));
+        self.add_str_to_current_scope("rational?", Value::new_native_proc(native::rational_));
+        self.add_str_to_current_scope("float?", Value::new_native_proc(native::float_));
+        self.add_str_to_current_scope("number?", Value::new_native_proc(native::number_));
+        self.add_str_to_current_scope("char?", Value::new_native_proc(native::char_));
+        self.add_str_to_current_scope("string?", Value::new_native_proc(native::string_));
+        self.add_str_to_current_scope("procedure?", Value::new_native_proc(native::procedure_));
+        self.add_str_to_current_scope("list?", Value::new_native_proc(native::list_));
+        self.add_str_to_current_scope("pair?", Value::new_native_proc(native::pair_));
+        self.add_str_to_current_scope("vector?", Value::new_native_proc(native::vector_));
+        self.add_str_to_current_scope("hash?", Value::new_native_proc(native::hash_));
+        self.add_str_to_current_scope("port?", Value::new_native_proc(native::port_));
+        self.add_str_to_current_scope("eof-object?", Value::new_native_proc(native::eof_object_));
+        self.add_str_to_current_scope("environment?", Value::new_native_proc(native::environment_));
+        self.add_str_to_current_scope("promise?", Value::new_native_proc(native::promise_));
+        self.add_str_to_current_scope("userdata?", Value::new_native_proc(native::userdata_));
+
+        self.add_str_to_current_scope("char->integer", Value::new_native_proc(native::char_integer));
+        self.add_str_to_current_scope("integer->char", Value::new_native_proc(native::integer_char));
+        self.add_str_to_current_scope("number->string", Value::new_native_proc(native::number_string));
+        self.add_str_to_current_scope("string->number", Value::new_native_proc(native::string_number));
+        self.add_str_to_current_scope("symbol->string", Value::new_native_proc(native::symbol_string));
+        self.add_str_to_current_scope("string->symbol", Value::new_native_proc(native::string_symbol));
+        self.add_str_to_current_scope("gensym", Value::new_native_proc(native::gensym));
+        self.add_str_to_current_scope("string->uninterned-symbol", Value::new_native_proc(native::string_uninterned_symbol));
+        self.add_str_to_current_scope("symbol-interned?", Value::new_native_proc(native::symbol_interned_));
+        self.add_str_to_current_scope("symbol-put!", Value::new_native_proc(native::symbol_put));
+        self.add_str_to_current_scope("symbol-get", Value::new_native_proc(native::symbol_get));
+        self.add_str_to_current_scope("symbol-remove!", Value::new_native_proc(native::symbol_remove));
+        self.add_str_to_current_scope("symbol-properties", Value::new_native_proc(native::symbol_properties));
+        self.add_str_to_current_scope("keyword->string", Value::new_native_proc(native::keyword_string));
+        self.add_str_to_current_scope("string->keyword", Value::new_native_proc(native::string_keyword));
+
+        self.add_str_to_current_scope("+", Value::new_native_proc(native::plus));
+        self.add_str_to_current_scope("-", Value::new_native_proc(native::minus));
+        self.add_str_to_current_scope("*", Value::new_native_proc(native::multiply));
+        self.add_str_to_current_scope("/", Value::new_native_proc(native::divide));
+        self.add_str_to_current_scope("quotient", Value::new_native_proc(native::quotient));
+        self.add_str_to_current_scope("remainder", Value::new_native_proc(native::remainder));
+        self.add_str_to_current_scope("modulo", Value::new_native_proc(native::modulo));
+        self.add_str_to_current_scope("abs", Value::new_native_proc(native::abs));
+        self.add_str_to_current_scope("min", Value::new_native_proc(native::min));
+        self.add_str_to_current_scope("max", Value::new_native_proc(native::max));
+        self.add_str_to_current_scope("gcd", Value::new_native_proc(native::gcd));
+        self.add_str_to_current_scope("lcm", Value::new_native_proc(native::lcm));
+        self.add_str_to_current_scope("expt", Value::new_native_proc(native::expt));
+        self.add_str_to_current_scope("exact->inexact", Value::new_native_proc(native::exact_inexact));
+        self.add_str_to_current_scope("floor", Value::new_native_proc(native::floor));
+        self.add_str_to_current_scope("ceiling", Value::new_native_proc(native::ceiling));
+        self.add_str_to_current_scope("truncate", Value::new_native_proc(native::truncate));
+        self.add_str_to_current_scope("round", Value::new_native_proc(native::round));
+        self.add_str_to_current_scope("sqrt", Value::new_native_proc(native::sqrt));
+        self.add_str_to_current_scope("exp", Value::new_native_proc(native::exp));
+        self.add_str_to_current_scope("log", Value::new_native_proc(native::log));
+        self.add_str_to_current_scope("sin", Value::new_native_proc(native::sin));
+        self.add_str_to_current_scope("cos", Value::new_native_proc(native::cos));
+        self.add_str_to_current_scope("tan", Value::new_native_proc(native::tan));
+        self.add_str_to_current_scope("atan", Value::new_native_proc(native::atan));
+
+        self.add_str_to_current_scope("bitwise-and", Value::new_native_proc(native::bitwise_and));
+        self.add_str_to_current_scope("bitwise-or", Value::new_native_proc(native::bitwise_or));
+        self.add_str_to_current_scope("bitwise-xor", Value::new_native_proc(native::bitwise_xor));
+        self.add_str_to_current_scope("bitwise-not", Value::new_native_proc(native::bitwise_not));
+        self.add_str_to_current_scope("arithmetic-shift", Value::new_native_proc(native::arithmetic_shift));
+        self.add_str_to_current_scope("bit-count", Value::new_native_proc(native::bit_count));
+
+        self.add_str_to_current_scope("random", Value::new_native_proc(native::random));
+        self.add_str_to_current_scope("random-seed!", Value::new_native_proc(native::random_seed));
+
+        self.add_str_to_current_scope("current-seconds", Value::new_native_proc(native::current_seconds));
+        self.add_str_to_current_scope("current-milliseconds", Value::new_native_proc(native::current_milliseconds));
+        self.add_str_to_current_scope("runtime", Value::new_native_proc(native::runtime));
+
+        self.add_str_to_current_scope("=", Value::new_native_proc(native::eq));
+        self.add_str_to_current_scope(">", Value::new_native_proc(native::gt));
+        self.add_str_to_current_scope(">=", Value::new_native_proc(native::ge));
+        self.add_str_to_current_scope("<", Value::new_native_proc(native::lt));
+        self.add_str_to_current_scope("<=", Value::new_native_proc(native::le));
+
+        self.add_str_to_current_scope("apply", Value::new_native_proc(native::apply));
+
+        self.add_str_to_current_scope("list", Value::new_native_proc(native::list));
+        self.add_str_to_current_scope("first", Value::new_native_proc(native::first));
+        self.add_str_to_current_scope("rest", Value::new_native_proc(native::rest));
+        self.add_str_to_current_scope("cons", Value::new_native_proc(native::cons));
+        self.add_str_to_current_scope("car", Value::new_native_proc(native::car));
+        self.add_str_to_current_scope("cdr", Value::new_native_proc(native::cdr));
+        self.add_str_to_current_scope("set-car!", Value::new_native_proc(native::set_car));
+        self.add_str_to_current_scope("set-cdr!", Value::new_native_proc(native::set_cdr));
+        self.add_str_to_current_scope("map", Value::new_native_proc(native::map));
+        self.add_str_to_current_scope("for-each", Value::new_native_proc(native::for_each));
+        self.add_str_to_current_scope("filter", Value::new_native_proc(native::filter));
+        self.add_str_to_current_scope("foldl", Value::new_native_proc(native::foldl));
+        self.add_str_to_current_scope("foldr", Value::new_native_proc(native::foldr));
+        self.add_str_to_current_scope("sort", Value::new_native_proc(native::sort));
+        self.add_str_to_current_scope("sort!", Value::new_native_proc(native::sort_in_place));
+
+        self.add_str_to_current_scope("make-vector", Value::new_native_proc(native::make_vector));
+        self.add_str_to_current_scope("vector", Value::new_native_proc(native::vector));
+        self.add_str_to_current_scope("vector-ref", Value::new_native_proc(native::vector_ref));
+        self.add_str_to_current_scope("vector-set!", Value::new_native_proc(native::vector_set));
+        self.add_str_to_current_scope("vector-length", Value::new_native_proc(native::vector_length));
+        self.add_str_to_current_scope("vector->list", Value::new_native_proc(native::vector_list));
+        self.add_str_to_current_scope("list->vector", Value::new_native_proc(native::list_vector));
+
+        self.add_str_to_current_scope("make-hash", Value::new_native_proc(native::make_hash));
+        self.add_str_to_current_scope("hash-ref", Value::new_native_proc(native::hash_ref));
+        self.add_str_to_current_scope("hash-set!", Value::new_native_proc(native::hash_set));
+        self.add_str_to_current_scope("hash-remove!", Value::new_native_proc(native::hash_remove));
+        self.add_str_to_current_scope("hash-keys", Value::new_native_proc(native::hash_keys));
+        self.add_str_to_current_scope("hash-count", Value::new_native_proc(native::hash_count));
+
+        self.add_str_to_current_scope("open-input-string", Value::new_native_proc(native::open_input_string));
+        self.add_str_to_current_scope("open-output-string", Value::new_native_proc(native::open_output_string));
+        self.add_str_to_current_scope("get-output-string", Value::new_native_proc(native::get_output_string));
+        self.add_str_to_current_scope("read-line", Value::new_native_proc(native::read_line));
+        self.add_str_to_current_scope("read-char", Value::new_native_proc(native::read_char));
+        let current_output_port = self.current_output_port.clone();
+        self.add_str_to_current_scope("current-output-port", current_output_port);
+        self.add_str_to_current_scope("write", Value::new_native_proc(native::write));
+        self.add_str_to_current_scope("write-shared", Value::new_native_proc(native::write_shared));
+        self.add_str_to_current_scope("display", Value::new_native_proc(native::display));
+        self.add_str_to_current_scope("newline", Value::new_native_proc(native::newline));
+        self.add_str_to_current_scope("format", Value::new_native_proc(native::format));
+        self.add_str_to_current_scope("pp", Value::new_native_proc(native::pp));
+        self.add_str_to_current_scope("read", Value::new_native_proc(native::read));
+        self.add_str_to_current_scope("read-string", Value::new_native_proc(native::read_string));
+        self.add_str_to_current_scope("close-port", Value::new_native_proc(native::close_port));
+
+        self.add_str_to_current_scope("char-alphabetic?", Value::new_native_proc(native::char_alphabetic));
+        self.add_str_to_current_scope("char-numeric?", Value::new_native_proc(native::char_numeric));
+        self.add_str_to_current_scope("char-whitespace?", Value::new_native_proc(native::char_whitespace));
+        self.add_str_to_current_scope("char-upper-case?", Value::new_native_proc(native::char_upper_case));
+        self.add_str_to_current_scope("char-lower-case?", Value::new_native_proc(native::char_lower_case));
+        self.add_str_to_current_scope("char-upcase", Value::new_native_proc(native::char_upcase));
+        self.add_str_to_current_scope("char-downcase", Value::new_native_proc(native::char_downcase));
+        self.add_str_to_current_scope("digit-value", Value::new_native_proc(native::digit_value));
+        self.add_str_to_current_scope("char=?", Value::new_native_proc(native::char_eq));
+        self.add_str_to_current_scope("char<?", Value::new_native_proc(native::char_lt));
+        self.add_str_to_current_scope("char<=?", Value::new_native_proc(native::char_le));
+        self.add_str_to_current_scope("char>?", Value::new_native_proc(native::char_gt));
+        self.add_str_to_current_scope("char>=?", Value::new_native_proc(native::char_ge));
+        self.add_str_to_current_scope("char-ci=?", Value::new_native_proc(native::char_ci_eq));
+        self.add_str_to_current_scope("char-ci<?", Value::new_native_proc(native::char_ci_lt));
+        self.add_str_to_current_scope("char-ci<=?", Value::new_native_proc(native::char_ci_le));
+        self.add_str_to_current_scope("char-ci>?", Value::new_native_proc(native::char_ci_gt));
+        self.add_str_to_current_scope("char-ci>=?", Value::new_native_proc(native::char_ci_ge));
+
+        self.add_str_to_current_scope("string-length", Value::new_native_proc(native::string_length));
+        self.add_str_to_current_scope("string-ref", Value::new_native_proc(native::string_ref));
+        self.add_str_to_current_scope("substring", Value::new_native_proc(native::substring));
+        self.add_str_to_current_scope("string-append", Value::new_native_proc(native::string_append));
+        self.add_str_to_current_scope("string-upcase", Value::new_native_proc(native::string_upcase));
+        self.add_str_to_current_scope("string-downcase", Value::new_native_proc(native::string_downcase));
+        self.add_str_to_current_scope("string-split", Value::new_native_proc(native::string_split));
+        self.add_str_to_current_scope("string-join", Value::new_native_proc(native::string_join));
+        self.add_str_to_current_scope("string-contains?", Value::new_native_proc(native::string_contains));
+        self.add_str_to_current_scope("string", Value::new_native_proc(native::string));
+        self.add_str_to_current_scope("make-string", Value::new_native_proc(native::make_string));
+        self.add_str_to_current_scope("string-copy", Value::new_native_proc(native::string_copy));
+        self.add_str_to_current_scope("string->list", Value::new_native_proc(native::string_list));
+        self.add_str_to_current_scope("list->string", Value::new_native_proc(native::list_string));
+
+        self.add_str_to_current_scope("raise", Value::new_native_proc(native::raise));
+        self.add_str_to_current_scope("error", Value::new_native_proc(native::error));
+        self.add_str_to_current_scope("with-exception-handler", Value::new_native_proc(native::with_exception_handler));
+        self.add_str_to_current_scope("error-object?", Value::new_native_proc(native::error_object_));
+        self.add_str_to_current_scope("error-object-message", Value::new_native_proc(native::error_object_message));
+        self.add_str_to_current_scope("error-object-irritants", Value::new_native_proc(native::error_object_irritants));
+        self.add_str_to_current_scope("condition-kind", Value::new_native_proc(native::condition_kind));
+        self.add_str_to_current_scope("call/cc", Value::new_native_proc(native::call_cc));
+        self.add_str_to_current_scope("values", Value::new_native_proc(native::values));
+        self.add_str_to_current_scope("call-with-values", Value::new_native_proc(native::call_with_values));
+        self.add_str_to_current_scope("call-with-current-continuation", Value::new_native_proc(native::call_cc));
+        self.add_str_to_current_scope("dynamic-wind", Value::new_native_proc(native::dynamic_wind));
+        self.add_str_to_current_scope("make-parameter", Value::new_native_proc(native::make_parameter));
+
+        self.add_str_to_current_scope("force", Value::new_native_proc(native::force));
+        self.add_str_to_current_scope("make-promise", Value::new_native_proc(native::make_promise));
+
+        self.add_str_to_current_scope("value->json", Value::new_native_proc(native::value_json));
+        self.add_str_to_current_scope("json->value", Value::new_native_proc(native::json_value));
+
+        self.add_str_to_current_scope("userdata-type", Value::new_native_proc(native::userdata_type));
+        self.add_str_to_current_scope("call-method", Value::new_native_proc(native::call_method));
+
+        self.add_str_to_current_scope("collect-garbage", Value::new_native_proc(native::collect_garbage));
+        self.add_str_to_current_scope("set-backtrace-limit!", Value::new_native_proc(native::set_backtrace_limit));
+
+        self.add_str_to_current_scope("symbol-space", Value::new_native_proc(native::symbol_space));
+        self.add_str_to_current_scope("environment-bindings", Value::new_native_proc(native::environment_bindings));
+        self.add_str_to_current_scope("procedure-name", Value::new_native_proc(native::procedure_name));
+        self.add_str_to_current_scope("procedure-arity", Value::new_native_proc(native::procedure_arity));
+        self.add_str_to_current_scope("procedure-source", Value::new_native_proc(native::procedure_source));
+        self.add_str_to_current_scope("describe", Value::new_native_proc(native::describe));
+        self.add_str_to_current_scope("trace", Value::new_native_proc(native::trace));
+        self.add_str_to_current_scope("untrace", Value::new_native_proc(native::untrace));
+        self.add_str_to_current_scope("break", Value::new_native_proc(native::debug_break));
+        self.add_str_to_current_scope("set-breakpoint", Value::new_native_proc(native::set_breakpoint));
+        self.add_str_to_current_scope("remove-breakpoint", Value::new_native_proc(native::remove_breakpoint));
+        self.add_str_to_current_scope("watch", Value::new_native_proc(native::watch));
+        self.add_str_to_current_scope("unwatch", Value::new_native_proc(native::unwatch));
+        self.add_str_to_current_scope("profile-start", Value::new_native_proc(native::profile_start));
+        self.add_str_to_current_scope("profile-report", Value::new_native_proc(native::profile_report));
+        self.add_str_to_current_scope("coverage-start", Value::new_native_proc(native::coverage_start));
+        self.add_str_to_current_scope("coverage-report", Value::new_native_proc(native::coverage_report));
+        self.add_str_to_current_scope("coverage-lcov", Value::new_native_proc(native::coverage_lcov));
+        self.add_str_to_current_scope("assert-equal", Value::new_native_proc(native::assert_equal));
+        self.add_str_to_current_scope("assert-true", Value::new_native_proc(native::assert_true));
+        self.add_str_to_current_scope("run-tests", Value::new_native_proc(native::run_tests));
+        self.add_str_to_current_scope("check-property", Value::new_native_proc(native::check_property));
+        self.add_str_to_current_scope("gen-integer", Value::new_native_proc(native::gen_integer));
+        self.add_str_to_current_scope("gen-boolean", Value::new_native_proc(native::gen_boolean));
+        self.add_str_to_current_scope("gen-list", Value::new_native_proc(native::gen_list));
+        self.add_str_to_current_scope("gen-one-of", Value::new_native_proc(native::gen_one_of));
+
+        if builder.io {
+            self.add_str_to_current_scope("open-input-file", Value::new_native_proc(native::open_input_file));
+            self.add_str_to_current_scope("open-output-file", Value::new_native_proc(native::open_output_file));
+            self.add_str_to_current_scope("call-with-input-file", Value::new_native_proc(native::call_with_input_file));
+            self.add_str_to_current_scope("call-with-output-file", Value::new_native_proc(native::call_with_output_file));
+            self.add_str_to_current_scope("load", Value::new_native_proc(native::load));
+            self.add_str_to_current_scope("command-line", Value::new_native_proc(native::command_line));
+            self.add_str_to_current_scope("exit", Value::new_native_proc(native::exit));
+            self.add_str_to_current_scope("dump-scope", Value::new_native_proc(native::dump_scope));
+            self.add_str_to_current_scope("load-scope", Value::new_native_proc(native::load_scope));
+            self.add_str_to_current_scope("getenv", Value::new_native_proc(native::getenv));
+            self.add_str_to_current_scope("setenv", Value::new_native_proc(native::setenv));
+            self.add_str_to_current_scope("system", Value::new_native_proc(native::system));
+            self.add_str_to_current_scope("process-run", Value::new_native_proc(native::process_run));
+            self.add_str_to_current_scope("file-exists?", Value::new_native_proc(native::file_exists));
+            self.add_str_to_current_scope("delete-file", Value::new_native_proc(native::delete_file));
+            self.add_str_to_current_scope("directory-list", Value::new_native_proc(native::directory_list));
+            self.add_str_to_current_scope("create-directory", Value::new_native_proc(native::create_directory));
+            self.add_str_to_current_scope("file-size", Value::new_native_proc(native::file_size));
+            self.add_str_to_current_scope("current-directory", Value::new_native_proc(native::current_directory));
+            self.add_str_to_current_scope("set-current-directory!", Value::new_native_proc(native::set_current_directory));
+            self.add_str_to_current_scope("tcp-connect", Value::new_native_proc(native::tcp_connect));
+            self.add_str_to_current_scope("tcp-listen", Value::new_native_proc(native::tcp_listen));
+            self.add_str_to_current_scope("tcp-accept", Value::new_native_proc(native::tcp_accept));
+            self.add_str_to_current_scope("tcp-listener-port", Value::new_native_proc(native::tcp_listener_port));
+        }
+        if builder.eval {
+            self.add_str_to_current_scope("eval", Value::new_native_proc(native::eval));
+            self.add_str_to_current_scope("interaction-environment", Value::new_native_proc(native::interaction_environment));
+        }
+
+        self.load_prelude();
+        for id in self.global_scope.symbol_ids() {
+            // safe because the ids are bound in the global scope
+            let value = self.global_scope.lookup_symbol(id).unwrap();
+            self.initial_bindings.insert(id, value.id());
+        }
+    }
+
+    // the prelude is part of the binary, so it failing is a bug
+    fn load_prelude(&mut self) {
+        let input = include_str!("prelude.lisp");
+        let parsed = grammar::parse_with_locations(input, "prelude", 1, &mut self.interner, &mut self.macros, &mut self.locations)
+        .unwrap_or_else(|err| panic!("internal error: could not parse prelude:\n{}", error_printing::create_error_message(input, &err)));
+        for x in &parsed {
+            if let Err(condition) = self.evaluate(x) {
+                panic!("internal error: could not evaluate prelude: {}", condition.to_string(&self.interner));
+            }
+        }
+    }
+
+    pub fn evaluate(&mut self, value: &Value) -> Result<Value, Condition> {
+        let _running = self.heap.enter();
+        if self.pre_eval_hook.is_some() { self.before_eval(value); }
+        // tail calls replace the current scope without restoring it, so this is done here
+        let scope = self.current_scope.clone();
+        let res = self.with_frames(|interpreter| interpreter.trampoline(Trampoline::Eval(value.clone())));
+        self.current_scope = scope;
+        if self.post_eval_hook.is_some() { self.after_eval(value, &res); }
+        res
+    }
+
+    // Calls func with already evaluated args and returns the result, for natives that call procedures.
+    pub fn call_procedure(&mut self, func: &Value, args: Vec<Value>) -> Result<Value, Condition> {
+        let _running = self.heap.enter();
+        let scope = self.current_scope.clone();
+        let res = self.with_frames(|interpreter| interpreter.apply(func, args).and_then(|next| interpreter.trampoline(next)));
+        self.current_scope = scope;
+        res
+    }
+
+    // Frames pushed in f are popped afterwards.
+    // A condition gets the backtrace of the place it was raised, so it's set by the innermost call.
+    fn with_frames<F>(&mut self, f: F) -> Result<Value, Condition>
+    where F: FnOnce(&mut Self) -> Result<Value, Condition> {
+        let base = self.call_stack.len();
+        let outer_base = mem::replace(&mut self.frame_base, base);
+        let mut res = f(self);
+        if let Err(ref mut condition) = res {
+            if condition.backtrace().is_none() {
+                condition.set_backtrace(self.backtrace());
+                // escapes to continuations aren't raised by the program
+                if self.debugger.is_some() && condition.continuation().is_none() {
+                    let reason = format!("condition: {}", condition.to_string(&self.interner));
+                    self.debug_break(&reason);
+                }
+            }
+        }
+        self.call_stack.truncate(base);
+        self.frame_base = outer_base;
+        res
+    }
+
+    // the innermost frames first, at most backtrace_limit of them
+    pub fn backtrace(&self) -> Vec<String> {
+        let mut frames: Vec<String> = self.call_stack.iter().rev().take(self.backtrace_limit).map(|frame| {
+            let name = frame.procedure.get_proc().and_then(|p| p.name()).unwrap_or("lambda");
+            let location = frame.call_site.as_ref().map(|x| self.locations.describe(x)).unwrap_or_default();
+            format!("in {}{}", name, location)
+        }).collect();
+        if self.call_stack.len() > self.backtrace_limit {
+            frames.push(format!("... {} more", self.call_stack.len() - self.backtrace_limit));
+        }
+        frames
+    }
+
+    // Everything in tail position is evaluated in this loop instead of recursively,
+    // so tail calls run in constant stack space.
+    fn trampoline(&mut self, next: Trampoline) -> Result<Value, Condition> {
+        // when the profiled call evaluated in this trampoline started, tail calls replace it
+        let mut profiled = None;
+        let res = self.run_trampoline(next, &mut profiled);
+        if let Some(start) = profiled { self.leave_profiled(start); }
+        res
+    }
+
+    fn run_trampoline(&mut self, mut next: Trampoline, profiled: &mut Option<Instant>) -> Result<Value, Condition> {
+        let mut recursion_point: Option<RecursionPoint> = None;
+        loop {
+            next = match next {
+                Trampoline::Return(res) => return Ok(res),
+                Trampoline::Eval(value) => {
+                    self.check_limits()?;
+                    if self.stepping.is_some() { self.break_at_step(&value); }
+                    self.evaluate_step(&value)?
+                },
+                Trampoline::RecursionPoint(point) => {
+                    let next = point.evaluate(self)?;
+                    recursion_point = Some(point);
+                    next
+                },
+                Trampoline::Recur(args) => match recursion_point {
+                    Some(ref point) => point.recur(self, args)?,
+                    None => raise_condition!("recur outside of recursion point"),
+                },
+                Trampoline::Profiled(name, next) => {
+                    if let Some(start) = profiled.take() { self.leave_profiled(start); }
+                    *profiled = Some(self.enter_profiled(&name));
+                    *next
+                },
+            };
+        }
+    }
+
+    fn evaluate_step(&mut self, value: &Value) -> Result<Trampoline, Condition> {
+        if let Some(mut list) = value.get_list() {
+            if list.len() > 0 {
+                let (operator, mut args) = list.split_at_mut(1);
+                let func = self.evaluate(&operator[0])?;
+                if self.has_breakpoint(&operator[0]) {
+                    let reason = format!("breakpoint: {}", value.to_string(&self.interner));
+                    self.debug_break(&reason);
+                }
+
+                if let Some(f) = func.get_native_fn_ptr() {
+                    f(self, &mut args).map(Trampoline::Return)
+                } else {
+                    let args = args.iter().map(|x| self.evaluate(x)).collect::<Result<_, _>>()?;
+                    self.apply_at(&func, args, Some(&operator[0]))
+                }
+            } else {
+                raise_condition!("tried to evaluate ()")
+            }
+        } else if let Some(special_form) = value.get_special_form() {
+            special_form.evaluate(self)
+        } else if let Some(symbol) = value.get_symbol() {
+            if self.coverage.is_some() { self.cover(value); }
+            match self.current_scope.lookup_symbol(symbol) {
+                Some(x) => Ok(Trampoline::Return(x)),
+                None => raise_condition!(format!("undefined ident: {}{}", value.to_string(&self.interner), self.locations.describe(value))),
+            }
+        } else {
+            Ok(Trampoline::Return(value.clone()))
+        }
+    }
+
+    // Calls func with already evaluated args.
+    // Native procedures evaluate their args themselves, so they get them quoted.
+    pub fn apply(&mut self, func: &Value, args: Vec<Value>) -> Result<Trampoline, Condition> {
+        self.apply_at(func, args, None)
+    }
+
+    fn apply_at(&mut self, func: &Value, args: Vec<Value>, call_site: Option<&Value>) -> Result<Trampoline, Condition> {
+        if let Some(f) = func.get_native_fn_ptr() {
+            let mut args: Vec<Value> = args.into_iter().map(Value::new_quote).collect();
+            f(self, &mut args).map(Trampoline::Return)
+        } else if let Some(p) = func.get_host_proc() {
+            p.call(self, &args).map(Trampoline::Return)
+        } else if func.get_parameter().is_some() {
+            check_arity!("parameter", args.len(), 0);
+            Ok(Trampoline::Return(self.parameter_value(func)))
+        } else if let Some(k) = func.get_continuation() {
+            check_arity!("continuation", args.len(), 0, 1);
+            assert_or_condition!(k.is_active(), "continuation called outside of call/cc");
+            let value = args.into_iter().next().unwrap_or_else(Value::empty_list);
+            Err(Condition::escape(func.clone(), value))
+        } else if let Some(k) = func.get_composable_continuation() {
+            check_arity!("continuation", args.len(), 0, 1);
+            let value = args.into_iter().next().unwrap_or_else(Value::empty_list);
+            let mut log = k.log.clone();
+            log.push(value);
+            self.reset(k.func.clone(), k.args.clone(), log).map(Trampoline::Return)
+        } else if func.get_proc().is_some() && self.is_profiling() {
+            self.apply_profiled(func, args, call_site)
+        } else if func.get_proc().is_some() && self.is_traced(func) {
+            self.apply_traced(func, args, call_site)
+        } else if let Some(p) = func.get_proc() {
+            // the body is evaluated in the current trampoline, so this replaces the frame of a tail call
+            self.call_stack.truncate(self.frame_base);
+            self.call_stack.push(Frame { procedure: func.clone(), call_site: call_site.cloned() });
+            p.evaluate(self, args)
+        } else {
+            raise_condition!(format!("tried to call {}, which is not possible", func.to_string(&self.interner)))
+        }
+    }
+
+    // the value of the innermost parameterize that binds parameter, or the value it was created with
+    pub fn parameter_value(&self, parameter: &Value) -> Value {
+        match self.parameterized.iter().rev().find(|&&(ref p, _)| p.eqv(parameter)) {
+            Some(&(_, ref value)) => value.clone(),
+            // safe because this is only called with parameters
+            None => parameter.get_parameter().unwrap().value().clone(),
+        }
+    }
+
+    // the doc property of symbol, set by docstrings, or the documentation of the native it names
+    pub fn documentation(&mut self, symbol: u64) -> Option<String> {
+        let key = self.interner.intern("doc");
+        let property = self.symbol_properties.get(&symbol).and_then(|properties| properties.iter().find(|x| x.0 == key));
+        if let Some(doc) = property.and_then(|x| x.1.get_string()) {
+            return Some(doc.to_string());
+        }
+        let name = self.interner.lookup(symbol);
+        native::NATIVE_DOCS.iter().find(|x| Some(x.0) == name).map(|x| x.1.to_string())
+    }
+
+    // calls func with args delimited by a reset, the shifts in it return the values of log first
+    pub fn reset(&mut self, func: Value, args: Vec<Value>, log: Vec<Value>) -> Result<Value, Condition> {
+        let marker = Value::new_continuation();
+        let context = ResetContext { func: func.clone(), args: args.clone(), log: log, cursor: 0, marker: marker.clone() };
+        let depth = self.resets.len();
+        self.resets.push(context);
+        let res = self.call_procedure(&func, args);
+        self.resets.truncate(depth);
+        match res {
+            Err(ref condition) if condition.continuation().map_or(false, |k| k.id() == marker.id()) => Ok(condition.to_value()),
+            res => res,
+        }
+    }
+
+    // evaluates all forms in the file in the current scope and returns the last result
+    pub fn load(&mut self, path: PathBuf) -> Result<Value, Condition> {
+        let _running = self.heap.enter();
+        let mut input = String::new();
+        if let Err(err) = File::open(&path).and_then(|mut file| file.read_to_string(&mut input)) {
+            raise_condition!(format!("load could not open {:?}: {}", path, err));
+        }
+        let source = path.to_string_lossy().into_owned();
+        let parsed = match grammar::parse_with_locations(&input, &source, 1, &mut self.interner, &mut self.macros, &mut self.locations) {
+            Ok(parsed) => parsed,
+            Err(ref err) => raise_condition!(format!("load could not parse {:?}:\n{}", path, error_printing::create_error_message(&input, err))),
+        };
+
+        self.loading.push(path);
+        let mut result = Ok(Value::empty_list());
+        for x in &parsed {
+            result = self.evaluate(x);
+            if result.is_err() { break }
+        }
+        self.loading.pop();
+        result
+    }
+
+    // Parses and evaluates src in the current scope like load does with a file, returns the last result.
+    // For applications embedding the interpreter, see Value::as_i64 and friends for getting at the result.
+    pub fn eval_str(&mut self, src: &str) -> Result<Value, Error> {
+        let _running = self.heap.enter();
+        let parsed = match grammar::parse_with_locations(src, "eval_str", 1, &mut self.interner, &mut self.macros, &mut self.locations) {
+            Ok(parsed) => parsed,
+            Err(ref err) => return Err(Error::Parse(error_printing::create_error_message(src, err))),
+        };
+
+        let mut result = Value::empty_list();
+        for x in &parsed {
+            result = self.evaluate(x).map_err(Error::Condition)?;
+        }
+        Ok(result)
+    }
+
+    // Calls a procedure with args, for applications embedding the interpreter.
+    // Scripts can hand procedures to the application as callbacks, e.g. (on-event (lambda (e) ...)).
+    pub fn call(&mut self, procedure: &Value, args: &[Value]) -> Result<Value, Error> {
+        self.call_procedure(procedure, args.to_vec()).map_err(Error::Condition)
+    }
+
+    // Limits the evaluation steps until a fuel condition is raised, for running untrusted scripts.
+    // Every step raises it again once the fuel is used up, until set_fuel or remove_fuel is called.
+    pub fn set_fuel(&mut self, limit: u64) {
+        self.fuel = Some(limit);
+    }
+
+    pub fn remove_fuel(&mut self) {
+        self.fuel = None;
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    // Limits the bytes of values that were allocated after this call and are still alive,
+    // a memory condition is raised in the first step after it was exceeded.
+    // The bytes are estimated and counted per interpreter, see gc::Heap.
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.memory_limit = Some((self.heap.allocated(), bytes));
+    }
+
+    pub fn remove_memory_limit(&mut self) {
+        self.memory_limit = None;
+    }
+
+    // the bytes allocated since set_memory_limit, None if there's no limit
+    pub fn memory_used(&self) -> Option<usize> {
+        self.memory_limit.map(|(base, _)| self.heap.allocated().saturating_sub(base))
+    }
+
+    // For natives that allocate a lot at once like make-vector, so the limit is checked before.
+    pub fn reserve_memory(&mut self, bytes: usize) -> Result<(), Condition> {
+        match self.memory_limit {
+            Some((base, limit)) if self.heap.allocated().saturating_sub(base).saturating_add(bytes) > limit => {
+                Err(Condition::new(ConditionKind::Memory, "out of memory"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // called once per evaluation step and vm instruction, uses up fuel
+    pub fn check_limits(&mut self) -> Result<(), Condition> {
+        self.reserve_memory(0)?;
+        match self.fuel {
+            Some(0) => Err(Condition::new(ConditionKind::Fuel, "out of fuel")),
+            Some(ref mut fuel) => { *fuel -= 1; Ok(()) },
+            None => Ok(()),
+        }
+    }
+
+    // Binds a procedure written in rust in the global scope, for applications embedding the interpreter.
+    // f gets the evaluated args, e.g. (add 1 2) could be implemented as |_, args| Ok(Value::new_integer(args.get_i64(0)? + args.get_i64(1)?))
+    pub fn register_fn<F>(&mut self, name: &str, f: F)
+    where F: Fn(&mut Interpreter, Args) -> Result<Value, Condition> + Send + Sync + 'static {
+        let _running = self.heap.enter();
+        let id = self.interner.intern(name);
+        let procedure = Value::new_host_proc(HostProc::new(name.into(), Box::new(f)));
+        self.global_scope.add_symbol(id, procedure);
+        // like a define, it shadows a special form of the same name
+        self.macros.define_global(id);
+    }
+
+    fn add_str_to_current_scope(&mut self, s: &str, value: Value) {
+        let id = self.interner.intern(s);
+        self.current_scope.add_symbol(id, value);
+    }
+}
+
+// The procedures bound in the global scope and in modules form cycles with their scopes,
+// so the scopes are dropped and the cycles collected. Values still used by the application stay alive.
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        self.current_scope = Scope::new();
+        self.global_scope = Scope::new();
+        self.modules.clear();
+        self.heap.collect();
+    }
+}
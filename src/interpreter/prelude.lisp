@@ -0,0 +1,66 @@
+; Procedures that can be written in flip itself, evaluated in the global scope by Interpreter::init.
+; Loops are written as tail calls, so long lists don't overflow the stack.
+
+(define (caar x) (car (car x)))
+(define (cadr x) (car (cdr x)))
+(define (cdar x) (cdr (car x)))
+(define (cddr x) (cdr (cdr x)))
+(define (caddr x) (car (cddr x)))
+
+(define (length list)
+  (let count ((rest list) (n 0))
+    (if (null? rest) n (count (cdr rest) (+ n 1)))))
+
+(define (reverse list) (foldl cons '() list))
+
+; the last list isn't copied, it's the tail of the result
+(define (append . lists)
+  (cond ((null? lists) '())
+        ((null? (cdr lists)) (car lists))
+        (else (foldr cons (apply append (cdr lists)) (car lists)))))
+
+(define (list-tail list k)
+  (if (= k 0) list (list-tail (cdr list) (- k 1))))
+
+(define (list-ref list k) (car (list-tail list k)))
+
+(define (last-pair list)
+  (if (pair? (cdr list)) (last-pair (cdr list)) list))
+
+; (member x list [compare]), the rest of list starting with x or false, compare defaults to equal?
+(define (member x list . compare)
+  (let (same? (if (null? compare) equal? (car compare)))
+    (let search ((rest list))
+      (cond ((null? rest) false)
+            ((same? x (car rest)) rest)
+            (else (search (cdr rest)))))))
+
+; (assoc key alist [compare]), the first pair in alist with key as car or false
+(define (assoc key alist . compare)
+  (let (same? (if (null? compare) equal? (car compare)))
+    (let search ((rest alist))
+      (cond ((null? rest) false)
+            ((same? key (caar rest)) (car rest))
+            (else (search (cdr rest)))))))
+
+; the same with eq? and eqv?, eq? compares values in flip, so memq and assq only differ from member and
+; assoc for cyclic structures
+(define (memq x list) (member x list eq?))
+(define (memv x list) (member x list eqv?))
+(define (assq key alist) (assoc key alist eq?))
+(define (assv key alist) (assoc key alist eqv?))
+
+; (alist-delete key alist [compare]), alist without the pairs with key as car, compare defaults to equal?
+(define (alist-delete key alist . compare)
+  (let (same? (if (null? compare) equal? (car compare)))
+    (filter (lambda (entry) (not (same? key (car entry)))) alist)))
+
+; the pairs are copied, so set-cdr! on the copy doesn't change alist
+(define (alist-copy alist)
+  (map (lambda (entry) (cons (car entry) (cdr entry))) alist))
+
+; (with-output-to-string thunk), what thunk writes to the current output port
+(define (with-output-to-string thunk)
+  (let (port (open-output-string))
+    (parameterize ((current-output-port port)) (thunk))
+    (get-output-string port)))
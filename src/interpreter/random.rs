@@ -0,0 +1,52 @@
+// The pseudo random number generator of an interpreter, see the random native.
+// It's splitmix64, which is fast and good enough for simulations and games, but not for cryptography.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// interpreters created at the same time still get different seeds
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    // the same seed always produces the same numbers
+    pub fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+    // seeded from the current time
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs().wrapping_mul(1_000_000_000) ^ time.subsec_nanos() as u64)
+            .unwrap_or(0);
+        let created = CREATED.fetch_add(1, Ordering::Relaxed) as u64;
+        Random::new(nanos ^ created.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // uniform in 0..bound, bound must not be 0
+    pub fn below(&mut self, bound: u64) -> u64 {
+        // numbers from the incomplete last range would make the small results more likely
+        let limit = ::std::u64::MAX - ::std::u64::MAX % bound;
+        loop {
+            let x = self.next_u64();
+            if x < limit { return x % bound; }
+        }
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
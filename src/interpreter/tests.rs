@@ -0,0 +1,2453 @@
+use ::interpreter::{Interpreter, Op, Error, DebugAction};
+use ::value::{Value, Userdata};
+use ::scope::Address;
+use ::grammar;
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// parses and evaluates input, returns the printed result of the last expression
+// or the message of the first condition raised
+fn eval(interpreter: &mut Interpreter, input: &str) -> Result<String, String> {
+    let parsed = grammar::parse_with_macros(input, &mut interpreter.interner, &mut interpreter.macros).expect("parse error");
+    let mut result = Err("no input".into());
+    for x in &parsed {
+        result = interpreter.evaluate(x)
+        .map(|v| v.to_string(&interpreter.interner))
+        .map_err(|c| c.to_string(&interpreter.interner));
+        if result.is_err() { break }
+    }
+    result
+}
+
+// like eval, but locations are tracked as if input started at line of the repl
+// and conditions are printed with their backtrace
+fn eval_at(interpreter: &mut Interpreter, input: &str, line: usize) -> Result<String, String> {
+    let parsed = grammar::parse_with_locations(input, "repl", line, &mut interpreter.interner,
+                                               &mut interpreter.macros, &mut interpreter.locations).expect("parse error");
+    let mut result = Err("no input".into());
+    for x in &parsed {
+        result = interpreter.evaluate(x)
+        .map(|v| v.to_string(&interpreter.interner))
+        .map_err(|c| c.to_string_with_backtrace(&interpreter.interner));
+        if result.is_err() { break }
+    }
+    result
+}
+
+macro_rules! expect_eval {
+    ($interpreter:expr, $input:expr, $expected:expr) => (
+        assert_eq!(eval($interpreter, $input), Ok($expected.into()));
+    );
+}
+
+macro_rules! expect_condition {
+    ($interpreter:expr, $input:expr, $expected:expr) => (
+        assert_eq!(eval($interpreter, $input), Err($expected.into()));
+    );
+}
+
+#[test]
+fn conditions_are_recoverable() {
+    let interpreter = &mut Interpreter::new();
+    expect_condition!(interpreter, "undefined_symbol", "undefined ident: undefined_symbol");
+    expect_condition!(interpreter, "(1 2)", "tried to call 1, which is not possible");
+    expect_condition!(interpreter, "(first 1)", "first expected list, got: 1");
+    expect_condition!(interpreter, "(if 1 2 3)", "if expected bool, found: 1");
+
+    // the interpreter is still usable afterwards
+    expect_eval!(interpreter, "(+ 1 2)", "3");
+}
+
+#[test]
+fn conditions_propagate() {
+    let interpreter = &mut Interpreter::new();
+    expect_condition!(interpreter, "(+ 1 (first 1))", "first expected list, got: 1");
+    expect_condition!(interpreter, "(define a (+ 1 true))", "expected number, got: true");
+    expect_condition!(interpreter, "a", "undefined ident: a");
+}
+
+#[test]
+fn scope_restored_after_condition() {
+    let interpreter = &mut Interpreter::new();
+    expect_condition!(interpreter, "(let (x 1) (+ x undefined))", "undefined ident: undefined");
+    expect_condition!(interpreter, "x", "undefined ident: x");
+    expect_condition!(interpreter, "(loop (y 1) (if (< y 3) (recur (+ y 1)) (first y)))", "first expected list, got: 3");
+    expect_condition!(interpreter, "y", "undefined ident: y");
+}
+
+// runs f in a thread with a small stack, so unbounded recursion is detected quickly
+fn with_small_stack<F: FnOnce() + Send + 'static>(f: F) {
+    let handle = thread::Builder::new().stack_size(256 * 1024).spawn(f).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn tail_calls() {
+    with_small_stack(|| {
+        let interpreter = &mut Interpreter::new();
+        eval(interpreter, "(define count-down (lambda (n) (if (= 0 n) 'done (count-down (- n 1)))))").unwrap();
+        expect_eval!(interpreter, "(count-down 100000)", "done");
+
+        // mutual recursion
+        eval(interpreter, "(define even? (lambda (n) (if (= 0 n) true (odd? (- n 1)))))").unwrap();
+        eval(interpreter, "(define odd? (lambda (n) (if (= 0 n) false (even? (- n 1)))))").unwrap();
+        expect_eval!(interpreter, "(even? 100001)", "false");
+
+        // tail position in let and begin
+        eval(interpreter, "(define f (lambda (n) (let (m (- n 1)) (begin 1 (if (< m 0) 'done (f m))))))").unwrap();
+        expect_eval!(interpreter, "(f 100000)", "done");
+    });
+}
+
+#[test]
+fn recur() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(loop (x 1) (if (< x 10000) (recur (+ x 1)) x))", "10000");
+    eval(interpreter, "(define fac (lambda (n) (loop (n n res 1) (if (< n 2) res (recur (- n 1) (* res n))))))").unwrap();
+    expect_eval!(interpreter, "(fac 6)", "720");
+    eval(interpreter, "(define count-down (lambda (n) (if (= 0 n) 'done (recur (- n 1)))))").unwrap();
+    expect_eval!(interpreter, "(count-down 10000)", "done");
+    expect_condition!(interpreter, "(loop (x 1) (if (< x 3) (recur) x))", "arity mismatch for recur: expected: 1, got: 0");
+    expect_condition!(interpreter, "(count-down 1 2)", "arity mismatch for lambda: expected: 1, got: 2");
+}
+
+#[test]
+fn closures() {
+    let interpreter = &mut Interpreter::new();
+
+    // captures the defining scope
+    eval(interpreter, "(define make-adder (lambda (x) (lambda (y) (+ x y))))").unwrap();
+    eval(interpreter, "(define add2 (make-adder 2))").unwrap();
+    eval(interpreter, "(define add5 (make-adder 5))").unwrap();
+    expect_eval!(interpreter, "(add2 1)", "3");
+    expect_eval!(interpreter, "(add5 1)", "6");
+
+    // not the calling scope
+    expect_eval!(interpreter, "(let (x 100) (add2 1))", "3");
+    eval(interpreter, "(define get-z (lambda () z))").unwrap();
+    expect_condition!(interpreter, "(let (z 1) (get-z))", "undefined ident: z");
+
+    // nested closures
+    eval(interpreter, "(define curry3 (lambda (a) (lambda (b) (lambda (c) (list a b c)))))").unwrap();
+    expect_eval!(interpreter, "(((curry3 1) 2) 3)", "(1 2 3)");
+
+    // args are evaluated in the calling scope
+    expect_eval!(interpreter, "(let (y 10) (add5 y))", "15");
+
+    // definitions after the closure was created are visible
+    eval(interpreter, "(define get-later (lambda () later))").unwrap();
+    eval(interpreter, "(define later 42)").unwrap();
+    expect_eval!(interpreter, "(get-later)", "42");
+
+    // comparing procedures doesn't recurse into their scopes
+    expect_eval!(interpreter, "(eq? add2 add2)", "true");
+    expect_eval!(interpreter, "(eq? make-adder get-later)", "false");
+}
+
+#[test]
+fn shadowing() {
+    let interpreter = &mut Interpreter::new();
+    eval(interpreter, "(define x 1)").unwrap();
+    eval(interpreter, "(define f (lambda (x) (lambda () x)))").unwrap();
+    expect_eval!(interpreter, "((f 2))", "2");
+    expect_eval!(interpreter, "(let (x 3) ((lambda (x) x) 4))", "4");
+    expect_eval!(interpreter, "(let (x 3) ((lambda () x)))", "3");
+    expect_eval!(interpreter, "x", "1");
+
+    // defines inside a body stay local
+    eval(interpreter, "(define g (lambda () (define x 5) x))").unwrap();
+    expect_eval!(interpreter, "(g)", "5");
+    expect_eval!(interpreter, "x", "1");
+}
+
+#[test]
+fn let_forms() {
+    let interpreter = &mut Interpreter::new();
+
+    // both binding styles are accepted
+    expect_eval!(interpreter, "(let (x 1 y 2) (list x y))", "(1 2)");
+    expect_eval!(interpreter, "(let ((x 1) (y 2)) (list x y))", "(1 2)");
+    expect_eval!(interpreter, "(let* ((x 1)(y x)) (list x y))", "(1 1)");
+    expect_eval!(interpreter, "(let () 1)", "1");
+
+    // let binds in parallel
+    expect_eval!(interpreter, "(let (x 1) (let (x 2 y x) y))", "1");
+    expect_condition!(interpreter, "(let (a 1 b a) b)", "undefined ident: a");
+
+    // let* binds sequentially
+    expect_eval!(interpreter, "(let (x 1) (let* (x 2 y x) y))", "2");
+    expect_eval!(interpreter, "(let* (x 1 y 2 z (+ x y)) (list x y z))", "(1 2 3)");
+    expect_condition!(interpreter, "(let* (f (lambda () f)) (f))", "undefined ident: f");
+
+    // letrec binds recursively
+    let even_odd = "(letrec (even? (lambda (n) (if (= 0 n) true (odd? (- n 1))))
+                             odd? (lambda (n) (if (= 0 n) false (even? (- n 1)))))
+                      (even? 1000))";
+    expect_eval!(interpreter, even_odd, "true");
+    expect_eval!(interpreter, "(letrec (f (lambda (n) (if (< n 1) 'done (f (- n 1))))) (f 10))", "done");
+
+    // scopes are torn down afterwards
+    expect_condition!(interpreter, "(begin (let (x 1) x) x)", "undefined ident: x");
+    expect_condition!(interpreter, "(begin (let* (x 1) x) x)", "undefined ident: x");
+    expect_condition!(interpreter, "(begin (letrec (x 1) x) x)", "undefined ident: x");
+    expect_condition!(interpreter, "(begin (let* () (define x 1)) x)", "undefined ident: x");
+}
+
+#[test]
+fn cond() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(cond ((< 2 1) 'less) ((> 2 1) 'greater) (else 'equal))", "greater");
+    expect_eval!(interpreter, "(cond ((= 2 1) 'less) (else 'equal))", "equal");
+    expect_eval!(interpreter, "(cond (false 1))", "()");
+    expect_eval!(interpreter, "(cond)", "()");
+
+    // empty bodies return the test, bodies return their last expression
+    expect_eval!(interpreter, "(cond ((= 1 1)))", "true");
+    expect_eval!(interpreter, "(cond (true 1 2 3))", "3");
+
+    // only the selected clause is evaluated
+    expect_eval!(interpreter, "(cond (true 1) (undefined 2))", "1");
+    expect_eval!(interpreter, "(cond (false undefined) (true 2))", "2");
+
+    // receivers get the value of a non false test
+    expect_eval!(interpreter, "(cond ((first '(1 2)) => (lambda (x) (+ x 1))))", "2");
+    expect_eval!(interpreter, "(cond (false => undefined) (else 3))", "3");
+    expect_eval!(interpreter, "(cond ('(1 2) => rest))", "(2)");
+
+    expect_condition!(interpreter, "(cond (1 2))", "cond expected bool, found: 1");
+
+    // bodies are in tail position
+    expect_eval!(interpreter, "(loop (n 10) (cond ((= n 0) 'done) (else (recur (- n 1)))))", "done");
+}
+
+#[test]
+fn case() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(case (* 2 3) ((2 3 5 7) 'prime) ((1 4 6 8 9) 'composite))", "composite");
+    expect_eval!(interpreter, "(case 'x ((a b) 1) ((x) 2) (else 3))", "2");
+    expect_eval!(interpreter, "(case #\\a ((#\\a) 1))", "1");
+    expect_eval!(interpreter, "(case 10 ((1) 1))", "()");
+    expect_eval!(interpreter, "(case 10 ((1) 1) (else 2))", "2");
+    expect_eval!(interpreter, "(case 1 ((1) => (lambda (x) (+ x 1))))", "2");
+    expect_eval!(interpreter, "(case 1 (() 1) ((1) 2 3))", "3");
+
+    // strings don't have an identity that can be written down
+    expect_eval!(interpreter, "(case \"a\" ((\"a\") 1) (else 2))", "2");
+
+    // key is evaluated once, other clauses are not
+    expect_eval!(interpreter, "(case 1 ((1) 1) ((2) undefined))", "1");
+    expect_condition!(interpreter, "(case undefined ((1) 1))", "undefined ident: undefined");
+
+    expect_eval!(interpreter, "(loop (n 10) (case n ((0) 'done) (else (recur (- n 1)))))", "done");
+}
+
+#[test]
+fn quasiquote() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "`(1 2 3)", "(1 2 3)");
+    expect_eval!(interpreter, "`x", "x");
+    expect_eval!(interpreter, "`,(+ 1 2)", "3");
+    expect_eval!(interpreter, "`(1 ,(+ 1 1) 3)", "(1 2 3)");
+    expect_eval!(interpreter, "`(1 (2 ,(+ 1 2)))", "(1 (2 3))");
+
+    // splicing
+    expect_eval!(interpreter, "`(1 ,@(list 2 3) 4)", "(1 2 3 4)");
+    expect_eval!(interpreter, "`(1 ,@'() 2)", "(1 2)");
+    expect_eval!(interpreter, "`(,@'())", "()");
+    expect_eval!(interpreter, "`((,@(list 1 2)) ,@(list 3))", "((1 2) 3)");
+    expect_condition!(interpreter, "`(1 ,@2)", "unquote-splicing expected list, got: 2");
+
+    // nested quasiquotes need one unquote per level to evaluate something
+    expect_eval!(interpreter, "`(1 `(2 ,(3 ,(+ 1 3))))", "(1 (quasiquote (2 (unquote (3 4)))))");
+
+    // unquoted code is evaluated in the current scope
+    expect_eval!(interpreter, "(let (x 1 xs (list 2 3)) `(x ,x ,@xs))", "(x 1 2 3)");
+}
+
+#[test]
+fn macros() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define-syntax my-or (syntax-rules ()
+                                 ((_) false)
+                                 ((_ e) e)
+                                 ((_ e r ...) (let (t e) (if t t (my-or r ...))))))", "my-or");
+
+    // macros defined earlier can be used later
+    expect_eval!(interpreter, "(my-or)", "false");
+    expect_eval!(interpreter, "(my-or false false true)", "true");
+
+    // introduced identifiers don't capture the user's identifiers
+    expect_eval!(interpreter, "(let (t 5) (my-or false t))", "5");
+
+    // ellipsis
+    expect_eval!(interpreter, "(define-syntax my-list (syntax-rules () ((_ (a b) ...) (list (+ a b) ...))))", "my-list");
+    expect_eval!(interpreter, "(my-list (1 2) (3 4))", "(3 7)");
+    expect_eval!(interpreter, "(my-list)", "()");
+    expect_eval!(interpreter, "(define-syntax rev-args (syntax-rules () ((_ f a ... last) (f last a ...))))", "rev-args");
+    expect_eval!(interpreter, "(rev-args list 1 2 3)", "(3 1 2)");
+
+    // literals
+    expect_eval!(interpreter, "(define-syntax arrow (syntax-rules (=>) ((_ a => b) (list a b)) ((_ a b c) 'no-arrow)))", "arrow");
+    expect_eval!(interpreter, "(arrow 1 => 2)", "(1 2)");
+    expect_eval!(interpreter, "(arrow 1 2 3)", "no-arrow");
+
+    // expansions can contain tail calls
+    expect_eval!(interpreter, "(define-syntax repeat (syntax-rules () ((_ n body) (loop (i n) (if (= i 0) body (recur (- i 1)))))))", "repeat");
+    expect_eval!(interpreter, "(repeat 100000 'done)", "done");
+    expect_eval!(interpreter, "(let (i 'mine) (repeat 3 i))", "mine");
+
+    // expansion errors are parse errors
+    assert!(grammar::parse_with_macros("(arrow 1)", &mut interpreter.interner, &mut interpreter.macros).is_err());
+    assert!(grammar::parse_with_macros("(define-syntax x 1)", &mut interpreter.interner, &mut interpreter.macros).is_err());
+}
+
+#[test]
+fn floats() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "1.5", "1.5");
+    expect_eval!(interpreter, "-2.", "-2.0");
+    expect_eval!(interpreter, "1e3", "1000.0");
+
+    // integers are promoted as soon as a float is involved
+    expect_eval!(interpreter, "(+ 1 2)", "3");
+    expect_eval!(interpreter, "(+ 1 2.5)", "3.5");
+    expect_eval!(interpreter, "(* 2 1.5 2)", "6.0");
+    expect_eval!(interpreter, "(- 1.5)", "-1.5");
+    expect_eval!(interpreter, "(- 5)", "-5");
+
+    // division
+    expect_eval!(interpreter, "(/ 6 3)", "2");
+    expect_eval!(interpreter, "(/ 7 2.0)", "3.5");
+    expect_eval!(interpreter, "(/ 2.)", "0.5");
+    expect_eval!(interpreter, "(/ 1.0 0)", "+inf.0");
+    expect_condition!(interpreter, "(/ 1 0)", "division by zero");
+    expect_condition!(interpreter, "(quotient 1 0)", "division by zero");
+    expect_condition!(interpreter, "(quotient 1.5 1)", "quotient expected integer, got: 1.5");
+
+    // comparisons
+    expect_eval!(interpreter, "(= 1 1.0)", "true");
+    expect_eval!(interpreter, "(< 1 1.5 2)", "true");
+    expect_eval!(interpreter, "(> 1.5 2)", "false");
+
+    expect_eval!(interpreter, "(list (number? 1) (number? 1.5) (float? 1) (integer? 1.5))", "(true true false false)");
+    expect_eval!(interpreter, "(string->number \"2.5e1\")", "25.0");
+    expect_eval!(interpreter, "(number->string 0.25)", "\"0.25\"");
+
+    // radixes
+    expect_eval!(interpreter, "(+ #xff #b1 #o10)", "264");
+    expect_eval!(interpreter, "(list #e0.5 #i1/2)", "(1/2 0.5)");
+    expect_eval!(interpreter, "(string->number \"ff\" 16)", "255");
+    expect_eval!(interpreter, "(string->number \"#b11\" 16)", "3");
+    expect_eval!(interpreter, "(string->number \"#xff\")", "255");
+    expect_eval!(interpreter, "(list (number->string 255 16) (number->string -5 2) (number->string 3/4 8))", "(\"ff\" \"-101\" \"3/4\")");
+    expect_eval!(interpreter, "(number->string 10 10)", "\"10\"");
+    expect_condition!(interpreter, "(string->number \"12\" 2)", "string is not a valid number: \"12\"");
+    expect_condition!(interpreter, "(string->number \"1\" 3)", "string->number expected radix 2, 8, 10 or 16, got: 3");
+    expect_condition!(interpreter, "(number->string 1.5 16)", "number->string expected radix 10 for float, got: 16");
+}
+
+#[test]
+fn bigints() {
+    let interpreter = &mut Interpreter::new();
+
+    // integers are promoted on overflow instead of wrapping
+    expect_eval!(interpreter, "(+ 9223372036854775807 1)", "9223372036854775808");
+    expect_eval!(interpreter, "(- -9223372036854775808 1)", "-9223372036854775809");
+    expect_eval!(interpreter, "(* 4294967296 4294967296)", "18446744073709551616");
+    expect_eval!(interpreter, "(- 9223372036854775808 1)", "9223372036854775807");
+    expect_eval!(interpreter, "(integer? 100000000000000000000)", "true");
+
+    // and demoted if they fit again
+    expect_eval!(interpreter, "(quotient 100000000000000000000 10000000000)", "10000000000");
+    expect_eval!(interpreter, "(remainder 100000000000000000001 10)", "1");
+    expect_eval!(interpreter, "(/ 100000000000000000000 4)", "25000000000000000000");
+    expect_eval!(interpreter, "(quotient -9223372036854775808 -1)", "9223372036854775808");
+    expect_eval!(interpreter, "(+ 100000000000000000000 0.5)", "1e20");
+
+    expect_eval!(interpreter, "(< 9223372036854775807 9223372036854775808)", "true");
+    expect_eval!(interpreter, "(= 100000000000000000000 100000000000000000000)", "true");
+    expect_eval!(interpreter, "(case 100000000000000000000 ((100000000000000000000) 'big) (else 'small))", "big");
+
+    let fac = "(define fac (lambda (n) (loop (n n acc 1) (if (< n 2) acc (recur (- n 1) (* acc n))))))";
+    expect_eval!(interpreter, fac, "fac");
+    expect_eval!(interpreter, "(fac 25)", "15511210043330985984000000");
+}
+
+#[test]
+fn rationals() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "1/3", "1/3");
+    expect_eval!(interpreter, "2/6", "1/3");
+    expect_eval!(interpreter, "-4/2", "-2");
+
+    // division is exact
+    expect_eval!(interpreter, "(/ 1 3)", "1/3");
+    expect_eval!(interpreter, "(/ 6 3)", "2");
+    expect_eval!(interpreter, "(/ -2)", "-1/2");
+    expect_eval!(interpreter, "(/ 1 -3)", "-1/3");
+    expect_eval!(interpreter, "(/ 1/2 0.5)", "1.0");
+    expect_condition!(interpreter, "(/ 1/2 0)", "division by zero");
+
+    // interop with integers and floats
+    expect_eval!(interpreter, "(+ 1/3 2/3)", "1");
+    expect_eval!(interpreter, "(+ 1/2 1)", "3/2");
+    expect_eval!(interpreter, "(- 1/2 1/3)", "1/6");
+    expect_eval!(interpreter, "(* 2/3 3/4)", "1/2");
+    expect_eval!(interpreter, "(* 1/4 2.0)", "0.5");
+    expect_eval!(interpreter, "(+ 1/3 100000000000000000000)", "300000000000000000001/3");
+
+    expect_eval!(interpreter, "(< 1/3 1/2 1 1.5)", "true");
+    expect_eval!(interpreter, "(= 1/2 0.5)", "true");
+    expect_eval!(interpreter, "(= 2/4 1/2)", "true");
+    expect_condition!(interpreter, "(quotient 1/2 1)", "quotient expected integer, got: 1/2");
+
+    expect_eval!(interpreter, "(list (rational? 1/2) (rational? 1) (rational? 0.5) (integer? 1/2))", "(true true false false)");
+    expect_eval!(interpreter, "(string->number \"3/9\")", "1/3");
+}
+
+#[test]
+fn numeric_library() {
+    let interpreter = &mut Interpreter::new();
+    // modulo has the sign of the divisor, remainder the sign of the dividend
+    expect_eval!(interpreter, "(list (modulo 7 2) (modulo -7 2) (modulo 7 -2) (modulo -7 -2) (modulo 6 -2))", "(1 1 -1 -1 0)");
+    expect_eval!(interpreter, "(list (remainder -7 2) (modulo -100000000000000000001 10))", "(-1 9)");
+    expect_condition!(interpreter, "(modulo 1 0)", "division by zero");
+
+    expect_eval!(interpreter, "(list (abs -3) (abs 3) (abs -1/2) (abs -2.5) (abs -9223372036854775808))", "(3 3 1/2 2.5 9223372036854775808)");
+    expect_eval!(interpreter, "(list (min 3 1 2) (max 3 1 2) (max 1/2 1/3) (min -1))", "(1 3 1/2 -1)");
+    expect_eval!(interpreter, "(list (max 1 2.0) (max 3 2.0))", "(2.0 3.0)");
+    expect_condition!(interpreter, "(min 'a)", "expected number, got: a");
+
+    expect_eval!(interpreter, "(list (gcd) (gcd 12 -18) (gcd 0 5) (lcm) (lcm 4 6) (lcm -4 6 10) (lcm 0 5))", "(0 6 5 1 12 60 0)");
+    expect_condition!(interpreter, "(gcd 1.5)", "gcd expected integer, got: 1.5");
+
+    expect_eval!(interpreter, "(list (expt 2 10) (expt 2 0) (expt 2 -2) (expt 2/3 3) (expt -1 -3))", "(1024 1 1/4 8/27 -1)");
+    expect_eval!(interpreter, "(expt 2 100)", "1267650600228229401496703205376");
+    expect_eval!(interpreter, "(list (expt 2.0 3) (expt 4 0.5))", "(8.0 2.0)");
+    expect_condition!(interpreter, "(expt 0 -1)", "division by zero");
+
+    expect_eval!(interpreter, "(list (exact->inexact 1/2) (exact->inexact 3) (exact->inexact 1.5))", "(0.5 3.0 1.5)");
+
+    // rationals are rounded to integers, floats stay floats, ties are rounded to even
+    expect_eval!(interpreter, "(list (floor -7/2) (ceiling -7/2) (truncate -7/2) (round -7/2) (round 5/2) (round 7/3))", "(-4 -3 -3 -4 2 2)");
+    expect_eval!(interpreter, "(list (floor 2.7) (ceiling 2.2) (truncate -2.7) (round 2.5) (round 3.5) (round -2.5))", "(2.0 3.0 -2.0 2.0 4.0 -2.0)");
+    expect_eval!(interpreter, "(list (floor 3) (round 100000000000000000000))", "(3 100000000000000000000)");
+}
+
+#[test]
+fn transcendental() {
+    let interpreter = &mut Interpreter::new();
+    // roots of squares are exact
+    expect_eval!(interpreter, "(list (sqrt 4) (sqrt 0) (sqrt 9/4) (sqrt 4.0) (sqrt 2))", "(2 0 3/2 2.0 1.4142135623730951)");
+    expect_eval!(interpreter, "(float? (sqrt 100000000000000000000000000000000000000000))", "true");
+    expect_eval!(interpreter, "(sqrt 10000000000000000000000000000000000000000)", "100000000000000000000");
+
+    expect_eval!(interpreter, "(list (exp 0) (log 1) (sin 0) (cos 0) (tan 0) (atan 0))", "(1 0 0 1 0 0)");
+    expect_eval!(interpreter, "(list (exp 1) (log 1.0) (sin 0.0) (cos 0.0))", "(2.718281828459045 0.0 0.0 1.0)");
+    expect_eval!(interpreter, "(< 3.14159 (* 4 (atan 1)) 3.1416)", "true");
+    expect_eval!(interpreter, "(list (atan 0 1) (atan 1 0) (atan 0 -1))", "(0 1.5707963267948966 3.141592653589793)");
+    expect_condition!(interpreter, "(sin 'x)", "expected number, got: x");
+    expect_condition!(interpreter, "(atan 1 2 3)", "arity mismatch for atan: expected: 1..2, got: 3");
+}
+
+#[test]
+fn bitwise() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(list (bitwise-and 12 10) (bitwise-or 12 10) (bitwise-xor 12 10) (bitwise-and -1 255))", "(8 14 6 255)");
+    expect_eval!(interpreter, "(list (bitwise-and) (bitwise-or) (bitwise-xor 1 2 4))", "(-1 0 7)");
+    expect_eval!(interpreter, "(list (bitwise-not 0) (bitwise-not 5) (bitwise-not -9223372036854775809))", "(-1 -6 9223372036854775808)");
+    expect_condition!(interpreter, "(bitwise-and 1 100000000000000000000)",
+                      "bitwise-and expected integer that fits into 64 bits, got: 100000000000000000000");
+    expect_condition!(interpreter, "(bitwise-or 1.0)", "bitwise-or expected integer, got: 1.0");
+
+    // shifting right rounds down
+    expect_eval!(interpreter, "(list (arithmetic-shift 1 10) (arithmetic-shift 1024 -3) (arithmetic-shift -5 -1) (arithmetic-shift -1 -100))",
+                 "(1024 128 -3 -1)");
+    expect_eval!(interpreter, "(arithmetic-shift 1 100)", "1267650600228229401496703205376");
+    expect_eval!(interpreter, "(arithmetic-shift -3 63)", "-27670116110564327424");
+    expect_eval!(interpreter, "(list (arithmetic-shift 1267650600228229401496703205376 -99) (arithmetic-shift -1267650600228229401496703205377 -100))",
+                 "(2 -2)");
+    expect_eval!(interpreter, "(arithmetic-shift 100000000000000000000 -1000)", "0");
+
+    expect_eval!(interpreter, "(list (bit-count 0) (bit-count 7) (bit-count 256) (bit-count -1) (bit-count -8))", "(0 3 1 0 3)");
+}
+
+#[test]
+fn random() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define (all? f n) (or (= n 0) (and (f) (all? f (- n 1)))))", "all?");
+    expect_eval!(interpreter, "(all? (lambda () (let (x (random 6)) (and (integer? x) (>= x 0) (< x 6)))) 1000)", "true");
+    expect_eval!(interpreter, "(all? (lambda () (let (x (random 2.5)) (and (float? x) (>= x 0) (< x 2.5)))) 1000)", "true");
+    expect_eval!(interpreter, "(all? (lambda () (< (random) 1)) 1000)", "true");
+    expect_eval!(interpreter, "(random 1)", "0");
+    expect_condition!(interpreter, "(random 0)", "random expected positive integer or float, got: 0");
+    expect_condition!(interpreter, "(random 'a)", "random expected positive integer or float, got: a");
+
+    // the same seed gives the same numbers, also in other interpreters
+    expect_eval!(interpreter, "(define (draw) (random-seed! 42) (list (random 1000000) (random 1000000) (random)))", "draw");
+    let first = eval(interpreter, "(draw)");
+    assert_eq!(eval(interpreter, "(draw)"), first);
+    let other = &mut Interpreter::new();
+    eval(other, "(define (draw) (random-seed! 42) (list (random 1000000) (random 1000000) (random)))").unwrap();
+    assert_eq!(eval(other, "(draw)"), first);
+    // but the generators are independent
+    eval(other, "(random-seed! 1)").unwrap();
+    assert_eq!(eval(interpreter, "(draw)"), first);
+}
+
+#[test]
+fn time() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(> (current-seconds) 1500000000)", "true");
+    expect_eval!(interpreter, "(<= (- (quotient (current-milliseconds) 1000) (current-seconds)) 1)", "true");
+    expect_eval!(interpreter, "(let (start (runtime)) (and (float? start) (<= start (runtime))))", "true");
+    expect_condition!(interpreter, "(runtime 1)", "arity mismatch for runtime: expected: 0, got: 1");
+
+    // time writes how long its operand took and returns its value
+    expect_eval!(interpreter, "(time (+ 1 2))", "3");
+    expect_eval!(interpreter, "(define x 1)", "x");
+    expect_eval!(interpreter, "(begin (time (set! x 2)) x)", "2");
+    // with the cpu time, to the current output port
+    expect_eval!(interpreter, "(define report (with-output-to-string (lambda () (time (+ 1 2)))))", "report");
+    expect_eval!(interpreter, "(list (substring report 0 6) (string-contains? report \", cpu: \"))", "(\"time: \" true)");
+    expect_condition!(interpreter, "(eval '(time))", "eval invalid time, expected: (time expr)");
+}
+
+#[test]
+fn bindings_shadow_special_forms() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(let ((time (lambda (x) (* x 2)))) (time 21))", "42");
+    expect_eval!(interpreter, "((lambda (delay) (delay 1)) (lambda (x) (+ x 1)))", "2");
+    expect_eval!(interpreter, "(let* ((export list) (x (export 1 2))) x)", "(1 2)");
+    // the expr of a let binding doesn't see the name yet
+    expect_eval!(interpreter, "(let ((time (time 5))) time)", "5");
+    expect_eval!(interpreter, "(time (+ 1 2))", "3");
+
+    expect_eval!(interpreter, "(define (import x) (list 'imported x))", "import");
+    expect_eval!(interpreter, "(import 1)", "(imported 1)");
+    interpreter.register_fn("doc", |_, args| Ok(Value::new_integer(args.get_i64(0)? + 1)));
+    expect_eval!(interpreter, "(doc 1)", "2");
+
+    // the forms others are turned into can't be shadowed
+    expect_eval!(interpreter, "(let ((if 1)) (if true 2 3))", "2");
+}
+
+#[test]
+fn strings() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(string-length \"\")", "0");
+    expect_eval!(interpreter, "(string-length \"abc\")", "3");
+    expect_eval!(interpreter, "(string-ref \"abc\" 1)", "#\\b");
+    expect_condition!(interpreter, "(string-ref \"abc\" 3)", "string-ref index out of range: 3");
+    expect_condition!(interpreter, "(string-ref \"abc\" -1)", "string-ref expected index, got: -1");
+
+    expect_eval!(interpreter, "(substring \"hello world\" 6)", "\"world\"");
+    expect_eval!(interpreter, "(substring \"hello world\" 0 5)", "\"hello\"");
+    expect_eval!(interpreter, "(substring \"abc\" 1 1)", "\"\"");
+    expect_condition!(interpreter, "(substring \"abc\" 2 1)", "substring index out of range: 2..1");
+    expect_condition!(interpreter, "(substring \"abc\" 0 4)", "substring index out of range: 0..4");
+
+    // indices count chars, not the bytes of their utf8 encoding
+    expect_eval!(interpreter, "(string-length \"grüß λ 日本\")", "9");
+    expect_eval!(interpreter, "(string-ref \"grüß λ 日本\" 8)", "#\\本");
+    expect_eval!(interpreter, "(string-ref \"😀!\" 1)", "#\\!");
+    expect_eval!(interpreter, "(substring \"grüß λ 日本\" 2 6)", "\"üß λ\"");
+    expect_eval!(interpreter, "(substring \"日本語\" 1)", "\"本語\"");
+    expect_condition!(interpreter, "(substring \"日本語\" 0 4)", "substring index out of range: 0..4");
+    expect_eval!(interpreter, "(string-upcase \"grüß λ\")", "\"GRÜSS Λ\"");
+    expect_eval!(interpreter, "(define λ (string #\\λ #\\x3bc))", "λ");
+    expect_eval!(interpreter, "(list λ (string-length λ) (string->symbol λ))", "(\"λμ\" 2 λμ)");
+
+    expect_eval!(interpreter, "(string-append)", "\"\"");
+    expect_eval!(interpreter, "(string-append \"a\" \"b\" \"c\")", "\"abc\"");
+    expect_condition!(interpreter, "(string-append \"a\" 1)", "string-append expected string, got: 1");
+
+    expect_eval!(interpreter, "(string-upcase \"Hello\")", "\"HELLO\"");
+    expect_eval!(interpreter, "(string-downcase \"Hello\")", "\"hello\"");
+
+    expect_eval!(interpreter, "(string-split \"  a b\tc  \")", "(\"a\" \"b\" \"c\")");
+    expect_eval!(interpreter, "(string-split \"a,b,,c\" \",\")", "(\"a\" \"b\" \"\" \"c\")");
+    expect_eval!(interpreter, "(string-split \"\")", "()");
+    expect_condition!(interpreter, "(string-split \"abc\" \"\")", "string-split expected non empty separator");
+
+    expect_eval!(interpreter, "(string-join (list \"a\" \"b\" \"c\"))", "\"a b c\"");
+    expect_eval!(interpreter, "(string-join (list \"a\" \"b\") \", \")", "\"a, b\"");
+    expect_eval!(interpreter, "(string-join (list))", "\"\"");
+    expect_eval!(interpreter, "(string-join (string-split \"a-b-c\" \"-\") \"+\")", "\"a+b+c\"");
+    expect_condition!(interpreter, "(string-join (list \"a\" 1))", "string-join expected list of strings, got: 1");
+
+    expect_eval!(interpreter, "(string-contains? \"hello world\" \"o w\")", "true");
+    expect_eval!(interpreter, "(string-contains? \"hello\" \"\")", "true");
+    expect_eval!(interpreter, "(string-contains? \"hello\" \"x\")", "false");
+
+    expect_eval!(interpreter, "(string #\\a #\\b)", "\"ab\"");
+    expect_eval!(interpreter, "(string)", "\"\"");
+    expect_eval!(interpreter, "(make-string 3 #\\x)", "\"xxx\"");
+    expect_eval!(interpreter, "(make-string 2)", "\"  \"");
+    expect_condition!(interpreter, "(make-string 2 \"x\")", "make-string expected char, got: \"x\"");
+
+    expect_eval!(interpreter, "(string-copy \"hello\")", "\"hello\"");
+    expect_eval!(interpreter, "(string-copy \"hello\" 1 3)", "\"el\"");
+    expect_condition!(interpreter, "(string-copy \"abc\" 4)", "string-copy index out of range: 4..3");
+
+    expect_eval!(interpreter, "(string->list (string #\\a #\\x3bb #\\c))", "(#\\a #\\λ #\\c)");
+    expect_eval!(interpreter, "(string->list \"abc\" 1)", "(#\\b #\\c)");
+    expect_eval!(interpreter, "(list->string (list #\\a #\\space #\\b))", "\"a b\"");
+    expect_eval!(interpreter, "(list->string (map char-upcase (string->list \"abc\")))", "\"ABC\"");
+    expect_eval!(interpreter, "(list->string '())", "\"\"");
+    expect_condition!(interpreter, "(list->string (list #\\a 1))", "list->string expected list of chars, got: 1");
+}
+
+#[test]
+fn vectors() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "#(1 (+ 1 1) x)", "#(1 (+ 1 1) x)");
+    expect_eval!(interpreter, "(vector 1 (+ 1 1) 'x)", "#(1 2 x)");
+    expect_eval!(interpreter, "(make-vector 3)", "#(0 0 0)");
+    expect_eval!(interpreter, "(make-vector 2 'a)", "#(a a)");
+    expect_eval!(interpreter, "(make-vector 0)", "#()");
+    expect_condition!(interpreter, "(make-vector -1)", "make-vector expected index, got: -1");
+
+    expect_eval!(interpreter, "(define v (vector 1 2 3))", "v");
+    expect_eval!(interpreter, "(vector-length v)", "3");
+    expect_eval!(interpreter, "(vector-ref v 0)", "1");
+    expect_condition!(interpreter, "(vector-ref v 3)", "vector-ref index out of range: 3");
+    expect_condition!(interpreter, "(vector-ref '(1) 0)", "vector-ref expected vector, got: (1)");
+
+    // vector-set! mutates the vector for everyone holding it
+    expect_eval!(interpreter, "(define w v)", "w");
+    expect_eval!(interpreter, "(vector-set! v 1 'b)", "#(1 b 3)");
+    expect_eval!(interpreter, "w", "#(1 b 3)");
+    expect_condition!(interpreter, "(vector-set! v 5 'b)", "vector-set! index out of range: 5");
+
+    expect_eval!(interpreter, "(vector->list v)", "(1 b 3)");
+    expect_eval!(interpreter, "(vector->list #())", "()");
+    expect_eval!(interpreter, "(list->vector '(1 2))", "#(1 2)");
+    expect_eval!(interpreter, "(list->vector '())", "#()");
+
+    expect_eval!(interpreter, "(list (vector? v) (vector? '(1)) (list? v))", "(true false false)");
+
+    // filling a vector in a loop
+    expect_eval!(interpreter, "(define squares (make-vector 5))", "squares");
+    expect_eval!(interpreter, "(loop ((i 0)) (if (< i 5) (begin (vector-set! squares i (* i i)) (recur (+ i 1))) squares))", "#(0 1 4 9 16)");
+}
+
+#[test]
+fn hash_tables() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(make-hash)", "#hash()");
+    expect_eval!(interpreter, "(make-hash '((b 2) (a 1)))", "#hash((a 1) (b 2))");
+    expect_condition!(interpreter, "(make-hash '((a 1 2)))", "make-hash expected (key value), got: (a 1 2)");
+
+    expect_eval!(interpreter, "(define h (make-hash))", "h");
+    expect_eval!(interpreter, "(hash-set! h 'a 1)", "#hash((a 1))");
+    expect_eval!(interpreter, "(hash-set! h \"a\" 2)", "#hash((\"a\" 2) (a 1))");
+    expect_eval!(interpreter, "(hash-set! h '(1 2) 3)", "#hash((\"a\" 2) ((1 2) 3) (a 1))");
+    expect_eval!(interpreter, "(hash-count h)", "3");
+
+    // keys are compared by value
+    expect_eval!(interpreter, "(hash-ref h 'a)", "1");
+    expect_eval!(interpreter, "(hash-ref h (string-append \"\" \"a\"))", "2");
+    expect_eval!(interpreter, "(hash-ref h (list 1 2))", "3");
+    expect_eval!(interpreter, "(hash-ref h 2/4 'missing)", "missing");
+    expect_condition!(interpreter, "(hash-ref h 'b)", "hash-ref key not found: b");
+    expect_condition!(interpreter, "(hash-ref h 1.5)", "hash-ref expected hashable key, got: 1.5");
+    expect_condition!(interpreter, "(hash-ref '() 'a)", "hash-ref expected hash table, got: ()");
+
+    // overwriting and removing
+    expect_eval!(interpreter, "(hash-set! h 'a 'one)", "#hash((\"a\" 2) ((1 2) 3) (a one))");
+    expect_eval!(interpreter, "(hash-remove! h \"a\")", "#hash(((1 2) 3) (a one))");
+    expect_eval!(interpreter, "(hash-remove! h \"a\")", "#hash(((1 2) 3) (a one))");
+    expect_eval!(interpreter, "(hash-remove! h '(1 2))", "#hash((a one))");
+    expect_eval!(interpreter, "(hash-keys h)", "(a)");
+    expect_eval!(interpreter, "(hash-count h)", "1");
+
+    expect_eval!(interpreter, "(list (hash? h) (hash? #()))", "(true false)");
+}
+
+#[test]
+fn set() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define x 1)", "x");
+    expect_eval!(interpreter, "(set! x (+ x 1))", "x");
+    expect_eval!(interpreter, "x", "2");
+    expect_condition!(interpreter, "(set! y 1)", "set! undefined ident: y");
+
+    // the nearest binding is changed, outer ones stay the same
+    expect_eval!(interpreter, "(let ((x 10)) (set! x 11) x)", "11");
+    expect_eval!(interpreter, "x", "2");
+    expect_eval!(interpreter, "(let ((y 1)) (set! x 3) y)", "1");
+    expect_eval!(interpreter, "x", "3");
+
+    // closures see changes to captured bindings
+    expect_eval!(interpreter, "(define make-counter (lambda () (let ((n 0)) (lambda () (set! n (+ n 1)) n))))", "make-counter");
+    expect_eval!(interpreter, "(define c1 (make-counter))", "c1");
+    expect_eval!(interpreter, "(define c2 (make-counter))", "c2");
+    expect_eval!(interpreter, "(list (c1) (c1) (c2) (c1))", "(1 2 1 3)");
+
+    expect_eval!(interpreter, "(define get-x (lambda () x))", "get-x");
+    expect_eval!(interpreter, "(set! x 'changed)", "x");
+    expect_eval!(interpreter, "(get-x)", "changed");
+
+    // a loop like while with set!
+    expect_eval!(interpreter, "(define sum 0)", "sum");
+    expect_eval!(interpreter, "(loop ((i 1)) (if (<= i 10) (begin (set! sum (+ sum i)) (recur (+ i 1))) sum))", "55");
+}
+
+#[test]
+fn define_procedure() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define (square x) (* x x))", "square");
+    expect_eval!(interpreter, "(square 5)", "25");
+    expect_eval!(interpreter, "square", "[PROC: (square (x) (* x x))]");
+    expect_eval!(interpreter, "(define (answer) 42)", "answer");
+    expect_eval!(interpreter, "(answer)", "42");
+
+    // multiple expressions in the body, recursion
+    expect_eval!(interpreter, "(define (fact n) (define m n) (if (= m 0) 1 (* m (fact (- m 1)))))", "fact");
+    expect_eval!(interpreter, "(fact 10)", "3628800");
+
+    // rest args are collected into a list
+    expect_eval!(interpreter, "(define (args . xs) xs)", "args");
+    expect_eval!(interpreter, "(args)", "()");
+    expect_eval!(interpreter, "(args 1 2 3)", "(1 2 3)");
+    expect_eval!(interpreter, "args", "[PROC: (args xs xs)]");
+    expect_eval!(interpreter, "(define (tail a b . xs) (list a b xs))", "tail");
+    expect_eval!(interpreter, "(tail 1 2)", "(1 2 ())");
+    expect_eval!(interpreter, "(tail 1 2 3 4)", "(1 2 (3 4))");
+    expect_condition!(interpreter, "(tail 1)", "arity mismatch for tail: expected: 2.., got: 1");
+
+    // recur supplies rest args as list
+    expect_eval!(interpreter, "(define (count n . xs) (if (= n 0) xs (recur (- n 1) (list n xs))))", "count");
+    expect_eval!(interpreter, "(count 2 'x)", "(1 (2 (x)))");
+}
+
+#[test]
+fn variadic_lambda() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "((lambda args args))", "()");
+    expect_eval!(interpreter, "((lambda args args) 1 2 3)", "(1 2 3)");
+    expect_eval!(interpreter, "((lambda (a b . rest) (list a b rest)) 1 2 3 4)", "(1 2 (3 4))");
+    expect_eval!(interpreter, "((lambda (a . rest) (list a rest)) 1)", "(1 ())");
+    expect_condition!(interpreter, "((lambda (a b . rest) a) 1)", "arity mismatch for lambda: expected: 2.., got: 1");
+
+    // every call gets a fresh list
+    expect_eval!(interpreter, "(define f (lambda xs xs))", "f");
+    expect_eval!(interpreter, "(list (f 1) (f 2))", "((1) (2))");
+    expect_eval!(interpreter, "f", "[PROC: (lambda xs xs)]");
+
+    // named lambdas
+    expect_eval!(interpreter, "(define g (lambda g (a . xs) (if (null? xs) a (recur (+ a (first xs)) (rest xs)))))", "g");
+    expect_eval!(interpreter, "(g 1 2 3 4)", "10");
+    expect_eval!(interpreter, "g", "[PROC: (g (a . xs) (if (null? xs) a (recur (+ a (first xs)) (rest xs))))]");
+
+    // the body of a variadic lambda may look like a parameter list
+    expect_eval!(interpreter, "((lambda xs (list xs)) 1 2)", "((1 2))");
+
+    // rest parameters of lambdas in macros are hygienic
+    expect_eval!(interpreter, "(define-syntax my-list (syntax-rules () ((_ e ...) ((lambda xs xs) e ...))))", "my-list");
+    expect_eval!(interpreter, "(define xs 'outer)", "xs");
+    expect_eval!(interpreter, "(my-list xs 1)", "(outer 1)");
+    expect_eval!(interpreter, "(define-syntax my-tail (syntax-rules () ((_ e ...) ((lambda (a . xs) xs) e ...))))", "my-tail");
+    expect_eval!(interpreter, "(my-tail 1 xs)", "(outer)");
+}
+
+#[test]
+fn apply() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(apply + '(1 2 3))", "6");
+    expect_eval!(interpreter, "(apply + '())", "0");
+    expect_eval!(interpreter, "(apply list 1 2 '(3 4))", "(1 2 3 4)");
+    expect_eval!(interpreter, "(apply list '((a) b))", "((a) b)");
+    expect_eval!(interpreter, "(apply first '((1 2)))", "1");
+
+    // user procedures, including variadic ones and tail calls
+    expect_eval!(interpreter, "(define (add3 a b c) (+ a b c))", "add3");
+    expect_eval!(interpreter, "(apply add3 1 '(2 3))", "6");
+    expect_eval!(interpreter, "(apply (lambda (x . xs) (list x xs)) 1 2 '(3))", "(1 (2 3))");
+    expect_eval!(interpreter, "(define (count-down n) (if (= n 0) 'done (count-down (- n 1))))", "count-down");
+    expect_eval!(interpreter, "(apply count-down '(10000))", "done");
+    expect_eval!(interpreter, "(apply apply (list + (list 1 2)))", "3");
+
+    // the scope of the caller is restored
+    expect_eval!(interpreter, "(define x 'outer)", "x");
+    expect_eval!(interpreter, "(let ((x 'inner)) (apply (lambda (x) x) '(arg)) x)", "inner");
+
+    expect_condition!(interpreter, "(apply add3 '(1 2))", "arity mismatch for add3: expected: 3, got: 2");
+    expect_condition!(interpreter, "(apply + 1 2)", "apply expected list, got: 2");
+    expect_condition!(interpreter, "(apply +)", "arity mismatch for apply: expected: 2.., got: 1");
+    expect_condition!(interpreter, "(apply 1 '())", "tried to call 1, which is not possible");
+}
+
+#[test]
+fn higher_order() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(map (lambda (x) (* x x)) '(1 2 3))", "(1 4 9)");
+    expect_eval!(interpreter, "(map + '(1 2 3) '(10 20 30 40))", "(11 22 33)");
+    expect_eval!(interpreter, "(map first '())", "()");
+    expect_condition!(interpreter, "(map first '(1))", "first expected list, got: 1");
+    expect_condition!(interpreter, "(map first 1)", "map expected list, got: 1");
+
+    expect_eval!(interpreter, "(filter (lambda (x) (< x 3)) '(1 5 2 4))", "(1 2)");
+    expect_eval!(interpreter, "(filter integer? '(1 a 2.0 3))", "(1 3)");
+    expect_condition!(interpreter, "(filter (lambda (x) x) '(1))", "filter expected bool, got: 1");
+
+    expect_eval!(interpreter, "(foldl + 0 '(1 2 3))", "6");
+    expect_eval!(interpreter, "(foldl list '() '(1 2 3))", "(3 (2 (1 ())))");
+    expect_eval!(interpreter, "(foldr list '() '(1 2 3))", "(1 (2 (3 ())))");
+    expect_eval!(interpreter, "(foldr - 0 '(1 2 3))", "2");
+    expect_eval!(interpreter, "(foldl - 0 '(1 2 3))", "2");
+    expect_eval!(interpreter, "(foldr + 'init '())", "init");
+
+    // for-each is only useful for side effects
+    expect_eval!(interpreter, "(define v (make-vector 3))", "v");
+    expect_eval!(interpreter, "(for-each (lambda (i x) (vector-set! v i x)) '(0 1 2) '(a b c))", "()");
+    expect_eval!(interpreter, "v", "#(a b c)");
+
+    // closures see their scope, natives can be passed as well
+    expect_eval!(interpreter, "(define (make-adder n) (lambda (x) (+ x n)))", "make-adder");
+    expect_eval!(interpreter, "(map (make-adder 10) '(1 2))", "(11 12)");
+    expect_eval!(interpreter, "(map string-length (string-split \"a bb ccc\"))", "(1 2 3)");
+}
+
+#[test]
+fn sort() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(sort '(3 1 2 5 4) <)", "(1 2 3 4 5)");
+    expect_eval!(interpreter, "(sort '() <)", "()");
+    expect_eval!(interpreter, "(sort #(2.5 1 -3) <)", "#(-3 1 2.5)");
+    // equal elements keep their order
+    expect_eval!(interpreter, "(sort '((1 . a) (0 . b) (1 . c) (0 . d)) (lambda (x y) (< (car x) (car y))))",
+                 "((0 . b) (0 . d) (1 . a) (1 . c))");
+    expect_condition!(interpreter, "(sort '(2 1) (lambda (x y) 1))", "sort expected bool, got: 1");
+    expect_condition!(interpreter, "(sort 1 <)", "sort expected list or vector, got: 1");
+
+    // sort! changes the vector, sort returns a copy
+    expect_eval!(interpreter, "(define v (vector 3 1 2))", "v");
+    expect_eval!(interpreter, "(list (sort v >) v)", "(#(3 2 1) #(3 1 2))");
+    expect_eval!(interpreter, "(sort! v <)", "#(1 2 3)");
+    expect_eval!(interpreter, "v", "#(1 2 3)");
+    expect_condition!(interpreter, "(sort! '(1) <)", "sort! expected vector, got: (1)");
+
+    expect_eval!(interpreter, "(define long (vector->list (make-vector 10000 1)))", "long");
+    expect_eval!(interpreter, "(length (sort long <))", "10000");
+}
+
+#[test]
+fn and_or() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(and)", "true");
+    expect_eval!(interpreter, "(or)", "false");
+    expect_eval!(interpreter, "(and true false)", "false");
+    expect_eval!(interpreter, "(or false true)", "true");
+    // the last value is returned as is
+    expect_eval!(interpreter, "(and true 42)", "42");
+    expect_eval!(interpreter, "(or false '(1))", "(1)");
+    expect_condition!(interpreter, "(and 1 true)", "and expected bool, found: 1");
+    expect_condition!(interpreter, "(or 1 true)", "or expected bool, found: 1");
+
+    // skipped operands are never evaluated
+    expect_eval!(interpreter, "(and false undefined)", "false");
+    expect_eval!(interpreter, "(or true undefined)", "true");
+    expect_eval!(interpreter, "(define n 0)", "n");
+    expect_eval!(interpreter, "(or (begin (set! n (+ n 1)) false) (begin (set! n (+ n 1)) true) (begin (set! n (+ n 1)) true))", "true");
+    expect_eval!(interpreter, "n", "2");
+
+    // the last operand is in tail position
+    expect_eval!(interpreter, "(define (all-even? xs) (or (null? xs) (and (= 0 (remainder (first xs) 2)) (recur (rest xs)))))", "all-even?");
+    expect_eval!(interpreter, "(all-even? '(2 4 6))", "true");
+    expect_eval!(interpreter, "(all-even? '(2 3 6))", "false");
+
+    expect_eval!(interpreter, "(list (not true) (not false))", "(false true)");
+    expect_condition!(interpreter, "(not 1)", "not expected bool, got: 1");
+}
+
+#[test]
+fn when_unless() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(when true 1 2)", "2");
+    expect_eval!(interpreter, "(when false 1 2)", "()");
+    expect_eval!(interpreter, "(unless false 1 2)", "2");
+    expect_eval!(interpreter, "(unless true 1 2)", "()");
+    expect_condition!(interpreter, "(when 1 2)", "if expected bool, found: 1");
+
+    // the body is only evaluated if needed
+    expect_eval!(interpreter, "(when false undefined)", "()");
+    expect_eval!(interpreter, "(unless true undefined)", "()");
+    expect_eval!(interpreter, "(define n 0)", "n");
+    expect_eval!(interpreter, "(when (= n 0) (set! n 1) (set! n (+ n 1)) n)", "2");
+
+    expect_eval!(interpreter, "(loop ((i 0)) (unless (= i 10000) (recur (+ i 1))))", "()");
+}
+
+#[test]
+fn pairs() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(cons 1 2)", "(1 . 2)");
+    expect_eval!(interpreter, "(cons 1 '(2 3))", "(1 2 3)");
+    expect_eval!(interpreter, "(cons 1 (cons 2 '()))", "(1 2)");
+    expect_eval!(interpreter, "(cons 1 (cons 2 3))", "(1 2 . 3)");
+    expect_eval!(interpreter, "'(1 . (2 . (3 . ())))", "(1 2 3)");
+    expect_eval!(interpreter, "(car '(1 . 2))", "1");
+    expect_eval!(interpreter, "(cdr '(1 . 2))", "2");
+    expect_eval!(interpreter, "(cdr '(1 2))", "(2)");
+    expect_condition!(interpreter, "(car '())", "car expected pair, got: ()");
+    expect_condition!(interpreter, "(cdr 1)", "cdr expected pair, got: 1");
+
+    expect_eval!(interpreter, "(list (pair? '(1)) (pair? '(1 . 2)) (pair? '()) (pair? 1))", "(true true false false)");
+    expect_eval!(interpreter, "(list (list? '(1)) (list? '(1 . 2)) (list? '()))", "(true false true)");
+    expect_eval!(interpreter, "(first (cons 1 2))", "1");
+    expect_eval!(interpreter, "(rest (cons 1 2))", "2");
+    expect_eval!(interpreter, "(eq? (cons 1 2) '(1 . 2))", "true");
+
+    // mutation is visible to everyone sharing the pair
+    expect_eval!(interpreter, "(define xs (list 1 2 3))", "xs");
+    expect_eval!(interpreter, "(define tail (cdr xs))", "tail");
+    expect_eval!(interpreter, "(set-car! tail 'b)", "(b 3)");
+    expect_eval!(interpreter, "xs", "(1 b 3)");
+    expect_eval!(interpreter, "(set-cdr! tail 'c)", "(b . c)");
+    expect_eval!(interpreter, "xs", "(1 b . c)");
+    expect_eval!(interpreter, "(list? xs)", "false");
+    expect_condition!(interpreter, "(set-car! '() 1)", "set-car! expected pair, got: ()");
+
+    // circular lists aren't lists
+    expect_eval!(interpreter, "(define ring (list 1 2 3))", "ring");
+    expect_eval!(interpreter, "(pair? (set-cdr! (cdr (cdr ring)) ring))", "true");
+    expect_eval!(interpreter, "(list? ring)", "false");
+    expect_eval!(interpreter, "(car (cdr (cdr (cdr ring))))", "1");
+    expect_eval!(interpreter, "ring", "#0=(1 2 3 . #0#)");
+
+    // building long lists with cons
+    expect_eval!(interpreter, "(define (range n) (loop ((i n) (acc '())) (if (= i 0) acc (recur (- i 1) (cons i acc)))))", "range");
+    expect_eval!(interpreter, "(define long (range 100000))", "long");
+    expect_eval!(interpreter, "(foldl + 0 long)", "5000050000");
+    expect_eval!(interpreter, "(eq? long (range 100000))", "true");
+    expect_eval!(interpreter, "(define long '())", "long");
+}
+
+#[test]
+fn equality() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(list (eqv? 1 1) (eqv? 'a 'a) (eqv? #\\a #\\a) (eqv? 2.5 2.5) (eqv? '() '()))", "(true true true true true)");
+    expect_eval!(interpreter, "(list (eqv? 1 1.0) (eqv? \"a\" \"a\") (eqv? '(1) '(1)) (eqv? car car))", "(false false false true)");
+    expect_eval!(interpreter, "(let (v (vector 1)) (list (eqv? v v) (eqv? v (vector 1))))", "(true false)");
+    expect_eval!(interpreter, "(eqv? 1 1 2)", "false");
+
+    expect_eval!(interpreter, "(equal? '(1 (2 #(3 \"four\"))) (list 1 (list 2 (vector 3 \"four\"))))", "true");
+    expect_eval!(interpreter, "(list (equal? '(1 2) '(1 2 3)) (equal? #(1) #(1.0)) (equal? \"a\" \"b\"))", "(false false false)");
+    expect_eval!(interpreter, "(equal? (make-vector 100000 1) (make-vector 100000 1))", "true");
+
+    // cyclic structures are equal if they can't be told apart
+    expect_eval!(interpreter, "(define (ring . xs) (set-cdr! (last-pair xs) xs) xs)", "ring");
+    expect_eval!(interpreter, "(list (equal? (ring 1 2) (ring 1 2)) (equal? (ring 1 2) (ring 1 2 1 2)) (equal? (ring 1 2) (ring 1 3)))", "(true true false)");
+    expect_eval!(interpreter, "(let (v (vector 1 2)) (vector-set! v 1 v) (equal? v (vector 1 v)))", "true");
+}
+
+#[test]
+fn begin() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(begin)", "()");
+    expect_eval!(interpreter, "(begin 1 2 3)", "3");
+
+    // at top level, definitions end up in the current scope
+    expect_eval!(interpreter, "(begin (define a 1) (define b (+ a 1)))", "b");
+    expect_eval!(interpreter, "(list a b)", "(1 2)");
+    expect_eval!(interpreter, "(begin (define-syntax twice (syntax-rules () ((_ e) (begin e e)))) (twice (set! a (+ a 1))) a)", "3");
+
+    // inside lambda bodies, side effects happen in order
+    expect_eval!(interpreter, "(define v (make-vector 2))", "v");
+    expect_eval!(interpreter, "(define (fill! x) (begin (vector-set! v 0 x) (vector-set! v 1 (vector-ref v 0)) v))", "fill!");
+    expect_eval!(interpreter, "(fill! 'x)", "#(x x)");
+
+    // the last expression is in tail position
+    expect_eval!(interpreter, "(define (down n) (begin (set! a n) (if (= n 0) a (recur (- n 1)))))", "down");
+    expect_eval!(interpreter, "(down 10000)", "0");
+    expect_condition!(interpreter, "(begin 1 undefined 2)", "undefined ident: undefined");
+}
+
+#[test]
+fn ports() {
+    let interpreter = &mut Interpreter::new();
+    let path = ::std::env::temp_dir().join("flip_ports_test.txt");
+    let path = path.to_str().unwrap();
+
+    expect_eval!(interpreter, &format!("(define out (open-output-file \"{}\"))", path), "out");
+    expect_eval!(interpreter, "(list (port? out) (port? 1))", "(true false)");
+    expect_eval!(interpreter, "(display \"hello\" out)", "()");
+    expect_eval!(interpreter, "(newline out)", "()");
+    expect_eval!(interpreter, "(write \"hello\" out)", "()");
+    expect_eval!(interpreter, "(display #\\! out)", "()");
+    expect_eval!(interpreter, "(close-port out)", "()");
+    expect_condition!(interpreter, "(display 1 out)", &format!("display port {} is not open for output", path));
+
+    expect_eval!(interpreter, &format!("(define in (open-input-file \"{}\"))", path), "in");
+    expect_eval!(interpreter, "(read-line in)", "\"hello\"");
+    expect_eval!(interpreter, "(read-char in)", "#\\\"");
+    expect_eval!(interpreter, "(read-line in)", "\"hello\\\"!\"");
+    expect_eval!(interpreter, "(eof-object? (read-line in))", "true");
+    expect_eval!(interpreter, "(eof-object? (read-char in))", "true");
+    expect_condition!(interpreter, "(newline in)", &format!("newline port {} is not open for output", path));
+    expect_eval!(interpreter, "(close-port in)", "()");
+
+    expect_condition!(interpreter, "(read-line 1)", "read-line expected port, got: 1");
+    expect_condition!(interpreter, "(open-input-file \"/nonexistent/file\")",
+                      "open-input-file could not open \"/nonexistent/file\": No such file or directory (os error 2)");
+    ::std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn write_display() {
+    let interpreter = &mut Interpreter::new();
+    let path = ::std::env::temp_dir().join("flip_write_display_test.txt");
+    let path = path.to_str().unwrap();
+
+    // strings are escaped, so they can be read back in
+    expect_eval!(interpreter, r#""a \"quoted\" \\ string\n""#, r#""a \"quoted\" \\ string\n""#);
+    expect_eval!(interpreter, r#""bell\a\x1b;\r""#, r#""bell\x7;\x1b;\r""#);
+    expect_eval!(interpreter, r##"#"C:\dir"#"##, r#""C:\\dir""#);
+    expect_eval!(interpreter, r#"(read-string (with-output-to-string (lambda () (write "a\x0;b\tc"))))"#, r#""a\x0;b\tc""#);
+
+    expect_eval!(interpreter, &format!("(define out (open-output-file \"{}\"))", path), "out");
+    expect_eval!(interpreter, r#"(write '("a\tb" #\x #\\s 1/2) out)"#, "()");
+    expect_eval!(interpreter, "(newline out)", "()");
+    expect_eval!(interpreter, r#"(display '("a\tb" #\x (#\\s) #("c")) out)"#, "()");
+    expect_eval!(interpreter, "(close-port out)", "()");
+
+    expect_eval!(interpreter, &format!("(define in (open-input-file \"{}\"))", path), "in");
+    expect_eval!(interpreter, "(read-line in)", r#""(\"a\\tb\" #\\x #\\\\s 1/2)""#);
+    expect_eval!(interpreter, "(read-line in)", r#""(a\tb x ( ) #(c))""#);
+    expect_eval!(interpreter, "(close-port in)", "()");
+
+    // without a port both print to stdout
+    expect_eval!(interpreter, "(display \"\")", "()");
+    expect_eval!(interpreter, "(write \"\")", "()");
+    expect_condition!(interpreter, "(display 1 2)", "display expected port, got: 2");
+    ::std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn datum_labels() {
+    let interpreter = &mut Interpreter::new();
+    // write only labels what contains itself, so it terminates
+    expect_eval!(interpreter, "(define xs (list 1 2))", "xs");
+    expect_eval!(interpreter, "(set-car! xs xs)", "#0=(#0# 2)");
+    expect_eval!(interpreter, "(define v (vector 1 2))", "v");
+    expect_eval!(interpreter, "(vector-set! v 1 v)", "#0=#(1 #0#)");
+    expect_eval!(interpreter, "(list v v)", "(#0=#(1 #0#) #0#)");
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (display (list \"a\" v))))", r#""(a #0=#(1 #0#))""#);
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (pp v)))", r##""#0=#(1 #0#)\n""##);
+
+    // write-shared also labels what's only shared
+    expect_eval!(interpreter, "(define ys (list 'a))", "ys");
+    expect_eval!(interpreter, "(list ys ys)", "((a) (a))");
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (write-shared (list ys ys))))", r#""(#0=(a) #0#)""#);
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (write-shared (cons ys ys))))", r#""(#0=(a) . #0#)""#);
+
+    // read gives back the same structure
+    expect_eval!(interpreter, "(define ring (read-string \"#0=(a b . #0#)\"))", "ring");
+    expect_eval!(interpreter, "(eq? ring (cdr (cdr ring)))", "true");
+    expect_eval!(interpreter, "(eq? ring (read-string \"#0=(a b a b . #0#)\"))", "true");
+    expect_eval!(interpreter, "(eq? ring (read-string \"#0=(a b c . #0#)\"))", "false");
+    expect_eval!(interpreter, "(eq? v (read-string \"#0=#(1 #0#)\"))", "true");
+    expect_eval!(interpreter, "(let (x (read-string \"(#0=(a) #0#)\")) (eq? (car x) (car (cdr x))))", "true");
+    expect_eval!(interpreter, "(equal? v (read-string (with-output-to-string (lambda () (write v)))))", "true");
+    expect_eval!(interpreter, "'(#0=(a) #0#)", "((a) (a))");
+    expect_condition!(interpreter, "(read-string \"(#0#)\")", "read-string could not parse: \"(#0#)\"");
+}
+
+#[test]
+fn read() {
+    let interpreter = &mut Interpreter::new();
+    let path = ::std::env::temp_dir().join("flip_read_test.txt");
+    let path = path.to_str().unwrap();
+
+    // read returns data, nothing is evaluated
+    expect_eval!(interpreter, "(read-string \"(+ 1 2)\")", "(+ 1 2)");
+    expect_eval!(interpreter, "(eval (read-string \"(+ 1 2)\"))", "3");
+    expect_eval!(interpreter, "(read-string \"#(1 \\\"a\\\") b\")", "#(1 \"a\")");
+    expect_eval!(interpreter, "(read-string \"  'x \")", "(quote x)");
+    expect_eval!(interpreter, "(eof-object? (read-string \" \"))", "true");
+    expect_condition!(interpreter, "(read-string \"(1 2\")", "read-string could not parse: \"(1 2\"");
+
+    expect_eval!(interpreter, &format!("(define out (open-output-file \"{}\"))", path), "out");
+    expect_eval!(interpreter, "(write '(define (f x) (* x 2)) out)", "()");
+    expect_eval!(interpreter, "(display \" 1 \\\"two\\\"\\n\\n(f\\n 21)\" out)", "()");
+    expect_eval!(interpreter, "(close-port out)", "()");
+
+    // data can span multiple lines and share them
+    expect_eval!(interpreter, &format!("(define in (open-input-file \"{}\"))", path), "in");
+    expect_eval!(interpreter, "(read in)", "(define (f x) (* x 2))");
+    expect_eval!(interpreter, "(read in)", "1");
+    expect_eval!(interpreter, "(read in)", "\"two\"");
+    expect_eval!(interpreter, "(read in)", "(f 21)");
+    expect_eval!(interpreter, "(eof-object? (read in))", "true");
+    expect_eval!(interpreter, "(close-port in)", "()");
+
+    expect_condition!(interpreter, "(read 1)", "read expected port, got: 1");
+    ::std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn eval_datum() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(eval '(+ 1 2))", "3");
+    expect_eval!(interpreter, "(eval (list '* 2 21) (interaction-environment))", "42");
+    expect_eval!(interpreter, "(eval 1)", "1");
+    expect_eval!(interpreter, "(eval ''x)", "x");
+
+    // constructed code can use special forms and macros
+    expect_eval!(interpreter, "(eval '(define (square x) (* x x)))", "square");
+    expect_eval!(interpreter, "(square 3)", "9");
+    expect_eval!(interpreter, "(eval `(if ,(< 1 2) 'yes 'no))", "yes");
+    expect_eval!(interpreter, "(define-syntax swap (syntax-rules () ((_ a b) (list b a))))", "swap");
+    expect_eval!(interpreter, "(eval '(swap 1 2))", "(2 1)");
+
+    // eval uses the interaction environment, not the scope it's called in
+    expect_eval!(interpreter, "(define x 'global)", "x");
+    expect_eval!(interpreter, "((lambda (x) (eval 'x)) 'local)", "global");
+    expect_eval!(interpreter, "(let (x 'local) (list x (eval 'x)))", "(local global)");
+
+    expect_eval!(interpreter, "(environment? (interaction-environment))", "true");
+    expect_eval!(interpreter, "(environment? 1)", "false");
+    expect_eval!(interpreter, "(interaction-environment)", "[ENVIRONMENT]");
+    expect_condition!(interpreter, "(eval 'x 1)", "eval expected environment, got: 1");
+    expect_condition!(interpreter, "(eval '(if))", "eval invalid if, expected: (if condition then else)");
+    expect_condition!(interpreter, "(eval 'undefined)", "undefined ident: undefined");
+}
+
+#[test]
+fn load() {
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    let interpreter = &mut Interpreter::new();
+    let dir = ::std::env::temp_dir().join("flip_load_test");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    let write_file = |name: &str, content: &str| File::create(dir.join(name)).unwrap().write_all(content.as_bytes()).unwrap();
+    write_file("main.lisp", "(load \"lib/square.lisp\")\n(define nine (square 3))");
+    // relative to lib/square.lisp, not to the working directory
+    write_file("lib/square.lisp", "(load \"double.lisp\")\n(define (square x) (* x x))");
+    write_file("lib/double.lisp", "(define (double x) (+ x x))");
+    write_file("broken.lisp", "(define x 1)\n(+ 1");
+    write_file("script.lisp", "#!/usr/bin/env flip\n(define from-script (double 21))");
+    let path = dir.join("main.lisp");
+    let path = path.to_str().unwrap();
+
+    expect_eval!(interpreter, &format!("(load \"{}\")", path), "nine");
+    expect_eval!(interpreter, "(list nine (double 2))", "(9 4)");
+
+    // forms are evaluated in the scope load is called in
+    expect_eval!(interpreter, &format!("(define (f) (load \"{}\") (double 5))", dir.join("lib/double.lisp").to_str().unwrap()), "f");
+    expect_eval!(interpreter, "(f)", "10");
+    expect_eval!(interpreter, &format!("(load \"{}\")", dir.join("script.lisp").to_str().unwrap()), "from-script");
+    expect_eval!(interpreter, "from-script", "42");
+
+    let broken = dir.join("broken.lisp");
+    assert!(eval(interpreter, &format!("(load \"{}\")", broken.to_str().unwrap())).unwrap_err()
+            .starts_with(&format!("load could not parse {:?}:", broken)));
+    expect_condition!(interpreter, "(load \"/nonexistent.lisp\")",
+                      "load could not open \"/nonexistent.lisp\": No such file or directory (os error 2)");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn command_line() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(command-line)", "()");
+    interpreter.command_line = vec!["script.lisp".into(), "a".into(), "-b".into()];
+    expect_eval!(interpreter, "(command-line)", "(\"script.lisp\" \"a\" \"-b\")");
+    expect_eval!(interpreter, "(rest (command-line))", "(\"a\" \"-b\")");
+
+    // a valid exit would end the test process
+    expect_condition!(interpreter, "(exit 'x)", "exit expected integer, got: x");
+    expect_condition!(interpreter, "(exit 1 2)", "arity mismatch for exit: expected: 0..1, got: 2");
+}
+
+#[test]
+fn locations() {
+    let interpreter = &mut Interpreter::new();
+    assert_eq!(eval_at(interpreter, "(+ 1 foo)", 1), Err("undefined ident: foo at repl:1:6".into()));
+    assert_eq!(eval_at(interpreter, "(define (f x)\n  (* x\n     undefined))", 2), Ok("f".into()));
+    // the location is where the ident is written, not where the procedure is called
+    assert_eq!(eval_at(interpreter, "(f 1)", 5), Err("undefined ident: undefined at repl:4:6\n  in f at repl:5:2".into()));
+
+    // quoted symbols keep their location, data read at runtime has none
+    assert_eq!(eval_at(interpreter, "(eval (list 'car 'baz))", 6), Err("undefined ident: baz at repl:6:19".into()));
+    assert_eq!(eval_at(interpreter, "(eval (read-string \"baz\"))", 7), Err("undefined ident: baz".into()));
+}
+
+#[test]
+fn backtrace() {
+    let interpreter = &mut Interpreter::new();
+    eval_at(interpreter, "(define (inner x) (car x))", 1).unwrap();
+    eval_at(interpreter, "(define (outer x) (+ 1 (inner x)))", 1).unwrap();
+    assert_eq!(eval_at(interpreter, "(outer 1)", 1),
+               Err("car expected pair, got: 1\n  in inner at repl:1:25\n  in outer at repl:1:2".into()));
+
+    // tail calls replace the frame of their caller
+    eval_at(interpreter, "(define (tail x) (inner x))", 1).unwrap();
+    assert_eq!(eval_at(interpreter, "(tail 1)", 1), Err("car expected pair, got: 1\n  in inner at repl:1:19".into()));
+
+    // procedures called by natives have no call site
+    assert_eq!(eval_at(interpreter, "(map inner '(1))", 1), Err("car expected pair, got: 1\n  in inner".into()));
+
+    // only the innermost frames are kept
+    eval_at(interpreter, "(define (deep n) (if (= n 0) (car n) (+ 1 (deep (- n 1)))))", 1).unwrap();
+    eval_at(interpreter, "(set-backtrace-limit! 2)", 1).unwrap();
+    assert_eq!(eval_at(interpreter, "(deep 5)", 1),
+               Err("car expected pair, got: 0\n  in deep at repl:1:44\n  in deep at repl:1:44\n  ... 4 more".into()));
+
+    // the stack is empty again afterwards
+    assert_eq!(eval_at(interpreter, "(car 1)", 1), Err("car expected pair, got: 1".into()));
+}
+
+#[test]
+fn conditions() {
+    let interpreter = &mut Interpreter::new();
+    expect_condition!(interpreter, "(error \"something went wrong:\" 42 'x \"s\")", "something went wrong: 42 x \"s\"");
+    expect_condition!(interpreter, "(raise 'oops)", "uncaught raise: oops");
+    expect_condition!(interpreter, "(error 1)", "error expected string, got: 1");
+
+    // handlers get the raised value or an error object
+    expect_eval!(interpreter, "(with-exception-handler (lambda (e) (list 'caught e)) (lambda () (+ 1 (raise 'oops))))", "(caught oops)");
+    expect_eval!(interpreter, "(with-exception-handler (lambda (e) e) (lambda () 'fine))", "fine");
+    expect_eval!(interpreter, "(define e (with-exception-handler (lambda (e) e) (lambda () (error \"bad\" 1 2))))", "e");
+    expect_eval!(interpreter, "(list (error-object? e) (error-object-message e) (error-object-irritants e) (condition-kind e))",
+                 "(true \"bad\" (1 2) error)");
+    expect_eval!(interpreter, "e", "[ERROR: bad 1 2]");
+    expect_eval!(interpreter, "(error-object? 'oops)", "false");
+
+    // conditions raised by natives are error objects too
+    expect_eval!(interpreter, "(define (kind thunk) (with-exception-handler condition-kind thunk))", "kind");
+    expect_eval!(interpreter, "(list (kind (lambda () (car 1))) (kind (lambda () (car))) (kind (lambda () (vector-ref #() 0))))",
+                 "(type arity error)");
+    expect_eval!(interpreter, "(with-exception-handler error-object-message (lambda () undefined))", "\"undefined ident: undefined\"");
+
+    // raising an error object raises the original condition again
+    expect_condition!(interpreter, "(with-exception-handler (lambda (e) (raise e)) (lambda () (error \"again\")))", "again");
+}
+
+#[test]
+fn guard() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(guard (e (true (list 'caught e))) (raise 'oops))", "(caught oops)");
+    expect_eval!(interpreter, "(guard (e ((symbol? e) 'symbol) ((string? e) 'string)) (raise \"s\"))", "string");
+    expect_eval!(interpreter, "(guard (e ((error-object? e) (error-object-message e))) (car 1))", "\"car expected pair, got: 1\"");
+    expect_eval!(interpreter, "(guard (e (else 'else)) (raise 1))", "else");
+    expect_eval!(interpreter, "(guard (e (else 'else)) 'no-error)", "no-error");
+
+    // the body is a sequence
+    expect_eval!(interpreter, "(guard (e ((pair? e) (rest e))) (define x 1) (raise (list 'a x)))", "(1)");
+
+    // without a matching clause the condition is raised again
+    expect_condition!(interpreter, "(guard (e ((string? e) 'string)) (raise 'oops))", "uncaught raise: oops");
+    expect_condition!(interpreter, "(guard (e ((string? e) 'string)) (error \"bad\"))", "bad");
+
+    // guard still works with shadowed names
+    expect_eval!(interpreter, "(let (raise 1) (guard (e (false 'never)) (guard (e2 (true 'inner)) (car 1))))", "inner");
+    expect_condition!(interpreter, "(eval '(guard e (raise 1)))", "eval invalid guard, expected: (guard (var clause*) body)");
+}
+
+#[test]
+fn dynamic_wind() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define log '())", "log");
+    expect_eval!(interpreter, "(define (note x) (lambda () (set! log (cons x log))))", "note");
+    expect_eval!(interpreter, "(dynamic-wind (note 'before) (lambda () 'result) (note 'after))", "result");
+    expect_eval!(interpreter, "log", "(after before)");
+
+    // after is called when a condition leaves the extent
+    expect_eval!(interpreter, "(set! log '())", "log");
+    expect_eval!(interpreter, "(guard (e (true e)) (dynamic-wind (note 'before) (lambda () (raise 'oops) 'unreachable) (note 'after)))", "oops");
+    expect_eval!(interpreter, "log", "(after before)");
+
+    // and when a continuation escapes from it
+    expect_eval!(interpreter, "(set! log '())", "log");
+    expect_eval!(interpreter, "(call/cc (lambda (k) (dynamic-wind (note 'before) (lambda () (k 'escaped) 'unreachable) (note 'after))))", "escaped");
+    expect_eval!(interpreter, "log", "(after before)");
+
+    // ports are closed even if the procedure raises a condition
+    let path = ::std::env::temp_dir().join("flip_dynamic_wind_test.txt");
+    let path = path.to_str().unwrap();
+    expect_eval!(interpreter, &format!("(call-with-output-file \"{}\" (lambda (port) (write '(1 2) port) 'written))", path), "written");
+    expect_eval!(interpreter, &format!("(call-with-input-file \"{}\" read)", path), "(1 2)");
+    expect_eval!(interpreter, "(define leaked false)", "leaked");
+    expect_eval!(interpreter, &format!("(guard (e (true e)) (call-with-input-file \"{}\" (lambda (port) (set! leaked port) (raise 'oops))))", path), "oops");
+    expect_condition!(interpreter, "(read-line leaked)", format!("read-line port {} is not open for input", path));
+    ::std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn call_cc() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(call/cc (lambda (k) 1))", "1");
+    expect_eval!(interpreter, "(+ 1 (call/cc (lambda (k) (+ 10 (k 2)))))", "3");
+    expect_eval!(interpreter, "(call-with-current-continuation (lambda (k) (k)))", "()");
+
+    // early exit from a loop
+    expect_eval!(interpreter, "(define (find-first pred list)
+                                 (call/cc (lambda (return)
+                                   (for-each (lambda (x) (when (pred x) (return x))) list)
+                                   false)))", "find-first");
+    expect_eval!(interpreter, "(find-first (lambda (x) (> x 2)) '(1 2 3 4))", "3");
+    expect_eval!(interpreter, "(find-first (lambda (x) (> x 5)) '(1 2 3 4))", "false");
+
+    // nested continuations escape to their own call/cc
+    expect_eval!(interpreter, "(call/cc (lambda (outer) (+ 1 (call/cc (lambda (inner) (outer 10))))))", "10");
+    expect_eval!(interpreter, "(call/cc (lambda (outer) (+ 1 (call/cc (lambda (inner) (inner 10))))))", "11");
+
+    // handlers don't catch escapes, but dynamic-wind runs its after thunk
+    expect_eval!(interpreter, "(call/cc (lambda (k) (guard (e (true 'caught)) (k 'escaped))))", "escaped");
+    expect_eval!(interpreter, "(define log '())", "log");
+    expect_eval!(interpreter, "(call/cc (lambda (k) (dynamic-wind (lambda () 1) (lambda () (k 'escaped)) (lambda () (set! log 'after)))))", "escaped");
+    expect_eval!(interpreter, "log", "after");
+
+    // continuations are one-shot escapes
+    expect_eval!(interpreter, "(define saved (call/cc (lambda (k) k)))", "saved");
+    expect_eval!(interpreter, "(procedure? saved)", "true");
+    expect_condition!(interpreter, "(saved 1)", "continuation called outside of call/cc");
+}
+
+#[test]
+fn values() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define (div-mod a b) (values (quotient a b) (remainder a b)))", "div-mod");
+    expect_eval!(interpreter, "(call-with-values (lambda () (div-mod 7 2)) list)", "(3 1)");
+    expect_eval!(interpreter, "(call-with-values (lambda () 1) list)", "(1)");
+    expect_eval!(interpreter, "(call-with-values (lambda () (values)) list)", "()");
+    // a single value is just that value
+    expect_eval!(interpreter, "(+ (values 1) 2)", "3");
+    expect_eval!(interpreter, "(div-mod 7 2)", "3 1");
+
+    expect_eval!(interpreter, "(let-values (((q r) (div-mod 7 2)) (all (values 1 2))) (list q r all))", "(3 1 (1 2))");
+    expect_eval!(interpreter, "(let*-values (((a . rest) (values 1 2 3)) ((b) (car rest))) (list a rest b))", "(1 (2 3) 2)");
+    expect_eval!(interpreter, "(let-values () 1)", "1");
+    expect_condition!(interpreter, "(eval '(let-values ((a)) a))", "eval invalid let-values, expected: (let-values ((formals expr)*) body)");
+    expect_condition!(interpreter, "(call-with-values (lambda () (values 1 2)) (lambda (a) a))", "arity mismatch for lambda: expected: 1, got: 2");
+}
+
+#[test]
+fn parameters() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define width (make-parameter 80))", "width");
+    expect_eval!(interpreter, "(define (show) (width))", "show");
+    expect_eval!(interpreter, "(list (show) (parameterize ((width 40)) (show)) (show))", "(80 40 80)");
+    expect_eval!(interpreter, "(parameterize ((width 1)) (parameterize ((width 2)) (show)))", "2");
+    expect_eval!(interpreter, "(procedure? width)", "true");
+
+    // converters are called with the initial value and the parameterized ones
+    expect_eval!(interpreter, "(define label (make-parameter 1 number->string))", "label");
+    expect_eval!(interpreter, "(list (label) (parameterize ((label 2)) (label)))", "(\"1\" \"2\")");
+
+    // the binding ends when the body is left by a condition
+    expect_eval!(interpreter, "(guard (e (true (width))) (parameterize ((width 1)) (raise 'oops)))", "80");
+    expect_condition!(interpreter, "(parameterize ((1 2)) 3)", "parameterize expected parameter, got: 1");
+    expect_condition!(interpreter, "(width 1)", "arity mismatch for parameter: expected: 0, got: 1");
+
+    // output goes to the current output port
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (display \"a\") (write \"b\") (format true \"~a\" 1)))", "\"a\\\"b\\\"1\"");
+    expect_eval!(interpreter, "(current-output-port)", "[OUTPUT_PORT: stdout]");
+}
+
+#[test]
+fn shift_reset() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(reset 1)", "1");
+    expect_eval!(interpreter, "(+ 1 (reset (+ 10 (shift k 2))))", "3");
+    expect_eval!(interpreter, "(reset (+ 1 (shift k (k 10))))", "11");
+    expect_eval!(interpreter, "(reset (* 2 (shift k (k (k 10)))))", "40");
+
+    // continuations can be called more than once, e.g. for backtracking
+    expect_eval!(interpreter, "(reset (list (shift k (append (k 1) (k 2))) (shift k (append (k 'a) (k 'b)))))",
+                 "(1 a 1 b 2 a 2 b)");
+    expect_eval!(interpreter, "(define (choose . xs) (shift k (foldr (lambda (x rest) (append (k x) rest)) '() xs)))",
+                 "choose");
+    expect_eval!(interpreter, "(reset (let (x (choose 1 2 3) y (choose 1 2 3)) (if (= (+ x y) 4) (list (list x y)) '())))",
+                 "((1 3) (2 2) (3 1))");
+
+    // generators, the continuation is saved and resumed later
+    expect_eval!(interpreter, "(define (yield x) (shift k (cons x k)))", "yield");
+    expect_eval!(interpreter, "(define gen (reset (yield 1) (yield 2) 'done))", "gen");
+    expect_eval!(interpreter, "(car gen)", "1");
+    expect_eval!(interpreter, "(car ((cdr gen)))", "2");
+    expect_eval!(interpreter, "((cdr ((cdr gen))))", "done");
+    expect_eval!(interpreter, "(procedure? (cdr gen))", "true");
+
+    // calling a continuation replays the body of the reset, side effects before the shift happen again
+    expect_eval!(interpreter, "(define count 0)", "count");
+    expect_eval!(interpreter, "(reset (set! count (+ count 1)) (shift k (k (k 0))))", "0");
+    expect_eval!(interpreter, "count", "3");
+    expect_eval!(interpreter, "(set! count 0)", "count");
+    expect_eval!(interpreter, "(define gen (reset (set! count (+ count 1)) (yield 1) (yield 2) 'done))", "gen");
+    expect_eval!(interpreter, "((cdr ((cdr gen))))", "done");
+    expect_eval!(interpreter, "count", "3");
+
+    // the body of shift is delimited by its own reset
+    expect_eval!(interpreter, "(reset (+ 1 (shift k (+ 10 (shift j 100)))))", "100");
+
+    expect_condition!(interpreter, "(shift k 1)", "shift outside of reset");
+    expect_condition!(interpreter, "(reset (+ 1 (shift k (raise 'oops))))", "uncaught raise: oops");
+    expect_eval!(interpreter, "(reset (+ 1 (shift k 1)))", "1");
+    expect_condition!(interpreter, "(eval '(shift 1 2))", "eval invalid shift, expected: (shift k body)");
+}
+
+#[test]
+fn named_let_and_do() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(let sum ((i 0) (acc 0)) (if (> i 10) acc (sum (+ i 1) (+ acc i))))", "55");
+    expect_eval!(interpreter, "(let count (i 3 acc '()) (if (= i 0) acc (count (- i 1) (cons i acc))))", "(1 2 3)");
+    expect_eval!(interpreter, "(let f () 1)", "1");
+
+    // the inits are evaluated outside, the name isn't bound there
+    expect_eval!(interpreter, "(define i 5)", "i");
+    expect_eval!(interpreter, "(let f ((i 1) (j i)) j)", "5");
+    expect_condition!(interpreter, "(let f ((x f)) x)", "undefined ident: f");
+
+    // calls in tail position don't grow the stack
+    expect_eval!(interpreter, "(let down ((n 100000)) (if (= n 0) 'done (down (- n 1))))", "done");
+
+    expect_eval!(interpreter, "(do ((i 0 (+ i 1)) (acc '() (cons i acc))) ((= i 3) acc))", "(2 1 0)");
+    expect_eval!(interpreter, "(do ((vec (make-vector 3 0)) (i 0 (+ i 1))) ((= i 3) vec) (vector-set! vec i (* i i)))",
+                 "#(0 1 4)");
+    expect_eval!(interpreter, "(do ((i 0 (+ i 1))) ((= i 3)))", "()");
+    expect_eval!(interpreter, "(do ((n 100000 (- n 1))) ((= n 0) 'done))", "done");
+
+    // do loops and named lets can be in tail position of lambdas
+    expect_eval!(interpreter, "(define (last-index n) (do ((i 0 (+ i 1))) ((= i n) i)))", "last-index");
+    expect_eval!(interpreter, "(last-index 4)", "4");
+
+    expect_condition!(interpreter, "(eval '(do ((1 2)) (true)))", "eval invalid do, expected: (do ((name init step?)*) (test result*) command*)");
+    expect_condition!(interpreter, "(eval '(let f (x) x))", "eval invalid let, expected: (let name (bindings) body)");
+}
+
+#[test]
+fn promises() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define count 0)", "count");
+    expect_eval!(interpreter, "(define p (delay (begin (set! count (+ count 1)) (* 6 7))))", "p");
+    expect_eval!(interpreter, "(list (promise? p) count)", "(true 0)");
+    expect_eval!(interpreter, "(list (force p) (force p) count)", "(42 42 1)");
+
+    expect_eval!(interpreter, "(force 5)", "5");
+    expect_eval!(interpreter, "(force (make-promise 5))", "5");
+    expect_eval!(interpreter, "(let (p (delay 1)) (eq? p (make-promise p)))", "true");
+    expect_eval!(interpreter, "(promise? 5)", "false");
+
+    // a failed force can be retried
+    expect_eval!(interpreter, "(define fails true)", "fails");
+    expect_eval!(interpreter, "(define q (delay (if fails (raise 'oops) 'ok)))", "q");
+    expect_condition!(interpreter, "(force q)", "uncaught raise: oops");
+    expect_eval!(interpreter, "(set! fails false)", "fails");
+    expect_eval!(interpreter, "(force q)", "ok");
+
+    // lazy streams
+    expect_eval!(interpreter, "(define-syntax cons-stream (syntax-rules () ((_ a b) (cons a (delay b)))))", "cons-stream");
+    expect_eval!(interpreter, "(define (integers-from n) (cons-stream n (integers-from (+ n 1))))", "integers-from");
+    expect_eval!(interpreter, "(define (stream-take s n) (if (= n 0) '() (cons (car s) (stream-take (force (cdr s)) (- n 1)))))",
+                 "stream-take");
+    expect_eval!(interpreter, "(stream-take (integers-from 1) 5)", "(1 2 3 4 5)");
+
+    expect_condition!(interpreter, "(eval '(delay))", "eval invalid delay, expected: (delay expr)");
+}
+
+#[test]
+fn records() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define-record-type point (make-point x y) point? (x point-x set-point-x!) (y point-y))", "point");
+    expect_eval!(interpreter, "(define p (make-point 1 2))", "p");
+    expect_eval!(interpreter, "(list (point-x p) (point-y p) (point? p) (point? '(1 2)))", "(1 2 true false)");
+    expect_eval!(interpreter, "(begin (set-point-x! p 3) (point-x p))", "3");
+    expect_eval!(interpreter, "p", "[RECORD point x: 3 y: 2]");
+    expect_eval!(interpreter, "point", "[RECORD_TYPE: point]");
+    expect_condition!(interpreter, "(point-x 1)", "point-x expected point, got: 1");
+    expect_condition!(interpreter, "(make-point 1)", "arity mismatch for make-point: expected: 2, got: 1");
+
+    // fields the constructor leaves out are false, instances of other types aren't accepted
+    expect_eval!(interpreter, "(define-record-type box (make-box) box? (x box-x set-box-x!))", "box");
+    expect_eval!(interpreter, "(box-x (make-box))", "false");
+    expect_eval!(interpreter, "(point? (make-box))", "false");
+    expect_condition!(interpreter, "(point-y (make-box))", "point-y expected point, got: [RECORD box x: false]");
+
+    expect_condition!(interpreter, "(eval '(define-record-type a (make-a b) a?))",
+                      "eval invalid define-record-type, constructor has unknown field: b");
+    expect_condition!(interpreter, "(eval '(define-record-type a make-a a?))",
+                      "eval invalid define-record-type, expected: (define-record-type name (constructor field*) predicate (field accessor modifier?)*)");
+
+    // a record referring to itself is freed, the type is kept alive by the procedures
+    eval(interpreter, "(collect-garbage)").unwrap();
+    expect_eval!(interpreter, "(let (b (make-box)) (set-box-x! b b) 'done)", "done");
+    expect_eval!(interpreter, "(collect-garbage)", "1");
+}
+
+#[test]
+fn gensym() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define g (gensym))", "g");
+    expect_eval!(interpreter, "(list (symbol? g) (eq? g g) (eq? g (gensym)) (symbol-interned? g) (symbol-interned? 'g))", "(true true false false true)");
+    // the name is printed, but reading it gives you another symbol
+    expect_eval!(interpreter, "(eq? g (string->symbol (symbol->string g)))", "false");
+    expect_eval!(interpreter, "(list (substring (symbol->string (gensym \"tmp\")) 0 3) (substring (symbol->string (gensym 'x)) 0 1))", "(\"tmp\" \"x\")");
+    expect_eval!(interpreter, "(define a (string->uninterned-symbol \"a\"))", "a");
+    expect_eval!(interpreter, "(list a (eq? a 'a) (eq? a (string->uninterned-symbol \"a\")))", "(a false false)");
+    expect_condition!(interpreter, "(gensym 1)", "gensym expected string or symbol, got: 1");
+}
+
+#[test]
+fn pipe_symbols() {
+    let interpreter = &mut Interpreter::new();
+    // names that couldn't be read back as the same symbol are written in pipes
+    expect_eval!(interpreter, "(string->symbol \"hello world\")", "|hello world|");
+    expect_eval!(interpreter, "(list (string->symbol \"1\") (string->symbol \"true\") (string->symbol \"\") 'plain)", "(|1| |true| || plain)");
+    expect_eval!(interpreter, "(eq? '|hello world| (string->symbol \"hello world\"))", "true");
+    expect_eval!(interpreter, "(list (eq? '|abc| 'abc) (symbol->string '|a\\|b\\x41;|))", "(true \"a|bA\")");
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (display '|hello world|)))", "\"hello world\"");
+    // any name can be bound
+    expect_eval!(interpreter, "(define |my var| 1)", "|my var|");
+    expect_eval!(interpreter, "(+ |my var| 1)", "2");
+}
+
+#[test]
+fn symbol_properties() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(symbol-put! 'f 'doc \"does f\")", "\"does f\"");
+    expect_eval!(interpreter, "(symbol-put! 'f 'deprecated true)", "true");
+    expect_eval!(interpreter, "(list (symbol-get 'f 'doc) (symbol-get 'f 'other) (symbol-get 'g 'doc 'none))", "(\"does f\" false none)");
+    expect_eval!(interpreter, "(symbol-put! 'f 'doc \"does f better\")", "\"does f better\"");
+    expect_eval!(interpreter, "(symbol-properties 'f)", "((doc . \"does f better\") (deprecated . true))");
+    expect_eval!(interpreter, "(list (symbol-remove! 'f 'deprecated) (symbol-remove! 'f 'deprecated) (symbol-properties 'f))", "(true false ((doc . \"does f better\")))");
+    // uninterned symbols have their own properties
+    expect_eval!(interpreter, "(symbol-get (string->uninterned-symbol \"f\") 'doc)", "false");
+    expect_condition!(interpreter, "(symbol-get \"f\" 'doc)", "symbol-get expected symbol, got: \"f\"");
+}
+
+#[test]
+fn docs() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define (double x) \"(double x), twice x\" (* 2 x))", "double");
+    expect_eval!(interpreter, "(list (double 2) (doc double))", "(4 \"(double x), twice x\")");
+    expect_eval!(interpreter, "(define half (lambda half (x) \"half of x\" (/ x 2)))", "half");
+    expect_eval!(interpreter, "(doc half)", "\"half of x\"");
+    // a string that is the whole body is the result instead
+    expect_eval!(interpreter, "(define (name) \"flip\")", "name");
+    expect_eval!(interpreter, "(list (name) (doc name) (doc undefined))", "(\"flip\" false false)");
+    expect_eval!(interpreter, "(doc car)", "\"(car pair), the first element of pair\"");
+    expect_condition!(interpreter, "(eval '(doc \"car\"))", "eval invalid doc, expected: (doc name)");
+
+    // every native has documentation
+    for id in interpreter.global_scope.symbol_ids() {
+        let is_native = interpreter.global_scope.lookup_symbol(id).map_or(false, |x| x.get_native_fn_ptr().is_some());
+        let name = interpreter.interner.lookup(id).unwrap().to_string();
+        assert!(!is_native || interpreter.documentation(id).is_some(), "{} has no documentation", name);
+    }
+}
+
+#[test]
+fn introspection() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define (add a . rest) (apply + a rest))", "add");
+    expect_eval!(interpreter, "(list (procedure-name add) (procedure-arity add) (procedure-arity (lambda (x y) x)))", "(add (1 . true) 2)");
+    expect_eval!(interpreter, "(procedure-source (lambda (x) (* x x)))", "\"(lambda (x) (* x x))\"");
+    expect_eval!(interpreter, "(list (procedure-name (lambda (x) x)) (procedure-arity (make-parameter 1)))", "(false 0)");
+    // natives are named by their global binding
+    expect_eval!(interpreter, "(list (procedure-name car) (procedure-name call/cc) (procedure-arity car) (procedure-source car))", "(car call/cc false false)");
+    expect_condition!(interpreter, "(procedure-name 1)", "procedure-name expected procedure, got: 1");
+
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (describe #(1 2))))", "\"value: #(1 2)\\ntype: vector\\nlength: 2\\n\"");
+    expect_eval!(interpreter, "(define (double x) \"twice x\" (* 2 x))", "double");
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (describe double)))",
+                 "\"value: [PROC: (double (x) \\\"twice x\\\" (* 2 x))]\\ntype: procedure\\nname: double\\narity: 1\\nsource: (lambda double (x) \\\"twice x\\\" (* 2 x))\\ndoc: twice x\\n\"");
+}
+
+#[test]
+fn environment_bindings() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define x 1)", "x");
+    expect_eval!(interpreter, "(let ((a 1)) (let ((b 2) (x 3)) (environment-bindings 2)))", "((b . 2) (x . 3) (a . 1))");
+    // shadowed bindings are left out
+    expect_eval!(interpreter, "(let ((x 3)) (list (assq 'x (environment-bindings)) (eq? (cdr (assq 'car (environment-bindings))) car)))", "((x . 3) true)");
+    expect_eval!(interpreter, "(environment-bindings 0)", "()");
+    expect_condition!(interpreter, "(environment-bindings -1)", "environment-bindings expected index, got: -1");
+}
+
+#[test]
+fn trace() {
+    for &use_vm in &[false, true] {
+        let interpreter = &mut Interpreter::new();
+        interpreter.use_vm = use_vm;
+        expect_eval!(interpreter, "(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))", "fact");
+        expect_eval!(interpreter, "(trace fact)", "()");
+        expect_eval!(interpreter, "(with-output-to-string (lambda () (fact 2)))", "\"> (fact 2)\\n  > (fact 1)\\n    > (fact 0)\\n    < 1\\n  < 1\\n< 2\\n\"");
+        // the indentation starts over after a condition
+        expect_condition!(interpreter, "(fact 'a)", "expected number, got: a");
+        expect_eval!(interpreter, "(with-output-to-string (lambda () (fact 0)))", "\"> (fact 0)\\n< 1\\n\"");
+        expect_eval!(interpreter, "(untrace fact)", "()");
+        expect_eval!(interpreter, "(with-output-to-string (lambda () (fact 3)))", "\"\"");
+        expect_condition!(interpreter, "(trace 1)", "trace expected procedure written in flip, got: 1");
+    }
+}
+
+#[test]
+fn profile() {
+    for &use_vm in &[false, true] {
+        let interpreter = &mut Interpreter::new();
+        interpreter.use_vm = use_vm;
+        expect_eval!(interpreter, "(define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))", "fib");
+        expect_eval!(interpreter, "(define (run) (fib 5))", "run");
+        expect_eval!(interpreter, "(define (count-down n) (if (= n 0) 'done (count-down (- n 1))))", "count-down");
+        expect_condition!(interpreter, "(profile-report)", "profile-report without profile-start");
+        expect_eval!(interpreter, "(profile-start)", "()");
+        expect_eval!(interpreter, "(run)", "5");
+        // tail calls still run in constant space
+        expect_eval!(interpreter, "(count-down 100000)", "done");
+        let report = interpreter.eval_str("(with-output-to-string (lambda () (profile-report)))").unwrap();
+        let report = report.get_string().unwrap().to_string();
+        let mut lines = report.lines();
+        assert_eq!(lines.next().unwrap().split_whitespace().collect::<Vec<_>>(), vec!["procedure", "calls", "inclusive", "ms", "exclusive", "ms"]);
+        let mut calls: Vec<(String, String)> = lines.map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(columns.len(), 4);
+            (columns[0].to_string(), columns[1].to_string())
+        }).collect();
+        calls.sort();
+        assert_eq!(calls, vec![("count-down".to_string(), "100001".to_string()), ("fib".to_string(), "15".to_string()), ("run".to_string(), "1".to_string())]);
+        // profile-report stopped profiling
+        expect_condition!(interpreter, "(profile-report)", "profile-report without profile-start");
+    }
+}
+
+#[test]
+fn coverage() {
+    for &use_vm in &[false, true] {
+        let interpreter = &mut Interpreter::new();
+        interpreter.use_vm = use_vm;
+        expect_condition!(interpreter, "(coverage-report)", "coverage-report without coverage-start");
+        expect_eval!(interpreter, "(coverage-start)", "()");
+        assert_eq!(eval_at(interpreter, "(define (sign x)\n  (if (< x 0)\n      'negative\n      'positive))", 1), Ok("sign".into()));
+        assert_eq!(eval_at(interpreter, "(sign 1)", 5), Ok("positive".into()));
+        // the first line has no symbols that are evaluated
+        expect_eval!(interpreter, "(with-output-to-string (lambda () (coverage-report)))", "\"repl: 3 of 4 lines (75.0%), not covered: 3\\n\"");
+        expect_eval!(interpreter, "(with-output-to-string (lambda () (coverage-lcov)))",
+                     "\"TN:\\nSF:repl\\nDA:2,2\\nDA:3,0\\nDA:4,1\\nDA:5,1\\nLF:4\\nLH:3\\nend_of_record\\n\"");
+    }
+}
+
+#[test]
+fn unit_tests() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define-test addition (assert-equal 3 (+ 1 2)) (assert-true (< 1 2)))", "addition");
+    expect_eval!(interpreter, "(define-test subtraction (assert-equal 1 (- 3 1) \"3 - 1\"))", "subtraction");
+    expect_eval!(interpreter, "(define-test raising (assert-raises (car 1)))", "raising");
+    expect_eval!(interpreter, "(with-output-to-string (lambda () (run-tests)))",
+                 "\"ok addition\\nFAIL subtraction: assert-equal expected 1, got: 2: 3 - 1\\nok raising\\n3 tests, 2 passed, 1 failed\\n\"");
+    // tests with the same name are replaced
+    expect_eval!(interpreter, "(define-test subtraction (assert-equal 2 (- 3 1)))", "subtraction");
+    expect_eval!(interpreter, "(let ((port (open-output-string))) (run-tests port))", "0");
+
+    expect_condition!(interpreter, "(assert-equal '(1 2) '(1 3))", "assert-equal expected (1 2), got: (1 3)");
+    expect_condition!(interpreter, "(assert-true false \"always\")", "assert-true got false: always");
+    expect_condition!(interpreter, "(assert-raises (+ 1 2))", "assert-raises expected a condition from (+ 1 2), got: 3");
+    expect_eval!(interpreter, "(assert-raises (error \"oops\"))", "true");
+    expect_condition!(interpreter, "(eval '(define-test empty))", "eval invalid define-test, expected: (define-test name body)");
+}
+
+#[test]
+fn check_property() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(random-seed! 42)", "()");
+    expect_eval!(interpreter, "(check-property (lambda (x y) (= (+ x y) (+ y x))) (gen-integer) (gen-integer))", "true");
+    expect_eval!(interpreter, "(check-property (lambda (xs) (equal? xs (reverse (reverse xs)))) (gen-list (gen-boolean)))", "true");
+    // the failing values are shrunk to the simplest ones
+    expect_condition!(interpreter, "(check-property (lambda (x) (< x 10)) (gen-integer 0 100))", "check-property failed for (10): returned false");
+    expect_condition!(interpreter, "(check-property (lambda (xs) (< (length xs) 3)) (gen-list (gen-integer 0 10)))",
+                      "check-property failed for ((0 0 0)): returned false");
+    expect_condition!(interpreter, "(check-property (lambda (x) (car x)) (gen-one-of 'a 'b))", "check-property failed for (a): raised: car expected pair, got: a");
+    expect_condition!(interpreter, "(check-property (lambda (x) x) 1)", "check-property expected generator, got: 1");
+    expect_condition!(interpreter, "(gen-integer 2 1)", "gen-integer expected lo <= hi, got: 2 1");
+}
+
+#[test]
+fn eval_hooks() {
+    let interpreter = &mut Interpreter::new();
+    let forms = Arc::new(Mutex::new(vec![]));
+    let results = Arc::new(Mutex::new(vec![]));
+    let recorded_forms = forms.clone();
+    let recorded_results = results.clone();
+    interpreter.set_pre_eval_hook(move |interpreter: &mut Interpreter, form: &Value| {
+        recorded_forms.lock().unwrap().push(form.to_string(&interpreter.interner));
+    });
+    interpreter.set_post_eval_hook(move |interpreter: &mut Interpreter, form: &Value, res: &Result<Value, ::value::Condition>| {
+        if form.get_list().is_none() { return; }
+        let res = match *res {
+            Ok(ref value) => value.to_string(&interpreter.interner),
+            Err(ref condition) => condition.to_string(&interpreter.interner),
+        };
+        recorded_results.lock().unwrap().push(format!("{} => {}", form.to_string(&interpreter.interner), res));
+    });
+
+    expect_eval!(interpreter, "(+ 1 (* 2 3))", "7");
+    assert_eq!(*forms.lock().unwrap(), vec!["(+ 1 (* 2 3))", "+", "1", "(* 2 3)", "*", "2", "3"]);
+    assert_eq!(*results.lock().unwrap(), vec!["(* 2 3) => 6", "(+ 1 (* 2 3)) => 7"]);
+    expect_condition!(interpreter, "(car 1)", "car expected pair, got: 1");
+    assert_eq!(results.lock().unwrap().last().unwrap(), "(car 1) => car expected pair, got: 1");
+
+    interpreter.remove_eval_hooks();
+    forms.lock().unwrap().clear();
+    expect_eval!(interpreter, "(+ 1 2)", "3");
+    assert!(forms.lock().unwrap().is_empty());
+}
+
+#[test]
+fn debugger() {
+    let interpreter = &mut Interpreter::new();
+    let reasons = Arc::new(Mutex::new(vec![]));
+    let recorded = reasons.clone();
+    let mut actions = vec![DebugAction::Continue, DebugAction::Continue, DebugAction::Step, DebugAction::Next, DebugAction::Continue].into_iter();
+    interpreter.debugger = Some(Box::new(move |interpreter: &mut Interpreter, reason: &str| {
+        // the debugger sees the scope the program stopped in
+        let x = interpreter.interner.intern("x");
+        let value = interpreter.current_scope.lookup_symbol(x).map_or("unbound".to_string(), |x| x.to_string(&interpreter.interner));
+        recorded.lock().unwrap().push(format!("{}, x = {}", reason, value));
+        actions.next().unwrap_or(DebugAction::Continue)
+    }));
+
+    expect_eval!(interpreter, "(define (f x) (break \"in f\") (* x 2))", "f");
+    expect_eval!(interpreter, "(f 21)", "42");
+    expect_condition!(interpreter, "(let ((x 1)) (car x))", "car expected pair, got: 1");
+    // next stops at the operator of the native call first, evaluating it doesn't call procedures
+    expect_eval!(interpreter, "(define (g x) (break) (+ x 1))", "g");
+    expect_eval!(interpreter, "(g 1)", "2");
+    assert_eq!(*reasons.lock().unwrap(), vec![
+        "break: in f, x = 21", "condition: car expected pair, got: 1, x = 1", "break, x = 1", "step: (+ x 1), x = 1", "step: +, x = 1",
+    ]);
+}
+
+#[test]
+fn breakpoints_and_watchpoints() {
+    for &use_vm in &[false, true] {
+        let interpreter = &mut Interpreter::new();
+        interpreter.use_vm = use_vm;
+        let reasons = Arc::new(Mutex::new(vec![]));
+        let recorded = reasons.clone();
+        interpreter.debugger = Some(Box::new(move |_: &mut Interpreter, reason: &str| {
+            recorded.lock().unwrap().push(reason.to_string());
+            DebugAction::Continue
+        }));
+
+        expect_eval!(interpreter, "(define (square x) (* x x))", "square");
+        expect_eval!(interpreter, "(define (sum-squares a b) (+ (square a) (square b)))", "sum-squares");
+        expect_eval!(interpreter, "(set-breakpoint 'square)", "()");
+        expect_eval!(interpreter, "(sum-squares 1 2)", "5");
+        assert_eq!(reasons.lock().unwrap().len(), 2);
+        if !use_vm {
+            assert_eq!(*reasons.lock().unwrap(), vec!["breakpoint: (square a)", "breakpoint: (square b)"]);
+        }
+        expect_eval!(interpreter, "(remove-breakpoint 'square)", "()");
+        expect_eval!(interpreter, "(sum-squares 1 2)", "5");
+        assert_eq!(reasons.lock().unwrap().len(), 2);
+
+        expect_eval!(interpreter, "(define counter 0)", "counter");
+        expect_eval!(interpreter, "(define (bump) (set! counter (+ counter 1)))", "bump");
+        expect_eval!(interpreter, "(watch 'counter)", "()");
+        expect_eval!(interpreter, "(with-output-to-string (lambda () (bump) (bump)))",
+                     "\"watch: counter changed from 0 to 1\\nwatch: counter changed from 1 to 2\\n\"");
+        expect_eval!(interpreter, "(unwatch 'counter)", "()");
+        expect_eval!(interpreter, "(with-output-to-string bump)", "\"\"");
+        expect_condition!(interpreter, "(watch \"counter\")", "watch expected symbol, got: \"counter\"");
+    }
+}
+
+#[test]
+fn keywords() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "#:name", "#:name");
+    expect_eval!(interpreter, "(list :a (keyword? #:a) (keyword? 'a) (eq? #:a :a) (eq? #:a 'a))", "(#:a true false true false)");
+    expect_eval!(interpreter, "(list (keyword->string #:a) (string->keyword \"b\"))", "(\"a\" #:b)");
+    expect_condition!(interpreter, "(keyword->string 'a)", "keyword->string expected keyword, got: a");
+
+    // optional parameters are filled from the front, defaults can use the parameters before them
+    expect_eval!(interpreter, "(define (f a #:optional (b (+ a 1)) c) (list a b c))", "f");
+    expect_eval!(interpreter, "(list (f 1) (f 1 5) (f 1 5 6))", "((1 2 false) (1 5 false) (1 5 6))");
+    expect_condition!(interpreter, "(f 1 2 3 4)", "arity mismatch for f: expected: 1..3, got: 4");
+    expect_condition!(interpreter, "(f)", "arity mismatch for f: expected: 1..3, got: 0");
+
+    // keyword arguments can be passed in any order
+    expect_eval!(interpreter, "(define g (lambda g (#:optional (x 0) #:key (width 80) (height (* 2 width))) (list x width height)))", "g");
+    expect_eval!(interpreter, "(list (g) (g 1) (g #:height 3) (g 1 #:height 3 #:width 4) (g #:width 4))",
+                 "((0 80 160) (1 80 160) (0 80 3) (1 4 3) (0 4 8))");
+    expect_condition!(interpreter, "(g 1 2)", "g expected keyword argument, got: 2");
+    expect_condition!(interpreter, "(g #:depth 1)", "g expected keyword argument, got: #:depth");
+    expect_condition!(interpreter, "(g #:width)", "g missing value for keyword argument: #:width");
+    expect_eval!(interpreter, "((lambda (#:key a) a) #:a 1)", "1");
+    expect_eval!(interpreter, "((lambda (#:optional o) o) #:a)", "#:a");
+
+    expect_condition!(interpreter, "(eval '(lambda (#:key 1) 1))", "eval invalid lambda, expected: (args #:optional (arg default)* #:key (arg default)*)");
+}
+
+#[test]
+fn chars() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(list (char-alphabetic? #\\a) (char-alphabetic? #\\1) (char-numeric? #\\1) (char-numeric? #\\a))",
+                 "(true false true false)");
+    expect_eval!(interpreter, "(list (char-whitespace? #\\space) (char-whitespace? #\\tab) (char-whitespace? #\\a))",
+                 "(true true false)");
+    expect_eval!(interpreter, "(list (char-upper-case? #\\A) (char-lower-case? #\\A))", "(true false)");
+    expect_eval!(interpreter, "(list (char-upcase #\\a) (char-downcase #\\A) (char-upcase #\\1))", "(#\\A #\\a #\\1)");
+    expect_eval!(interpreter, "(list (digit-value #\\7) (digit-value #\\a))", "(7 false)");
+
+    expect_eval!(interpreter, "(list (char=? #\\a #\\a) (char<? #\\a #\\b #\\c) (char<? #\\a #\\c #\\b))", "(true true false)");
+    expect_eval!(interpreter, "(list (char>? #\\b #\\a) (char<=? #\\a #\\a) (char>=? #\\a #\\b))", "(true true false)");
+    expect_eval!(interpreter, "(list (char-ci=? #\\a #\\A) (char-ci<? #\\a #\\B) (char<? #\\a #\\B))", "(true true false)");
+
+    // named chars are written in the short form, other unprintable chars in hex
+    expect_eval!(interpreter, "(list #\\space #\\newline #\\x41 #\\return)", "(#\\\\s #\\\\n #\\A #\\xd)");
+    expect_eval!(interpreter, "(char->integer #\\escape)", "27");
+
+    expect_condition!(interpreter, "(char-upcase \"a\")", "char-upcase expected char, got: \"a\"");
+    expect_condition!(interpreter, "(char<? #\\a)", "arity mismatch for char<?: expected: 2.., got: 1");
+}
+
+#[test]
+fn modules() {
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define-module (my math)
+                                 (export square cube)
+                                 (define (square x) (* x x))
+                                 (define (cube x) (* x (square x)))
+                                 (define secret 42))", "(my math)");
+    expect_condition!(interpreter, "square", "undefined ident: square");
+    expect_eval!(interpreter, "(import (my math))", "()");
+    expect_eval!(interpreter, "(list (square 3) (cube 2))", "(9 8)");
+    expect_condition!(interpreter, "secret", "undefined ident: secret");
+
+    // modules only see the global scope, imports are bound in the scope of import
+    expect_eval!(interpreter, "(define (f) (let (x 1) (define-module (sees x) (define y x)) 'ok))", "f");
+    expect_condition!(interpreter, "(f)", "undefined ident: x");
+    expect_eval!(interpreter, "(define-module (other) (export twice) (define (twice x) (* 2 x)))", "(other)");
+    expect_eval!(interpreter, "(let (a 1) (import (other)) (twice 2))", "4");
+    expect_condition!(interpreter, "twice", "undefined ident: twice");
+
+    expect_condition!(interpreter, "(define-module (broken) (export nothing))", "module (broken) exports undefined ident: nothing");
+    expect_condition!(interpreter, "(import (broken))", "import could not find module (broken)");
+    expect_condition!(interpreter, "(export square)", "export outside of define-module");
+    expect_condition!(interpreter, "(import \"lib\")", "import expected module name, got: \"lib\"");
+    expect_condition!(interpreter, "(eval '(import))", "eval invalid import, expected: (import name+)");
+
+    // modules that aren't defined are loaded from name/parts.lisp relative to the file being loaded
+    let dir = ::std::env::temp_dir().join("flip_modules_test");
+    fs::create_dir_all(dir.join("util")).unwrap();
+    let write_file = |name: &str, content: &str| File::create(dir.join(name)).unwrap().write_all(content.as_bytes()).unwrap();
+    write_file("main.lisp", "(import (util strings))\n(define greeting (greet \"flip\"))");
+    write_file("util/strings.lisp", "(define-module (util strings) (export greet) (define (greet x) (string-append \"hello \" x)))");
+    expect_eval!(interpreter, &format!("(load \"{}\")", dir.join("main.lisp").to_str().unwrap()), "greeting");
+    expect_eval!(interpreter, "greeting", "\"hello flip\"");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn prelude() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(list (caar '((1) 2)) (cadr '(1 2)) (cdar '((1 3) 2)) (cddr '(1 2 3)) (caddr '(1 2 3)))",
+                 "(1 2 (3) (3) 3)");
+    expect_eval!(interpreter, "(list (length '()) (length '(1 2 3)))", "(0 3)");
+    expect_eval!(interpreter, "(reverse '(1 2 3))", "(3 2 1)");
+    expect_eval!(interpreter, "(list (append) (append '(1)) (append '(1 2) '() '(3) '(4 5)))", "(() (1) (1 2 3 4 5))");
+    expect_eval!(interpreter, "(append '(1) 2)", "(1 . 2)");
+    expect_eval!(interpreter, "(list (list-tail '(1 2 3) 1) (list-ref '(1 2 3) 2) (last-pair '(1 2 3)))", "((2 3) 3 (3))");
+
+    expect_eval!(interpreter, "(member 2 '(1 2 3))", "(2 3)");
+    expect_eval!(interpreter, "(member '(1) '(1 (1) 2))", "((1) 2)");
+    expect_eval!(interpreter, "(member 4 '(1 2 3))", "false");
+    expect_eval!(interpreter, "(member 2 '(1 2 3) (lambda (a b) (< a b)))", "(3)");
+    expect_eval!(interpreter, "(assoc 'b '((a 1) (b 2)))", "(b 2)");
+    expect_eval!(interpreter, "(assoc 'c '((a 1) (b 2)))", "false");
+    expect_eval!(interpreter, "(assoc 2.0 '((1 a) (2 b)) =)", "(2 b)");
+    expect_eval!(interpreter, "(equal? \"a\" \"a\")", "true");
+    expect_eval!(interpreter, "(list (memq 'c '(a b c d)) (memq 'e '(a b c)) (memv 2.0 '(1 2 3)) (memv 2.0 '(1.0 2.0)))",
+                 "((c d) false false (2.0))");
+    expect_eval!(interpreter, "(list (assq 'b '((a . 1) (b . 2))) (assv 2 '((1 one) (2 two))) (assv 2.0 '((2 two))))",
+                 "((b . 2) (2 two) false)");
+    expect_eval!(interpreter, "(alist-delete 'a '((a . 1) (b . 2) (a . 3)))", "((b . 2))");
+    expect_eval!(interpreter, "(alist-delete 2 '((1 . a) (2 . b)) (lambda (k x) (< x k)))", "((2 . b))");
+    expect_eval!(interpreter, "(define alist '((a . 1)))", "alist");
+    expect_eval!(interpreter, "(let (copy (alist-copy alist)) (set-cdr! (car copy) 2) (list copy alist))", "(((a . 2)) ((a . 1)))");
+
+    // the loops don't grow the stack
+    expect_eval!(interpreter, "(define long (vector->list (make-vector 100000 1)))", "long");
+    expect_eval!(interpreter, "(list (length long) (length (reverse long)) (member 2 long))", "(100000 100000 false)");
+
+    // errors are raised by the natives the prelude calls
+    expect_condition!(interpreter, "(length 1)", "cdr expected pair, got: 1");
+}
+
+// evaluates the inputs with and without the vm and expects the same results
+fn expect_same_with_vm(inputs: &[&str]) {
+    let interpreter = &mut Interpreter::new();
+    let vm = &mut Interpreter::new();
+    vm.use_vm = true;
+    for input in inputs {
+        assert_eq!(eval_at(vm, input, 1), eval_at(interpreter, input, 1), "input: {}", input);
+    }
+}
+
+#[test]
+fn vm() {
+    expect_same_with_vm(&[
+        "(define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))",
+        "(fib 15)",
+        "(define (classify x) (cond ((not (integer? x)) => (lambda (b) (list 'other b))) ((< x 0) 'negative) ((= x 0)) (else 'positive)))",
+        "(map classify '(-1 0 1 a))",
+        "(define (size x) (case x ((0) 'zero) ((1 2 3) => (lambda (n) (* n 10))) (else 'big)))",
+        "(map size '(0 2 7))",
+        "(define (no-else x) (list (cond ((= x 1) 'one)) (case x ((1) 'one))))",
+        "(no-else 2)",
+        "(define (between x) (and (> x 0) (or (= x 1) (< x 5))))",
+        "(map between '(0 1 3 9))",
+        "(define (empty) (list (and) (or)))",
+        "(empty)",
+        "(define (lets x) (let ((x (+ x 1)) (y x)) (let* ((a x) (b (* a 2))) (letrec ((ev? (lambda (n) (if (= n 0) true (od? (- n 1))))) (od? (lambda (n) (if (= n 0) false (ev? (- n 1)))))) (list x y a b (ev? 10))))))",
+        "(lets 1)",
+        "(define (counter) (define n 0) (set! n (+ n 1)) (let ((m n)) (set! n (+ m 1))) (begin n))",
+        "(counter)",
+        "(define (named n) (let lp ((i 0) (acc '())) (if (= i n) acc (lp (+ i 1) (cons i acc)))))",
+        "(named 3)",
+        "(define (loops n) (do ((i 0 (+ i 1)) (acc 0 (+ acc i))) ((= i n) acc)))",
+        "(loops 10)",
+        "(define (recurs n acc) (if (= n 0) acc (recur (- n 1) (+ acc n))))",
+        "(recurs 10 0)",
+        "(define (rest a . xs) (list a xs `(,a ,@xs)))",
+        "(rest 1 2 3)",
+        "(define (escape) (+ 1 (call/cc (lambda (k) (+ 10 (k 1))))))",
+        "(escape)",
+        "(define (guarded x) (guard (e ((string? e) (string-append \"caught \" e))) (if x (raise \"it\") 'fine)))",
+        "(list (guarded true) (guarded false))",
+        "(define (shifted) (reset (+ 1 (shift k (k (k 10))))))",
+        "(shifted)",
+        "(define (bad x) (+ 1 (car x)))",
+        "(define (calls-bad) (let ((r (bad 5))) r))",
+        "(calls-bad)",
+        "(define (not-bool) (if 1 2 3))",
+        "(not-bool)",
+        "(define (undefined) (set! nope 1))",
+        "(undefined)",
+        "((lambda (x) x))",
+        "(define (let-twice) (let ((x 1) (x 2)) x))",
+        "(let-twice)",
+    ]);
+
+    // calls of compiled procedures don't grow the rust stack
+    with_small_stack(|| {
+        let interpreter = &mut Interpreter::new();
+        interpreter.use_vm = true;
+        eval(interpreter, "(define (deep n) (if (= n 0) 0 (+ 1 (deep (- n 1)))))").unwrap();
+        expect_eval!(interpreter, "(deep 100000)", "100000");
+        eval(interpreter, "(define (count-down n) (cond ((= n 0) 'done) (else (count-down (- n 1)))))").unwrap();
+        expect_eval!(interpreter, "(count-down 100000)", "done");
+    });
+}
+
+#[test]
+fn vm_backtrace() {
+    let interpreter = &mut Interpreter::new();
+    interpreter.use_vm = true;
+    eval_at(interpreter, "(define (inner x) (car x))", 1).unwrap();
+    eval_at(interpreter, "(define (outer x) (+ 1 (inner x)))", 1).unwrap();
+    assert_eq!(eval_at(interpreter, "(outer 1)", 1),
+               Err("car expected pair, got: 1\n  in inner at repl:1:25\n  in outer at repl:1:2".into()));
+
+    eval_at(interpreter, "(define (tail x) (inner x))", 1).unwrap();
+    assert_eq!(eval_at(interpreter, "(tail 1)", 1), Err("car expected pair, got: 1\n  in inner at repl:1:19".into()));
+
+    eval_at(interpreter, "(define (deep n) (if (= n 0) (car n) (+ 1 (deep (- n 1)))))", 1).unwrap();
+    eval_at(interpreter, "(set-backtrace-limit! 2)", 1).unwrap();
+    assert_eq!(eval_at(interpreter, "(deep 5)", 1),
+               Err("car expected pair, got: 0\n  in deep at repl:1:44\n  in deep at repl:1:44\n  ... 4 more".into()));
+    assert_eq!(eval_at(interpreter, "(car 1)", 1), Err("car expected pair, got: 1".into()));
+}
+
+#[test]
+fn lexical_addresses() {
+    let interpreter = &mut Interpreter::new();
+    interpreter.use_vm = true;
+    eval(interpreter, "(define (f x) (let ((y 1)) (+ x y)))").unwrap();
+    expect_eval!(interpreter, "(f 1)", "2");
+
+    let (f, x, y, plus) = (interpreter.interner.intern("f"), interpreter.interner.intern("x"),
+                           interpreter.interner.intern("y"), interpreter.interner.intern("+"));
+    let chunk = interpreter.current_scope.lookup_symbol(f).unwrap().get_proc().unwrap().compiled().unwrap();
+    let lookups: Vec<Op> = chunk.code.iter().cloned().filter(|op| match *op {
+        Op::Lookup(..) | Op::LookupLocal(..) => true,
+        _ => false,
+    }).collect();
+    // the constants are 1 and the symbols as written
+    assert_eq!(lookups, vec![Op::Lookup(plus, 1, 0), Op::LookupLocal(x, Address { depth: 1, index: 0 }, 2),
+                             Op::LookupLocal(y, Address { depth: 0, index: 0 }, 3)]);
+
+    expect_same_with_vm(&[
+        "(define x 'global)",
+        // define shadows the binding the address points to
+        "(define (shadow x) (let ((y 1)) (define x 'defined) (list x y)))",
+        "(shadow 'param)",
+        "(define (define-later) (let ((a x)) (define x 'later) (list a x)))",
+        "(define-later)",
+        "(define (letrec-forward) (letrec ((f (lambda () y)) (y 'inner)) (f)))",
+        "(letrec-forward)",
+        "(define (letrec-outer y) (letrec ((a y) (y 'inner)) (list a y)))",
+        "(letrec-outer 'outer)",
+        // closures share the bindings they capture
+        "(define (make-counter) (let ((n 0)) (lambda () (set! n (+ n 1)) n)))",
+        "(define counter (make-counter))",
+        "(list (counter) (counter) (counter))",
+        "(define (adder a) (lambda (b) (let* ((c (+ a b)) (d (* c 2))) (lambda (e) (list a b c d e)))))",
+        "(((adder 1) 2) 3)",
+        "(define (evaluated z) (eval '(define z 'evaluated) (interaction-environment)) z)",
+        "(evaluated 'param)",
+        "(define (branches c) (if c (let ((v 1)) v) (let* ((w 2) (v 3)) (list v w))))",
+        "(list (branches true) (branches false))",
+        "(define (set-param p) (set! p (+ p 1)) (let ((q p)) (set! q (* q 2)) (list p q)))",
+        "(set-param 1)",
+    ]);
+}
+
+#[test]
+fn register_fn() {
+    let interpreter = &mut Interpreter::new();
+    interpreter.register_fn("add", |_, args| Ok(Value::new_integer(args.get_i64(0)? + args.get_i64(1)?)));
+    expect_eval!(interpreter, "(add 1 (add 2 3))", "6");
+    expect_eval!(interpreter, "(map (lambda (x) (add x 1)) (list 1 2))", "(2 3)");
+    expect_eval!(interpreter, "(procedure? add)", "true");
+    expect_condition!(interpreter, "(add 1 \"2\")", "add expected integer as argument 2, got: \"2\"");
+    expect_condition!(interpreter, "(add 1)", "arity mismatch for add: expected: 2.., got: 1");
+
+    // closures can hold state of the application
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    interpreter.register_fn("greet", move |_, args| {
+        counted.fetch_add(1, Ordering::SeqCst);
+        Ok(Value::new_string(format!("hello {}", args.get_str(0)?)))
+    });
+    expect_eval!(interpreter, "(greet \"you\")", "\"hello you\"");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    interpreter.use_vm = true;
+    eval(interpreter, "(define (sum-to n) (if (= n 0) 0 (add n (sum-to (- n 1)))))").unwrap();
+    expect_eval!(interpreter, "(sum-to 100)", "5050");
+}
+
+#[test]
+fn eval_str() {
+    let interpreter = &mut Interpreter::new();
+    assert_eq!(interpreter.eval_str("(define x 20) (+ x 22)").unwrap().as_i64(), Some(42));
+    assert_eq!(interpreter.eval_str("(string-append \"a\" \"b\")").unwrap().as_str(), Some("ab"));
+    assert_eq!(interpreter.eval_str("(< x 3)").unwrap().as_bool(), Some(false));
+    assert_eq!(interpreter.eval_str("(/ x 8)").unwrap().as_f64(), None);
+    assert_eq!(interpreter.eval_str("x").unwrap().as_f64(), Some(20.0));
+    let values: Vec<i64> = interpreter.eval_str("#(1 2 3)").unwrap().as_vec().unwrap().iter().filter_map(Value::as_i64).collect();
+    assert_eq!(values, vec![1, 2, 3]);
+    assert_eq!(interpreter.eval_str("(list x)").unwrap().as_vec(), Some(vec![Value::new_integer(20)]));
+
+    let err = interpreter.eval_str("(car 1)").unwrap_err();
+    assert_eq!(err.to_string(&interpreter.interner), "car expected pair, got: 1");
+    match interpreter.eval_str("(+ 1 2") {
+        Err(Error::Parse(_)) => (),
+        res => panic!("expected a parse error, got: {:?}", res),
+    }
+}
+
+#[test]
+fn userdata() {
+    struct Counter { count: Mutex<i64> }
+
+    let interpreter = &mut Interpreter::new();
+    interpreter.register_fn("make-counter", |_, args| {
+        let counter = Counter { count: Mutex::new(args.get_i64(0)?) };
+        let userdata = Userdata::new(counter).with_type_name("counter").with_method("incr", |_, args| {
+            let counter: &Counter = args.get_userdata(0)?;
+            let mut count = counter.count.lock().unwrap();
+            *count += 1;
+            Ok(Value::new_integer(*count))
+        });
+        Ok(Value::new_userdata(userdata))
+    });
+
+    expect_eval!(interpreter, "(define c (make-counter 10))", "c");
+    expect_eval!(interpreter, "c", "[USERDATA: counter]");
+    expect_eval!(interpreter, "(list (userdata? c) (userdata? 1) (userdata-type c))", "(true false \"counter\")");
+    expect_eval!(interpreter, "(begin (call-method c 'incr) (call-method c 'incr))", "12");
+    expect_eval!(interpreter, "(eq? c c)", "true");
+    expect_condition!(interpreter, "(call-method c 'decr)", "call-method: counter has no method decr");
+    expect_condition!(interpreter, "(call-method 1 'incr)", "call-method expected userdata, got: 1");
+
+    // the application gets the object back
+    let c = interpreter.eval_str("c").unwrap();
+    assert_eq!(c.get_userdata().and_then(|x| x.downcast_ref::<Counter>()).map(|x| *x.count.lock().unwrap()), Some(12));
+    assert!(c.get_userdata().unwrap().downcast_ref::<String>().is_none());
+}
+
+#[test]
+fn call_from_rust() {
+    let interpreter = &mut Interpreter::new();
+    let handler = interpreter.eval_str("(lambda (x y) (+ x y))").unwrap();
+    assert_eq!(interpreter.call(&handler, &[Value::new_integer(1), Value::new_integer(2)]).unwrap().as_i64(), Some(3));
+
+    // callbacks registered by a script
+    interpreter.eval_str("(define handlers '()) (define (on-event f) (set! handlers (cons f handlers)))").unwrap();
+    interpreter.eval_str("(define count 0) (on-event (lambda (n) (set! count (+ count n)) count))").unwrap();
+    let handlers = interpreter.eval_str("handlers").unwrap().as_vec().unwrap();
+    interpreter.call(&handlers[0], &[Value::new_integer(5)]).unwrap();
+    assert_eq!(interpreter.call(&handlers[0], &[Value::new_integer(5)]).unwrap().as_i64(), Some(10));
+
+    let car = interpreter.eval_str("car").unwrap();
+    let err = interpreter.call(&car, &[Value::new_integer(1)]).unwrap_err();
+    assert_eq!(err.to_string(&interpreter.interner), "car expected pair, got: 1");
+}
+
+#[test]
+fn fuel() {
+    for &use_vm in &[false, true] {
+        let interpreter = &mut Interpreter::new();
+        interpreter.use_vm = use_vm;
+        eval(interpreter, "(define (forever n) (forever (+ n 1)))").unwrap();
+        eval(interpreter, "(define (count n) (if (= n 0) 'done (count (- n 1))))").unwrap();
+
+        interpreter.set_fuel(10000);
+        expect_eval!(interpreter, "(count 10)", "done");
+        assert!(interpreter.fuel().unwrap() < 10000);
+        expect_condition!(interpreter, "(forever 0)", "out of fuel");
+        // handlers can't keep an untrusted script running
+        expect_condition!(interpreter, "(with-exception-handler (lambda (e) 'caught) (lambda () (forever 0)))", "out of fuel");
+
+        // the interpreter is still usable afterwards
+        interpreter.set_fuel(10000);
+        expect_eval!(interpreter, "(count 10)", "done");
+        interpreter.remove_fuel();
+        expect_eval!(interpreter, "(count 100000)", "done");
+        assert_eq!(interpreter.fuel(), None);
+    }
+}
+
+#[test]
+fn memory_limit() {
+    for &use_vm in &[false, true] {
+        let interpreter = &mut Interpreter::new();
+        interpreter.use_vm = use_vm;
+        eval(interpreter, "(define (grow l) (grow (cons 1 l)))").unwrap();
+        eval(interpreter, "(define (fill h n) (hash-set! h n n) (fill h (+ n 1)))").unwrap();
+        eval(interpreter, "(define (spill port) (display \"0123456789\" port) (spill port))").unwrap();
+        eval(interpreter, "(define (bind-all n) (eval (list 'define (string->symbol (number->string n)) n)) (bind-all (+ n 1)))").unwrap();
+
+        interpreter.set_memory_limit(100000);
+        expect_eval!(interpreter, "(vector-length (make-vector 100))", "100");
+        expect_condition!(interpreter, "(make-vector 1000000)", "out of memory");
+        expect_condition!(interpreter, "(make-string 1000000)", "out of memory");
+        expect_condition!(interpreter, "(grow '())", "out of memory");
+        expect_condition!(interpreter, "(with-exception-handler (lambda (e) 'caught) (lambda () (grow '())))", "out of memory");
+        expect_condition!(interpreter, "(fill (make-hash) 0)", "out of memory");
+        expect_condition!(interpreter, "(spill (open-output-string))", "out of memory");
+
+        // the values of the failed evaluations are freed again
+        assert!(interpreter.memory_used().unwrap() < 10000);
+        expect_eval!(interpreter, "(+ 1 2)", "3");
+        // bindings count too, the global ones stay
+        expect_condition!(interpreter, "(bind-all 0)", "out of memory");
+        interpreter.remove_memory_limit();
+        expect_eval!(interpreter, "(vector-length (make-vector 1000000))", "1000000");
+        assert_eq!(interpreter.memory_used(), None);
+    }
+}
+
+#[test]
+fn sandbox() {
+    let interpreter = &mut Interpreter::builder().without_io().without_eval().build();
+    expect_condition!(interpreter, "(open-input-file \"Cargo.toml\")", "undefined ident: open-input-file");
+    expect_condition!(interpreter, "(load \"Cargo.toml\")", "undefined ident: load");
+    expect_condition!(interpreter, "(exit 1)", "undefined ident: exit");
+    expect_condition!(interpreter, "(getenv \"HOME\")", "undefined ident: getenv");
+    expect_condition!(interpreter, "(system \"true\")", "undefined ident: system");
+    expect_condition!(interpreter, "(delete-file \"Cargo.toml\")", "undefined ident: delete-file");
+    expect_condition!(interpreter, "(tcp-listen 0)", "undefined ident: tcp-listen");
+    expect_condition!(interpreter, "(eval '(+ 1 2))", "undefined ident: eval");
+    // modules defined in the script can still be imported, but none are loaded from files
+    expect_eval!(interpreter, "(define-module (m) (export x) (define x 1))", "(m)");
+    expect_eval!(interpreter, "(begin (import (m)) x)", "1");
+    expect_condition!(interpreter, "(import (cargo))", "import could not find module (cargo)");
+    expect_eval!(interpreter, "(map (lambda (x) (* x x)) '(1 2 3))", "(1 4 9)");
+
+    let interpreter = &mut Interpreter::builder().without_eval().build();
+    expect_eval!(interpreter, "(procedure? load)", "true");
+    expect_condition!(interpreter, "(interaction-environment)", "undefined ident: interaction-environment");
+}
+
+#[test]
+fn process() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(setenv \"FLIP_TEST_VARIABLE\" \"x=1\")", "()");
+    expect_eval!(interpreter, "(getenv \"FLIP_TEST_VARIABLE\")", "\"x=1\"");
+    expect_eval!(interpreter, "(begin (setenv \"FLIP_TEST_VARIABLE\" false) (getenv \"FLIP_TEST_VARIABLE\"))", "false");
+    expect_condition!(interpreter, "(setenv \"A=B\" \"1\")", "setenv invalid variable name: \"A=B\"");
+    expect_condition!(interpreter, "(setenv \"A\" 1)", "setenv expected string or false, got: 1");
+
+    if cfg!(unix) {
+        expect_eval!(interpreter, "(list (system \"true\") (system \"exit 3\"))", "(0 3)");
+        expect_eval!(interpreter, "(process-run \"echo\" \"hello\" \"flip\")", "(0 \"hello flip\\n\")");
+        expect_eval!(interpreter, "(car (process-run \"sh\" \"-c\" \"exit 2\"))", "2");
+        expect_condition!(interpreter, "(process-run \"flip-no-such-program\")",
+                          "process-run could not run \"flip-no-such-program\": No such file or directory (os error 2)");
+    }
+}
+
+#[test]
+fn files() {
+    use std::fs;
+
+    let interpreter = &mut Interpreter::new();
+    let dir = ::std::env::temp_dir().join("flip_files_test");
+    let _ = fs::remove_dir_all(&dir);
+    let dir = dir.to_str().unwrap().to_string();
+    expect_eval!(interpreter, &format!("(define dir \"{}\")", dir), "dir");
+    expect_eval!(interpreter, "(file-exists? dir)", "false");
+    expect_eval!(interpreter, "(create-directory (string-append dir \"/sub/inner\"))", "()");
+    expect_eval!(interpreter, "(create-directory dir)", "()");
+    expect_eval!(interpreter, "(call-with-output-file (string-append dir \"/a.txt\") (lambda (out) (display \"hello\" out)))", "()");
+    expect_eval!(interpreter, "(list (file-exists? dir) (file-size (string-append dir \"/a.txt\")) (directory-list dir))", "(true 5 (\"a.txt\" \"sub\"))");
+
+    expect_eval!(interpreter, "(delete-file (string-append dir \"/a.txt\"))", "()");
+    expect_eval!(interpreter, "(directory-list dir)", "(\"sub\")");
+    expect_condition!(interpreter, "(delete-file (string-append dir \"/a.txt\"))",
+                      &format!("delete-file failed for \"{}/a.txt\": No such file or directory (os error 2)", dir));
+
+    // changing the directory would affect the other tests
+    expect_eval!(interpreter, "(string? (current-directory))", "true");
+    expect_eval!(interpreter, "(set-current-directory! (current-directory))", "()");
+    expect_condition!(interpreter, "(file-size 1)", "file-size expected string, got: 1");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tcp() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    // a client in rust talking to a server in flip
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define listener (tcp-listen 0))", "listener");
+    let port = interpreter.eval_str("(tcp-listener-port listener)").unwrap().as_i64().unwrap() as u16;
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(b"ping\n").unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        line
+    });
+    expect_eval!(interpreter, "(define conn (tcp-accept listener))", "conn");
+    expect_eval!(interpreter, "(read-line conn)", "\"ping\"");
+    expect_eval!(interpreter, "(begin (display \"pong\" conn) (newline conn) (close-port conn))", "()");
+    assert_eq!(client.join().unwrap(), "pong\n");
+    expect_condition!(interpreter, "(tcp-accept (open-input-string \"\"))", "tcp-accept port string is not listening");
+
+    // and the other way around
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(stream.try_clone().unwrap()).read_line(&mut line).unwrap();
+        stream.write_all(line.to_uppercase().as_bytes()).unwrap();
+    });
+    expect_eval!(interpreter, &format!("(define conn (tcp-connect \"127.0.0.1\" {}))", port), "conn");
+    expect_eval!(interpreter, "(begin (display \"hello\\n\" conn) (read-line conn))", "\"HELLO\"");
+    server.join().unwrap();
+    // the server closed the connection
+    expect_eval!(interpreter, "(eof-object? (read-line conn))", "true");
+    expect_eval!(interpreter, "(close-port conn)", "()");
+
+    expect_condition!(interpreter, "(tcp-listen 70000)", "tcp-listen expected port number, got: 70000");
+    expect_condition!(interpreter, "(tcp-connect 'localhost 80)", "tcp-connect expected string, got: localhost");
+}
+
+#[test]
+fn interpreter_per_thread() {
+    let handles: Vec<_> = (0..4).map(|i| thread::spawn(move || {
+        let interpreter = &mut Interpreter::new();
+        interpreter.set_memory_limit(1 << 20);
+        interpreter.eval_str(&format!("(define n {})", i)).unwrap();
+        interpreter.eval_str("(define (count n acc) (if (= n 0) acc (count (- n 1) (cons n acc))))").unwrap();
+        let sum = interpreter.eval_str("(foldl + n (count 1000 '()))").unwrap().as_i64().unwrap();
+        (sum, interpreter.eval_str("n").unwrap().as_i64().unwrap())
+    })).collect();
+
+    let results: Vec<(i64, i64)> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+    assert_eq!(results, vec![(500500, 0), (500501, 1), (500502, 2), (500503, 3)]);
+}
+
+#[test]
+fn interpreter_moves_between_threads() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_memory_limit(1 << 20);
+    interpreter.eval_str("(define (cycle) (define (f) f) 'done)").unwrap();
+    interpreter.eval_str("(define runs '())").unwrap();
+
+    // like a pool of workers, each run happens on another thread
+    for i in 0..3 {
+        interpreter = thread::spawn(move || {
+            interpreter.eval_str(&format!("(set! runs (cons {} runs))", i)).unwrap();
+            // the cycle was registered with the heap of the interpreter, not the one of the thread
+            assert_eq!(interpreter.eval_str("(begin (cycle) (collect-garbage))").unwrap().as_i64(), Some(2));
+            interpreter
+        }).join().unwrap();
+    }
+    expect_eval!(&mut interpreter, "runs", "(2 1 0)");
+
+    // memory allocated on other threads is counted for the interpreter
+    let before = interpreter.memory_used().unwrap();
+    interpreter = thread::spawn(move || {
+        interpreter.eval_str("(define v (make-vector 10000))").unwrap();
+        interpreter
+    }).join().unwrap();
+    assert!(interpreter.memory_used().unwrap() > before + 10000);
+    expect_condition!(&mut interpreter, "(make-vector 1000000)", "out of memory");
+
+    // and freed there, whichever thread drops the value
+    let v = interpreter.eval_str("v").unwrap();
+    interpreter.eval_str("(set! v '())").unwrap();
+    thread::spawn(move || drop(v)).join().unwrap();
+    assert!(interpreter.memory_used().unwrap() < before + 10000);
+
+    // values can be handed to other threads too
+    let runs = interpreter.eval_str("runs").unwrap();
+    assert_eq!(thread::spawn(move || runs.get_list().unwrap().len()).join().unwrap(), 3);
+}
+
+#[test]
+fn json() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(value->json (list 1 #(2 3) \"s\" 'null))", "\"[1,[2,3],\\\"s\\\",null]\"");
+    expect_eval!(interpreter, "(json->value \"{\\\"a\\\": [1, 2.5, true]}\")", "#hash((\"a\" (1 2.5 true)))");
+    expect_condition!(interpreter, "(value->json #\\a)", "value->json can't convert: #\\a");
+    expect_condition!(interpreter, "(json->value \"[1 2]\")", "json->value could not parse: expected ',' at 3, got: '2'");
+}
+
+#[test]
+fn dump_scope() {
+    let interpreter = &mut Interpreter::new();
+    let path = ::std::env::temp_dir().join("flip_dump_scope_test.lisp");
+    let path = path.to_str().unwrap();
+
+    eval(interpreter, "(define l '(1 \"two\" #\\3 four 1/2 2.5))").unwrap();
+    eval(interpreter, "(define v #(1 (2)))").unwrap();
+    eval(interpreter, "(define h (make-hash '((a 1) (\"b\" (2)))))").unwrap();
+    eval(interpreter, "(define p '((1 . 2) 3 . 4))").unwrap();
+    eval(interpreter, "(define inf (list +inf.0 -inf.0))").unwrap();
+    eval(interpreter, "(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))").unwrap();
+    eval(interpreter, "(define (make-counter) (let ((n 0)) (lambda () (set! n (+ n 1)) n)))").unwrap();
+    // closures and environments can't be written
+    eval(interpreter, "(define counter (make-counter))").unwrap();
+    eval(interpreter, "(define env (interaction-environment))").unwrap();
+    expect_eval!(interpreter, &format!("(dump-scope \"{}\")", path), "7");
+
+    // the bindings are sorted by name, so v is the last one
+    let restored = &mut Interpreter::new();
+    expect_eval!(restored, &format!("(load-scope \"{}\")", path), "v");
+    expect_eval!(restored, "(list l v h)", "((1 \"two\" #\\3 four 1/2 2.5) #(1 (2)) #hash((\"b\" (2)) (a 1)))");
+    expect_eval!(restored, "(list p inf)", "(((1 . 2) 3 . 4) (+inf.0 -inf.0))");
+    expect_eval!(restored, "(fact 10)", "3628800");
+    expect_eval!(restored, "(let ((c (make-counter))) (c) (c))", "2");
+    expect_condition!(restored, "counter", "undefined ident: counter");
+    expect_condition!(restored, "env", "undefined ident: env");
+
+    // procedures are restored in the global scope
+    expect_eval!(restored, &format!("(let ((fact 1)) (load-scope \"{}\") fact)", path), "1");
+    expect_eval!(restored, "(fact 3)", "6");
+}
+
+#[test]
+fn string_ports() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define in (open-input-string \"first line\\n(1 2) x\\nz\"))", "in");
+    expect_eval!(interpreter, "in", "[INPUT_PORT: string]");
+    expect_eval!(interpreter, "(read-line in)", "\"first line\"");
+    expect_eval!(interpreter, "(list (read in) (read in))", "((1 2) x)");
+    expect_eval!(interpreter, "(read-char in)", "#\\z");
+    expect_eval!(interpreter, "(eof-object? (read-char in))", "true");
+    expect_eval!(interpreter, "(define in (open-input-string \"été\"))", "in");
+    expect_eval!(interpreter, "(list (read-char in) (read-line in))", "(#\\é \"té\")");
+
+    expect_eval!(interpreter, "(define out (open-output-string))", "out");
+    expect_eval!(interpreter, "(begin (display \"a\" out) (write \"b\" out) (newline out) (display 1 out))", "()");
+    expect_eval!(interpreter, "(get-output-string out)", "\"a\\\"b\\\"\\n1\"");
+    expect_condition!(interpreter, "(read-line out)", "read-line port string is not open for input");
+    expect_condition!(interpreter, "(get-output-string in)", "get-output-string port string is not an open output string port");
+    expect_eval!(interpreter, "(close-port out)", "()");
+    expect_condition!(interpreter, "(get-output-string out)", "get-output-string port string is not an open output string port");
+}
+
+#[test]
+fn format() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(format \"~a is ~s~%\" \"x\" \"x\")", "\"x is \\\"x\\\"\\n\"");
+    expect_eval!(interpreter, "(format false \"~d/~d = ~a ~~\" 1 2 1/2)", "\"1/2 = 1/2 ~\"");
+    expect_eval!(interpreter, "(format \"~a\" '(1 \"a\" #\\b))", "\"(1 a b)\"");
+    expect_eval!(interpreter, "(define out (open-output-string))", "out");
+    expect_eval!(interpreter, "(begin (format out \"~s~%\" 'x) (format out \"~a\" 2.5) (get-output-string out))", "\"x\\n2.5\"");
+
+    expect_condition!(interpreter, "(format \"~d\" 'x)", "format expected number for ~d, got: x");
+    expect_condition!(interpreter, "(format \"~a ~a\" 1)", "format is missing an argument for ~a");
+    expect_condition!(interpreter, "(format \"~a\" 1 2)", "format got more arguments than directives");
+    expect_condition!(interpreter, "(format \"~x\")", "format unknown directive: ~x");
+    expect_condition!(interpreter, "(format \"oops ~\")", "format template ends with ~");
+    expect_condition!(interpreter, "(format 1 \"~a\" 1)", "format expected port, got: 1");
+    expect_condition!(interpreter, "(format out)", "arity mismatch for format: expected: 2.., got: 1");
+}
+
+#[test]
+fn pp() {
+    let interpreter = &mut Interpreter::new();
+    expect_eval!(interpreter, "(define out (open-output-string))", "out");
+    expect_eval!(interpreter, "(begin (pp '(a b) out) (pp (make-vector 30 'abc) out) (get-output-string out))",
+                 &format!("\"(a b)\\n#(abc\\n{}  abc)\\n\"", "  abc\\n".repeat(28)));
+}
+
+#[test]
+fn lookup_caches() {
+    expect_same_with_vm(&[
+        "(define v 'global)",
+        "(define (read-v) v)",
+        "(list (read-v) (begin (set! v 'changed) (read-v)))",
+        "(define (sum a b) (+ a b))",
+        "(sum 1 2)",
+        "(define + -)",
+        "(sum 1 2)",
+        // define shadows the global after its place was cached
+        "(define (outer) (define (inner) v) (define before (inner)) (define v 'outer) (list before (inner)))",
+        "(outer)",
+    ]);
+}
+
+#[test]
+fn collect_garbage() {
+    let interpreter = &mut Interpreter::new();
+    eval(interpreter, "(collect-garbage)").unwrap();
+
+    // the scope of the call and the procedure bound in it refer to each other
+    eval(interpreter, "(define (cycle) (define (f) f) 'done)").unwrap();
+    expect_eval!(interpreter, "(begin (cycle) (collect-garbage))", "2");
+
+    // cycles that are still referenced survive
+    expect_eval!(interpreter, "(define l (list 1 2))", "l");
+    expect_eval!(interpreter, "(begin (set-cdr! (cdr l) l) (collect-garbage))", "0");
+    expect_eval!(interpreter, "(car (cdr (cdr l)))", "1");
+    // the pairs, small numbers aren't on the heap
+    expect_eval!(interpreter, "(begin (set! l 0) (collect-garbage))", "2");
+
+    eval(interpreter, "(define (make-counter) (let ((n 0)) (lambda () (set! n (+ n 1)) n)))").unwrap();
+    expect_eval!(interpreter, "(define counter (make-counter))", "counter");
+    expect_eval!(interpreter, "(begin (counter) (collect-garbage) (counter))", "2");
+
+    // collections also happen on their own
+    eval(interpreter, "(define (many n) (if (= n 0) 'done (begin (cycle) (many (- n 1)))))").unwrap();
+    expect_eval!(interpreter, "(many 30000)", "done");
+    expect_eval!(interpreter, "(< (collect-garbage) 20000)", "true");
+}
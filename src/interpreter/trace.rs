@@ -0,0 +1,48 @@
+// Tracing calls of procedures marked by trace, for debugging recursive code.
+//
+// A traced call writes its procedure and args to the current output port and its result when it returns,
+// indented by the number of traced calls it's nested in:
+// > (fact 2)
+//   > (fact 1)
+//   < 1
+// < 2
+// Traced procedures are called in their own trampoline to see their results, so calls of them aren't tail calls.
+
+use itertools::Itertools;
+use ::value::{Value, Condition};
+use ::native;
+use super::{Interpreter, Trampoline, Frame};
+
+impl Interpreter {
+    pub fn is_traced(&self, procedure: &Value) -> bool {
+        self.traced.iter().any(|x| x.eqv(procedure))
+    }
+
+    // func has to be a procedure written in flip
+    pub fn apply_traced(&mut self, func: &Value, args: Vec<Value>, call_site: Option<&Value>) -> Result<Trampoline, Condition> {
+        // safe because only procedures written in flip can be traced
+        let procedure = func.get_proc().unwrap();
+        let name = procedure.name().unwrap_or("lambda");
+        let call = format!("({}{})", name, args.iter().map(|x| format!(" {}", x.to_string(&self.interner))).join(""));
+        self.write_trace(&format!("> {}", call))?;
+
+        let scope = self.current_scope.clone();
+        let depth = self.trace_depth;
+        self.trace_depth += 1;
+        let res = self.with_frames(|interpreter| {
+            interpreter.call_stack.push(Frame { procedure: func.clone(), call_site: call_site.cloned() });
+            procedure.evaluate(interpreter, args).and_then(|next| interpreter.trampoline(next))
+        });
+        self.trace_depth = depth;
+        self.current_scope = scope;
+
+        let value = res?;
+        self.write_trace(&format!("< {}", value.to_string(&self.interner)))?;
+        Ok(Trampoline::Return(value))
+    }
+
+    fn write_trace(&mut self, line: &str) -> Result<(), Condition> {
+        let indent = "  ".repeat(self.trace_depth);
+        native::output("trace", &format!("{}{}\n", indent, line), None, self).map(|_| ())
+    }
+}
@@ -0,0 +1,253 @@
+use std::mem;
+use std::sync::Arc;
+use ::value::{Value, Condition};
+use ::scope::Scope;
+use super::{Interpreter, Trampoline, Frame};
+use super::compiler::{Chunk, Op};
+
+// A call of a compiled procedure that hasn't returned yet.
+struct VmFrame {
+    chunk: Arc<Chunk>,
+    ip: usize,
+    // the values below belong to the caller
+    stack_base: usize,
+    // the scopes to go back to after let bodies, see Op::SaveScope
+    saved_scopes: Vec<Scope>,
+    // the scope of the caller, None for the procedure run was called for
+    return_scope: Option<Scope>,
+}
+
+impl VmFrame {
+    fn new(chunk: Arc<Chunk>, stack_base: usize, return_scope: Option<Scope>) -> Self {
+        VmFrame { chunk: chunk, ip: 0, stack_base: stack_base, saved_scopes: vec![], return_scope: return_scope }
+    }
+}
+
+// Runs the body of a compiled procedure, its args are already bound in the current scope.
+//
+// Calls of other compiled procedures get a frame on the vm's own stack, so they neither recurse in rust
+// nor return to the interpreter. Everything else is left to the interpreter, the first frame returns
+// calls and forms in tail position as trampoline, like Proc::evaluate would.
+pub fn run(interpreter: &mut Interpreter, chunk: Arc<Chunk>) -> Result<Trampoline, Condition> {
+    let mut stack: Vec<Value> = vec![];
+    let mut frames = vec![VmFrame::new(chunk, 0, None)];
+
+    loop {
+        interpreter.check_limits()?;
+        let (op, chunk) = {
+            // safe because the loop returns once the first frame returns
+            let frame = frames.last_mut().unwrap();
+            frame.ip += 1;
+            (frame.chunk.code[frame.ip - 1], frame.chunk.clone())
+        };
+
+        match op {
+            Op::Const(i) => {
+                if interpreter.coverage.is_some() { interpreter.cover_quoted(&chunk.constants[i]); }
+                stack.push(chunk.constants[i].clone());
+            },
+            Op::Lookup(id, symbol, cache) => {
+                if interpreter.coverage.is_some() { interpreter.cover(&chunk.constants[symbol]); }
+                match interpreter.current_scope.lookup_cached(id, &chunk.caches[cache]) {
+                    Some(x) => stack.push(x),
+                    None => {
+                        let symbol = &chunk.constants[symbol];
+                        raise_condition!(format!("undefined ident: {}{}", symbol.to_string(&interpreter.interner), interpreter.locations.describe(symbol)));
+                    },
+                }
+            },
+            Op::LookupLocal(id, address, symbol) => {
+                if interpreter.coverage.is_some() { interpreter.cover(&chunk.constants[symbol]); }
+                match interpreter.current_scope.lookup_address(id, address) {
+                    Some(x) => stack.push(x),
+                    // e.g. the binding was shadowed by define, the address is only a shortcut
+                    None => match interpreter.current_scope.lookup_symbol(id) {
+                        Some(x) => stack.push(x),
+                        None => {
+                            let symbol = &chunk.constants[symbol];
+                            raise_condition!(format!("undefined ident: {}{}", symbol.to_string(&interpreter.interner), interpreter.locations.describe(symbol)));
+                        },
+                    },
+                }
+            },
+            Op::Define(id) => {
+                let value = pop(&mut stack);
+                interpreter.current_scope.add_symbol(id, value);
+                stack.push(Value::new_symbol(id));
+            },
+            Op::Set(id) => {
+                let value = pop(&mut stack);
+                interpreter.report_set(id, &value)?;
+                if !interpreter.current_scope.set_symbol(id, value) {
+                    let name = Value::new_symbol(id).to_string(&interpreter.interner);
+                    raise_condition!(format!("set! undefined ident: {}", name));
+                }
+                stack.push(Value::new_symbol(id));
+            },
+            Op::SetLocal(id, address) => {
+                let value = pop(&mut stack);
+                interpreter.report_set(id, &value)?;
+                if !interpreter.current_scope.set_address(id, address, &value) && !interpreter.current_scope.set_symbol(id, value) {
+                    let name = Value::new_symbol(id).to_string(&interpreter.interner);
+                    raise_condition!(format!("set! undefined ident: {}", name));
+                }
+                stack.push(Value::new_symbol(id));
+            },
+            Op::Pop => { pop(&mut stack); },
+            Op::Swap => {
+                let len = stack.len();
+                stack.swap(len - 1, len - 2);
+            },
+            Op::Jump(target) => jump(&mut frames, target),
+            Op::JumpUnless(target, form) => {
+                let value = pop(&mut stack);
+                match value.get_bool() {
+                    Some(true) => (),
+                    Some(false) => jump(&mut frames, target),
+                    None => raise_condition!(format!("{} expected bool, found: {}", form, value.to_string(&interpreter.interner))),
+                }
+            },
+            Op::ShortCircuit(short_circuit, target, form) => {
+                // safe because the operand was just pushed
+                match stack.last().unwrap().get_bool() {
+                    Some(b) if b == short_circuit => jump(&mut frames, target),
+                    Some(_) => { pop(&mut stack); },
+                    None => raise_condition!(format!("{} expected bool, found: {}", form, stack.last().unwrap().to_string(&interpreter.interner))),
+                }
+            },
+            Op::JumpIfFalse(target) => {
+                if stack.last().and_then(Value::get_bool) == Some(false) {
+                    pop(&mut stack);
+                    jump(&mut frames, target);
+                }
+            },
+            Op::JumpUnlessCase(data, target) => {
+                let key = stack.last().unwrap();
+                // safe because the compiler stores the data as list
+                if !chunk.constants[data].get_list().unwrap().iter().any(|datum| datum.eqv(key)) {
+                    jump(&mut frames, target);
+                }
+            },
+            Op::SaveScope => {
+                let scope = interpreter.current_scope.clone();
+                frames.last_mut().unwrap().saved_scopes.push(scope);
+            },
+            Op::NewScope => interpreter.current_scope = interpreter.current_scope.new_child(),
+            Op::Bind(id) => {
+                let value = pop(&mut stack);
+                interpreter.current_scope.bind(id, value);
+            },
+            Op::RestoreScope => {
+                // safe because the compiler emits SaveScope before
+                interpreter.current_scope = frames.last_mut().unwrap().saved_scopes.pop().unwrap();
+            },
+            Op::Call(argc, site) => {
+                let args = stack.split_off(stack.len() - argc);
+                let func = pop(&mut stack);
+                break_at_call(interpreter, &chunk.constants[site], &args);
+                let compiled = compiled(interpreter, &func);
+                match compiled {
+                    Some(callee) => {
+                        let scope = func.get_proc().unwrap().bind(args)?;
+                        let return_scope = mem::replace(&mut interpreter.current_scope, scope);
+                        interpreter.call_stack.push(Frame { procedure: func.clone(), call_site: Some(chunk.constants[site].clone()) });
+                        frames.push(VmFrame::new(callee, stack.len(), Some(return_scope)));
+                    },
+                    None => {
+                        let value = call(interpreter, &func, args)?;
+                        stack.push(value);
+                    },
+                }
+            },
+            Op::TailCall(argc, site) => {
+                let args = stack.split_off(stack.len() - argc);
+                let func = pop(&mut stack);
+                let site = chunk.constants[site].clone();
+                break_at_call(interpreter, &site, &args);
+                let compiled = compiled(interpreter, &func);
+                match compiled {
+                    // the frame of the caller is reused, so tail calls run in constant space
+                    Some(callee) => {
+                        interpreter.current_scope = func.get_proc().unwrap().bind(args)?;
+                        interpreter.call_stack.pop();
+                        interpreter.call_stack.push(Frame { procedure: func.clone(), call_site: Some(site) });
+                        let frame = frames.last_mut().unwrap();
+                        stack.truncate(frame.stack_base);
+                        frame.chunk = callee;
+                        frame.ip = 0;
+                        frame.saved_scopes.clear();
+                    },
+                    None if frames.len() == 1 => return interpreter.apply_at(&func, args, Some(&site)),
+                    None => {
+                        let value = call(interpreter, &func, args)?;
+                        if let Some(res) = return_from(&mut frames, &mut stack, interpreter, value) { return Ok(res); }
+                    },
+                }
+            },
+            Op::Return => {
+                let value = pop(&mut stack);
+                if let Some(res) = return_from(&mut frames, &mut stack, interpreter, value) { return Ok(res); }
+            },
+            Op::Eval(form) => {
+                let value = interpreter.evaluate(&chunk.constants[form])?;
+                stack.push(value);
+            },
+            Op::TailEval(form) => {
+                if frames.len() == 1 { return Ok(Trampoline::Eval(chunk.constants[form].clone())); }
+                let value = interpreter.evaluate(&chunk.constants[form])?;
+                if let Some(res) = return_from(&mut frames, &mut stack, interpreter, value) { return Ok(res); }
+            },
+        }
+    }
+}
+
+// calls procedures that aren't compiled, natives are called directly
+fn call(interpreter: &mut Interpreter, func: &Value, args: Vec<Value>) -> Result<Value, Condition> {
+    match func.get_native_fn_ptr() {
+        Some(f) => {
+            let mut args: Vec<Value> = args.into_iter().map(Value::new_quote).collect();
+            f(interpreter, &mut args)
+        },
+        None => interpreter.call_procedure(func, args),
+    }
+}
+
+// the bytecode of procedures that are called in the vm, traced procedures are called by apply_at to print their calls
+// and all of them while profiling
+fn compiled(interpreter: &Interpreter, func: &Value) -> Option<Arc<Chunk>> {
+    if interpreter.is_traced(func) || interpreter.is_profiling() { return None; }
+    func.get_proc().and_then(|p| p.compiled())
+}
+
+// calls the debugger before a call of a name with a breakpoint, with the evaluated args
+fn break_at_call(interpreter: &mut Interpreter, site: &Value, args: &[Value]) {
+    if interpreter.has_breakpoint(site) {
+        let call: Vec<Value> = Some(site.clone()).into_iter().chain(args.iter().cloned()).collect();
+        let reason = format!("breakpoint: {}", Value::new_list(&call).to_string(&interpreter.interner));
+        interpreter.debug_break(&reason);
+    }
+}
+
+// the compiler only emits instructions that pop what was pushed before
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("internal error: vm stack is empty")
+}
+
+fn jump(frames: &mut Vec<VmFrame>, target: usize) {
+    frames.last_mut().unwrap().ip = target;
+}
+
+// pops the current frame, returns the result of run if it was the first one
+fn return_from(frames: &mut Vec<VmFrame>, stack: &mut Vec<Value>, interpreter: &mut Interpreter, value: Value) -> Option<Trampoline> {
+    let frame = frames.pop().unwrap();
+    match frame.return_scope {
+        Some(scope) => {
+            stack.truncate(frame.stack_base);
+            stack.push(value);
+            interpreter.current_scope = scope;
+            interpreter.call_stack.pop();
+            None
+        },
+        None => Some(Trampoline::Return(value)),
+    }
+}
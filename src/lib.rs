@@ -0,0 +1,16 @@
+#![feature(conservative_impl_trait)]
+#[macro_use] extern crate itertools;
+
+extern crate siphasher;
+extern crate lalrpop_util;
+extern crate libc;
+
+#[macro_use]
+mod native;
+
+pub mod gc;
+pub mod grammar;
+pub mod value;
+pub mod interpreter;
+pub mod scope;
+pub mod string_interner;
@@ -1,38 +1,82 @@
-#![feature(conservative_impl_trait)]
 #[macro_use] extern crate clap;
-#[macro_use] extern crate itertools;
 
 extern crate rustyline;
-extern crate siphasher;
-extern crate lalrpop_util;
-
-#[macro_use]
-mod native;
+extern crate flip;
 
 mod cli;
-mod grammar;
-mod value;
-mod interpreter;
 mod repl;
-mod scope;
-mod string_interner;
 
-use std::io::Read;
-use ::value::Value;
+use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::Write;
+use std::process::exit;
+use flip::{interpreter, grammar, value};
 
 fn main() {
-    if let Some(mut file) = cli::get_args() {
-        let mut input = String::new();
-        file.read_to_string(&mut input).expect("Couldn't read file");
+    let options = cli::get_args();
+    if let Some(ref dir) = options.test {
+        let mut interpreter = interpreter::Interpreter::new();
+        interpreter.use_vm = options.use_vm;
+        if options.coverage.is_some() { interpreter.coverage = Some(interpreter::Coverage::new()); }
+
+        let passed = run_tests(&mut interpreter, dir);
+        if let Some(ref path) = options.coverage {
+            write_coverage(&interpreter, path);
+        }
+        exit(if passed { 0 } else { 1 });
+    } else if let Some(args) = options.script {
         let mut interpreter = interpreter::Interpreter::new();
-        let parsed = grammar::parse(&input, &mut interpreter.interner).unwrap();
+        interpreter.use_vm = options.use_vm;
+        let path = PathBuf::from(&args[0]);
+        interpreter.command_line = args;
+        if options.coverage.is_some() { interpreter.coverage = Some(interpreter::Coverage::new()); }
 
-        let mut result = Value::empty_list();
-        for x in &parsed {
-            result = interpreter.evaluate(x);
+        // the script can set another exit code with (exit n)
+        let res = interpreter.load(path);
+        if let Some(ref path) = options.coverage {
+            write_coverage(&interpreter, path);
+        }
+        if let Err(condition) = res {
+            println!("error: {}", condition.to_string_with_backtrace(&interpreter.interner));
+            exit(-1);
         }
-        println!("=> {}", result.to_string(&interpreter.interner))
     } else {
-        repl::Repl::start();
+        repl::Repl::start(options.use_vm);
+    }
+}
+
+fn write_coverage(interpreter: &interpreter::Interpreter, path: &str) {
+    // safe because coverage was started before the script
+    let lcov = interpreter.coverage.as_ref().unwrap().lcov(&interpreter.locations);
+    if let Err(err) = File::create(path).and_then(|mut file| file.write_all(lcov.as_bytes())) {
+        println!("could not write coverage to {}: {}", path, err);
+    }
+}
+
+// loads the .lisp files in dir sorted by name and runs the tests they define,
+// false if a test failed or a file couldn't be loaded
+fn run_tests(interpreter: &mut interpreter::Interpreter, dir: &str) -> bool {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path())
+                       .filter(|path| path.extension().map_or(false, |x| x == "lisp")).collect(),
+        Err(err) => {
+            println!("could not read {}: {}", dir, err);
+            return false;
+        },
+    };
+    paths.sort();
+
+    for path in paths {
+        if let Err(condition) = interpreter.load(path.clone()) {
+            println!("error in {}: {}", path.display(), condition.to_string_with_backtrace(&interpreter.interner));
+            return false;
+        }
+    }
+    match interpreter.eval_str("(run-tests)") {
+        Ok(failed) => failed.as_i64() == Some(0),
+        Err(err) => {
+            println!("error: {}", err.to_string(&interpreter.interner));
+            false
+        },
     }
 }
@@ -0,0 +1,90 @@
+use ::value::{Value, Condition, BigInt};
+use ::interpreter::Interpreter;
+use std::ops::{BitAnd, BitOr, BitXor};
+
+// Bitwise operations on integers in two's complement. and, or, xor and bit-count only work on
+// integers that fit into an i64, not and arithmetic-shift on integers of any size.
+
+fn try_unwrap_fixnum(name: &str, value: &Value, interpreter: &Interpreter) -> Result<i64, Condition> {
+    if let Some(i) = value.get_integer() { return Ok(i); }
+    if value.get_bigint().is_some() {
+        raise_condition!(format!("{} expected integer that fits into 64 bits, got: {}", name, value.to_string(&interpreter.interner)));
+    }
+    raise_condition!(Type, format!("{} expected integer, got: {}", name, value.to_string(&interpreter.interner)))
+}
+
+fn try_unwrap_bigint(name: &str, value: &Value, interpreter: &Interpreter) -> Result<BigInt, Condition> {
+    if let Some(i) = value.get_integer() { return Ok(BigInt::from(i)); }
+    Ok(try_unwrap_type!(name, "integer", Value::get_bigint, value, interpreter).clone())
+}
+
+macro_rules! bitwise_operator {
+    ($func:ident, $lisp_name:expr, $operator:path, $identity:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        let mut res = $identity;
+        for x in args.iter() {
+            res = $operator(res, try_unwrap_fixnum($lisp_name, x, interpreter)?);
+        }
+        Ok(Value::new_integer(res))
+    }););
+}
+
+bitwise_operator!(bitwise_and, "bitwise-and", BitAnd::bitand, -1);
+bitwise_operator!(bitwise_or, "bitwise-or", BitOr::bitor, 0);
+bitwise_operator!(bitwise_xor, "bitwise-xor", BitXor::bitxor, 0);
+
+// -n - 1, which works for bigints too
+eval_args!(fn bitwise_not(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("bitwise-not", args.len(), 1);
+    if let Some(i) = args[0].get_integer() { return Ok(Value::new_integer(!i)); }
+    let n = try_unwrap_bigint("bitwise-not", &args[0], interpreter)?;
+    Ok(Value::new_bigint(-n - BigInt::from(1)))
+});
+
+// by squaring, like expt
+fn power_of_two(exponent: u64) -> BigInt {
+    let (mut res, mut square, mut rest) = (BigInt::from(1), BigInt::from(2), exponent);
+    while rest > 0 {
+        if rest % 2 == 1 { res = res * square.clone(); }
+        rest /= 2;
+        if rest > 0 { square = square.clone() * square; }
+    }
+    res
+}
+
+// (arithmetic-shift n count), shifts left for a positive count and right for a negative one
+// shifting right rounds towards negative infinity, like for i64s
+eval_args!(fn arithmetic_shift(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("arithmetic-shift", args.len(), 2);
+    let count = try_unwrap_type!("arithmetic-shift", "integer", Value::get_integer, &args[1], interpreter);
+    if let Some(i) = args[0].get_integer() {
+        if count <= -64 { return Ok(Value::new_integer(if i < 0 { -1 } else { 0 })); }
+        if count < 0 { return Ok(Value::new_integer(i >> -count)); }
+        if let Some(x) = if count < 63 { i.checked_mul(1 << count) } else { None } {
+            return Ok(Value::new_integer(x));
+        }
+    }
+
+    let n = try_unwrap_bigint("arithmetic-shift", &args[0], interpreter)?;
+    if count >= 0 { return Ok(Value::new_bigint(n * power_of_two(count as u64))); }
+    let shift = count.wrapping_neg() as u64;
+    // everything is shifted out, this avoids computing huge powers of two
+    if n.to_f64().abs().log2() < shift as f64 {
+        return Ok(Value::new_integer(if n.is_negative() { -1 } else { 0 }));
+    }
+    // safe because powers of two aren't zero
+    let (quotient, remainder) = n.div_rem(&power_of_two(shift)).unwrap();
+    if remainder.is_negative() {
+        Ok(Value::new_bigint(quotient - BigInt::from(1)))
+    } else {
+        Ok(Value::new_bigint(quotient))
+    }
+});
+
+// the number of ones, or of zeros for negative integers
+eval_args!(fn bit_count(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("bit-count", args.len(), 1);
+    let i = try_unwrap_fixnum("bit-count", &args[0], interpreter)?;
+    let ones = if i < 0 { (!i).count_ones() } else { i.count_ones() };
+    Ok(Value::new_integer(ones as i64))
+});
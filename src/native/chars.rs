@@ -0,0 +1,83 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+
+// Char predicates, conversions and comparisons, the -ci comparisons ignore case
+
+macro_rules! char_predicate {
+    ($func:ident, $lisp_name:expr, $predicate:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        check_arity!($lisp_name, args.len(), 1);
+        let c = try_unwrap_type!($lisp_name, "char", Value::get_char, &args[0], interpreter);
+        Ok(Value::new_bool($predicate(c)))
+    }););
+}
+
+char_predicate!(char_alphabetic, "char-alphabetic?", char::is_alphabetic);
+char_predicate!(char_numeric, "char-numeric?", char::is_numeric);
+char_predicate!(char_whitespace, "char-whitespace?", char::is_whitespace);
+char_predicate!(char_upper_case, "char-upper-case?", char::is_uppercase);
+char_predicate!(char_lower_case, "char-lower-case?", char::is_lowercase);
+
+// chars that change into more than one char keep their case, e.g. ß
+fn upcase(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(x), None) => x,
+        _ => c,
+    }
+}
+
+fn downcase(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(x), None) => x,
+        _ => c,
+    }
+}
+
+eval_args!(fn char_upcase(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("char-upcase", args.len(), 1);
+    let c = try_unwrap_type!("char-upcase", "char", Value::get_char, &args[0], interpreter);
+    Ok(Value::new_char(upcase(c)))
+});
+
+eval_args!(fn char_downcase(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("char-downcase", args.len(), 1);
+    let c = try_unwrap_type!("char-downcase", "char", Value::get_char, &args[0], interpreter);
+    Ok(Value::new_char(downcase(c)))
+});
+
+// (digit-value c), the value of a decimal digit, false for other chars
+eval_args!(fn digit_value(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("digit-value", args.len(), 1);
+    let c = try_unwrap_type!("digit-value", "char", Value::get_char, &args[0], interpreter);
+    match c.to_digit(10) {
+        Some(x) => Ok(Value::new_integer(x as i64)),
+        None => Ok(Value::new_bool(false)),
+    }
+});
+
+// like the number comparisons, (char<? a b c) is true if a < b < c
+macro_rules! char_comparison {
+    ($func:ident, $lisp_name:expr, $operator:path, $fold:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        check_arity!($lisp_name, args.len(), min => 2);
+
+        let mut chars = Vec::with_capacity(args.len());
+        for x in args.iter() {
+            chars.push($fold(try_unwrap_type!($lisp_name, "char", Value::get_char, x, interpreter)));
+        }
+        Ok(Value::new_bool(chars.windows(2).all(|pair| $operator(&pair[0], &pair[1]))))
+    }););
+}
+
+char_comparison!(char_eq, "char=?", PartialEq::eq, |c| c);
+char_comparison!(char_lt, "char<?", PartialOrd::lt, |c| c);
+char_comparison!(char_le, "char<=?", PartialOrd::le, |c| c);
+char_comparison!(char_gt, "char>?", PartialOrd::gt, |c| c);
+char_comparison!(char_ge, "char>=?", PartialOrd::ge, |c| c);
+char_comparison!(char_ci_eq, "char-ci=?", PartialEq::eq, downcase);
+char_comparison!(char_ci_lt, "char-ci<?", PartialOrd::lt, downcase);
+char_comparison!(char_ci_le, "char-ci<=?", PartialOrd::le, downcase);
+char_comparison!(char_ci_gt, "char-ci>?", PartialOrd::gt, downcase);
+char_comparison!(char_ci_ge, "char-ci>=?", PartialOrd::ge, downcase);
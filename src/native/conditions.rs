@@ -0,0 +1,61 @@
+use ::value::{Value, Condition, ConditionKind};
+use ::interpreter::Interpreter;
+
+// Raising and handling conditions from flip code, guard is built on with-exception-handler
+
+// (raise obj), handlers get obj itself
+eval_args!(fn raise(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("raise", args.len(), 1);
+    Err(Condition::raise(args[0].clone()))
+});
+
+// (error message irritants*)
+eval_args!(fn error(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("error", args.len(), min => 1);
+    let message = try_unwrap_type!("error", "string", Value::get_string, &args[0], interpreter);
+    Err(Condition::error(message, args[1..].to_vec()))
+});
+
+// (with-exception-handler handler thunk), calls thunk and returns its result
+// if a condition is raised, the result of calling handler with it is returned instead
+eval_args!(fn with_exception_handler(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("with-exception-handler", args.len(), 2);
+    match interpreter.call_procedure(&args[1], vec![]) {
+        Err(ref condition) if condition.is_catchable() => interpreter.call_procedure(&args[0], vec![condition.to_value()]),
+        res => res,
+    }
+});
+
+eval_args!(fn error_object_(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("error-object?", args.len(), 1);
+    Ok(Value::new_bool(args[0].get_condition().is_some()))
+});
+
+eval_args!(fn error_object_message(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("error-object-message", args.len(), 1);
+    let condition = try_unwrap_type!("error-object-message", "error object", Value::get_condition, &args[0], interpreter);
+    Ok(Value::new_string(condition.message()))
+});
+
+eval_args!(fn error_object_irritants(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("error-object-irritants", args.len(), 1);
+    let condition = try_unwrap_type!("error-object-irritants", "error object", Value::get_condition, &args[0], interpreter);
+    Ok(Value::new_list(condition.irritants()))
+});
+
+// one of the symbols error, arity or type
+eval_args!(fn condition_kind(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("condition-kind", args.len(), 1);
+    let kind = try_unwrap_type!("condition-kind", "error object", Value::get_condition, &args[0], interpreter).kind();
+    Ok(Value::new_symbol(interpreter.interner.intern(kind.name())))
+});
+
+// (dynamic-wind before thunk after), after is called however thunk leaves the extent:
+// by returning, by raising a condition or by calling an escaping continuation, which are conditions too
+eval_args!(fn dynamic_wind(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("dynamic-wind", args.len(), 3);
+    interpreter.call_procedure(&args[0], vec![])?;
+    let res = interpreter.call_procedure(&args[1], vec![]);
+    interpreter.call_procedure(&args[2], vec![])?;
+    res
+});
@@ -0,0 +1,50 @@
+use ::value::{Value, Condition};
+use ::interpreter::{Interpreter, ResetContext};
+
+// Continuations, both kinds escape with conditions of kind Escape
+
+// (call/cc f), calls f with an escaping continuation
+// calling the continuation with x returns x from call/cc, it can't be called after call/cc returned
+eval_args!(fn call_cc(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("call/cc", args.len(), 1);
+    let continuation = Value::new_continuation();
+    let res = interpreter.call_procedure(&args[0], vec![continuation.clone()]);
+    // safe because continuation was created above
+    continuation.get_continuation().unwrap().deactivate();
+    match res {
+        Err(ref condition) if condition.continuation().map_or(false, |k| k.id() == continuation.id()) => {
+            Ok(condition.to_value())
+        },
+        res => res,
+    }
+});
+
+// (%reset f), (reset body*) is turned into this, with f being (lambda () body*)
+eval_args!(fn reset(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("reset", args.len(), 1);
+    interpreter.reset(args[0].clone(), vec![], vec![])
+});
+
+// (%shift f), (shift k body*) is turned into this, with f being (lambda (k) body*)
+// f is called with the continuation up to the enclosing reset, which returns the result of f
+eval_args!(fn shift(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("shift", args.len(), 1);
+    let context = match interpreter.resets.pop() {
+        Some(context) => context,
+        None => raise_condition!("shift outside of reset"),
+    };
+
+    // replaying for a call of a composable continuation
+    if context.cursor < context.log.len() {
+        let value = context.log[context.cursor].clone();
+        interpreter.resets.push(ResetContext { cursor: context.cursor + 1, ..context });
+        return Ok(value);
+    }
+
+    // f is called outside of the reset, but in a reset of its own
+    let k = Value::new_composable_continuation(context.func.clone(), context.args.clone(), context.log.clone());
+    let res = interpreter.reset(args[0].clone(), vec![k], vec![]);
+    let marker = context.marker.clone();
+    interpreter.resets.push(context);
+    Err(Condition::escape(marker, res?))
+});
@@ -0,0 +1,259 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+
+// Documentation, (define (f x) "doc" body) stores "doc" as the doc property of f, see Compiler::compile_define.
+// The natives are documented here instead, see Interpreter::documentation.
+
+// (%doc 'name), (doc name) is turned into this. The documentation of name or false if it has none.
+eval_args!(fn doc(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("doc", args.len(), 1);
+    let symbol = try_unwrap_type!("doc", "symbol", Value::get_symbol, &args[0], interpreter);
+    match interpreter.documentation(symbol) {
+        Some(doc) => Ok(Value::new_string(doc)),
+        None => Ok(Value::new_bool(false)),
+    }
+});
+
+// the documentation of every native bound by Interpreter::init, by name
+pub static NATIVE_DOCS: &'static [(&'static str, &'static str)] = &[
+    ("eq?", "(eq? x y), true if x and y are the same value"),
+    ("eqv?", "(eqv? x y), true if x and y are the same object, or equal numbers, chars or strings"),
+    ("equal?", "(equal? x y), true if x and y have the same structure, compares lists, vectors and strings element wise"),
+    ("not", "(not x), true if x is false"),
+
+    ("null?", "(null? x), true if x is the empty list"),
+    ("boolean?", "(boolean? x), true if x is true or false"),
+    ("symbol?", "(symbol? x), true if x is a symbol"),
+    ("keyword?", "(keyword? x), true if x is a keyword like #:name"),
+    ("integer?", "(integer? x), true if x is an integer"),
+    ("rational?", "(rational? x), true if x is an integer or a fraction"),
+    ("float?", "(float? x), true if x is a floating point number"),
+    ("number?", "(number? x), true if x is any kind of number"),
+    ("char?", "(char? x), true if x is a char"),
+    ("string?", "(string? x), true if x is a string"),
+    ("procedure?", "(procedure? x), true if x can be called, e.g. a lambda, a native or a parameter"),
+    ("list?", "(list? x), true if x is a proper list"),
+    ("pair?", "(pair? x), true if x is a pair"),
+    ("vector?", "(vector? x), true if x is a vector"),
+    ("hash?", "(hash? x), true if x is a hash table"),
+    ("port?", "(port? x), true if x is an input or output port"),
+    ("eof-object?", "(eof-object? x), true if x is the value read returns at the end of a port"),
+    ("environment?", "(environment? x), true if x is an environment, e.g. from interaction-environment"),
+    ("promise?", "(promise? x), true if x is a promise created by delay or make-promise"),
+    ("userdata?", "(userdata? x), true if x is a value of the host program"),
+
+    ("char->integer", "(char->integer char), the unicode code point of char"),
+    ("integer->char", "(integer->char n), the char with the unicode code point n"),
+    ("number->string", "(number->string n [radix]), n written as string in radix 2, 8, 10 or 16, floats only in 10"),
+    ("string->number", "(string->number string [radix]), the number string is written as in radix, unless it has a prefix like #x, raises a condition if it isn't one"),
+    ("symbol->string", "(symbol->string symbol), the name of symbol"),
+    ("string->symbol", "(string->symbol string), the interned symbol named string"),
+    ("gensym", "(gensym [prefix]), a new uninterned symbol, its name starts with prefix"),
+    ("string->uninterned-symbol", "(string->uninterned-symbol string), a new symbol named string that isn't eq? to any other"),
+    ("symbol-interned?", "(symbol-interned? symbol), false for symbols from gensym and string->uninterned-symbol"),
+    ("symbol-put!", "(symbol-put! symbol key value), sets the property key of symbol, returns value"),
+    ("symbol-get", "(symbol-get symbol key [default]), the property key of symbol, default or false if it isn't set"),
+    ("symbol-remove!", "(symbol-remove! symbol key), removes the property key of symbol, true if it was set"),
+    ("symbol-properties", "(symbol-properties symbol), the properties of symbol as alist"),
+    ("keyword->string", "(keyword->string keyword), the name of keyword without #:"),
+    ("string->keyword", "(string->keyword string), the keyword named string"),
+
+    ("+", "(+ n*), the sum of the numbers, 0 without any"),
+    ("-", "(- n n*), the first number minus the others, or its negation if it's the only one"),
+    ("*", "(* n*), the product of the numbers, 1 without any"),
+    ("/", "(/ n n*), the first number divided by the others, integers are divided exactly"),
+    ("quotient", "(quotient n m), n divided by m, truncated towards zero"),
+    ("remainder", "(remainder n m), the remainder of quotient, it has the sign of n"),
+    ("modulo", "(modulo n m), n modulo m, it has the sign of m"),
+    ("abs", "(abs n), the absolute value of n"),
+    ("min", "(min n n*), the smallest of the numbers"),
+    ("max", "(max n n*), the largest of the numbers"),
+    ("gcd", "(gcd n*), the greatest common divisor of the integers, 0 without any"),
+    ("lcm", "(lcm n*), the least common multiple of the integers, 1 without any"),
+    ("expt", "(expt base exponent), base to the power of exponent"),
+    ("exact->inexact", "(exact->inexact n), n as floating point number"),
+    ("floor", "(floor n), the largest integer not larger than n"),
+    ("ceiling", "(ceiling n), the smallest integer not smaller than n"),
+    ("truncate", "(truncate n), n without its fractional part"),
+    ("round", "(round n), the integer closest to n, halves are rounded to even"),
+    ("sqrt", "(sqrt n), the square root of n, exact for perfect squares"),
+    ("exp", "(exp n), e to the power of n"),
+    ("log", "(log n), the natural logarithm of n"),
+    ("sin", "(sin n), the sine of n radians"),
+    ("cos", "(cos n), the cosine of n radians"),
+    ("tan", "(tan n), the tangent of n radians"),
+    ("atan", "(atan y [x]), the arc tangent of y, or the angle of the point (x, y)"),
+    ("bitwise-and", "(bitwise-and n*), the bits set in all integers, -1 without any"),
+    ("bitwise-or", "(bitwise-or n*), the bits set in any of the integers, 0 without any"),
+    ("bitwise-xor", "(bitwise-xor n*), the bits set in an odd number of the integers, 0 without any"),
+    ("bitwise-not", "(bitwise-not n), n with all bits flipped"),
+    ("arithmetic-shift", "(arithmetic-shift n count), n shifted left by count bits, right if count is negative"),
+    ("bit-count", "(bit-count n), the number of set bits of n, of the unset ones if n is negative"),
+    ("random", "(random [bound]), an integer or float from 0 below bound, a float from 0 below 1 without bound"),
+    ("random-seed!", "(random-seed! n), seeds the generator of random, the same seed gives the same numbers"),
+    ("current-seconds", "(current-seconds), the seconds since the unix epoch"),
+    ("current-milliseconds", "(current-milliseconds), the milliseconds since the unix epoch"),
+    ("runtime", "(runtime), the seconds since the interpreter was created, as float"),
+
+    ("=", "(= n n*), true if all numbers are equal"),
+    (">", "(> n n*), true if the numbers are decreasing"),
+    (">=", "(>= n n*), true if the numbers are not increasing"),
+    ("<", "(< n n*), true if the numbers are increasing"),
+    ("<=", "(<= n n*), true if the numbers are not decreasing"),
+
+    ("apply", "(apply f x* list), calls f with the xs and the elements of list as args"),
+    ("list", "(list x*), a new list of the xs"),
+    ("first", "(first list), the first element of list"),
+    ("rest", "(rest list), list without its first element"),
+    ("cons", "(cons x y), a new pair of x and y"),
+    ("car", "(car pair), the first element of pair"),
+    ("cdr", "(cdr pair), the second element of pair"),
+    ("set-car!", "(set-car! pair x), sets the first element of pair"),
+    ("set-cdr!", "(set-cdr! pair x), sets the second element of pair"),
+    ("map", "(map f list list*), a list of the results of calling f with the elements of the lists at each index"),
+    ("for-each", "(for-each f list list*), calls f with the elements of the lists at each index"),
+    ("filter", "(filter predicate list), the elements of list predicate returns true for"),
+    ("foldl", "(foldl f init list), combines the elements with (f x acc) from the left, starting with init"),
+    ("foldr", "(foldr f init list), combines the elements with (f x acc) from the right, starting with init"),
+    ("sort", "(sort sequence less), a sorted copy of a list or vector, elements that are equal keep their order"),
+    ("sort!", "(sort! vector less), sorts vector in place and returns it"),
+
+    ("make-vector", "(make-vector n [fill]), a vector of n elements, all fill, which defaults to 0"),
+    ("vector", "(vector x*), a new vector of the xs"),
+    ("vector-ref", "(vector-ref vector index), the element at index"),
+    ("vector-set!", "(vector-set! vector index x), sets the element at index"),
+    ("vector-length", "(vector-length vector), the number of elements of vector"),
+    ("vector->list", "(vector->list vector), a list of the elements of vector"),
+    ("list->vector", "(list->vector list), a vector of the elements of list"),
+
+    ("make-hash", "(make-hash), a new empty hash table"),
+    ("hash-ref", "(hash-ref hash key [default]), the value of key, a missing key without default raises a condition"),
+    ("hash-set!", "(hash-set! hash key value), sets the value of key"),
+    ("hash-remove!", "(hash-remove! hash key), removes key and its value"),
+    ("hash-keys", "(hash-keys hash), a list of the keys of hash"),
+    ("hash-count", "(hash-count hash), the number of keys of hash"),
+
+    ("open-input-string", "(open-input-string string), a port that reads from string"),
+    ("open-output-string", "(open-output-string), a port that collects what is written to it, see get-output-string"),
+    ("get-output-string", "(get-output-string port), everything written to the string port so far"),
+    ("read-line", "(read-line [port]), the next line without its line ending, or the eof object"),
+    ("read-char", "(read-char [port]), the next char, or the eof object"),
+    ("current-output-port", "(current-output-port), the port write and display use by default, a parameter"),
+    ("write", "(write x [port]), writes x so that read gives it back"),
+    ("write-shared", "(write-shared x [port]), like write, but everything that occurs more than once is labeled like #0=(a) #0#"),
+    ("display", "(display x [port]), writes x for humans, strings and chars without quotes"),
+    ("newline", "(newline [port]), writes a line ending"),
+    ("format", "(format [dest] template x*), template with ~a replaced by displayed and ~s by written xs, written to dest if it's true or a port"),
+    ("pp", "(pp x [port]), writes x pretty printed on multiple lines"),
+    ("read", "(read [port]), the next datum, or the eof object"),
+    ("read-string", "(read-string string), the first datum written in string, or the eof object"),
+    ("close-port", "(close-port port), closes port and flushes it if it's an output port"),
+
+    ("char-alphabetic?", "(char-alphabetic? char), true if char is a letter"),
+    ("char-numeric?", "(char-numeric? char), true if char is a digit"),
+    ("char-whitespace?", "(char-whitespace? char), true if char is whitespace"),
+    ("char-upper-case?", "(char-upper-case? char), true if char is an upper case letter"),
+    ("char-lower-case?", "(char-lower-case? char), true if char is a lower case letter"),
+    ("char-upcase", "(char-upcase char), the upper case of char"),
+    ("char-downcase", "(char-downcase char), the lower case of char"),
+    ("digit-value", "(digit-value char), the value of the digit char or false"),
+    ("char=?", "(char=? char char*), true if all chars are equal"),
+    ("char<?", "(char<? char char*), true if the chars are increasing"),
+    ("char<=?", "(char<=? char char*), true if the chars are not decreasing"),
+    ("char>?", "(char>? char char*), true if the chars are decreasing"),
+    ("char>=?", "(char>=? char char*), true if the chars are not increasing"),
+    ("char-ci=?", "(char-ci=? char char*), char=? ignoring case"),
+    ("char-ci<?", "(char-ci<? char char*), char<? ignoring case"),
+    ("char-ci<=?", "(char-ci<=? char char*), char<=? ignoring case"),
+    ("char-ci>?", "(char-ci>? char char*), char>? ignoring case"),
+    ("char-ci>=?", "(char-ci>=? char char*), char>=? ignoring case"),
+
+    ("string-length", "(string-length string), the number of chars of string"),
+    ("string-ref", "(string-ref string index), the char at index"),
+    ("substring", "(substring string start [end]), the chars from start below end, end defaults to the length"),
+    ("string-append", "(string-append string*), the strings joined"),
+    ("string-upcase", "(string-upcase string), string in upper case"),
+    ("string-downcase", "(string-downcase string), string in lower case"),
+    ("string-split", "(string-split string [separator]), a list of the parts between the separators, without one string is split at whitespace"),
+    ("string-join", "(string-join list [separator]), the strings of list joined with separator between them, a space by default"),
+    ("string-contains?", "(string-contains? string part), true if part is in string"),
+    ("string", "(string char*), a string of the chars"),
+    ("make-string", "(make-string n [char]), a string of n chars, spaces if char is left out"),
+    ("string-copy", "(string-copy string), a new string with the chars of string"),
+    ("string->list", "(string->list string), a list of the chars of string"),
+    ("list->string", "(list->string list), a string of the chars of list"),
+
+    ("raise", "(raise x), raises x as condition, handlers of with-exception-handler and guard get it"),
+    ("error", "(error message irritant*), raises an error object with message and the irritants"),
+    ("with-exception-handler", "(with-exception-handler handler thunk), calls thunk, handler is called with the conditions raised in it"),
+    ("error-object?", "(error-object? x), true if x is a condition raised by error or by a native"),
+    ("error-object-message", "(error-object-message condition), the message of condition"),
+    ("error-object-irritants", "(error-object-irritants condition), the irritants given to error"),
+    ("condition-kind", "(condition-kind condition), one of the symbols error, arity or type"),
+    ("call/cc", "(call/cc f), calls f with the current continuation, calling it returns from call/cc"),
+    ("values", "(values x*), returns all xs, see call-with-values and let-values"),
+    ("call-with-values", "(call-with-values producer consumer), calls consumer with the values producer returns"),
+    ("call-with-current-continuation", "(call-with-current-continuation f), the same as call/cc"),
+    ("dynamic-wind", "(dynamic-wind before thunk after), calls before, thunk and after, after also if thunk is left by a condition or continuation"),
+    ("make-parameter", "(make-parameter value [converter]), a parameter bound to value, see parameterize"),
+    ("force", "(force promise), the value of promise, its expression is evaluated the first time"),
+    ("make-promise", "(make-promise x), a promise that is already forced to x"),
+    ("value->json", "(value->json x), x as json string, hash tables are objects and vectors arrays"),
+    ("json->value", "(json->value string), the value of the json in string"),
+    ("userdata-type", "(userdata-type x), the type name of the host value x"),
+    ("call-method", "(call-method x 'name arg*), calls the method name of the host value x with the args"),
+    ("collect-garbage", "(collect-garbage), frees the values only referenced by cycles"),
+    ("set-backtrace-limit!", "(set-backtrace-limit! n), the maximum number of frames in backtraces"),
+    ("symbol-space", "(symbol-space), a list of the symbols bound in the current scope"),
+    ("environment-bindings", "(environment-bindings [depth]), the bindings of the current scope as alist, only of the innermost depth scopes if it's given"),
+    ("procedure-name", "(procedure-name f), the name of f as symbol, false for anonymous procedures"),
+    ("procedure-arity", "(procedure-arity f), the number of args f takes, (n . true) if it takes n or more, false if it isn't known"),
+    ("procedure-source", "(procedure-source f), the lambda expression that created f as string, false if f isn't written in flip"),
+    ("trace", "(trace f+), writes the calls of the procedures with their args and results, indented by how deeply they're nested"),
+    ("untrace", "(untrace f*), stops tracing the procedures, or all of them without any"),
+    ("break", "(break [message]), stops in the debugger if there is one, like the repl's in :debug mode"),
+    ("set-breakpoint", "(set-breakpoint 'name+), calls of the names stop in the debugger"),
+    ("remove-breakpoint", "(remove-breakpoint 'name+), calls of the names don't stop anymore"),
+    ("watch", "(watch 'name+), set! of the names writes their old and new values"),
+    ("unwatch", "(unwatch 'name+), stops writing the set!s of the names"),
+    ("profile-start", "(profile-start), starts counting the calls of procedures and the time spent in them"),
+    ("profile-report", "(profile-report [port]), stops profiling and writes the calls and inclusive and exclusive times of the procedures, the slowest first"),
+    ("coverage-start", "(coverage-start), starts tracking which lines of code are evaluated"),
+    ("coverage-report", "(coverage-report [port]), writes how many lines of each source were evaluated since coverage-start and which weren't"),
+    ("coverage-lcov", "(coverage-lcov [port]), writes the coverage since coverage-start in the lcov format"),
+    ("assert-equal", "(assert-equal expected actual [message]), raises a condition unless expected and actual are equal?"),
+    ("assert-true", "(assert-true x [message]), raises a condition if x is false"),
+    ("run-tests", "(run-tests [port]), runs the tests defined by define-test, writes their results and returns the number of failed tests"),
+    ("check-property", "(check-property prop gen+), calls prop with random values of the generators, raises a condition with the simplest failing values if it returns false or raises one"),
+    ("gen-integer", "(gen-integer [lo hi]), a generator of integers from lo to hi, from -1000 to 1000 by default"),
+    ("gen-boolean", "(gen-boolean), a generator of true and false"),
+    ("gen-list", "(gen-list gen [max-length]), a generator of lists of values of gen with at most max-length elements, 10 by default"),
+    ("gen-one-of", "(gen-one-of x+), a generator of the xs"),
+    ("describe", "(describe x [port]), writes the type of x and what is known about it, e.g. the arity and documentation of procedures"),
+    ("open-input-file", "(open-input-file path), a port that reads the file at path"),
+    ("open-output-file", "(open-output-file path), a port that writes the file at path, it's created or truncated"),
+    ("call-with-input-file", "(call-with-input-file path f), calls f with a port reading the file, it's closed afterwards"),
+    ("call-with-output-file", "(call-with-output-file path f), calls f with a port writing the file, it's closed afterwards"),
+    ("load", "(load path), evaluates all forms in the file in the current scope and returns the last result"),
+    ("command-line", "(command-line), a list of the script and its arguments"),
+    ("exit", "(exit [code]), ends the process, with code 0 if it's left out"),
+    ("dump-scope", "(dump-scope path), writes the bindings of the global scope defined by the program to the file, returns how many"),
+    ("load-scope", "(load-scope path), evaluates a file written by dump-scope in the global scope"),
+    ("getenv", "(getenv name), the value of the environment variable or false"),
+    ("setenv", "(setenv name value), sets the environment variable"),
+    ("system", "(system command), runs command with the shell, returns its exit code or false if it was killed"),
+    ("process-run", "(process-run program arg*), runs program without a shell, returns (exit-code output)"),
+    ("file-exists?", "(file-exists? path), true if there's a file or directory at path"),
+    ("delete-file", "(delete-file path), deletes the file at path"),
+    ("directory-list", "(directory-list path), the sorted names of the entries of the directory"),
+    ("create-directory", "(create-directory path), creates the directory and its missing parents"),
+    ("file-size", "(file-size path), the size of the file in bytes"),
+    ("current-directory", "(current-directory), the working directory of the process"),
+    ("set-current-directory!", "(set-current-directory! path), changes the working directory of the process"),
+    ("tcp-connect", "(tcp-connect host port), a port for reading from and writing to the connection"),
+    ("tcp-listen", "(tcp-listen port [host]), a listener for connections to port, host defaults to \"127.0.0.1\" and 0 picks a free port"),
+    ("tcp-accept", "(tcp-accept listener), waits for a connection and returns a port for it"),
+    ("tcp-listener-port", "(tcp-listener-port listener), the port number listener accepts connections on"),
+    ("eval", "(eval datum [environment]), evaluates datum, in the global scope by default"),
+    ("interaction-environment", "(interaction-environment), the global scope as environment for eval"),
+];
@@ -0,0 +1,76 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// File management, only installed if the interpreter has io, see Builder::without_io.
+// Relative paths are resolved against the current directory of the process.
+
+macro_rules! try_fs {
+    ($fn_name:expr, $path:expr, $result:expr) => ({
+        match $result {
+            Ok(x) => x,
+            Err(err) => raise_condition!(format!("{} failed for {:?}: {}", $fn_name, $path, err)),
+        }
+    });
+}
+
+// (file-exists? path), true for directories too
+eval_args!(fn file_exists(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("file-exists?", args.len(), 1);
+    let path = try_unwrap_type!("file-exists?", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_bool(Path::new(path).exists()))
+});
+
+// (delete-file path), directories can't be deleted
+eval_args!(fn delete_file(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("delete-file", args.len(), 1);
+    let path = try_unwrap_type!("delete-file", "string", Value::get_string, &args[0], interpreter);
+    try_fs!("delete-file", path, fs::remove_file(path));
+    Ok(Value::empty_list())
+});
+
+// (directory-list path), the sorted names of the entries, without . and ..
+eval_args!(fn directory_list(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("directory-list", args.len(), 1);
+    let path = try_unwrap_type!("directory-list", "string", Value::get_string, &args[0], interpreter);
+    let mut names = vec![];
+    for entry in try_fs!("directory-list", path, fs::read_dir(path)) {
+        let entry = try_fs!("directory-list", path, entry);
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    let names: Vec<Value> = names.into_iter().map(Value::new_string).collect();
+    Ok(Value::new_list(&names))
+});
+
+// (create-directory path), missing parents are created too, existing directories are fine
+eval_args!(fn create_directory(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("create-directory", args.len(), 1);
+    let path = try_unwrap_type!("create-directory", "string", Value::get_string, &args[0], interpreter);
+    try_fs!("create-directory", path, fs::create_dir_all(path));
+    Ok(Value::empty_list())
+});
+
+// (file-size path) in bytes
+eval_args!(fn file_size(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("file-size", args.len(), 1);
+    let path = try_unwrap_type!("file-size", "string", Value::get_string, &args[0], interpreter);
+    let metadata = try_fs!("file-size", path, fs::metadata(path));
+    Ok(Value::new_integer(metadata.len() as i64))
+});
+
+eval_args!(fn current_directory(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("current-directory", args.len(), 0);
+    let dir = try_fs!("current-directory", ".", env::current_dir());
+    Ok(Value::new_string(dir.to_string_lossy().into_owned()))
+});
+
+// (set-current-directory! path), this changes the directory of the whole process, not only of the interpreter
+eval_args!(fn set_current_directory(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("set-current-directory!", args.len(), 1);
+    let path = try_unwrap_type!("set-current-directory!", "string", Value::get_string, &args[0], interpreter);
+    try_fs!("set-current-directory!", path, env::set_current_dir(path));
+    Ok(Value::empty_list())
+});
@@ -0,0 +1,189 @@
+use ::value::{Value, Condition, Userdata};
+use ::interpreter::{Interpreter, Random};
+
+// Property based testing, (check-property prop gen+) calls prop with random values of the generators.
+// Generators are userdata of type generator, they use the generator of random, so random-seed! repeats a run.
+// Failing values are shrunk to simpler ones that still fail: integers towards 0, lists to fewer and simpler elements.
+
+// the number of times check-property calls a property that holds
+const TRIALS: usize = 100;
+// the number of simpler values tried at most when shrinking
+const MAX_SHRINKS: usize = 1000;
+
+#[derive(Debug, Clone)]
+enum Generator {
+    // from lo to hi, both included
+    Integer(i64, i64),
+    Boolean,
+    // of the element generator, with at most the given number of elements
+    List(Box<Generator>, usize),
+    // one of the values, the first ones are the simplest
+    OneOf(Vec<Value>),
+}
+
+impl Generator {
+    fn generate(&self, random: &mut Random) -> Value {
+        match *self {
+            Generator::Integer(lo, hi) => {
+                let span = (hi as u64).wrapping_sub(lo as u64).wrapping_add(1);
+                // 0 if the range contains every i64
+                let offset = if span == 0 { random.next_u64() } else { random.below(span) };
+                Value::new_integer((lo as u64).wrapping_add(offset) as i64)
+            },
+            Generator::Boolean => Value::new_bool(random.below(2) == 1),
+            Generator::List(ref element, max_length) => {
+                let length = random.below(max_length as u64 + 1) as usize;
+                let elements: Vec<Value> = (0..length).map(|_| element.generate(random)).collect();
+                Value::new_list(&elements)
+            },
+            Generator::OneOf(ref choices) => choices[random.below(choices.len() as u64) as usize].clone(),
+        }
+    }
+
+    // values simpler than x, the simplest first
+    fn shrink(&self, x: &Value) -> Vec<Value> {
+        match *self {
+            Generator::Integer(lo, hi) => match x.get_integer() {
+                Some(n) => shrink_integer(n, lo, hi).into_iter().map(Value::new_integer).collect(),
+                None => vec![],
+            },
+            Generator::Boolean if x.get_bool() == Some(true) => vec![Value::new_bool(false)],
+            Generator::Boolean => vec![],
+            Generator::List(ref element, _) => {
+                let elements = match x.get_list() {
+                    Some(ref elements) if !elements.is_empty() => elements.clone(),
+                    _ => return vec![],
+                };
+                let mut candidates = vec![Value::empty_list()];
+                if elements.len() > 1 {
+                    for i in 0..elements.len() {
+                        let mut fewer = elements.clone();
+                        fewer.remove(i);
+                        candidates.push(Value::new_list(&fewer));
+                    }
+                }
+                for i in 0..elements.len() {
+                    for simpler in element.shrink(&elements[i]) {
+                        let mut shrunk = elements.clone();
+                        shrunk[i] = simpler;
+                        candidates.push(Value::new_list(&shrunk));
+                    }
+                }
+                candidates
+            },
+            Generator::OneOf(ref choices) => match choices.iter().position(|choice| choice.equal(x)) {
+                Some(i) => choices[..i].to_vec(),
+                None => vec![],
+            },
+        }
+    }
+}
+
+// the integer in lo..hi closest to 0 first, then halfway between it and n and so on up to n - 1
+fn shrink_integer(n: i64, lo: i64, hi: i64) -> Vec<i64> {
+    let target = if lo > 0 { lo } else if hi < 0 { hi } else { 0 };
+    if n == target { return vec![]; }
+    let mut candidates = vec![target];
+    // halving both doesn't overflow
+    let mut distance = n / 2 - target / 2;
+    while distance != 0 {
+        if n - distance != target { candidates.push(n - distance); }
+        distance /= 2;
+    }
+    candidates
+}
+
+fn new_generator(generator: Generator) -> Value {
+    Value::new_userdata(Userdata::new(generator).with_type_name("generator"))
+}
+
+fn get_generator(name: &str, value: &Value, interpreter: &Interpreter) -> Result<Generator, Condition> {
+    match value.get_userdata().and_then(|x| x.downcast_ref::<Generator>()) {
+        Some(generator) => Ok(generator.clone()),
+        None => raise_condition!(Type, format!("{} expected generator, got: {}", name, value.to_string(&interpreter.interner))),
+    }
+}
+
+// (gen-integer [lo hi]), integers from lo to hi, from -1000 to 1000 without bounds
+eval_args!(fn gen_integer(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    let (lo, hi) = match args.len() {
+        0 => (-1000, 1000),
+        2 => (try_unwrap_type!("gen-integer", "integer", Value::get_integer, &args[0], interpreter),
+              try_unwrap_type!("gen-integer", "integer", Value::get_integer, &args[1], interpreter)),
+        n => raise_condition!(Arity, format!("arity mismatch for gen-integer: expected: 0 or 2, got: {}", n)),
+    };
+    if lo > hi { raise_condition!(format!("gen-integer expected lo <= hi, got: {} {}", lo, hi)); }
+    Ok(new_generator(Generator::Integer(lo, hi)))
+});
+
+// (gen-boolean), true or false
+eval_args!(fn gen_boolean(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("gen-boolean", args.len(), 0);
+    Ok(new_generator(Generator::Boolean))
+});
+
+// (gen-list gen [max-length]), lists of values of gen with at most max-length elements, 10 by default
+eval_args!(fn gen_list(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("gen-list", args.len(), 1, 2);
+    let element = get_generator("gen-list", &args[0], interpreter)?;
+    let max_length = match args.get(1) {
+        Some(x) => try_unwrap_index!("gen-list", x, interpreter),
+        None => 10,
+    };
+    Ok(new_generator(Generator::List(Box::new(element), max_length)))
+});
+
+// (gen-one-of x+), one of the xs, they're shrunk towards the first one
+eval_args!(fn gen_one_of(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("gen-one-of", args.len(), min => 1);
+    Ok(new_generator(Generator::OneOf(args.to_vec())))
+});
+
+// None if prop holds for args, otherwise why it doesn't
+fn falsify(interpreter: &mut Interpreter, prop: &Value, args: &[Value]) -> Result<Option<String>, Condition> {
+    match interpreter.call_procedure(prop, args.to_vec()) {
+        Ok(ref x) if x.get_bool() == Some(false) => Ok(Some("returned false".to_string())),
+        Ok(_) => Ok(None),
+        Err(ref condition) if condition.is_catchable() => Ok(Some(format!("raised: {}", condition.to_string(&interpreter.interner)))),
+        Err(condition) => Err(condition),
+    }
+}
+
+// tries the simpler values of each arg and keeps the first that still fails, until none of them does
+fn shrink(interpreter: &mut Interpreter, prop: &Value, generators: &[Generator], mut args: Vec<Value>, mut failure: String)
+-> Result<(Vec<Value>, String), Condition> {
+    let mut tried = 0;
+    'shrinking: while tried < MAX_SHRINKS {
+        for i in 0..args.len() {
+            for simpler in generators[i].shrink(&args[i]) {
+                tried += 1;
+                let mut shrunk = args.clone();
+                shrunk[i] = simpler;
+                if let Some(reason) = falsify(interpreter, prop, &shrunk)? {
+                    args = shrunk;
+                    failure = reason;
+                    continue 'shrinking;
+                }
+            }
+        }
+        break;
+    }
+    Ok((args, failure))
+}
+
+// (check-property prop gen+), calls prop with values of the generators, returns true if it never returned false
+// or raised a condition. Otherwise a condition with the shrunk args is raised.
+eval_args!(fn check_property(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("check-property", args.len(), min => 2);
+    let prop = args[0].clone();
+    let generators = args[1..].iter().map(|x| get_generator("check-property", x, interpreter)).collect::<Result<Vec<_>, _>>()?;
+
+    for _ in 0..TRIALS {
+        let values: Vec<Value> = generators.iter().map(|generator| generator.generate(&mut interpreter.random)).collect();
+        if let Some(failure) = falsify(interpreter, &prop, &values)? {
+            let (values, failure) = shrink(interpreter, &prop, &generators, values, failure)?;
+            raise_condition!(format!("check-property failed for {}: {}", Value::new_list(&values).to_string(&interpreter.interner), failure));
+        }
+    }
+    Ok(Value::new_bool(true))
+});
@@ -0,0 +1,86 @@
+use ::value::{Value, Condition, HashTable, HashKey};
+use ::interpreter::Interpreter;
+use ::gc;
+
+// Hash table operations, like vectors hash tables are mutable
+
+macro_rules! try_unwrap_key {
+    ($fn_name:expr, $value:expr, $interpreter:expr) => ({
+        match HashKey::from_value($value) {
+            Some(key) => key,
+            None => {
+                let s = format!("{} expected hashable key, got: {}", $fn_name, $value.to_string(&$interpreter.interner));
+                raise_condition!(s);
+            }
+        }
+    });
+}
+
+// (make-hash [entries]), entries is a list of (key value) lists
+eval_args!(fn make_hash(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("make-hash", args.len(), 0, 1);
+    let mut table = HashTable::new();
+    if let Some(entries) = args.get(0) {
+        let entries = try_unwrap_type!("make-hash", "list", Value::get_list, entries, interpreter);
+        for entry in entries.iter() {
+            match entry.get_list() {
+                Some(ref pair) if pair.len() == 2 => {
+                    let key = try_unwrap_key!("make-hash", &pair[0], interpreter);
+                    table.insert(key, pair[0].clone(), pair[1].clone());
+                },
+                _ => raise_condition!(format!("make-hash expected (key value), got: {}", entry.to_string(&interpreter.interner))),
+            }
+        }
+    }
+    Ok(Value::new_hash_table(table))
+});
+
+// (hash-ref table key [default]), without a default a missing key raises a condition
+eval_args!(fn hash_ref(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("hash-ref", args.len(), 2, 3);
+    let table = try_unwrap_type!("hash-ref", "hash table", Value::get_hash_table, &args[0], interpreter);
+    let key = try_unwrap_key!("hash-ref", &args[1], interpreter);
+    let value = table.read().unwrap().get(&key).cloned();
+    match (value, args.get(2)) {
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => Ok(default.clone()),
+        (None, None) => raise_condition!(format!("hash-ref key not found: {}", args[1].to_string(&interpreter.interner))),
+    }
+});
+
+// returns the table
+eval_args!(fn hash_set(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("hash-set!", args.len(), 3);
+    {
+        let table = try_unwrap_type!("hash-set!", "hash table", Value::get_hash_table, &args[0], interpreter);
+        let key = try_unwrap_key!("hash-set!", &args[1], interpreter);
+        table.write().unwrap().insert(key, args[1].clone(), args[2].clone());
+    }
+    gc::register_value(&args[0]);
+    Ok(args[0].clone())
+});
+
+// returns the table, removing a missing key does nothing
+eval_args!(fn hash_remove(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("hash-remove!", args.len(), 2);
+    {
+        let table = try_unwrap_type!("hash-remove!", "hash table", Value::get_hash_table, &args[0], interpreter);
+        let key = try_unwrap_key!("hash-remove!", &args[1], interpreter);
+        table.write().unwrap().remove(&key);
+    }
+    Ok(args[0].clone())
+});
+
+// in no particular order
+eval_args!(fn hash_keys(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("hash-keys", args.len(), 1);
+    let table = try_unwrap_type!("hash-keys", "hash table", Value::get_hash_table, &args[0], interpreter);
+    let keys: Vec<Value> = table.read().unwrap().entries().map(|(key, _)| key.clone()).collect();
+    Ok(Value::new_list(&keys))
+});
+
+eval_args!(fn hash_count(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("hash-count", args.len(), 1);
+    let table = try_unwrap_type!("hash-count", "hash table", Value::get_hash_table, &args[0], interpreter);
+    Ok(Value::new_integer(table.read().unwrap().len() as i64))
+});
@@ -0,0 +1,135 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use std::cmp;
+
+// Higher order list operations, procedures are called with Interpreter::call_procedure
+
+// unwraps all lists after the procedure, for procedures taking multiple lists like map
+fn unwrap_lists(name: &str, args: &[Value], interpreter: &Interpreter) -> Result<Vec<Vec<Value>>, Condition> {
+    let mut lists = Vec::with_capacity(args.len());
+    for x in args {
+        lists.push(try_unwrap_type!(name, "list", Value::get_list, x, interpreter));
+    }
+    Ok(lists)
+}
+
+// the i-th elements of all lists, the shortest list determines the number of calls
+fn nth_elements(lists: &[Vec<Value>], i: usize) -> Vec<Value> {
+    lists.iter().map(|list| list[i].clone()).collect()
+}
+
+fn shortest(lists: &[Vec<Value>]) -> usize {
+    lists.iter().map(Vec::len).min().unwrap_or(0)
+}
+
+// (map f list+)
+eval_args!(fn map(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("map", args.len(), min => 2);
+    let lists = unwrap_lists("map", &args[1..], interpreter)?;
+
+    let mut res = Vec::with_capacity(shortest(&lists));
+    for i in 0..shortest(&lists) {
+        res.push(interpreter.call_procedure(&args[0], nth_elements(&lists, i))?);
+    }
+    Ok(Value::new_list(&res))
+});
+
+// (for-each f list+), like map, but only for side effects
+eval_args!(fn for_each(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("for-each", args.len(), min => 2);
+    let lists = unwrap_lists("for-each", &args[1..], interpreter)?;
+
+    for i in 0..shortest(&lists) {
+        interpreter.call_procedure(&args[0], nth_elements(&lists, i))?;
+    }
+    Ok(Value::empty_list())
+});
+
+// (filter predicate list)
+eval_args!(fn filter(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("filter", args.len(), 2);
+    let list = try_unwrap_type!("filter", "list", Value::get_list, &args[1], interpreter);
+
+    let mut res = vec![];
+    for x in list {
+        let keep = interpreter.call_procedure(&args[0], vec![x.clone()])?;
+        if try_unwrap_type!("filter", "bool", Value::get_bool, &keep, interpreter) {
+            res.push(x);
+        }
+    }
+    Ok(Value::new_list(&res))
+});
+
+// (foldl f init list), calls (f element accumulator) from left to right
+eval_args!(fn foldl(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("foldl", args.len(), 3);
+    let list = try_unwrap_type!("foldl", "list", Value::get_list, &args[2], interpreter);
+
+    let mut acc = args[1].clone();
+    for x in list {
+        acc = interpreter.call_procedure(&args[0], vec![x, acc])?;
+    }
+    Ok(acc)
+});
+
+// (foldr f init list), calls (f element accumulator) from right to left
+eval_args!(fn foldr(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("foldr", args.len(), 3);
+    let list = try_unwrap_type!("foldr", "list", Value::get_list, &args[2], interpreter);
+
+    let mut acc = args[1].clone();
+    for x in list.into_iter().rev() {
+        acc = interpreter.call_procedure(&args[0], vec![x, acc])?;
+    }
+    Ok(acc)
+});
+
+// Stable merge sort, (less a b) must return a bool. Runs of width 1, 2, 4 ... are merged bottom up.
+fn merge_sort(name: &str, mut values: Vec<Value>, less: &Value, interpreter: &mut Interpreter) -> Result<Vec<Value>, Condition> {
+    let mut width = 1;
+    while width < values.len() {
+        let mut merged = Vec::with_capacity(values.len());
+        for chunk in values.chunks(2 * width) {
+            let (left, right) = chunk.split_at(cmp::min(width, chunk.len()));
+            let (mut i, mut j) = (0, 0);
+            while i < left.len() && j < right.len() {
+                // the right element goes first only if it's strictly less, so equal elements keep their order
+                let res = interpreter.call_procedure(less, vec![right[j].clone(), left[i].clone()])?;
+                if try_unwrap_type!(name, "bool", Value::get_bool, &res, interpreter) {
+                    merged.push(right[j].clone());
+                    j += 1;
+                } else {
+                    merged.push(left[i].clone());
+                    i += 1;
+                }
+            }
+            merged.extend_from_slice(&left[i..]);
+            merged.extend_from_slice(&right[j..]);
+        }
+        values = merged;
+        width *= 2;
+    }
+    Ok(values)
+}
+
+// (sort sequence less), a sorted copy of a list or vector
+eval_args!(fn sort(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("sort", args.len(), 2);
+    if let Some(vector) = args[0].get_vector() {
+        let values = vector.read().unwrap().clone();
+        return Ok(Value::new_vector(merge_sort("sort", values, &args[1], interpreter)?));
+    }
+    let list = try_unwrap_type!("sort", "list or vector", Value::get_list, &args[0], interpreter);
+    Ok(Value::new_list(&merge_sort("sort", list, &args[1], interpreter)?))
+});
+
+// (sort! vector less), sorts the vector in place and returns it
+eval_args!(fn sort_in_place(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("sort!", args.len(), 2);
+    // less could change the vector, so the elements are sorted outside of it
+    let values = try_unwrap_type!("sort!", "vector", Value::get_vector, &args[0], interpreter).read().unwrap().clone();
+    let sorted = merge_sort("sort!", values, &args[1], interpreter)?;
+    // safe because it was checked above
+    *args[0].get_vector().unwrap().write().unwrap() = sorted;
+    Ok(args[0].clone())
+});
@@ -0,0 +1,111 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use super::ports::output;
+
+// Introspection of procedures and other values, for interactive development
+
+// natives don't know their names, they're named by the global binding they came from
+fn name_of(value: &Value, interpreter: &Interpreter) -> Option<String> {
+    if let Some(procedure) = value.get_proc() { return procedure.name().map(String::from); }
+    if let Some(procedure) = value.get_host_proc() { return Some(procedure.name().to_string()); }
+    if value.get_native_fn_ptr().is_none() { return None; }
+
+    let scope = &interpreter.global_scope;
+    let mut names: Vec<&str> = scope.symbol_ids().into_iter()
+    .filter(|&id| scope.lookup_symbol(id).map_or(false, |x| x.eqv(value)))
+    .filter_map(|id| interpreter.interner.lookup(id))
+    .collect();
+    // sorted, so names bound to the same native are picked the same way every time
+    names.sort();
+    names.first().map(|x| x.to_string())
+}
+
+// the number of args and if more are allowed, None if it isn't known
+fn arity_of(value: &Value) -> Option<(usize, bool)> {
+    if let Some(procedure) = value.get_proc() { return Some(procedure.arity()); }
+    if value.get_parameter().is_some() { return Some((0, false)); }
+    None
+}
+
+fn expect_procedure(name: &str, value: &Value, interpreter: &Interpreter) -> Result<(), Condition> {
+    if !value.is_procedure() {
+        raise_condition!(Type, format!("{} expected procedure, got: {}", name, value.to_string(&interpreter.interner)));
+    }
+    Ok(())
+}
+
+// (procedure-name f), the name of f as symbol, false for anonymous procedures
+eval_args!(fn procedure_name(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("procedure-name", args.len(), 1);
+    expect_procedure("procedure-name", &args[0], interpreter)?;
+    match name_of(&args[0], interpreter) {
+        Some(name) => Ok(Value::new_symbol(interpreter.interner.intern(name))),
+        None => Ok(Value::new_bool(false)),
+    }
+});
+
+// (procedure-arity f), the number of args f takes, (n . true) if it takes n or more, false if it isn't known like for natives
+eval_args!(fn procedure_arity(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("procedure-arity", args.len(), 1);
+    expect_procedure("procedure-arity", &args[0], interpreter)?;
+    match arity_of(&args[0]) {
+        Some((n, false)) => Ok(Value::new_integer(n as i64)),
+        Some((n, true)) => Ok(Value::new_pair(Value::new_integer(n as i64), Value::new_bool(true))),
+        None => Ok(Value::new_bool(false)),
+    }
+});
+
+// (procedure-source f), the lambda expression that created f with its body as it was compiled, false if f isn't written in flip
+eval_args!(fn procedure_source(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("procedure-source", args.len(), 1);
+    expect_procedure("procedure-source", &args[0], interpreter)?;
+    match args[0].get_proc() {
+        Some(procedure) => Ok(Value::new_string(procedure.source(&interpreter.interner))),
+        None => Ok(Value::new_bool(false)),
+    }
+});
+
+// (describe x [port]), writes what x is, its type and what is known about it, e.g. the arity and documentation of procedures
+eval_args!(fn describe(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("describe", args.len(), 1, 2);
+    let x = &args[0];
+    let mut lines = vec![
+        format!("value: {}", x.to_string(&interpreter.interner)),
+        format!("type: {}", x.type_name()),
+    ];
+
+    if let Some(elements) = x.get_list() {
+        if !elements.is_empty() { lines.push(format!("length: {}", elements.len())); }
+    }
+    if let Some(s) = x.get_string() { lines.push(format!("length: {}", s.chars().count())); }
+    if let Some(elements) = x.get_vector() { lines.push(format!("length: {}", elements.read().unwrap().len())); }
+    if let Some(table) = x.get_hash_table() { lines.push(format!("count: {}", table.read().unwrap().len())); }
+    if let Some(record) = x.get_record() { lines.push(format!("record type: {}", record.record_type().to_string(&interpreter.interner))); }
+    if let Some(condition) = x.get_condition() { lines.push(format!("kind: {}", condition.kind().name())); }
+    if x.get_parameter().is_some() {
+        lines.push(format!("bound to: {}", interpreter.parameter_value(x).to_string(&interpreter.interner)));
+    }
+    if let Some(id) = x.get_symbol() {
+        if !interpreter.interner.is_interned(id) { lines.push("uninterned".to_string()); }
+        if let Some(doc) = interpreter.documentation(id) { lines.push(format!("doc: {}", doc)); }
+    }
+
+    if x.is_procedure() {
+        let name = name_of(x, interpreter);
+        if let Some(ref name) = name { lines.push(format!("name: {}", name)); }
+        match arity_of(x) {
+            Some((n, false)) => lines.push(format!("arity: {}", n)),
+            Some((n, true)) => lines.push(format!("arity: {}..", n)),
+            None => {},
+        }
+        if let Some(procedure) = x.get_proc() { lines.push(format!("source: {}", procedure.source(&interpreter.interner))); }
+        if let Some(name) = name {
+            let id = interpreter.interner.intern(name);
+            if let Some(doc) = interpreter.documentation(id) { lines.push(format!("doc: {}", doc)); }
+        }
+    }
+
+    let mut s = lines.join("\n");
+    s.push('\n');
+    output("describe", &s, args.get(1), interpreter)
+});
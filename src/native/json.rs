@@ -0,0 +1,21 @@
+use ::value::{self, Value, Condition};
+use ::interpreter::Interpreter;
+
+// JSON conversions, see value::to_json for how values are mapped
+
+eval_args!(fn value_json(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("value->json", args.len(), 1);
+    match value::to_json(&args[0], &interpreter.interner) {
+        Ok(json) => Ok(Value::new_string(json)),
+        Err(message) => raise_condition!(format!("value->json {}", message)),
+    }
+});
+
+eval_args!(fn json_value(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("json->value", args.len(), 1);
+    let json = try_unwrap_type!("json->value", "string", Value::get_string, &args[0], interpreter).to_string();
+    match value::from_json(&json, &mut interpreter.interner) {
+        Ok(x) => Ok(x),
+        Err(message) => raise_condition!(format!("json->value could not parse: {}", message)),
+    }
+});
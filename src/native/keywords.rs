@@ -0,0 +1,67 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+
+// Keywords, #:name evaluates to itself, they name the keyword parameters of procedures
+
+eval_args!(fn keyword_(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("keyword?", args.len(), 1);
+    Ok(Value::new_bool(args[0].get_keyword().is_some()))
+});
+
+// (keyword->string #:name) gives you "name"
+eval_args!(fn keyword_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("keyword->string", args.len(), 1);
+    let id = try_unwrap_type!("keyword->string", "keyword", Value::get_keyword, &args[0], interpreter);
+    match interpreter.interner.lookup(id) {
+        Some(name) => Ok(Value::new_string(name)),
+        None => raise_condition!("internal error: invalid keyword"),
+    }
+});
+
+eval_args!(fn string_keyword(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string->keyword", args.len(), 1);
+    let string = try_unwrap_type!("string->keyword", "string", Value::get_string, &args[0], interpreter);
+    let id = interpreter.interner.intern(string);
+    Ok(Value::new_keyword(id))
+});
+
+// (%lambda-arguments 'name args required optional '(keywords)), procedures with #:optional or #:key parameters
+// get all their args as rest, see Compiler::compile_optional_parameters.
+// Returns a vector with the required args, followed by an element per optional and keyword parameter,
+// (value) if it was passed and () if not. Optional parameters are filled from the front, until a keyword is reached
+// if there are keyword parameters, so a wrong keyword isn't taken for an optional arg.
+eval_args!(fn lambda_arguments(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("lambda-arguments", args.len(), 5);
+    // safe because the args are created by the compiler
+    let name = args[0].to_string(&interpreter.interner);
+    let passed = args[1].get_list().unwrap();
+    let required = args[2].get_integer().unwrap() as usize;
+    let optional = args[3].get_integer().unwrap() as usize;
+    let keywords: Vec<u64> = args[4].get_list().unwrap().iter().map(|x| x.get_keyword().unwrap()).collect();
+
+    if keywords.is_empty() {
+        check_arity!(name, passed.len(), required as u32, (required + optional) as u32);
+    } else {
+        check_arity!(name, passed.len(), min => required as u32);
+    }
+
+    let mut values = passed[..required].to_vec();
+    values.extend(vec![Value::empty_list(); optional + keywords.len()]);
+    let rest = &passed[required..];
+    let positional = rest.iter().take(optional).take_while(|x| keywords.is_empty() || x.get_keyword().is_none()).count();
+    for (i, x) in rest[..positional].iter().enumerate() {
+        values[required + i] = Value::new_list(&[x.clone()]);
+    }
+
+    for pair in rest[positional..].chunks(2) {
+        let index = match pair[0].get_keyword().and_then(|id| keywords.iter().position(|&x| x == id)) {
+            Some(i) => required + optional + i,
+            None => raise_condition!(format!("{} expected keyword argument, got: {}", name, pair[0].to_string(&interpreter.interner))),
+        };
+        match pair.get(1) {
+            Some(x) => values[index] = Value::new_list(&[x.clone()]),
+            None => raise_condition!(format!("{} missing value for keyword argument: {}", name, pair[0].to_string(&interpreter.interner))),
+        }
+    }
+    Ok(Value::new_vector(values))
+});
@@ -6,15 +6,18 @@ macro_rules! check_arity {
     ($name:expr, $len:expr, $exact:expr) => ({
         let len = $len as u32;
         if len != $exact {
-            raise_condition!(format!("arity mismatch for {}: expected: {}, got: {}", $name, $exact, len));
+            raise_condition!(Arity, format!("arity mismatch for {}: expected: {}, got: {}", $name, $exact, len));
         }
     });
 
     // range
     ($name:expr, $len:expr, $lo:expr, $hi:expr) => ({
         let len = $len as u32;
-        if len < $lo || len > $hi {
-            raise_condition!(format!("arity mismatch for {}: expected: {}..{}, got: {}", $name, $lo, $hi, len));
+        // $lo is 0 for optional arguments
+        #[allow(unused_comparisons)]
+        let out_of_range = len < $lo || len > $hi;
+        if out_of_range {
+            raise_condition!(Arity, format!("arity mismatch for {}: expected: {}..{}, got: {}", $name, $lo, $hi, len));
         }
     });
 
@@ -22,12 +25,12 @@ macro_rules! check_arity {
     ($name:expr, $len:expr, min => $min:expr) => ({
         let len = $len as u32;
         if len < $min {
-            raise_condition!(format!("arity mismatch for {}: expected: {}.., got: {}", $name, $min, len));
+            raise_condition!(Arity, format!("arity mismatch for {}: expected: {}.., got: {}", $name, $min, len));
         }
     });
 }
 
-// this automates type checking. If the expected type is not found, a condition is returned early
+// this automates type checking. If the expected type is not found, a condition is raised
 // name: functions name
 // type_name: name of type, eg. "string" or "list"
 // unwrap_fn: a fn taking a value and returning Option<T>, where T is the rust type that is expected from the value.
@@ -39,7 +42,20 @@ macro_rules! try_unwrap_type {
             Some(x) => x,
             None => {
                 let s = format!("{} expected {}, got: {}", $fn_name, $type_name, $value.to_string(&$interpreter.interner));
-                return Value::new_condition(Value::new_string(s));
+                raise_condition!(Type, s);
+            }
+        }
+    });
+}
+
+// unwraps a non negative integer that can be used as index into strings and vectors
+macro_rules! try_unwrap_index {
+    ($fn_name:expr, $value:expr, $interpreter:expr) => ({
+        match $value.get_integer() {
+            Some(i) if i >= 0 => i as usize,
+            _ => {
+                let s = format!("{} expected index, got: {}", $fn_name, $value.to_string(&$interpreter.interner));
+                raise_condition!(Type, s);
             }
         }
     });
@@ -47,14 +63,22 @@ macro_rules! try_unwrap_type {
 
 macro_rules! new_condition {
     ($msg:expr) => (
-        Value::new_condition(Value::new_string($msg))
-    )
+        new_condition!(Error, $msg)
+    );
+    // kind is a variant of ConditionKind
+    ($kind:ident, $msg:expr) => (
+        ::value::Condition::new(::value::ConditionKind::$kind, $msg)
+    );
 }
 
+// returns early with Err(condition)
 macro_rules! raise_condition {
     ($msg:expr) => (
-        return new_condition!($msg);
-    )
+        return Err(new_condition!($msg));
+    );
+    ($kind:ident, $msg:expr) => (
+        return Err(new_condition!($kind, $msg));
+    );
 }
 
 macro_rules! assert_or_condition {
@@ -74,7 +98,7 @@ macro_rules! eval_args {
         pub fn $func(interpreter: &mut Interpreter, args: $arg_ty) -> $ret_ty {
             fn inner($args: $arg_ty) -> $ret_ty $blk;
             for x in args.iter_mut() {
-                *x = interpreter.evaluate(x);
+                *x = interpreter.evaluate(x)?;
             }
             inner(args)
         }
@@ -86,7 +110,7 @@ macro_rules! eval_args {
         pub fn $func(interpreter: &mut Interpreter, args: $arg_ty) -> $ret_ty {
             fn inner($interpreter: &mut Interpreter, $args: $arg_ty) -> $ret_ty $blk;
             for x in args.iter_mut() {
-                *x = interpreter.evaluate(x);
+                *x = interpreter.evaluate(x)?;
             }
             inner(interpreter, args)
         }
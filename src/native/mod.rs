@@ -3,3 +3,84 @@ mod macros;
 
 mod primitive_forms;
 pub use self::primitive_forms::*;
+
+mod strings;
+pub use self::strings::*;
+
+mod vectors;
+pub use self::vectors::*;
+
+mod hash_tables;
+pub use self::hash_tables::*;
+
+mod higher_order;
+pub use self::higher_order::*;
+
+mod ports;
+pub use self::ports::*;
+
+mod conditions;
+pub use self::conditions::*;
+
+mod continuations;
+pub use self::continuations::*;
+
+mod values;
+pub use self::values::*;
+
+mod parameters;
+pub use self::parameters::*;
+
+mod properties;
+pub use self::properties::*;
+
+mod docs;
+pub use self::docs::*;
+
+mod introspection;
+pub use self::introspection::*;
+
+mod trace;
+pub use self::trace::*;
+
+mod testing;
+pub use self::testing::*;
+
+mod generators;
+pub use self::generators::*;
+
+mod promises;
+pub use self::promises::*;
+
+mod records;
+pub use self::records::*;
+
+mod keywords;
+pub use self::keywords::*;
+
+mod chars;
+pub use self::chars::*;
+
+mod modules;
+pub use self::modules::*;
+
+mod userdata;
+pub use self::userdata::*;
+
+mod json;
+pub use self::json::*;
+
+mod bitwise;
+pub use self::bitwise::*;
+
+mod random;
+pub use self::random::*;
+
+mod time;
+pub use self::time::*;
+
+mod process;
+pub use self::process::*;
+
+mod files;
+pub use self::files::*;
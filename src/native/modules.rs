@@ -0,0 +1,88 @@
+use std::mem;
+use std::path::{Path, PathBuf};
+use ::value::{Value, Condition};
+use ::interpreter::{Interpreter, Module};
+
+// Modules, see define-module in docs/special_forms.md
+
+// the written name of a module, e.g. "(my lib)", names are non empty lists of symbols
+fn module_name(name: &str, datum: &Value, interpreter: &Interpreter) -> Result<String, Condition> {
+    let valid = datum.get_list().map_or(false, |list| !list.is_empty() && list.iter().all(|x| x.get_symbol().is_some()));
+    assert_or_condition!(valid, format!("{} expected module name, got: {}", name, datum.to_string(&interpreter.interner)));
+    Ok(datum.to_string(&interpreter.interner))
+}
+
+// the file (my lib) is loaded from when it's imported before it's defined, relative to the file being loaded
+fn module_path(datum: &Value, interpreter: &Interpreter) -> PathBuf {
+    // safe because module_name checked datum
+    let parts: Vec<String> = datum.get_list().unwrap().iter().map(|x| x.to_string(&interpreter.interner)).collect();
+    let path = PathBuf::from(format!("{}.lisp", parts.join("/")));
+    match interpreter.loading.last() {
+        Some(file) => file.parent().unwrap_or(Path::new("")).join(path),
+        None => path,
+    }
+}
+
+// (%define-module 'name body*), (define-module name body*) is turned into this
+// the body is evaluated in a new scope, which only sees the global scope, the args aren't evaluated up front
+pub fn define_module(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("define-module", args.len(), min => 1);
+    let datum = interpreter.evaluate(&args[0])?;
+    let name = module_name("define-module", &datum, interpreter)?;
+
+    let scope = interpreter.global_scope.new_child();
+    let old_scope = mem::replace(&mut interpreter.current_scope, scope.clone());
+    interpreter.exports.push(vec![]);
+    let mut res = Ok(Value::empty_list());
+    for x in &args[1..] {
+        res = interpreter.evaluate(x);
+        if res.is_err() { break; }
+    }
+    let exports = interpreter.exports.pop().unwrap_or_default();
+    interpreter.current_scope = old_scope;
+    res?;
+
+    for &id in &exports {
+        if scope.lookup_symbol(id).is_none() {
+            let export = interpreter.interner.lookup(id).unwrap_or("?");
+            raise_condition!(format!("module {} exports undefined ident: {}", name, export));
+        }
+    }
+    interpreter.modules.insert(name, Module { scope: scope, exports: exports });
+    Ok(datum)
+}
+
+// (%export 'name*), (export name*) is turned into this
+eval_args!(fn export(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    let mut names = Vec::with_capacity(args.len());
+    for x in args.iter() {
+        names.push(try_unwrap_type!("export", "symbol", Value::get_symbol, x, interpreter));
+    }
+    match interpreter.exports.last_mut() {
+        Some(exports) => exports.extend(names),
+        None => raise_condition!("export outside of define-module"),
+    }
+    Ok(Value::empty_list())
+});
+
+// (%import 'name*), (import name*) is turned into this
+// binds the exports of the modules in the current scope, modules that aren't defined yet are loaded from their file
+eval_args!(fn import(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    for datum in args.iter() {
+        let name = module_name("import", datum, interpreter)?;
+        if !interpreter.modules.contains_key(&name) && interpreter.file_access {
+            let path = module_path(datum, interpreter);
+            if path.is_file() { interpreter.load(path)?; }
+        }
+
+        let bindings: Vec<(u64, Value)> = match interpreter.modules.get(&name) {
+            // safe because define-module checked that all exports are defined
+            Some(module) => module.exports.iter().map(|&id| (id, module.scope.lookup_symbol(id).unwrap())).collect(),
+            None => raise_condition!(format!("import could not find module {}", name)),
+        };
+        for (id, value) in bindings {
+            interpreter.current_scope.add_symbol(id, value);
+        }
+    }
+    Ok(Value::empty_list())
+});
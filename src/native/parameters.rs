@@ -0,0 +1,44 @@
+use ::value::{Value, Condition, Parameter};
+use ::interpreter::Interpreter;
+
+// Parameters, dynamically bound values like the current output port
+
+// the value that is bound, converted by the converter of the parameter if it has one
+fn convert(parameter: &Parameter, value: Value, interpreter: &mut Interpreter) -> Result<Value, Condition> {
+    match parameter.converter() {
+        Some(converter) => interpreter.call_procedure(converter, vec![value]),
+        None => Ok(value),
+    }
+}
+
+// (make-parameter value [converter]), the converter is also called with value
+eval_args!(fn make_parameter(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("make-parameter", args.len(), 1, 2);
+    let converter = args.get(1).cloned();
+    let value = match converter {
+        Some(ref converter) => interpreter.call_procedure(converter, vec![args[0].clone()])?,
+        None => args[0].clone(),
+    };
+    Ok(Value::new_parameter(Parameter::new(value, converter)))
+});
+
+// (%parameterize f parameter value ...), (parameterize ((parameter value) ...) body) is turned into this,
+// with f being (lambda () body). All values are converted before any parameter is bound.
+eval_args!(fn parameterize(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("parameterize", args.len(), min => 1);
+    let mut bindings = vec![];
+    for pair in args[1..].chunks(2) {
+        let value = {
+            let parameter = try_unwrap_type!("parameterize", "parameter", Value::get_parameter, &pair[0], interpreter);
+            convert(parameter, pair[1].clone(), interpreter)?
+        };
+        bindings.push((pair[0].clone(), value));
+    }
+
+    // the bindings are removed however the body is left, e.g. by a condition or a continuation
+    let depth = interpreter.parameterized.len();
+    interpreter.parameterized.extend(bindings);
+    let res = interpreter.call_procedure(&args[0], vec![]);
+    interpreter.parameterized.truncate(depth);
+    res
+});
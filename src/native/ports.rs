@@ -0,0 +1,285 @@
+use ::value::{Value, Condition, Port};
+use ::interpreter::Interpreter;
+use ::grammar;
+use std::path::{Path, PathBuf};
+
+// File, string and tcp ports, io errors are raised as conditions prefixed with the procedure name
+
+macro_rules! try_io {
+    ($fn_name:expr, $result:expr) => ({
+        match $result {
+            Ok(x) => x,
+            Err(err) => raise_condition!(format!("{} {}", $fn_name, err)),
+        }
+    });
+}
+
+// (open-input-file path)
+eval_args!(fn open_input_file(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("open-input-file", args.len(), 1);
+    let path = try_unwrap_type!("open-input-file", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_port(try_io!("open-input-file", Port::open_input(path))))
+});
+
+// (open-output-file path), an existing file is truncated
+eval_args!(fn open_output_file(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("open-output-file", args.len(), 1);
+    let path = try_unwrap_type!("open-output-file", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_port(try_io!("open-output-file", Port::open_output(path))))
+});
+
+// (open-input-string s), reads from s like from a file
+eval_args!(fn open_input_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("open-input-string", args.len(), 1);
+    let s = try_unwrap_type!("open-input-string", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_port(Port::open_input_string(s)))
+});
+
+// (open-output-string), the output is returned by get-output-string
+eval_args!(fn open_output_string(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("open-output-string", args.len(), 0);
+    Ok(Value::new_port(Port::open_output_string()))
+});
+
+// (get-output-string port), everything written to port so far
+eval_args!(fn get_output_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("get-output-string", args.len(), 1);
+    let port = try_unwrap_type!("get-output-string", "port", Value::get_port, &args[0], interpreter);
+    Ok(Value::new_string(try_io!("get-output-string", port.output_string())))
+});
+
+// returns the eof object at the end of the file
+eval_args!(fn read_line(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("read-line", args.len(), 1);
+    let port = try_unwrap_type!("read-line", "port", Value::get_port, &args[0], interpreter);
+    match try_io!("read-line", port.read_line()) {
+        Some(line) => Ok(Value::new_string(line)),
+        None => Ok(Value::new_eof()),
+    }
+});
+
+// returns the eof object at the end of the file
+eval_args!(fn read_char(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("read-char", args.len(), 1);
+    let port = try_unwrap_type!("read-char", "port", Value::get_port, &args[0], interpreter);
+    match try_io!("read-char", port.read_char()) {
+        Some(c) => Ok(Value::new_char(c)),
+        None => Ok(Value::new_eof()),
+    }
+});
+
+// (read [port]), the next datum from port or stdin, it's not evaluated
+eval_args!(fn read(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("read", args.len(), 0, 1);
+    let port = args.get(0).cloned().unwrap_or_else(|| interpreter.stdin.clone());
+    let port = try_unwrap_type!("read", "port", Value::get_port, &port, interpreter);
+    match try_io!("read", port.read_datum(&mut interpreter.interner)) {
+        Some(datum) => Ok(datum),
+        None => Ok(Value::new_eof()),
+    }
+});
+
+// (read-string s), the first datum in s, the eof object if there is none
+eval_args!(fn read_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("read-string", args.len(), 1);
+    let s = try_unwrap_type!("read-string", "string", Value::get_string, &args[0], interpreter);
+    match grammar::parse_data(s, &mut interpreter.interner) {
+        Ok(data) => Ok(data.into_iter().next().unwrap_or_else(Value::new_eof)),
+        Err(_) => raise_condition!(format!("read-string could not parse: {}", args[0].to_string(&interpreter.interner))),
+    }
+});
+
+// writes to the given port or to the current output port, which is stdout unless it's parameterized
+pub fn output(name: &str, s: &str, port: Option<&Value>, interpreter: &Interpreter) -> Result<Value, Condition> {
+    let current = interpreter.parameter_value(&interpreter.current_output_port);
+    let port = try_unwrap_type!(name, "port", Value::get_port, port.unwrap_or(&current), interpreter);
+    try_io!(name, port.write_str(s));
+    Ok(Value::empty_list())
+}
+
+// (write x [port]), writes x the way it would be read back in
+eval_args!(fn write(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("write", args.len(), 1, 2);
+    output("write", &args[0].to_string(&interpreter.interner), args.get(1), interpreter)
+});
+
+// (write-shared x [port]), like write, but pairs and vectors that occur more than once get datum labels
+eval_args!(fn write_shared(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("write-shared", args.len(), 1, 2);
+    output("write-shared", &args[0].to_shared_string(&interpreter.interner), args.get(1), interpreter)
+});
+
+// (display x [port]), like write, but strings and chars are written without quotes
+eval_args!(fn display(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("display", args.len(), 1, 2);
+    output("display", &args[0].to_display_string(&interpreter.interner), args.get(1), interpreter)
+});
+
+// (pp x [port]), writes x like write, but breaks lists that don't fit into 80 columns into lines
+eval_args!(fn pp(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("pp", args.len(), 1, 2);
+    let s = format!("{}\n", args[0].pretty_string(&interpreter.interner, 80));
+    output("pp", &s, args.get(1), interpreter)
+});
+
+// (newline [port])
+eval_args!(fn newline(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("newline", args.len(), 0, 1);
+    output("newline", "\n", args.get(0), interpreter)
+});
+
+// ~a displays the next arg, ~s writes it, ~d writes a number, ~% is a newline and ~~ a tilde
+fn format_template(template: &str, args: &[Value], interpreter: &Interpreter) -> Result<String, Condition> {
+    let mut s = String::new();
+    let mut args = args.iter();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            s.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => s.push('\n'),
+            Some('~') => s.push('~'),
+            Some(directive) if directive == 'a' || directive == 's' || directive == 'd' => {
+                let arg = match args.next() {
+                    Some(arg) => arg,
+                    None => raise_condition!(format!("format is missing an argument for ~{}", directive)),
+                };
+                let is_number = arg.get_integer().is_some() || arg.get_bigint().is_some() || arg.get_rational().is_some() || arg.get_float().is_some();
+                if directive == 'd' && !is_number {
+                    raise_condition!(Type, format!("format expected number for ~d, got: {}", arg.to_string(&interpreter.interner)));
+                }
+                if directive == 'a' {
+                    s.push_str(&arg.to_display_string(&interpreter.interner));
+                } else {
+                    s.push_str(&arg.to_string(&interpreter.interner));
+                }
+            },
+            Some(directive) => raise_condition!(format!("format unknown directive: ~{}", directive)),
+            None => raise_condition!("format template ends with ~"),
+        }
+    }
+    assert_or_condition!(args.next().is_none(), "format got more arguments than directives");
+    Ok(s)
+}
+
+// (format dest template args*), dest is false for returning a string, true for stdout or a port
+// (format template args*) returns a string too
+eval_args!(fn format(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("format", args.len(), min => 1);
+    let (dest, rest) = if args[0].get_string().is_some() { (None, &args[..]) } else { (Some(&args[0]), &args[1..]) };
+    if dest.is_some() { check_arity!("format", args.len(), min => 2); }
+    let template = try_unwrap_type!("format", "string", Value::get_string, &rest[0], interpreter);
+    let s = format_template(template, &rest[1..], interpreter)?;
+    match dest.map(|dest| (dest, dest.get_bool())) {
+        None | Some((_, Some(false))) => Ok(Value::new_string(s)),
+        Some((_, Some(true))) => output("format", &s, None, interpreter),
+        Some((dest, None)) => output("format", &s, Some(dest), interpreter),
+    }
+});
+
+// calls f with the port and closes it afterwards, even if f raises a condition
+fn call_with_port(name: &str, port: Port, f: &Value, interpreter: &mut Interpreter) -> Result<Value, Condition> {
+    let port = Value::new_port(port);
+    let res = interpreter.call_procedure(f, vec![port.clone()]);
+    // safe because port was created above
+    try_io!(name, port.get_port().unwrap().close());
+    res
+}
+
+// (call-with-input-file path f), returns the result of (f port)
+eval_args!(fn call_with_input_file(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("call-with-input-file", args.len(), 2);
+    let port = {
+        let path = try_unwrap_type!("call-with-input-file", "string", Value::get_string, &args[0], interpreter);
+        try_io!("call-with-input-file", Port::open_input(path))
+    };
+    call_with_port("call-with-input-file", port, &args[1], interpreter)
+});
+
+// (call-with-output-file path f), returns the result of (f port)
+eval_args!(fn call_with_output_file(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("call-with-output-file", args.len(), 2);
+    let port = {
+        let path = try_unwrap_type!("call-with-output-file", "string", Value::get_string, &args[0], interpreter);
+        try_io!("call-with-output-file", Port::open_output(path))
+    };
+    call_with_port("call-with-output-file", port, &args[1], interpreter)
+});
+
+fn try_unwrap_port_number(name: &str, value: &Value, interpreter: &Interpreter) -> Result<u16, Condition> {
+    match value.get_integer() {
+        Some(i) if i >= 0 && i <= u16::max_value() as i64 => Ok(i as u16),
+        _ => raise_condition!(Type, format!("{} expected port number, got: {}", name, value.to_string(&interpreter.interner))),
+    }
+}
+
+// (tcp-connect host port), a port for reading from and writing to the connection
+eval_args!(fn tcp_connect(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("tcp-connect", args.len(), 2);
+    let host = try_unwrap_type!("tcp-connect", "string", Value::get_string, &args[0], interpreter);
+    let port = try_unwrap_port_number("tcp-connect", &args[1], interpreter)?;
+    Ok(Value::new_port(try_io!("tcp-connect", Port::connect(host, port))))
+});
+
+// (tcp-listen port [host]), host defaults to "127.0.0.1", port 0 picks a free port, see tcp-listener-port
+eval_args!(fn tcp_listen(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("tcp-listen", args.len(), 1, 2);
+    let port = try_unwrap_port_number("tcp-listen", &args[0], interpreter)?;
+    let host = match args.get(1) {
+        Some(host) => try_unwrap_type!("tcp-listen", "string", Value::get_string, host, interpreter),
+        None => "127.0.0.1",
+    };
+    Ok(Value::new_port(try_io!("tcp-listen", Port::listen(host, port))))
+});
+
+// (tcp-accept listener), waits for a connection and returns a port for it
+eval_args!(fn tcp_accept(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("tcp-accept", args.len(), 1);
+    let listener = try_unwrap_type!("tcp-accept", "port", Value::get_port, &args[0], interpreter);
+    Ok(Value::new_port(try_io!("tcp-accept", listener.accept())))
+});
+
+// (tcp-listener-port listener), the port number the listener is bound to
+eval_args!(fn tcp_listener_port(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("tcp-listener-port", args.len(), 1);
+    let listener = try_unwrap_type!("tcp-listener-port", "port", Value::get_port, &args[0], interpreter);
+    Ok(Value::new_integer(try_io!("tcp-listener-port", listener.local_port()) as i64))
+});
+
+// flushes output ports, closing a port twice does nothing
+eval_args!(fn close_port(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("close-port", args.len(), 1);
+    let port = try_unwrap_type!("close-port", "port", Value::get_port, &args[0], interpreter);
+    try_io!("close-port", port.close());
+    Ok(Value::empty_list())
+});
+
+// (load path), evaluates all forms in the file in the current scope and returns the last result
+// relative paths are resolved against the directory of the file being loaded, if any
+eval_args!(fn load(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("load", args.len(), 1);
+    let path = {
+        let path = Path::new(try_unwrap_type!("load", "string", Value::get_string, &args[0], interpreter));
+        match interpreter.loading.last() {
+            Some(file) if path.is_relative() => file.parent().unwrap_or(Path::new("")).join(path),
+            _ => path.to_path_buf(),
+        }
+    };
+    interpreter.load(path)
+});
+
+// (dump-scope path), writes the bindings of the global scope that were defined by the program, returns how many
+eval_args!(fn dump_scope(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("dump-scope", args.len(), 1);
+    let path = PathBuf::from(try_unwrap_type!("dump-scope", "string", Value::get_string, &args[0], interpreter));
+    interpreter.dump_scope(path).map(|n| Value::new_integer(n as i64))
+});
+
+// (load-scope path), evaluates a file written by dump-scope in the global scope
+eval_args!(fn load_scope(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("load-scope", args.len(), 1);
+    let path = PathBuf::from(try_unwrap_type!("load-scope", "string", Value::get_string, &args[0], interpreter));
+    interpreter.load_scope(path)
+});
@@ -1,58 +1,104 @@
-use std::ops::{Add, Sub, Mul, Div, Rem};
-use ::value::Value;
+use std::ops::{Add, Sub, Mul};
+use std::cmp::Ordering;
+use std::mem;
+use std::i32;
+use std::io::{self, Write};
+use std::process;
+use ::value::{Value, Condition, BigInt, Rational};
 use ::interpreter::Interpreter;
 use ::grammar;
+use ::gc;
 
 // Polymorphic equality
-eval_args!(fn poly_eq(args: &mut [Value]) -> Value {
+eval_args!(fn poly_eq(args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("eq?", args.len(), min => 2);
-    Value::new_bool(args.windows(2).all(|window| window[0] == window[1]))
+    Ok(Value::new_bool(args.windows(2).all(|window| window[0] == window[1])))
+});
+
+// numbers, chars and symbols by value, everything else by identity
+eval_args!(fn eqv(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("eqv?", args.len(), min => 2);
+    Ok(Value::new_bool(args.windows(2).all(|window| window[0].eqv(&window[1]))))
+});
+
+// pairs, vectors and strings by their contents, everything else like eqv?
+eval_args!(fn equal(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("equal?", args.len(), min => 2);
+    Ok(Value::new_bool(args.windows(2).all(|window| window[0].equal(&window[1]))))
+});
+
+eval_args!(fn not(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("not", args.len(), 1);
+    let b = try_unwrap_type!("not", "bool", Value::get_bool, &args[0], interpreter);
+    Ok(Value::new_bool(!b))
 });
 
 // Type checking
 macro_rules! type_checker {
     ($func:ident, $lisp_name:expr, $checking_fn:ident) =>
-    (eval_args!(fn $func(args: &mut [Value]) -> Value {
+    (eval_args!(fn $func(args: &mut [Value]) -> Result<Value, Condition> {
         check_arity!($lisp_name, args.len(), 1);
-        Value::new_bool(args[0].$checking_fn().is_some())
+        Ok(Value::new_bool(args[0].$checking_fn().is_some()))
     }););
 }
 
 type_checker!(null_, "null?", get_empty_list);
 type_checker!(boolean_, "boolean?", get_bool);
 type_checker!(symbol_, "symbol?", get_symbol);
-type_checker!(integer_, "integer?", get_integer);
+type_checker!(float_, "float?", get_float);
 type_checker!(char_, "char?", get_char);
 type_checker!(string_, "string?", get_string);
 type_checker!(list_, "list?", get_list);
+type_checker!(pair_, "pair?", get_pair);
+type_checker!(vector_, "vector?", get_vector);
+type_checker!(hash_, "hash?", get_hash_table);
+type_checker!(port_, "port?", get_port);
+type_checker!(eof_object_, "eof-object?", get_eof);
+type_checker!(environment_, "environment?", get_environment);
+type_checker!(promise_, "promise?", get_promise);
+type_checker!(userdata_, "userdata?", get_userdata);
+
+eval_args!(fn integer_(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("integer?", args.len(), 1);
+    Ok(Value::new_bool(args[0].get_integer().is_some() || args[0].get_bigint().is_some()))
+});
+
+eval_args!(fn rational_(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("rational?", args.len(), 1);
+    Ok(Value::new_bool(Number::from_value(&args[0]).map_or(false, |n| !n.is_float())))
+});
+
+eval_args!(fn number_(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("number?", args.len(), 1);
+    Ok(Value::new_bool(Number::from_value(&args[0]).is_some()))
+});
 
-eval_args!(fn procedure_(args: &mut [Value]) -> Value {
+eval_args!(fn procedure_(args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("procedure?", args.len(), 1);
-    let is_proc = Value::get_native_fn_ptr(&args[0]).is_some() || Value::get_proc(&args[0]).is_some();
-    Value::new_bool(is_proc)
+    Ok(Value::new_bool(args[0].is_procedure()))
 });
 
 // Type conversions
 macro_rules! type_conversion {
     ($func:ident, $lisp_name:expr, $type_name:expr, $get_fn:path, $conversion_fn:expr, $new_fn:path) =>
-    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
         check_arity!($lisp_name, args.len(), 1);
         let conversion_fn = $conversion_fn;
         let rust_value = try_unwrap_type!($lisp_name, $type_name, $get_fn, &args[0], interpreter);
         let converted = conversion_fn(rust_value);
-        $new_fn(converted)
+        Ok($new_fn(converted))
     }););
 }
 
 
 
-eval_args!(fn char_integer(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+eval_args!(fn char_integer(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("char->integer", args.len(), 1);
     let c = try_unwrap_type!("char->integer", "char", Value::get_char, &args[0], interpreter);
-    Value::new_integer(c as i64)
+    Ok(Value::new_integer(c as i64))
 });
 
-eval_args!(fn integer_char(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+eval_args!(fn integer_char(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
     use std::u32;
     use std::char;
     check_arity!("integer->char", args.len(), 1);
@@ -60,95 +106,539 @@ eval_args!(fn integer_char(interpreter: &mut Interpreter, args: &mut [Value]) ->
     if i > 0 && i < u32::MAX as i64 {
         let u = i as u32;
         if let Some(c) = char::from_u32(u) {
-            return Value::new_char(c)
+            return Ok(Value::new_char(c))
         }
     }
     raise_condition!("integer is not a valid char");
 });
 
-eval_args!(fn number_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
-    check_arity!("number->string", args.len(), 1);
-    let i = try_unwrap_type!("number->string", "integer", Value::get_integer, &args[0], interpreter);
-    Value::new_string(format!("{}", i))
+// the optional radix argument of number->string and string->number, 10 by default
+fn get_radix(name: &str, radix: Option<&Value>, interpreter: &Interpreter) -> Result<u32, Condition> {
+    match radix {
+        None => Ok(10),
+        Some(x) => match x.get_integer() {
+            Some(r) if r == 2 || r == 8 || r == 10 || r == 16 => Ok(r as u32),
+            _ => raise_condition!(format!("{} expected radix 2, 8, 10 or 16, got: {}", name, x.to_string(&interpreter.interner))),
+        },
+    }
+}
+
+eval_args!(fn number_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("number->string", args.len(), 1, 2);
+    let n = try_unwrap_type!("number->string", "number", Number::from_value, &args[0], interpreter);
+    let radix = get_radix("number->string", args.get(1), interpreter)?;
+    let n = n.to_value();
+    if radix == 10 {
+        return Ok(Value::new_string(n.to_string(&interpreter.interner)));
+    }
+    match grammar::exact_to_string(&n, radix) {
+        Some(s) => Ok(Value::new_string(s)),
+        None => raise_condition!(format!("number->string expected radix 10 for float, got: {}", radix)),
+    }
 });
 
-eval_args!(fn string_number(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
-    check_arity!("string->number", args.len(), 1);
+eval_args!(fn string_number(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string->number", args.len(), 1, 2);
     let s = try_unwrap_type!("string->number", "string", Value::get_string, &args[0], interpreter);
-    if let Ok(v) = grammar::parse_integer(s) {
-        return v;
+    let radix = get_radix("string->number", args.get(1), interpreter)?;
+    // prefixes in s take precedence over radix
+    let number = if radix == 10 { grammar::parse_number(s).ok() } else { grammar::parse_prefixed(s, radix) };
+    match number {
+        Some(v) => Ok(v),
+        None => raise_condition!(format!("string is not a valid number: {:?}", s)),
     }
-    raise_condition!(format!("string is not a valid integer: {:?}", s));
 });
 
-pub fn symbol_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+pub fn symbol_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("symbol->string", args.len(), 1);
-    let evaled = interpreter.evaluate(&args[0]);
+    let evaled = interpreter.evaluate(&args[0])?;
     let id = try_unwrap_type!("symbol->string", "symbol", Value::get_symbol, &evaled, interpreter);
     if let Some(string) = interpreter.interner.lookup(id) {
-        Value::new_string(string)
+        Ok(Value::new_string(string))
     } else {
         raise_condition!("internal error: invalid symbol")
     }
 }
 
-pub fn string_symbol(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+pub fn string_symbol(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("string->symbol", args.len(), 1);
-    let evaled = interpreter.evaluate(&args[0]);
+    let evaled = interpreter.evaluate(&args[0])?;
     let string = try_unwrap_type!("string->symbol", "string", Value::get_string, &evaled, interpreter);
     let id = interpreter.interner.intern(string);
-    Value::new_symbol(id)
+    Ok(Value::new_symbol(id))
+}
+
+// Uninterned symbols look like other symbols, but they're only eq? to themselves,
+// not even to the symbol that is read or made by string->symbol from their name
+
+// (gensym [prefix]), a new uninterned symbol named prefix and a number, prefix is a string or symbol and defaults to g
+eval_args!(fn gensym(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("gensym", args.len(), 0, 1);
+    let prefix = match args.get(0) {
+        None => "g".to_string(),
+        Some(x) => match (x.get_string(), x.get_symbol()) {
+            (Some(s), _) => s.to_string(),
+            (None, Some(id)) => interpreter.interner.lookup(id).unwrap_or("g").to_string(),
+            _ => raise_condition!(Type, format!("gensym expected string or symbol, got: {}", x.to_string(&interpreter.interner))),
+        },
+    };
+    Ok(Value::new_symbol(interpreter.interner.gensym_numbered(&prefix)))
+});
+
+eval_args!(fn string_uninterned_symbol(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string->uninterned-symbol", args.len(), 1);
+    let name = try_unwrap_type!("string->uninterned-symbol", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_symbol(interpreter.interner.gensym(name)))
+});
+
+eval_args!(fn symbol_interned_(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("symbol-interned?", args.len(), 1);
+    let id = try_unwrap_type!("symbol-interned?", "symbol", Value::get_symbol, &args[0], interpreter);
+    Ok(Value::new_bool(interpreter.interner.is_interned(id)))
+});
+
+// Numbers: integers -> rationals -> floats, results have the type of the "biggest" operand.
+// Integers that don't fit into an i64 are promoted to BigInts, integers and rationals
+// are demoted again if possible.
+#[derive(Clone)]
+enum Number {
+    Integer(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.get_integer().map(Number::Integer)
+        .or_else(|| value.get_bigint().map(|x| Number::BigInt(x.clone())))
+        .or_else(|| value.get_rational().map(|x| Number::Rational(x.clone())))
+        .or_else(|| value.get_float().map(Number::Float))
+    }
+
+    fn from_bigint(x: BigInt) -> Self {
+        match x.to_i64() {
+            Some(i) => Number::Integer(i),
+            None => Number::BigInt(x),
+        }
+    }
+
+    fn from_rational(x: Rational) -> Self {
+        if x.denominator() == &BigInt::from(1) {
+            Number::from_bigint(x.numerator().clone())
+        } else {
+            Number::Rational(x)
+        }
+    }
+
+    fn to_value(self) -> Value {
+        match self {
+            Number::Integer(i) => Value::new_integer(i),
+            Number::BigInt(x) => Value::new_bigint(x),
+            Number::Rational(x) => Value::new_rational(x),
+            Number::Float(f) => Value::new_float(f),
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        if let &Number::Float(_) = self { true } else { false }
+    }
+
+    fn is_rational(&self) -> bool {
+        if let &Number::Rational(_) = self { true } else { false }
+    }
+
+    fn is_integer(&self) -> bool {
+        !self.is_float() && !self.is_rational()
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            &Number::Integer(i) => i == 0,
+            &Number::BigInt(ref x) => x.is_zero(),
+            &Number::Rational(_) => false,
+            &Number::Float(f) => f == 0.0,
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        match self {
+            &Number::Integer(i) => i < 0,
+            &Number::BigInt(ref x) => x.is_negative(),
+            &Number::Rational(ref x) => x.numerator().is_negative(),
+            &Number::Float(f) => f < 0.0,
+        }
+    }
+
+    fn as_float(&self) -> f64 {
+        match self {
+            &Number::Integer(i) => i as f64,
+            &Number::BigInt(ref x) => x.to_f64(),
+            &Number::Rational(ref x) => x.to_f64(),
+            &Number::Float(f) => f,
+        }
+    }
+
+    // not valid for floats
+    fn as_rational(&self) -> Rational {
+        match self {
+            &Number::Rational(ref x) => x.clone(),
+            x => Rational::from(x.as_bigint()),
+        }
+    }
+
+    // only valid for integers
+    fn as_bigint(&self) -> BigInt {
+        match self {
+            &Number::Integer(i) => BigInt::from(i),
+            &Number::BigInt(ref x) => x.clone(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+macro_rules! try_unwrap_number {
+    ($value:expr, $interpreter:expr) => ({
+        match Number::from_value($value) {
+            Some(n) => n,
+            None => raise_condition!(format!("expected number, got: {}", $value.to_string(&$interpreter.interner))),
+        }
+    });
+}
+
+// applies operator to a and b, checked_operator is used for i64s, which are promoted on overflow
+macro_rules! number_operation {
+    ($a:expr, $b:expr, $checked_operator:ident, $operator:path) => ({
+        match ($a, $b) {
+            (Number::Integer(a), Number::Integer(b)) => match a.$checked_operator(b) {
+                Some(i) => Number::Integer(i),
+                None => Number::from_bigint($operator(BigInt::from(a), BigInt::from(b))),
+            },
+            (ref a, ref b) if a.is_float() || b.is_float() => Number::Float($operator(a.as_float(), b.as_float())),
+            (ref a, ref b) if a.is_rational() || b.is_rational() => Number::from_rational($operator(a.as_rational(), b.as_rational())),
+            (a, b) => Number::from_bigint($operator(a.as_bigint(), b.as_bigint())),
+        }
+    });
 }
 
 // Arithmetic operators
 macro_rules! arithmetic_operator {
-    ($func:ident, $operator:path, $default:expr) =>
-    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
-        let mut res = if args.len() < 2 {
-            $default as i64
+    ($func:ident, $checked_operator:ident, $operator:path, $default:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        // with less than 2 args the default is the first operand
+        let (mut res, rest) = if args.len() < 2 {
+            (Number::Integer($default), &args[..])
         } else {
-            match args[0].get_integer() {
-                Some(i) => i,
-                None => raise_condition!(format!("expected integer, got: {}", &args[0].to_string(&interpreter.interner)))
+            (try_unwrap_number!(&args[0], interpreter), &args[1..])
+        };
+        for x in rest {
+            res = number_operation!(res, try_unwrap_number!(x, interpreter), $checked_operator, $operator);
+        }
+        Ok(res.to_value())
+    }););
+}
+
+arithmetic_operator!(plus, checked_add, Add::add, 0);
+arithmetic_operator!(minus, checked_sub, Sub::sub, 0);
+arithmetic_operator!(multiply, checked_mul, Mul::mul, 1);
+
+// integer division of a by b, None if b is 0
+fn div_rem(a: &Number, b: &Number) -> Option<(Number, Number)> {
+    match (a, b) {
+        (&Number::Integer(a), &Number::Integer(b)) if a.checked_div(b).is_some() => {
+            Some((Number::Integer(a / b), Number::Integer(a % b)))
+        },
+        (a, b) => {
+            a.as_bigint().div_rem(&b.as_bigint())
+            .map(|(quotient, remainder)| (Number::from_bigint(quotient), Number::from_bigint(remainder)))
+        },
+    }
+}
+
+// exact unless a float is involved
+eval_args!(fn divide(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("/", args.len(), min => 1);
+    let (mut res, rest) = if args.len() < 2 {
+        (Number::Integer(1), &args[..])
+    } else {
+        (try_unwrap_number!(&args[0], interpreter), &args[1..])
+    };
+    for x in rest {
+        let divisor = try_unwrap_number!(x, interpreter);
+        res = if res.is_float() || divisor.is_float() {
+            Number::Float(res.as_float() / divisor.as_float())
+        } else {
+            match res.as_rational().checked_div(divisor.as_rational()) {
+                Some(x) => Number::from_rational(x),
+                None => raise_condition!("division by zero"),
             }
         };
-        for x in args[1..].iter() {
-            if let Some(i) = x.get_integer() {
-                res = $operator(res, i);
-            } else {
-                raise_condition!(format!("expected integer, got: {}", x.to_string(&interpreter.interner)))
+    }
+    Ok(res.to_value())
+});
+
+fn try_unwrap_integer(name: &str, value: &Value, interpreter: &Interpreter) -> Result<Number, Condition> {
+    match Number::from_value(value) {
+        Some(ref n) if n.is_integer() => return Ok(n.clone()),
+        _ => (),
+    }
+    raise_condition!(format!("{} expected integer, got: {}", name, value.to_string(&interpreter.interner)))
+}
+
+// integer division, select picks the quotient or the remainder
+macro_rules! integer_operator {
+    ($func:ident, $lisp_name:expr, $select:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        check_arity!($lisp_name, args.len(), min => 1);
+        let (mut res, rest) = if args.len() < 2 {
+            (Number::Integer(1), &args[..])
+        } else {
+            (try_unwrap_integer($lisp_name, &args[0], interpreter)?, &args[1..])
+        };
+        for x in rest {
+            let divisor = try_unwrap_integer($lisp_name, x, interpreter)?;
+            res = match div_rem(&res, &divisor) {
+                Some(result) => $select(result, &divisor),
+                None => raise_condition!("division by zero"),
+            };
+        }
+        Ok(res.to_value())
+    }););
+}
+
+integer_operator!(quotient, "quotient", |(quotient, _), _| quotient);
+integer_operator!(remainder, "remainder", |(_, remainder), _| remainder);
+// like remainder, but the result has the sign of the divisor
+integer_operator!(modulo, "modulo", |(_, remainder): (Number, Number), divisor: &Number| {
+    if !remainder.is_zero() && remainder.is_negative() != divisor.is_negative() {
+        number_operation!(remainder, divisor.clone(), checked_add, Add::add)
+    } else {
+        remainder
+    }
+});
+
+eval_args!(fn abs(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("abs", args.len(), 1);
+    let x = try_unwrap_number!(&args[0], interpreter);
+    if x.is_negative() {
+        Ok(number_operation!(Number::Integer(0), x, checked_sub, Sub::sub).to_value())
+    } else {
+        Ok(x.to_value())
+    }
+});
+
+fn is_less(a: &Number, b: &Number) -> bool {
+    match (a, b) {
+        (&Number::Integer(a), &Number::Integer(b)) => a < b,
+        (a, b) if a.is_float() || b.is_float() => a.as_float() < b.as_float(),
+        (a, b) if a.is_rational() || b.is_rational() => a.as_rational() < b.as_rational(),
+        (a, b) => a.as_bigint() < b.as_bigint(),
+    }
+}
+
+// min and max, the result is a float if any operand is
+macro_rules! extremum_operator {
+    ($func:ident, $lisp_name:expr, $replaces:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        check_arity!($lisp_name, args.len(), min => 1);
+        let mut res = try_unwrap_number!(&args[0], interpreter);
+        let mut is_float = res.is_float();
+        for x in &args[1..] {
+            let x = try_unwrap_number!(x, interpreter);
+            is_float = is_float || x.is_float();
+            if $replaces(&x, &res) { res = x; }
+        }
+        if is_float { res = Number::Float(res.as_float()); }
+        Ok(res.to_value())
+    }););
+}
+
+extremum_operator!(min, "min", |x: &Number, res: &Number| is_less(x, res));
+extremum_operator!(max, "max", |x: &Number, res: &Number| is_less(res, x));
+
+// (gcd n*), 0 without arguments, the result is never negative
+eval_args!(fn gcd(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    let mut res = BigInt::from(0);
+    for x in args.iter() {
+        res = res.gcd(try_unwrap_integer("gcd", x, interpreter)?.as_bigint());
+    }
+    Ok(Number::from_bigint(res).to_value())
+});
+
+// (lcm n*), 1 without arguments, the result is never negative
+eval_args!(fn lcm(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    let mut res = BigInt::from(1);
+    for x in args.iter() {
+        let x = try_unwrap_integer("lcm", x, interpreter)?.as_bigint().abs();
+        res = if res.is_zero() || x.is_zero() {
+            BigInt::from(0)
+        } else {
+            // safe because the gcd of non zero numbers isn't zero
+            (res.clone() * x.clone()).div_rem(&res.gcd(x)).unwrap().0
+        };
+    }
+    Ok(Number::from_bigint(res).to_value())
+});
+
+// (expt base exponent), exact if base is exact and exponent an integer
+eval_args!(fn expt(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("expt", args.len(), 2);
+    let base = try_unwrap_number!(&args[0], interpreter);
+    let exponent = try_unwrap_number!(&args[1], interpreter);
+    let n = match exponent {
+        Number::Integer(n) if !base.is_float() => n,
+        _ => return Ok(Value::new_float(base.as_float().powf(exponent.as_float()))),
+    };
+
+    // by squaring, base^n = (base^2)^(n/2) * base^(n%2)
+    let (mut res, mut square, mut rest) = (Number::Integer(1), base, n.wrapping_abs() as u64);
+    while rest > 0 {
+        if rest % 2 == 1 { res = number_operation!(res, square.clone(), checked_mul, Mul::mul); }
+        rest /= 2;
+        if rest > 0 { square = number_operation!(square.clone(), square, checked_mul, Mul::mul); }
+    }
+    if n >= 0 { return Ok(res.to_value()); }
+    match Rational::from(BigInt::from(1)).checked_div(res.as_rational()) {
+        Some(x) => Ok(Number::from_rational(x).to_value()),
+        None => raise_condition!("division by zero"),
+    }
+});
+
+eval_args!(fn exact_inexact(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("exact->inexact", args.len(), 1);
+    Ok(Value::new_float(try_unwrap_number!(&args[0], interpreter).as_float()))
+});
+
+enum Rounding {
+    Floor,
+    Ceiling,
+    Truncate,
+    // to the nearest integer, to the even one if x is halfway between two
+    Round,
+}
+
+// integers are returned as they are, rationals are rounded to integers and floats stay floats
+fn round_number(x: Number, rounding: Rounding) -> Number {
+    let x = match x {
+        Number::Float(f) => return Number::Float(match rounding {
+            Rounding::Floor => f.floor(),
+            Rounding::Ceiling => f.ceil(),
+            Rounding::Truncate => f.trunc(),
+            Rounding::Round if (f - f.trunc()).abs() == 0.5 => 2.0 * (f / 2.0).round(),
+            Rounding::Round => f.round(),
+        }),
+        Number::Rational(x) => x,
+        x => return x,
+    };
+
+    // the quotient is truncated and the remainder, which is never zero, has the sign of x
+    // safe because the denominator is never zero
+    let (quotient, remainder) = x.numerator().div_rem(x.denominator()).unwrap();
+    let away_from_zero = if remainder.is_negative() { -1 } else { 1 };
+    let offset = match rounding {
+        Rounding::Floor => if remainder.is_negative() { -1 } else { 0 },
+        Rounding::Ceiling => if remainder.is_negative() { 0 } else { 1 },
+        Rounding::Truncate => 0,
+        Rounding::Round => {
+            // safe because 2 isn't zero
+            let is_odd = !quotient.div_rem(&BigInt::from(2)).unwrap().1.is_zero();
+            match (remainder.abs() * BigInt::from(2)).cmp(x.denominator()) {
+                Ordering::Greater => away_from_zero,
+                Ordering::Equal if is_odd => away_from_zero,
+                _ => 0,
             }
+        },
+    };
+    Number::from_bigint(quotient + BigInt::from(offset))
+}
+
+macro_rules! rounding_operator {
+    ($func:ident, $lisp_name:expr, $rounding:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        check_arity!($lisp_name, args.len(), 1);
+        let x = try_unwrap_number!(&args[0], interpreter);
+        Ok(round_number(x, $rounding).to_value())
+    }););
+}
+
+rounding_operator!(floor, "floor", Rounding::Floor);
+rounding_operator!(ceiling, "ceiling", Rounding::Ceiling);
+rounding_operator!(truncate, "truncate", Rounding::Truncate);
+rounding_operator!(round, "round", Rounding::Round);
+
+// the exact root if x is the square of an integer or rational
+fn exact_sqrt(x: &Number) -> Option<Number> {
+    fn root(n: &BigInt) -> Option<BigInt> {
+        let root = n.sqrt();
+        if root.clone() * root.clone() == *n { Some(root) } else { None }
+    }
+
+    if x.is_float() || x.is_negative() { return None; }
+    let x = x.as_rational();
+    let (numerator, denominator) = (root(x.numerator())?, root(x.denominator())?);
+    Rational::new(numerator, denominator).map(Number::from_rational)
+}
+
+eval_args!(fn sqrt(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("sqrt", args.len(), 1);
+    let x = try_unwrap_number!(&args[0], interpreter);
+    match exact_sqrt(&x) {
+        Some(root) => Ok(root.to_value()),
+        None => Ok(Value::new_float(x.as_float().sqrt())),
+    }
+});
+
+// Functions on floats, the result is only exact for one exact argument, like (exp 0) => 1
+macro_rules! float_operator {
+    ($func:ident, $lisp_name:expr, $operator:path, $exact:expr => $exact_result:expr) =>
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+        check_arity!($lisp_name, args.len(), 1);
+        match try_unwrap_number!(&args[0], interpreter) {
+            Number::Integer(i) if i == $exact => Ok(Value::new_integer($exact_result)),
+            x => Ok(Value::new_float($operator(x.as_float()))),
         }
-        Value::new_integer(res)
     }););
 }
 
-arithmetic_operator!(plus, Add::add, 0);
-arithmetic_operator!(minus, Sub::sub, 0);
-arithmetic_operator!(multiply, Mul::mul, 1);
-arithmetic_operator!(quotient, Div::div, 1);
-arithmetic_operator!(remainder, Rem::rem, 1);
+float_operator!(exp, "exp", f64::exp, 0 => 1);
+float_operator!(log, "log", f64::ln, 1 => 0);
+float_operator!(sin, "sin", f64::sin, 0 => 0);
+float_operator!(cos, "cos", f64::cos, 0 => 1);
+float_operator!(tan, "tan", f64::tan, 0 => 0);
+
+// (atan x) or (atan y x), the angle of the point (x, y), exact zero for an exact zero y and a positive exact x
+eval_args!(fn atan(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("atan", args.len(), 1, 2);
+    let y = try_unwrap_number!(&args[0], interpreter);
+    let x = match args.get(1) {
+        Some(x) => try_unwrap_number!(x, interpreter),
+        None => Number::Integer(1),
+    };
+    if y.is_zero() && !y.is_float() && !x.is_float() && !x.is_zero() && !x.is_negative() {
+        return Ok(Value::new_integer(0));
+    }
+    Ok(Value::new_float(y.as_float().atan2(x.as_float())))
+});
 
 // Comparison Operators
 macro_rules! comparison_operator {
     ($func:ident, $lisp_name:expr, $operator:path) =>
-    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+    (eval_args!(fn $func(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
         check_arity!($lisp_name, args.len(), min => 2);
 
         let mut res = true;
-        let compared_element = match args[0].get_integer() {
-            Some(i) => i,
-            None => raise_condition!(format!("expected integer, got: {}", &args[0].to_string(&interpreter.interner)))
-        };
+        let compared_element = try_unwrap_number!(&args[0], interpreter);
 
         for x in &args[1..] {
-            let num = match x.get_integer() {
-                Some(i) => i,
-                None => raise_condition!(format!("expected integer, got: {}", x.to_string(&interpreter.interner)))
+            let num = try_unwrap_number!(x, interpreter);
+            res = res && match (&compared_element, &num) {
+                (&Number::Integer(a), &Number::Integer(b)) => $operator(&a, &b),
+                (a, b) if a.is_float() || b.is_float() => $operator(&a.as_float(), &b.as_float()),
+                (a, b) if a.is_rational() || b.is_rational() => $operator(&a.as_rational(), &b.as_rational()),
+                (a, b) => $operator(&a.as_bigint(), &b.as_bigint()),
             };
-            res = res && $operator(&compared_element, &num);
         }
-        Value::new_bool(res)
+        Ok(Value::new_bool(res))
     }););
 }
 
@@ -158,26 +648,96 @@ comparison_operator!(le, "<=", PartialOrd::le);
 comparison_operator!(gt, ">", PartialOrd::gt);
 comparison_operator!(ge, ">=", PartialOrd::ge);
 
+// (apply f args* list), calls f with args followed by the elements of list
+eval_args!(fn apply(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("apply", args.len(), min => 2);
+    let (last, init) = args.split_last().unwrap();
+    let spread = try_unwrap_type!("apply", "list", Value::get_list, last, interpreter);
+
+    let mut call_args = init[1..].to_vec();
+    call_args.extend(spread);
+    interpreter.call_procedure(&init[0], call_args)
+});
+
+// (eval datum [environment]), the datum is compiled like code typed into the repl
+// and evaluated in environment, which defaults to the interaction environment
+eval_args!(fn eval(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("eval", args.len(), 1, 2);
+    let scope = match args.get(1) {
+        Some(env) => try_unwrap_type!("eval", "environment", Value::get_environment, env, interpreter).clone(),
+        None => interpreter.global_scope.clone(),
+    };
+    let code = match grammar::compile(&args[0], &mut interpreter.interner, &mut interpreter.macros) {
+        Ok(code) => code,
+        Err(msg) => raise_condition!(format!("eval {}", msg)),
+    };
+
+    let old_scope = mem::replace(&mut interpreter.current_scope, scope);
+    let res = interpreter.evaluate(&code);
+    interpreter.current_scope = old_scope;
+    res
+});
+
+eval_args!(fn interaction_environment(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("interaction-environment", args.len(), 0);
+    Ok(Value::new_environment(interpreter.global_scope.clone()))
+});
+
 // List operations:
-eval_args!(fn first(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+eval_args!(fn first(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("first", args.len(), 1);
-    let list = try_unwrap_type!("first", "list", Value::get_list, &args[0], interpreter);
-    assert_or_condition!(list.len() > 0, "expected list with len > 0");
-    list[0].clone()
+    assert_or_condition!(args[0].get_empty_list().is_none(), "expected list with len > 0");
+    let pair = try_unwrap_type!("first", "list", Value::get_pair, &args[0], interpreter);
+    Ok(pair.car())
 });
 
-eval_args!(fn rest(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+// shares the rest of the list instead of copying it
+eval_args!(fn rest(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("rest", args.len(), 1);
-    let list = try_unwrap_type!("rest", "list", Value::get_list, &args[0], interpreter);
-    assert_or_condition!(list.len() > 0, "expected list with len > 0");
-    Value::new_list(&list[1..])
+    assert_or_condition!(args[0].get_empty_list().is_none(), "expected list with len > 0");
+    let pair = try_unwrap_type!("rest", "list", Value::get_pair, &args[0], interpreter);
+    Ok(pair.cdr())
+});
+
+// Pairs, lists are made of them
+eval_args!(fn cons(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("cons", args.len(), 2);
+    Ok(Value::new_pair(args[0].clone(), args[1].clone()))
 });
 
-eval_args!(fn list(args: &mut [Value]) -> Value {
-    Value::new_list(args)
+eval_args!(fn car(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("car", args.len(), 1);
+    let pair = try_unwrap_type!("car", "pair", Value::get_pair, &args[0], interpreter);
+    Ok(pair.car())
 });
 
-pub fn symbol_space(interpreter: &mut Interpreter, args: &mut [Value]) -> Value {
+eval_args!(fn cdr(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("cdr", args.len(), 1);
+    let pair = try_unwrap_type!("cdr", "pair", Value::get_pair, &args[0], interpreter);
+    Ok(pair.cdr())
+});
+
+// returns the pair
+eval_args!(fn set_car(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("set-car!", args.len(), 2);
+    try_unwrap_type!("set-car!", "pair", Value::get_pair, &args[0], interpreter).set_car(args[1].clone());
+    gc::register_value(&args[0]);
+    Ok(args[0].clone())
+});
+
+// returns the pair
+eval_args!(fn set_cdr(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("set-cdr!", args.len(), 2);
+    try_unwrap_type!("set-cdr!", "pair", Value::get_pair, &args[0], interpreter).set_cdr(args[1].clone());
+    gc::register_value(&args[0]);
+    Ok(args[0].clone())
+});
+
+eval_args!(fn list(args: &mut [Value]) -> Result<Value, Condition> {
+    Ok(Value::new_list(args))
+});
+
+pub fn symbol_space(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
     check_arity!("symbol-space", args.len(), 0);
 
     let symbols: Vec<Value> = interpreter.current_scope.symbol_ids()
@@ -185,5 +745,54 @@ pub fn symbol_space(interpreter: &mut Interpreter, args: &mut [Value]) -> Value
     .map(|s| Value::new_symbol(s))
     .collect();
 
-    Value::new_list(&symbols)
+    Ok(Value::new_list(&symbols))
+}
+
+// (environment-bindings [depth]), the bindings visible in the current scope as alist, innermost first.
+// With a depth only the innermost depth scopes are included, e.g. 1 for the bindings of the enclosing procedure.
+eval_args!(fn environment_bindings(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("environment-bindings", args.len(), 0, 1);
+    let depth = match args.get(0) {
+        Some(depth) => Some(try_unwrap_index!("environment-bindings", depth, interpreter)),
+        None => None,
+    };
+
+    let bindings: Vec<Value> = interpreter.current_scope.bindings(depth)
+    .into_iter()
+    .map(|(id, value)| Value::new_pair(Value::new_symbol(id), value))
+    .collect();
+
+    Ok(Value::new_list(&bindings))
+});
+
+// the script followed by its arguments, as strings
+pub fn command_line(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("command-line", args.len(), 0);
+    let args: Vec<Value> = interpreter.command_line.iter().map(Value::new_string).collect();
+    Ok(Value::new_list(&args))
 }
+
+// (collect-garbage), frees unreachable cycles right away and returns the number of objects freed
+eval_args!(fn collect_garbage(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("collect-garbage", args.len(), 0);
+    Ok(Value::new_integer(interpreter.heap.collect() as i64))
+});
+
+// (exit [code]), ends the process, code defaults to 0
+eval_args!(fn exit(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("exit", args.len(), 0, 1);
+    let code = match args.get(0) {
+        Some(code) => try_unwrap_type!("exit", "integer", Value::get_integer, code, interpreter),
+        None => 0,
+    };
+    assert_or_condition!(code >= i32::MIN as i64 && code <= i32::MAX as i64, format!("exit code out of range: {}", code));
+    let _ = io::stdout().flush();
+    process::exit(code as i32)
+});
+
+// (set-backtrace-limit! n), the maximum number of frames printed with a condition
+eval_args!(fn set_backtrace_limit(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("set-backtrace-limit!", args.len(), 1);
+    interpreter.backtrace_limit = try_unwrap_index!("set-backtrace-limit!", &args[0], interpreter);
+    Ok(Value::empty_list())
+});
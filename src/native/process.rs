@@ -0,0 +1,73 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use std::env;
+use std::io::{self, Write};
+use std::process::Command;
+
+// Environment variables and child processes, only installed if the interpreter has io, see Builder::without_io
+
+// (getenv name), the value of the environment variable or false if it's not set
+eval_args!(fn getenv(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("getenv", args.len(), 1);
+    let name = try_unwrap_type!("getenv", "string", Value::get_string, &args[0], interpreter);
+    match env::var(name) {
+        Ok(value) => Ok(Value::new_string(value)),
+        Err(_) => Ok(Value::new_bool(false)),
+    }
+});
+
+// (setenv name value), false as value removes the variable
+eval_args!(fn setenv(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("setenv", args.len(), 2);
+    let name = try_unwrap_type!("setenv", "string", Value::get_string, &args[0], interpreter);
+    let valid = !name.is_empty() && !name.contains('=') && !name.contains('\0');
+    assert_or_condition!(valid, format!("setenv invalid variable name: {}", args[0].to_string(&interpreter.interner)));
+    if args[1].get_bool() == Some(false) {
+        env::remove_var(name);
+    } else {
+        let value = try_unwrap_type!("setenv", "string or false", Value::get_string, &args[1], interpreter);
+        assert_or_condition!(!value.contains('\0'), "setenv value contains a null character");
+        env::set_var(name, value);
+    }
+    Ok(Value::empty_list())
+});
+
+// None if the process was killed by a signal
+fn exit_code(code: Option<i32>) -> Value {
+    code.map_or_else(|| Value::new_bool(false), |code| Value::new_integer(code as i64))
+}
+
+// (system command), runs command with the shell, its output goes to stdout
+// returns the exit code, false if the process was killed by a signal
+eval_args!(fn system(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("system", args.len(), 1);
+    let command = try_unwrap_type!("system", "string", Value::get_string, &args[0], interpreter);
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+
+    // output written before must come before the output of the command
+    let _ = io::stdout().flush();
+    match shell.status() {
+        Ok(status) => Ok(exit_code(status.code())),
+        Err(err) => raise_condition!(format!("system could not run {:?}: {}", command, err)),
+    }
+});
+
+// (process-run program arg*), runs program without a shell and returns (exit-code output),
+// output is what the program wrote to stdout, invalid UTF-8 is replaced
+eval_args!(fn process_run(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("process-run", args.len(), min => 1);
+    let program = try_unwrap_type!("process-run", "string", Value::get_string, &args[0], interpreter);
+    let mut command = Command::new(program);
+    for x in &args[1..] {
+        command.arg(try_unwrap_type!("process-run", "string", Value::get_string, x, interpreter));
+    }
+
+    match command.output() {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Ok(Value::new_list(&[exit_code(output.status.code()), Value::new_string(stdout)]))
+        },
+        Err(err) => raise_condition!(format!("process-run could not run {:?}: {}", program, err)),
+    }
+});
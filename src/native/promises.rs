@@ -0,0 +1,38 @@
+use ::value::{Value, Condition, Promise};
+use ::interpreter::Interpreter;
+use ::gc;
+
+// Promises, for lazy evaluation
+
+// (%delay f), (delay expr) is turned into this, with f being (lambda () expr)
+eval_args!(fn delay(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("delay", args.len(), 1);
+    Ok(Value::new_promise(Promise::delayed(args[0].clone())))
+});
+
+// (make-promise x), a promise that is already forced, promises are returned as they are
+eval_args!(fn make_promise(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("make-promise", args.len(), 1);
+    if args[0].get_promise().is_some() { return Ok(args[0].clone()); }
+    Ok(Value::new_promise(Promise::forced(args[0].clone())))
+});
+
+// (force x), the value of the promise x, other values are returned as they are
+eval_args!(fn force(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("force", args.len(), 1);
+    let promise = match args[0].get_promise() {
+        Some(promise) => promise,
+        None => return Ok(args[0].clone()),
+    };
+
+    match promise.thunk() {
+        Some(thunk) => {
+            let value = interpreter.call_procedure(&thunk, vec![])?;
+            let value = promise.fulfill(value);
+            gc::register_value(&args[0]);
+            Ok(value)
+        },
+        // safe because a promise is either delayed or forced
+        None => Ok(promise.value().unwrap()),
+    }
+});
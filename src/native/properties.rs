@@ -0,0 +1,56 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+
+// Property lists of symbols, e.g. for documentation or deprecation marks.
+// They're kept by the interpreter, so they don't change what a symbol is.
+
+// (symbol-put! symbol key value), returns value
+eval_args!(fn symbol_put(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("symbol-put!", args.len(), 3);
+    let symbol = try_unwrap_type!("symbol-put!", "symbol", Value::get_symbol, &args[0], interpreter);
+    let key = try_unwrap_type!("symbol-put!", "symbol", Value::get_symbol, &args[1], interpreter);
+    let properties = interpreter.symbol_properties.entry(symbol).or_insert_with(Vec::new);
+    match properties.iter_mut().find(|x| x.0 == key) {
+        Some(property) => property.1 = args[2].clone(),
+        None => properties.push((key, args[2].clone())),
+    }
+    Ok(args[2].clone())
+});
+
+// (symbol-get symbol key [default]), default is false if it's left out
+eval_args!(fn symbol_get(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("symbol-get", args.len(), 2, 3);
+    let symbol = try_unwrap_type!("symbol-get", "symbol", Value::get_symbol, &args[0], interpreter);
+    let key = try_unwrap_type!("symbol-get", "symbol", Value::get_symbol, &args[1], interpreter);
+    let value = interpreter.symbol_properties.get(&symbol).and_then(|properties| properties.iter().find(|x| x.0 == key));
+    match value {
+        Some(&(_, ref value)) => Ok(value.clone()),
+        None => Ok(args.get(2).cloned().unwrap_or_else(|| Value::new_bool(false))),
+    }
+});
+
+// (symbol-remove! symbol key), true if the symbol had the property
+eval_args!(fn symbol_remove(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("symbol-remove!", args.len(), 2);
+    let symbol = try_unwrap_type!("symbol-remove!", "symbol", Value::get_symbol, &args[0], interpreter);
+    let key = try_unwrap_type!("symbol-remove!", "symbol", Value::get_symbol, &args[1], interpreter);
+    let removed = match interpreter.symbol_properties.get_mut(&symbol) {
+        Some(properties) => {
+            let len = properties.len();
+            properties.retain(|x| x.0 != key);
+            properties.len() != len
+        },
+        None => false,
+    };
+    Ok(Value::new_bool(removed))
+});
+
+// (symbol-properties symbol), the properties as alist in the order they were first put
+eval_args!(fn symbol_properties(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("symbol-properties", args.len(), 1);
+    let symbol = try_unwrap_type!("symbol-properties", "symbol", Value::get_symbol, &args[0], interpreter);
+    let entries: Vec<Value> = interpreter.symbol_properties.get(&symbol).map_or(vec![], |properties| {
+        properties.iter().map(|&(key, ref value)| Value::new_pair(Value::new_symbol(key), value.clone())).collect()
+    });
+    Ok(Value::new_list(&entries))
+});
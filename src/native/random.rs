@@ -0,0 +1,27 @@
+use ::value::{Value, Condition};
+use ::interpreter::{Interpreter, Random};
+
+// Random numbers, every interpreter has its own generator, see Interpreter::random
+
+// (random [bound]), an integer in [0, bound) for an integer bound, a float in [0, bound) for a float one
+// and a float in [0, 1) without a bound
+eval_args!(fn random(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("random", args.len(), 0, 1);
+    let bound = match args.get(0) {
+        Some(bound) => bound,
+        None => return Ok(Value::new_float(interpreter.random.next_f64())),
+    };
+    match (bound.get_integer(), bound.get_float()) {
+        (Some(n), _) if n > 0 => Ok(Value::new_integer(interpreter.random.below(n as u64) as i64)),
+        (_, Some(x)) if x > 0.0 && x.is_finite() => Ok(Value::new_float(interpreter.random.next_f64() * x)),
+        _ => raise_condition!(Type, format!("random expected positive integer or float, got: {}", bound.to_string(&interpreter.interner))),
+    }
+});
+
+// (random-seed! seed), after this random returns the same numbers for the same seed
+eval_args!(fn random_seed(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("random-seed!", args.len(), 1);
+    let seed = try_unwrap_type!("random-seed!", "integer", Value::get_integer, &args[0], interpreter);
+    interpreter.random = Random::new(seed as u64);
+    Ok(Value::empty_list())
+});
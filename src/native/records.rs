@@ -0,0 +1,57 @@
+use ::value::{Value, Condition, RecordInstance};
+use ::interpreter::Interpreter;
+use ::gc;
+
+// Records, define-record-type is turned into procedures calling these with the record type
+
+// x if it's an instance of record_type
+fn instance<'a>(record_type: &Value, x: &'a Value) -> Option<&'a RecordInstance> {
+    x.get_record().and_then(|record| if record.record_type().eqv(record_type) { Some(record) } else { None })
+}
+
+// like try_unwrap_type, name is the accessor or modifier and the expected type is the name of record_type
+fn try_unwrap_instance<'a>(name: &Value, record_type: &Value, x: &'a Value, interpreter: &Interpreter) -> Result<&'a RecordInstance, Condition> {
+    match instance(record_type, x) {
+        Some(record) => Ok(record),
+        None => {
+            // safe because the natives are only called with the type created by define-record-type
+            let type_name = record_type.get_record_type().unwrap().name();
+            let s = format!("{} expected {}, got: {}", name.to_string(&interpreter.interner),
+                interpreter.interner.lookup(type_name).unwrap_or("record"), x.to_string(&interpreter.interner));
+            raise_condition!(Type, s);
+        },
+    }
+}
+
+// (%make-record type field*), the fields are in the order of the type, the constructor passes false for the missing ones
+eval_args!(fn make_record(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("make-record", args.len(), min => 1);
+    Ok(Value::new_record(RecordInstance::new(args[0].clone(), args[1..].to_vec())))
+});
+
+// (%record? type x)
+eval_args!(fn is_record(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("record?", args.len(), 2);
+    Ok(Value::new_bool(instance(&args[0], &args[1]).is_some()))
+});
+
+// (%record-ref type 'accessor index x), the name of the accessor is for conditions
+eval_args!(fn record_ref(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("record-ref", args.len(), 4);
+    let record = try_unwrap_instance(&args[1], &args[0], &args[3], interpreter)?;
+    let index = try_unwrap_index!("record-ref", &args[2], interpreter);
+    let value = record.fields().read().unwrap()[index].clone();
+    Ok(value)
+});
+
+// (%record-set! type 'modifier index x value), returns the record
+eval_args!(fn record_set(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("record-set!", args.len(), 5);
+    {
+        let record = try_unwrap_instance(&args[1], &args[0], &args[3], interpreter)?;
+        let index = try_unwrap_index!("record-set!", &args[2], interpreter);
+        record.fields().write().unwrap()[index] = args[4].clone();
+    }
+    gc::register_value(&args[3]);
+    Ok(args[3].clone())
+});
@@ -0,0 +1,150 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use itertools::Itertools;
+
+// String operations, all indices count chars, not bytes.
+// Strings are immutable, so there's no string-set!, new strings are built with string, make-string or list->string.
+
+// the range given by the optional start and end args, end defaults to len
+fn range(name: &str, len: usize, start: Option<&Value>, end: Option<&Value>, interpreter: &Interpreter) -> Result<(usize, usize), Condition> {
+    let start = match start {
+        Some(start) => try_unwrap_index!(name, start, interpreter),
+        None => 0,
+    };
+    let end = match end {
+        Some(end) => try_unwrap_index!(name, end, interpreter),
+        None => len,
+    };
+    assert_or_condition!(start <= end && end <= len, format!("{} index out of range: {}..{}", name, start, end));
+    Ok((start, end))
+}
+
+eval_args!(fn string_length(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-length", args.len(), 1);
+    let s = try_unwrap_type!("string-length", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_integer(s.chars().count() as i64))
+});
+
+eval_args!(fn string_ref(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-ref", args.len(), 2);
+    let s = try_unwrap_type!("string-ref", "string", Value::get_string, &args[0], interpreter);
+    let index = try_unwrap_index!("string-ref", &args[1], interpreter);
+    match s.chars().nth(index) {
+        Some(c) => Ok(Value::new_char(c)),
+        None => raise_condition!(format!("string-ref index out of range: {}", index)),
+    }
+});
+
+// (substring s start [end]), end defaults to the length of s
+eval_args!(fn substring(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("substring", args.len(), 2, 3);
+    let s = try_unwrap_type!("substring", "string", Value::get_string, &args[0], interpreter);
+    let (start, end) = range("substring", s.chars().count(), args.get(1), args.get(2), interpreter)?;
+    Ok(Value::new_string(s.chars().skip(start).take(end - start).collect::<String>()))
+});
+
+eval_args!(fn string_append(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    let mut res = String::new();
+    for x in args.iter() {
+        res.push_str(try_unwrap_type!("string-append", "string", Value::get_string, x, interpreter));
+    }
+    Ok(Value::new_string(res))
+});
+
+eval_args!(fn string_upcase(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-upcase", args.len(), 1);
+    let s = try_unwrap_type!("string-upcase", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_string(s.to_uppercase()))
+});
+
+eval_args!(fn string_downcase(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-downcase", args.len(), 1);
+    let s = try_unwrap_type!("string-downcase", "string", Value::get_string, &args[0], interpreter);
+    Ok(Value::new_string(s.to_lowercase()))
+});
+
+// (string-split s [separator]), without a separator s is split at whitespace
+eval_args!(fn string_split(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-split", args.len(), 1, 2);
+    let s = try_unwrap_type!("string-split", "string", Value::get_string, &args[0], interpreter);
+    let parts: Vec<Value> = match args.get(1) {
+        Some(separator) => {
+            let separator = try_unwrap_type!("string-split", "string", Value::get_string, separator, interpreter);
+            assert_or_condition!(!separator.is_empty(), "string-split expected non empty separator");
+            s.split(separator).map(Value::new_string).collect()
+        },
+        None => s.split_whitespace().map(Value::new_string).collect(),
+    };
+    Ok(Value::new_list(&parts))
+});
+
+// (string-join strings [separator]), separator defaults to a single space
+eval_args!(fn string_join(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-join", args.len(), 1, 2);
+    let list = try_unwrap_type!("string-join", "list", Value::get_list, &args[0], interpreter);
+    let separator = match args.get(1) {
+        Some(separator) => try_unwrap_type!("string-join", "string", Value::get_string, separator, interpreter),
+        None => " ",
+    };
+
+    let mut strings = Vec::with_capacity(list.len());
+    for x in list.iter() {
+        strings.push(try_unwrap_type!("string-join", "list of strings", Value::get_string, x, interpreter));
+    }
+    Ok(Value::new_string(strings.into_iter().join(separator)))
+});
+
+eval_args!(fn string_contains(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-contains?", args.len(), 2);
+    let s = try_unwrap_type!("string-contains?", "string", Value::get_string, &args[0], interpreter);
+    let pattern = try_unwrap_type!("string-contains?", "string", Value::get_string, &args[1], interpreter);
+    Ok(Value::new_bool(s.contains(pattern)))
+});
+
+// (string c*), a string of the chars
+eval_args!(fn string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    let mut res = String::new();
+    for x in args.iter() {
+        res.push(try_unwrap_type!("string", "char", Value::get_char, x, interpreter));
+    }
+    Ok(Value::new_string(res))
+});
+
+// (make-string n [c]), c defaults to a space
+eval_args!(fn make_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("make-string", args.len(), 1, 2);
+    let n = try_unwrap_index!("make-string", &args[0], interpreter);
+    let c = match args.get(1) {
+        Some(c) => try_unwrap_type!("make-string", "char", Value::get_char, c, interpreter),
+        None => ' ',
+    };
+    interpreter.reserve_memory(n.saturating_mul(c.len_utf8()))?;
+    Ok(Value::new_string(::std::iter::repeat(c).take(n).collect::<String>()))
+});
+
+// (string-copy s [start [end]]), same as substring, but start is optional too
+eval_args!(fn string_copy(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string-copy", args.len(), 1, 3);
+    let s = try_unwrap_type!("string-copy", "string", Value::get_string, &args[0], interpreter);
+    let (start, end) = range("string-copy", s.chars().count(), args.get(1), args.get(2), interpreter)?;
+    Ok(Value::new_string(s.chars().skip(start).take(end - start).collect::<String>()))
+});
+
+// (string->list s [start [end]])
+eval_args!(fn string_list(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("string->list", args.len(), 1, 3);
+    let s = try_unwrap_type!("string->list", "string", Value::get_string, &args[0], interpreter);
+    let (start, end) = range("string->list", s.chars().count(), args.get(1), args.get(2), interpreter)?;
+    let chars: Vec<Value> = s.chars().skip(start).take(end - start).map(Value::new_char).collect();
+    Ok(Value::new_list(&chars))
+});
+
+eval_args!(fn list_string(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("list->string", args.len(), 1);
+    let list = try_unwrap_type!("list->string", "list", Value::get_list, &args[0], interpreter);
+    let mut res = String::new();
+    for x in list.iter() {
+        res.push(try_unwrap_type!("list->string", "list of chars", Value::get_char, x, interpreter));
+    }
+    Ok(Value::new_string(res))
+});
@@ -0,0 +1,82 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use super::ports::output;
+
+// Unit tests, (define-test name body) registers a test and (run-tests) runs all of them in the order they were defined.
+// A test fails if it raises a condition, e.g. by a failed assertion.
+
+// (%define-test 'name thunk), (define-test name body) is turned into this, a test with the same name is replaced
+eval_args!(fn define_test(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("define-test", args.len(), 2);
+    let name = try_unwrap_type!("define-test", "symbol", Value::get_symbol, &args[0], interpreter);
+    let thunk = args[1].clone();
+    match interpreter.tests.iter().position(|test| test.0 == name) {
+        Some(i) => interpreter.tests[i].1 = thunk,
+        None => interpreter.tests.push((name, thunk)),
+    }
+    Ok(args[0].clone())
+});
+
+// (assert-equal expected actual [message]), raises a condition unless expected and actual are equal?
+eval_args!(fn assert_equal(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("assert-equal", args.len(), 2, 3);
+    if args[0].equal(&args[1]) { return Ok(Value::new_bool(true)); }
+    let failure = format!("assert-equal expected {}, got: {}", args[0].to_string(&interpreter.interner), args[1].to_string(&interpreter.interner));
+    fail(failure, args.get(2), interpreter)
+});
+
+// (assert-true x [message]), raises a condition if x is false
+eval_args!(fn assert_true(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("assert-true", args.len(), 1, 2);
+    if args[0].get_bool() != Some(false) { return Ok(Value::new_bool(true)); }
+    fail("assert-true got false".to_string(), args.get(1), interpreter)
+});
+
+// (%assert-raises 'expr thunk), (assert-raises expr) is turned into this, raises a condition unless calling thunk does
+eval_args!(fn assert_raises(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("assert-raises", args.len(), 2);
+    match interpreter.call_procedure(&args[1], vec![]) {
+        Err(ref condition) if condition.is_catchable() => Ok(Value::new_bool(true)),
+        Err(condition) => Err(condition),
+        Ok(value) => {
+            let failure = format!("assert-raises expected a condition from {}, got: {}",
+                                  args[0].to_string(&interpreter.interner), value.to_string(&interpreter.interner));
+            fail(failure, None, interpreter)
+        },
+    }
+});
+
+// the message of the assertion is written after what went wrong
+fn fail(failure: String, message: Option<&Value>, interpreter: &Interpreter) -> Result<Value, Condition> {
+    match message {
+        Some(message) => raise_condition!(format!("{}: {}", failure, message.to_display_string(&interpreter.interner))),
+        None => raise_condition!(failure),
+    }
+}
+
+// (run-tests [port]), runs the tests and writes their results and a summary, returns the number of failed tests
+// ok addition
+// FAIL subtraction: assert-equal expected 1, got: 2
+// 2 tests, 1 passed, 1 failed
+eval_args!(fn run_tests(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("run-tests", args.len(), 0, 1);
+    let tests = interpreter.tests.clone();
+    let mut failed = 0;
+    for (name, thunk) in tests {
+        let name = Value::new_symbol(name).to_string(&interpreter.interner);
+        let line = match interpreter.call_procedure(&thunk, vec![]) {
+            Ok(_) => format!("ok {}\n", name),
+            // e.g. the fuel ran out, the other tests couldn't run either
+            Err(ref condition) if !condition.is_catchable() => return Err(condition.clone()),
+            Err(ref condition) => {
+                failed += 1;
+                format!("FAIL {}: {}\n", name, condition.to_string(&interpreter.interner))
+            },
+        };
+        output("run-tests", &line, args.get(0), interpreter)?;
+    }
+    let count = interpreter.tests.len();
+    let summary = format!("{} tests, {} passed, {} failed\n", count, count - failed, failed);
+    output("run-tests", &summary, args.get(0), interpreter)?;
+    Ok(Value::new_integer(failed as i64))
+});
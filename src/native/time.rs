@@ -0,0 +1,68 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use super::ports::output;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Clocks, current-seconds and current-milliseconds follow the system clock, runtime and time are monotonic
+
+fn since_epoch() -> Result<::std::time::Duration, Condition> {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => Ok(duration),
+        Err(_) => raise_condition!("the system clock is set before 1970"),
+    }
+}
+
+// (current-seconds), the seconds since 1970-01-01 00:00 UTC
+eval_args!(fn current_seconds(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("current-seconds", args.len(), 0);
+    Ok(Value::new_integer(since_epoch()?.as_secs() as i64))
+});
+
+// (current-milliseconds), like current-seconds in milliseconds
+eval_args!(fn current_milliseconds(args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("current-milliseconds", args.len(), 0);
+    let duration = since_epoch()?;
+    Ok(Value::new_integer(duration.as_secs() as i64 * 1000 + duration.subsec_nanos() as i64 / 1_000_000))
+});
+
+fn seconds(since: Instant) -> f64 {
+    let duration = since.elapsed();
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+// (runtime), the seconds since the interpreter was created as a float, never goes backwards
+eval_args!(fn runtime(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("runtime", args.len(), 0);
+    Ok(Value::new_float(seconds(interpreter.created)))
+});
+
+// the user and system time the process used in seconds, of all threads, None if it can't be found out
+#[cfg(unix)]
+fn cpu_seconds() -> Option<f64> {
+    // safe because rusage is plain data, getrusage fills it in
+    let mut usage: ::libc::rusage = unsafe { ::std::mem::zeroed() };
+    if unsafe { ::libc::getrusage(::libc::RUSAGE_SELF, &mut usage) } != 0 { return None; }
+    let seconds = |time: ::libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 / 1e6;
+    Some(seconds(usage.ru_utime) + seconds(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_seconds() -> Option<f64> {
+    None
+}
+
+// (%time f), (time expr) is turned into this, with f being (lambda () expr)
+// writes how long evaluating expr took and the cpu time it used to the current output port and returns its value
+eval_args!(fn time(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("time", args.len(), 1);
+    let (start, cpu_start) = (Instant::now(), cpu_seconds());
+    let res = interpreter.call_procedure(&args[0], vec![])?;
+    let elapsed = seconds(start);
+
+    let report = match (cpu_start, cpu_seconds()) {
+        (Some(cpu_start), Some(cpu_end)) => format!("time: {:.3} ms, cpu: {:.3} ms\n", elapsed * 1000.0, (cpu_end - cpu_start) * 1000.0),
+        _ => format!("time: {:.3} ms\n", elapsed * 1000.0),
+    };
+    output("time", &report, None, interpreter)?;
+    Ok(res)
+});
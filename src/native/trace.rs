@@ -0,0 +1,120 @@
+use ::value::{Value, Condition};
+use ::interpreter::{Interpreter, Profile, Coverage};
+use super::ports::output;
+
+// Tracing, (trace f) writes the calls of f and their results until (untrace f), see Interpreter::apply_traced.
+// (break) and breakpoints stop in the debugger, the repl has one in :debug mode.
+// (profile-start) counts the calls of procedures until (profile-report), see interpreter::Profile.
+// (coverage-start) tracks the lines of code that are evaluated, see interpreter::Coverage.
+
+// (trace f+), only procedures written in flip can be traced
+eval_args!(fn trace(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("trace", args.len(), min => 1);
+    for x in args.iter() {
+        try_unwrap_type!("trace", "procedure written in flip", Value::get_proc, x, interpreter);
+        if !interpreter.is_traced(x) { interpreter.traced.push(x.clone()); }
+    }
+    Ok(Value::empty_list())
+});
+
+// (untrace f*), without procedures all are untraced
+eval_args!(fn untrace(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    if args.is_empty() {
+        interpreter.traced.clear();
+    } else {
+        interpreter.traced.retain(|x| !args.iter().any(|f| f.eqv(x)));
+    }
+    Ok(Value::empty_list())
+});
+
+// (break [message]), calls the debugger if there is one, see Interpreter::debug_break
+eval_args!(fn debug_break(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("break", args.len(), 0, 1);
+    let reason = match args.get(0) {
+        Some(message) => format!("break: {}", message.to_display_string(&interpreter.interner)),
+        None => "break".to_string(),
+    };
+    interpreter.debug_break(&reason);
+    Ok(Value::empty_list())
+});
+
+// adds the symbols to names or removes them from it, for the natives of breakpoints and watchpoints
+fn mark(name: &str, args: &[Value], add: bool, names: fn(&mut Interpreter) -> &mut Vec<u64>, interpreter: &mut Interpreter) -> Result<Value, Condition> {
+    check_arity!(name, args.len(), min => 1);
+    for x in args {
+        let id = try_unwrap_type!(name, "symbol", Value::get_symbol, x, interpreter);
+        let marked = names(interpreter);
+        marked.retain(|&other| other != id);
+        if add { marked.push(id); }
+    }
+    Ok(Value::empty_list())
+}
+
+fn breakpoints(interpreter: &mut Interpreter) -> &mut Vec<u64> {
+    &mut interpreter.breakpoints
+}
+
+fn watched(interpreter: &mut Interpreter) -> &mut Vec<u64> {
+    &mut interpreter.watched
+}
+
+// (set-breakpoint 'name+), calls of the names stop in the debugger
+eval_args!(fn set_breakpoint(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    mark("set-breakpoint", args, true, breakpoints, interpreter)
+});
+
+eval_args!(fn remove_breakpoint(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    mark("remove-breakpoint", args, false, breakpoints, interpreter)
+});
+
+// (watch 'name+), set! of the names writes the old and the new value
+eval_args!(fn watch(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    mark("watch", args, true, watched, interpreter)
+});
+
+eval_args!(fn unwatch(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    mark("unwatch", args, false, watched, interpreter)
+});
+
+// (profile-start), starts counting calls and their time anew
+eval_args!(fn profile_start(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("profile-start", args.len(), 0);
+    interpreter.profile = Some(Profile::new());
+    Ok(Value::empty_list())
+});
+
+// (profile-report [port]), stops profiling and writes a table of the calls since profile-start
+eval_args!(fn profile_report(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("profile-report", args.len(), 0, 1);
+    match interpreter.profile.take() {
+        Some(profile) => output("profile-report", &profile.report(), args.get(0), interpreter),
+        None => raise_condition!("profile-report without profile-start"),
+    }
+});
+
+// (coverage-start), starts tracking the evaluated lines anew
+eval_args!(fn coverage_start(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("coverage-start", args.len(), 0);
+    interpreter.coverage = Some(Coverage::new());
+    Ok(Value::empty_list())
+});
+
+fn write_coverage(name: &str, lcov: bool, args: &[Value], interpreter: &mut Interpreter) -> Result<Value, Condition> {
+    check_arity!(name, args.len(), 0, 1);
+    let report = match interpreter.coverage {
+        Some(ref coverage) if lcov => coverage.lcov(&interpreter.locations),
+        Some(ref coverage) => coverage.report(&interpreter.locations),
+        None => raise_condition!(format!("{} without coverage-start", name)),
+    };
+    output(name, &report, args.get(0), interpreter)
+}
+
+// (coverage-report [port]), writes how many lines of each source were evaluated and which weren't
+eval_args!(fn coverage_report(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    write_coverage("coverage-report", false, args, interpreter)
+});
+
+// (coverage-lcov [port]), writes the coverage in the lcov format
+eval_args!(fn coverage_lcov(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    write_coverage("coverage-lcov", true, args, interpreter)
+});
@@ -0,0 +1,28 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+
+// Userdata, rust objects of applications embedding the interpreter
+
+// (userdata-type x), the type name given by the application as string
+eval_args!(fn userdata_type(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("userdata-type", args.len(), 1);
+    let userdata = try_unwrap_type!("userdata-type", "userdata", Value::get_userdata, &args[0], interpreter);
+    Ok(Value::new_string(userdata.type_name()))
+});
+
+// (call-method x 'name args*), calls the method with x and args
+eval_args!(fn call_method(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("call-method", args.len(), min => 2);
+    let method = {
+        let userdata = try_unwrap_type!("call-method", "userdata", Value::get_userdata, &args[0], interpreter);
+        let id = try_unwrap_type!("call-method", "symbol", Value::get_symbol, &args[1], interpreter);
+        let name = interpreter.interner.lookup(id).unwrap_or("");
+        match userdata.method(name) {
+            Some(method) => method.clone(),
+            None => raise_condition!(format!("call-method: {} has no method {}", userdata.type_name(), name)),
+        }
+    };
+    let mut method_args = vec![args[0].clone()];
+    method_args.extend(args[2..].iter().cloned());
+    interpreter.call_procedure(&method, method_args)
+});
@@ -0,0 +1,20 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+
+// Multiple values, (values 1 2) returns both without consing a list, call-with-values passes them on
+
+// (values x*), a single value is returned as it is
+eval_args!(fn values(args: &mut [Value]) -> Result<Value, Condition> {
+    Ok(Value::new_values(args.to_vec()))
+});
+
+// (call-with-values producer consumer), calls consumer with the values producer returns
+eval_args!(fn call_with_values(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("call-with-values", args.len(), 2);
+    let produced = interpreter.call_procedure(&args[0], vec![])?;
+    let values = match produced.get_values() {
+        Some(values) => values.to_vec(),
+        None => vec![produced.clone()],
+    };
+    interpreter.call_procedure(&args[1], values)
+});
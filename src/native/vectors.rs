@@ -0,0 +1,63 @@
+use ::value::{Value, Condition};
+use ::interpreter::Interpreter;
+use ::gc;
+use std::mem;
+
+// Vector operations, unlike lists vectors are mutable and have O(1) indexed access
+
+// (make-vector len [fill]), fill defaults to 0
+eval_args!(fn make_vector(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("make-vector", args.len(), 1, 2);
+    let len = try_unwrap_index!("make-vector", &args[0], interpreter);
+    let fill = args.get(1).cloned().unwrap_or_else(|| Value::new_integer(0));
+    interpreter.reserve_memory(len.saturating_mul(mem::size_of::<Value>()))?;
+    Ok(Value::new_vector(vec![fill; len]))
+});
+
+eval_args!(fn vector(args: &mut [Value]) -> Result<Value, Condition> {
+    Ok(Value::new_vector(args.to_vec()))
+});
+
+eval_args!(fn vector_ref(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("vector-ref", args.len(), 2);
+    let vector = try_unwrap_type!("vector-ref", "vector", Value::get_vector, &args[0], interpreter);
+    let index = try_unwrap_index!("vector-ref", &args[1], interpreter);
+    let value = vector.read().unwrap().get(index).cloned();
+    match value {
+        Some(x) => Ok(x),
+        None => raise_condition!(format!("vector-ref index out of range: {}", index)),
+    }
+});
+
+// returns the vector
+eval_args!(fn vector_set(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("vector-set!", args.len(), 3);
+    {
+        let vector = try_unwrap_type!("vector-set!", "vector", Value::get_vector, &args[0], interpreter);
+        let index = try_unwrap_index!("vector-set!", &args[1], interpreter);
+        match vector.write().unwrap().get_mut(index) {
+            Some(x) => *x = args[2].clone(),
+            None => raise_condition!(format!("vector-set! index out of range: {}", index)),
+        }
+    }
+    gc::register_value(&args[0]);
+    Ok(args[0].clone())
+});
+
+eval_args!(fn vector_length(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("vector-length", args.len(), 1);
+    let vector = try_unwrap_type!("vector-length", "vector", Value::get_vector, &args[0], interpreter);
+    Ok(Value::new_integer(vector.read().unwrap().len() as i64))
+});
+
+eval_args!(fn vector_list(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("vector->list", args.len(), 1);
+    let vector = try_unwrap_type!("vector->list", "vector", Value::get_vector, &args[0], interpreter);
+    Ok(Value::new_list(&vector.read().unwrap()))
+});
+
+eval_args!(fn list_vector(interpreter: &mut Interpreter, args: &mut [Value]) -> Result<Value, Condition> {
+    check_arity!("list->vector", args.len(), 1);
+    let list = try_unwrap_type!("list->vector", "list", Value::get_list, &args[0], interpreter);
+    Ok(Value::new_vector(list))
+});
@@ -1,58 +1,200 @@
 use rustyline;
+use rustyline::error::ReadlineError;
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
 use std::iter;
 use std::collections::btree_set::BTreeSet;
-use ::interpreter;
-use ::grammar::{self, error_printing};
+use ::interpreter::{self, DebugAction};
+use ::grammar::{self, error_printing, Reader};
 use ::value::Value;
 
 pub struct Repl {}
 
 impl Repl {
-    pub fn start() {
+    pub fn start(use_vm: bool) {
         let quit = "(quit)";
-        let break_chars: BTreeSet<char> = vec![' ', '(', '\''].into_iter().collect();
+        let break_chars: BTreeSet<char> = vec![' ', '\t', '(', ')', '\'', '`', ',', '@'].into_iter().collect();
+        let history_file = env::var_os("HOME").map(|home| PathBuf::from(home).join(".flip_history"));
         let mut rl = rustyline::Editor::<IdentCompleter>::new();
+        // there's no history yet on the first start
+        if let Some(ref path) = history_file { let _ = rl.load_history(path); }
         rl.add_history_entry(quit);
 
         let mut interpreter = interpreter::Interpreter::new();
+        interpreter.use_vm = use_vm;
+        // the lines of unfinished data, each datum is evaluated once all its parens and strings are closed
+        let mut reader = Reader::new();
 
         loop {
-            let idents: Vec<String> = interpreter.current_scope.symbol_ids()
+            // bindings of all enclosing scopes, macros and special forms
+            let mut idents: Vec<String> = interpreter.current_scope.bindings(None)
             .into_iter()
+            .map(|(id, _)| id)
+            .chain(interpreter.macros.names())
             .filter_map(|id| interpreter.interner.lookup(id).map(Into::into))
+            .chain(grammar::KEYWORDS.iter().map(|&s| s.into()))
             .collect();
+            idents.sort();
+            idents.dedup();
 
             let completer = IdentCompleter {
                 break_chars: &break_chars,
-                ident_list: idents
+                ident_list: idents,
+                previous_lines: reader.buffered().to_string(),
             };
 
             rl.set_completer(Some(completer));
 
-            let line = rl.readline(">> ");
-            let line = if line.is_err() { return } else { line.unwrap() };
-            if line == quit { return }
+            let prompt = if reader.is_empty() { ">> " } else { ".. " };
+            let line = match rl.readline(prompt) {
+                // without a terminal the line ending is kept
+                Ok(line) => line.trim_end_matches(|c| c == '\n' || c == '\r').to_string(),
+                // Ctrl-C throws away the current input
+                Err(ReadlineError::Interrupted) => { reader.clear(); continue },
+                // Ctrl-D or a broken terminal
+                Err(_) => break,
+            };
+            if reader.is_empty() && line == quit { break }
+            if reader.is_empty() && line.trim().is_empty() { continue }
             rl.add_history_entry(&line);
+            if reader.is_empty() && line.trim() == ":debug" {
+                toggle_debugger(&mut interpreter, use_vm);
+                continue;
+            }
+            if reader.is_empty() && line.starts_with(",doc ") {
+                print_doc(&mut interpreter, line[5..].trim());
+                continue;
+            }
+
+            reader.feed(&line);
+            reader.feed("\n");
+            while let Some((input, line_no)) = reader.next_source() {
+                eval_input(&mut interpreter, &input, line_no);
+            }
+        }
+
+        if let Some(ref path) = history_file {
+            if let Err(err) = rl.save_history(path) {
+                println!("could not save history: {}", err);
+            }
+        }
+    }
+}
 
-            // strip \n at the end
-            let parsed = grammar::parse(&line, &mut interpreter.interner);
-            match parsed {
-                Ok(values) => {
-                    let mut result = Value::empty_list();
-                    for x in &values {
-                        result = interpreter.evaluate(x);
+// evaluates a complete datum of the repl and prints its result, input starts at line_no
+fn eval_input(interpreter: &mut interpreter::Interpreter, input: &str, line_no: usize) {
+    let parsed = grammar::parse_with_locations(input, "repl", line_no, &mut interpreter.interner,
+                                               &mut interpreter.macros, &mut interpreter.locations);
+    match parsed {
+        Ok(values) => {
+            let mut result = Ok(Value::empty_list());
+            for x in &values {
+                result = interpreter.evaluate(x);
+                if result.is_err() { break }
+            }
+            interpreter.stop_stepping();
+            match result {
+                Ok(value) => {
+                    // large results start on their own line
+                    let pretty = value.pretty_string(&interpreter.interner, 80);
+                    if pretty.contains('\n') {
+                        println!("*1 =>\n{}", pretty);
+                    } else {
+                        println!("*1 => {}", pretty);
                     }
-                    println!("=> {}", result.to_string(&interpreter.interner))
+                    remember_result(interpreter, value);
                 },
-                Err(ref err)  => println!("{}", error_printing::create_error_message(&line, err)),
+                Err(condition) => println!("error: {}", condition.to_string_with_backtrace(&interpreter.interner)),
             }
+        },
+        Err(ref err)  => println!("{}", error_printing::create_error_message(input, err)),
+    }
+}
+
+// ,doc name prints the documentation of name, see Interpreter::documentation
+fn print_doc(interpreter: &mut interpreter::Interpreter, name: &str) {
+    let id = interpreter.interner.intern(name);
+    match interpreter.documentation(id) {
+        Some(doc) => println!("{}", doc),
+        None => println!("no documentation for {}", name),
+    }
+}
+
+const DEBUG_HELP: &'static str = ":step (:s), :next (:n) and :continue (:c) go on, :bindings (:b) shows the bindings of the
+innermost scope and :backtrace (:bt) the calls, anything else is evaluated in the scope the program stopped in";
+
+// :debug switches the debugger on and off, the vm is off while debugging so stepping sees all code
+fn toggle_debugger(interpreter: &mut interpreter::Interpreter, use_vm: bool) {
+    if interpreter.debugger.take().is_some() {
+        interpreter.use_vm = use_vm;
+        println!("debugger off");
+    } else {
+        interpreter.debugger = Some(Box::new(debug_prompt));
+        interpreter.use_vm = false;
+        println!("debugger on, it stops at (break) and conditions, :help lists its commands");
+    }
+}
+
+// the prompt of the debugger, reads commands until one of them lets the program go on
+fn debug_prompt(interpreter: &mut interpreter::Interpreter, reason: &str) -> DebugAction {
+    println!("stopped at {}", reason);
+    loop {
+        print!("debug> ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            // the end of the input lets the program finish
+            Ok(0) | Err(_) => return DebugAction::Continue,
+            Ok(_) => {},
+        }
+        match line.trim() {
+            "" => {},
+            ":step" | ":s" => return DebugAction::Step,
+            ":next" | ":n" => return DebugAction::Next,
+            ":continue" | ":c" => return DebugAction::Continue,
+            ":bindings" | ":b" => for (id, value) in interpreter.current_scope.bindings(Some(1)) {
+                println!("{} = {}", Value::new_symbol(id).to_string(&interpreter.interner), value.to_string(&interpreter.interner));
+            },
+            ":backtrace" | ":bt" => for frame in interpreter.backtrace() {
+                println!("{}", frame);
+            },
+            ":help" => println!("{}", DEBUG_HELP),
+            input => {
+                let parsed = grammar::parse_with_locations(input, "debug", 1, &mut interpreter.interner,
+                                                           &mut interpreter.macros, &mut interpreter.locations);
+                match parsed {
+                    Ok(values) => for x in &values {
+                        match interpreter.evaluate(x) {
+                            Ok(value) => println!("=> {}", value.to_string(&interpreter.interner)),
+                            Err(condition) => { println!("error: {}", condition.to_string(&interpreter.interner)); break },
+                        }
+                    },
+                    Err(ref err) => println!("{}", error_printing::create_error_message(input, err)),
+                }
+            },
+        }
+    }
+}
+
+// the last three results are bound to *1, *2 and *3, the last one also to it
+fn remember_result(interpreter: &mut interpreter::Interpreter, value: Value) {
+    let ids: Vec<u64> = ["*1", "*2", "*3"].iter().map(|&s| interpreter.interner.intern(s)).collect();
+    for i in (1..ids.len()).rev() {
+        if let Some(older) = interpreter.current_scope.lookup_symbol(ids[i - 1]) {
+            interpreter.current_scope.add_symbol(ids[i], older);
         }
     }
+    interpreter.current_scope.add_symbol(ids[0], value.clone());
+    let it = interpreter.interner.intern("it");
+    interpreter.current_scope.add_symbol(it, value);
 }
 
 struct IdentCompleter<'a> {
     break_chars: &'a BTreeSet<char>,
     ident_list: Vec<String>,
+    // the unfinished input before the current line, for closing its parens
+    previous_lines: String,
 }
 
 impl<'a> rustyline::completion::Completer for IdentCompleter<'a> {
@@ -66,13 +208,13 @@ impl<'a> rustyline::completion::Completer for IdentCompleter<'a> {
 
         // if word is just whitespaces return closing parens as first result
         let matches: Vec<String> = if word.chars().all(|c| c == ' ') {
-            iter::once(close_params(line))
+            iter::once(close_params(&self.previous_lines, line))
             .chain(symbol_matches)
             .collect()
         // else return closing parens as last result
         } else {
             symbol_matches
-            .chain(iter::once(close_params(line)))
+            .chain(iter::once(close_params(&self.previous_lines, line)))
             .collect()
         };
 
@@ -80,8 +222,8 @@ impl<'a> rustyline::completion::Completer for IdentCompleter<'a> {
     }
 }
 
-fn close_params(line: &str) -> String {
-    let unclosed_parens = line.chars()
+fn close_params(previous_lines: &str, line: &str) -> String {
+    let unclosed_parens = previous_lines.chars().chain(line.chars())
     .fold(0, |n, c| if c == '(' { n + 1 } else if c == ')' { n - 1 } else { n });
     let missing_parens: String = iter::repeat(')').take(unclosed_parens).collect();
     missing_parens
@@ -1,14 +1,14 @@
 // Shamelessly copied and adapted from: https://github.com/rust-unofficial/too-many-lists/blob/master/lists/src/third.rs (16.12.2016)
 // (c) Alexis Beingessner
 
-use std::rc::Rc;
+use std::sync::{Arc, Weak};
 
 #[derive(Debug, PartialEq)]
 pub struct List<T> {
     list_head: Link<T>,
 }
 
-type Link<T> = Option<Rc<Node<T>>>;
+type Link<T> = Option<Arc<Node<T>>>;
 
 #[derive(Debug, Clone, PartialEq)]
 struct Node<T> {
@@ -24,7 +24,7 @@ impl<T> List<T> {
     pub fn append(&self, elem: T) -> List<T> {
         List {
             list_head: Some(
-                    Rc::new(Node {
+                    Arc::new(Node {
                         elem: elem,
                         next: self.list_head.clone(),
                     })
@@ -32,19 +32,54 @@ impl<T> List<T> {
         }
     }
 
-    /*
     pub fn tail(&self) -> List<T> {
         List { list_head: self.list_head.as_ref().and_then(|node| node.next.clone()) }
     }
-    */
 
     pub fn head(&self) -> Option<&T> {
         self.list_head.as_ref().map(|node| &node.elem)
     }
 
+    // true if both lists share the same head node
+    pub fn ptr_eq(&self, other: &List<T>) -> bool {
+        match (self.list_head.as_ref(), other.list_head.as_ref()) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
     pub fn iter(&self) -> Iter<T> {
         Iter::new(self)
     }
+
+    // the address and the number of strong references of the head node, for the cycle collector
+    pub fn head_address(&self) -> Option<usize> {
+        self.list_head.as_ref().map(|node| &**node as *const Node<T> as usize)
+    }
+
+    pub fn head_strong_count(&self) -> usize {
+        self.list_head.as_ref().map_or(0, Arc::strong_count)
+    }
+
+    pub fn downgrade(&self) -> WeakList<T> {
+        WeakList { list_head: self.list_head.as_ref().map(Arc::downgrade) }
+    }
+}
+
+// Refers to a list without keeping its nodes alive.
+pub struct WeakList<T> {
+    list_head: Option<Weak<Node<T>>>,
+}
+
+impl<T> WeakList<T> {
+    // None once the head node was dropped
+    pub fn upgrade(&self) -> Option<List<T>> {
+        match self.list_head {
+            Some(ref weak) => weak.upgrade().map(|node| List { list_head: Some(node) }),
+            None => Some(List::new()),
+        }
+    }
 }
 
 // This clones the first element of the list and returns a "new" list
@@ -55,6 +90,20 @@ impl<T> Clone for List<T> {
     }
 }
 
+// dropping has to be done iteratively, long lists would overflow the stack otherwise
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        let mut head = self.list_head.take();
+        while let Some(node) = head {
+            if let Ok(mut node) = Arc::try_unwrap(node) {
+                head = node.next.take();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Iter<'a, T:'a> {
     next: Option<&'a Node<T>>,
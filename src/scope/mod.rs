@@ -1,24 +1,45 @@
 mod linked_list;
 
 use std::collections::hash_map::HashMap;
-use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::mem;
+use std::usize;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use ::value::Value;
-use self::linked_list::List;
+use ::gc::{self, Heap};
+use self::linked_list::{List, WeakList};
 
 // The list holds ScopeData structs, which store the actual data (say bindings etc. etc.)
 // The front of the list is the lastly created ScopeData. The back is the last ScopeData that
 // should be searched when trying to match a binding.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone)]
 pub struct Scope {
-    list: List<RefCell<ScopeData>>,
+    list: List<RwLock<ScopeData>>,
+}
+
+// Scopes are compared by identity. Procedures capture the scope they're defined in
+// and are usually bound in that very scope, so comparing contents would never terminate.
+impl PartialEq for Scope {
+    fn eq(&self, other: &Scope) -> bool {
+        self.list.ptr_eq(&other.list)
+    }
+}
+
+// same reasoning as for PartialEq
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Scope {{ depth: {} }}", self.list.iter().count())
+    }
 }
 
 impl Scope {
     pub fn new() -> Self {
-        Scope::with_scope_data(RefCell::new(ScopeData::new()))
+        Scope::with_scope_data(RwLock::new(ScopeData::new()))
     }
 
-    fn with_scope_data(scope_data: RefCell<ScopeData>) -> Self {
+    fn with_scope_data(scope_data: RwLock<ScopeData>) -> Self {
         Scope {
             list: List::new().append(scope_data),
         }
@@ -26,16 +47,16 @@ impl Scope {
 
     // creates a child scope linked with it's parents
     pub fn new_child(&self) -> Self {
-        let fresh_data = RefCell::new(ScopeData::new());
+        let fresh_data = RwLock::new(ScopeData::new());
         Scope {
             list: self.list.append(fresh_data)
         }
     }
 
     pub fn lookup_symbol(&self, id: u64) -> Option<Value> {
-        for scope_data in self.list.iter().map(RefCell::borrow) {
-            match scope_data.lookup_symbol(id) {
-                // has to clone because of refcell
+        for scope_data in self.list.iter() {
+            match scope_data.read().unwrap().lookup_symbol(id) {
+                // has to clone because of the lock
                 Some(v) => return Some(v.clone()),
                 None => continue,
             }
@@ -43,39 +64,261 @@ impl Scope {
         None
     }
 
+    // for define, the binding shadows bindings of outer scopes that were resolved to lexical addresses
     pub fn add_symbol(&mut self, id: u64, value: Value) {
-        self.list.head().expect("internal error: scope without data")
-        .borrow_mut().bindings.insert(id, value);
+        let mut scope_data = self.head().write().unwrap();
+        scope_data.insert(id, value);
+        scope_data.defined = true;
+    }
+
+    // for the bindings of procedures and binding forms, their names are known when the code is compiled
+    pub fn bind(&mut self, id: u64, value: Value) {
+        self.head().write().unwrap().insert(id, value);
+    }
+
+    // changes the nearest binding of id, returns false if id is unbound
+    pub fn set_symbol(&self, id: u64, value: Value) -> bool {
+        for scope_data in self.list.iter() {
+            let mut scope_data = scope_data.write().unwrap();
+            if let Some(i) = scope_data.position(id) {
+                scope_data.values[i] = value;
+                return true;
+            }
+        }
+        false
+    }
+
+    // The binding of id at the address, without searching the scopes in between.
+    // None if the address doesn't hold id or a define in between might shadow it,
+    // lookup_symbol gives the right binding then.
+    pub fn lookup_address(&self, id: u64, address: Address) -> Option<Value> {
+        self.find_address(id, address, |scope_data, i| scope_data.values[i].clone())
+    }
+
+    // like lookup_address, returns false if set_symbol has to be used instead
+    pub fn set_address(&self, id: u64, address: Address, value: &Value) -> bool {
+        self.find_address(id, address, |scope_data, i| scope_data.values[i] = value.clone()).is_some()
+    }
+
+    fn find_address<F, T>(&self, id: u64, address: Address, f: F) -> Option<T>
+    where F: FnOnce(&mut ScopeData, usize) -> T {
+        for (depth, scope_data) in self.list.iter().enumerate() {
+            let mut scope_data = scope_data.write().unwrap();
+            if depth == address.depth {
+                if scope_data.names.get(address.index) != Some(&id) { return None; }
+                return Some(f(&mut scope_data, address.index));
+            }
+            if scope_data.defined { return None; }
+        }
+        None
+    }
+
+    // Like lookup_symbol, but remembers where the binding was found. The scopes the cache skips
+    // are only searched if define added bindings to them, the binding at the remembered address
+    // is used without searching its scope, so globals like + are found without hashing.
+    pub fn lookup_cached(&self, id: u64, cache: &LookupCache) -> Option<Value> {
+        for (depth, scope_data) in self.list.iter().enumerate() {
+            let scope_data = scope_data.read().unwrap();
+            if depth < cache.skip && !scope_data.defined { continue; }
+
+            if let Some(address) = cache.address() {
+                if address.depth == depth && scope_data.names.get(address.index) == Some(&id) {
+                    return Some(scope_data.values[address.index].clone());
+                }
+            }
+            if let Some(i) = scope_data.position(id) {
+                cache.set_address(Address { depth: depth, index: i });
+                return Some(scope_data.values[i].clone());
+            }
+        }
+        None
     }
 
     pub fn symbol_ids<'a>(&'a self) -> Vec<u64> {
         let mut symbol_strings: Vec<u64> = vec![];
-        for scope in self.list.iter().map(RefCell::borrow) {
-            for id in scope.symbol_ids() {
+        for scope in self.list.iter() {
+            for id in scope.read().unwrap().symbol_ids() {
                 symbol_strings.push(id);
             }
         }
         symbol_strings
     }
+
+    // the bindings of the innermost depth scopes, or of all of them if depth is None,
+    // innermost first and in the order they were added, shadowed bindings are left out
+    pub fn bindings(&self, depth: Option<usize>) -> Vec<(u64, Value)> {
+        let mut seen = HashSet::new();
+        let mut bindings = vec![];
+        for scope_data in self.list.iter().take(depth.unwrap_or(usize::MAX)) {
+            let scope_data = scope_data.read().unwrap();
+            for (&id, value) in scope_data.names.iter().zip(scope_data.values.iter()) {
+                if seen.insert(id) { bindings.push((id, value.clone())); }
+            }
+        }
+        bindings
+    }
+
+    // The following functions are used by the cycle collector, see gc.
+    // A scope is identified by its innermost ScopeData.
+
+    pub fn address(&self) -> usize {
+        self.list.head_address().unwrap_or(0)
+    }
+
+    pub fn strong_count(&self) -> usize {
+        self.list.head_strong_count()
+    }
+
+    // the values bound in the innermost ScopeData and the scope it's a child of,
+    // None if the bindings are locked for writing
+    pub fn references(&self) -> Option<(Vec<Value>, Option<Scope>)> {
+        let scope_data = match self.head().try_read() {
+            Ok(scope_data) => scope_data,
+            Err(_) => return None,
+        };
+        let parent = self.list.tail();
+        let parent = if parent.head().is_some() { Some(Scope { list: parent }) } else { None };
+        Some((scope_data.values.clone(), parent))
+    }
+
+    // removes the bindings of the innermost ScopeData
+    pub fn clear(&self) {
+        if let Ok(mut scope_data) = self.head().try_write() {
+            *scope_data = ScopeData::new();
+        }
+    }
+
+    pub fn downgrade(&self) -> WeakScope {
+        WeakScope { list: self.list.downgrade() }
+    }
+
+    fn head(&self) -> &RwLock<ScopeData> {
+        self.list.head().expect("internal error: scope without data")
+    }
+}
+
+// Refers to a scope without keeping it alive.
+pub struct WeakScope {
+    list: WeakList<RwLock<ScopeData>>,
+}
+
+impl WeakScope {
+    pub fn upgrade(&self) -> Option<Scope> {
+        self.list.upgrade().map(|list| Scope { list: list })
+    }
 }
 
-#[derive(Debug, PartialEq)]
+// Where a binding is, counted from the innermost scope.
+// Bindings are numbered in the order they were added to their scope.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Address {
+    pub depth: usize,
+    pub index: usize,
+}
+
+// the bits of LookupCache::address holding the depth
+const DEPTH_BITS: usize = 16;
+
+// Where a lookup found its binding the last time, see Scope::lookup_cached.
+#[derive(Debug)]
+pub struct LookupCache {
+    // the innermost scopes that don't bind the name unless define adds it, i.e. the ones known to the compiler
+    skip: usize,
+    // the index above the depth + 1, so it fits into an atomic, 0 if nothing was found yet
+    address: AtomicUsize,
+}
+
+impl LookupCache {
+    pub fn new(skip: usize) -> Self {
+        LookupCache { skip: skip, address: AtomicUsize::new(0) }
+    }
+
+    fn address(&self) -> Option<Address> {
+        match self.address.load(Ordering::Relaxed) {
+            0 => None,
+            x => Some(Address { depth: (x & ((1 << DEPTH_BITS) - 1)) - 1, index: x >> DEPTH_BITS }),
+        }
+    }
+
+    // addresses that don't fit aren't remembered, they're looked up every time
+    fn set_address(&self, address: Address) {
+        if address.depth + 1 < 1 << DEPTH_BITS && address.index < 1 << (64 - DEPTH_BITS) {
+            self.address.store(address.index << DEPTH_BITS | (address.depth + 1), Ordering::Relaxed);
+        }
+    }
+}
+
+impl PartialEq for LookupCache {
+    fn eq(&self, other: &LookupCache) -> bool {
+        self.skip == other.skip && self.address() == other.address()
+    }
+}
+
+// scopes with more bindings than this, like the global scope, are indexed by a hash map
+const INDEXED_SIZE: usize = 16;
+
+// the estimated bytes of a binding, for the memory limit
+const BINDING_SIZE: usize = mem::size_of::<u64>() + mem::size_of::<Value>();
+
+#[derive(Debug)]
 pub struct ScopeData {
-    bindings: HashMap<u64, Value>,
+    names: Vec<u64>,
+    values: Vec<Value>,
+    index: Option<HashMap<u64, usize>>,
+    // true once define added a binding, see Scope::add_symbol
+    defined: bool,
+    // the bindings are counted in the heap of the interpreter that created the scope, like values
+    heap: Option<Heap>,
 }
 
 impl ScopeData {
     fn new() -> Self {
         ScopeData {
-            bindings: HashMap::new(),
+            names: vec![],
+            values: vec![],
+            index: None,
+            defined: false,
+            heap: gc::running(),
+        }
+    }
+
+    // small scopes are searched linearly, which is faster than hashing
+    fn position(&self, id: u64) -> Option<usize> {
+        match self.index {
+            Some(ref index) => index.get(&id).cloned(),
+            None => self.names.iter().position(|&name| name == id),
+        }
+    }
+
+    // an existing binding of id keeps its place
+    fn insert(&mut self, id: u64, value: Value) {
+        if let Some(i) = self.position(id) {
+            self.values[i] = value;
+            return;
+        }
+
+        self.names.push(id);
+        self.values.push(value);
+        self.heap.as_ref().map(|heap| heap.track_allocation(BINDING_SIZE));
+        let i = self.names.len() - 1;
+        if let Some(ref mut index) = self.index {
+            index.insert(id, i);
+        } else if self.names.len() > INDEXED_SIZE {
+            self.index = Some(self.names.iter().enumerate().map(|(i, &name)| (name, i)).collect());
         }
     }
 
     fn lookup_symbol(&self, id: u64) -> Option<&Value> {
-        self.bindings.get(&id)
+        self.position(id).map(|i| &self.values[i])
     }
 
     fn symbol_ids<'a>(&'a self) -> impl Iterator<Item=u64> + 'a {
-        self.bindings.keys().map(|x| *x)
+        self.names.iter().map(|x| *x)
+    }
+}
+
+impl Drop for ScopeData {
+    fn drop(&mut self) {
+        self.heap.as_ref().map(|heap| heap.track_free(self.names.len() * BINDING_SIZE));
     }
 }
@@ -6,18 +6,17 @@ use siphasher::sip::SipHasher24 as SipHasher;
 
 pub struct StringInterner {
     map: HashMap<u64, String>,
+    gensym_counter: u64,
 }
 
 impl StringInterner {
     pub fn new() -> Self {
-        StringInterner { map: HashMap::new() }
+        StringInterner { map: HashMap::new(), gensym_counter: 0 }
     }
 
     pub fn intern<'a, T: 'a + Into<Cow<'a, str>>>(&mut self, s: T) -> u64 {
         let string = s.into();
-        let mut h = SipHasher::new();
-        string.hash(&mut h);
-        let id = h.finish();
+        let id = interned_id(&string);
         match self.map.entry(id) {
             Entry::Occupied(o) => debug_assert!(o.get() == &string),
             Entry::Vacant(o) => { o.insert(string.into_owned()); },
@@ -25,7 +24,41 @@ impl StringInterner {
         id
     }
 
+    // Creates a new id which is different from every other id, but looks up to s.
+    // Used to rename identifiers introduced by macros.
+    pub fn gensym(&mut self, s: &str) -> u64 {
+        loop {
+            self.gensym_counter += 1;
+            let mut h = SipHasher::new();
+            (s, self.gensym_counter).hash(&mut h);
+            let id = h.finish();
+            if let Entry::Vacant(o) = self.map.entry(id) {
+                o.insert(s.into());
+                return id;
+            }
+        }
+    }
+
+    // Like gensym, but the name is prefix followed by the number of the gensym, for symbols created by flip code
+    // that are printed, e.g. g12
+    pub fn gensym_numbered(&mut self, prefix: &str) -> u64 {
+        let name = format!("{}{}", prefix, self.gensym_counter + 1);
+        self.gensym(&name)
+    }
+
+    // false for the ids created by gensym, which intern never returns
+    pub fn is_interned(&self, id: u64) -> bool {
+        self.lookup(id).map_or(false, |s| interned_id(s) == id)
+    }
+
     pub fn lookup(&self, id: u64) -> Option<&str> {
         self.map.get(&id).map(|x| &**x)
     }
 }
+
+// the id intern returns for s
+fn interned_id(s: &str) -> u64 {
+    let mut h = SipHasher::new();
+    s.hash(&mut h);
+    h.finish()
+}
@@ -0,0 +1,316 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Sub, Mul, Neg};
+use std::fmt;
+
+const BASE: u64 = 1_000_000_000;
+
+// Arbitrary precision integer, integers are promoted to this if they don't fit into an i64.
+// Digits are stored in base 10^9, least significant first, without leading zeros.
+// Zero has no digits and is never negative.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, mut digits: Vec<u32>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigInt {
+            negative: negative && !digits.is_empty(),
+            digits: digits,
+        }
+    }
+
+    // parses an optional minus followed by decimal digits
+    pub fn parse(s: &str) -> Option<Self> {
+        let (negative, s) = if s.starts_with('-') { (true, &s[1..]) } else { (false, s) };
+        if s.is_empty() || !s.bytes().all(|b| b >= b'0' && b <= b'9') {
+            return None;
+        }
+
+        // chunks of 9 digits, starting at the least significant end
+        let mut digits = vec![];
+        let mut end = s.len();
+        while end > 0 {
+            let start = if end > 9 { end - 9 } else { 0 };
+            digits.push(s[start..end].parse().unwrap());
+            end = start;
+        }
+        Some(BigInt::from_parts(negative, digits))
+    }
+
+    // like parse, but with digits in radix, from 2 to 36
+    pub fn parse_radix(s: &str, radix: u32) -> Option<Self> {
+        if radix == 10 { return BigInt::parse(s); }
+        let (negative, s) = if s.starts_with('-') { (true, &s[1..]) } else { (false, s) };
+        if s.is_empty() { return None; }
+
+        let base = BigInt::from(radix as i64);
+        let mut x = BigInt::from(0);
+        for c in s.chars() {
+            x = x * base.clone() + BigInt::from(c.to_digit(radix)? as i64);
+        }
+        Some(if negative { -x } else { x })
+    }
+
+    // the digits in radix, from 2 to 36, in lower case
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if radix == 10 || self.is_zero() { return self.to_string(); }
+
+        let base = BigInt::from(radix as i64);
+        let mut digits = vec![];
+        let mut x = self.clone().abs();
+        while !x.is_zero() {
+            // safe because base isn't zero and the remainder is below it
+            let (quotient, remainder) = x.div_rem(&base).unwrap();
+            digits.push(::std::char::from_digit(remainder.to_i64().unwrap() as u32, radix).unwrap());
+            x = quotient;
+        }
+        if self.negative { digits.push('-'); }
+        digits.iter().rev().collect()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(self) -> Self {
+        BigInt::from_parts(false, self.digits)
+    }
+
+    // euclidean algorithm, the result is never negative
+    pub fn gcd(self, other: BigInt) -> BigInt {
+        let (mut a, mut b) = (self, other);
+        while !b.is_zero() {
+            let (_, remainder) = a.div_rem(&b).unwrap();
+            a = b;
+            b = remainder;
+        }
+        a.abs()
+    }
+
+    // the largest integer whose square isn't bigger than self, self must not be negative
+    pub fn sqrt(&self) -> BigInt {
+        if self.is_zero() { return self.clone(); }
+
+        // newton's method, the estimates start above the root and decrease until they reach it
+        let two = BigInt::from(2);
+        let mut x = self.clone();
+        loop {
+            // safe because x is never zero and 2 isn't either
+            let (quotient, _) = self.div_rem(&x).unwrap();
+            let (y, _) = (x.clone() + quotient).div_rem(&two).unwrap();
+            if y >= x { return x; }
+            x = y;
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        let mut magnitude: u64 = 0;
+        for &digit in self.digits.iter().rev() {
+            magnitude = magnitude.checked_mul(BASE)?.checked_add(digit as u64)?;
+        }
+
+        if self.negative {
+            if magnitude <= 1 << 63 { Some((magnitude as i64).wrapping_neg()) } else { None }
+        } else {
+            if magnitude < 1 << 63 { Some(magnitude as i64) } else { None }
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.digits.iter().rev().fold(0.0, |acc, &digit| acc * BASE as f64 + digit as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    // Truncating division, like for i64 the quotient is rounded towards zero
+    // and the remainder has the sign of self. Returns None if other is zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() { return None; }
+
+        let (quotient, remainder) = div_rem_magnitude(&self.digits, &other.digits);
+        Some((
+            BigInt::from_parts(self.negative != other.negative, quotient),
+            BigInt::from_parts(self.negative, remainder),
+        ))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(x: i64) -> Self {
+        // wrapping_abs is only wrong for i64::MIN, which is correct again as u64
+        let mut magnitude = x.wrapping_abs() as u64;
+        let mut digits = vec![];
+        while magnitude > 0 {
+            digits.push((magnitude % BASE) as u32);
+            magnitude /= BASE;
+        }
+        BigInt::from_parts(x < 0, digits)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() { return write!(f, "0"); }
+        if self.negative { write!(f, "-")?; }
+
+        let mut digits = self.digits.iter().rev();
+        write!(f, "{}", digits.next().unwrap())?;
+        for digit in digits {
+            write!(f, "{:09}", digit)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.digits, &other.digits),
+            (true, true) => cmp_magnitude(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        let negative = !self.negative;
+        BigInt::from_parts(negative, self.digits)
+    }
+}
+
+impl Add for BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_magnitude(&self.digits, &other.digits));
+        }
+
+        // different signs, subtract the smaller magnitude from the bigger one
+        match cmp_magnitude(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_magnitude(&other.digits, &self.digits)),
+            _ => BigInt::from_parts(self.negative, sub_magnitude(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl Sub for BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: BigInt) -> BigInt {
+        self + -other
+    }
+}
+
+impl Mul for BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: BigInt) -> BigInt {
+        BigInt::from_parts(self.negative != other.negative, mul_magnitude(&self.digits, &other.digits))
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push((sum % BASE) as u32);
+        carry = sum / BASE;
+    }
+    if carry > 0 { result.push(carry as u32); }
+    result
+}
+
+// a has to be at least as big as b
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for i in 0..a.len() {
+        let subtrahend = *b.get(i).unwrap_or(&0) as u64 + borrow;
+        let digit = a[i] as u64;
+        if digit >= subtrahend {
+            result.push((digit - subtrahend) as u32);
+            borrow = 0;
+        } else {
+            result.push((digit + BASE - subtrahend) as u32);
+            borrow = 1;
+        }
+    }
+    while result.last() == Some(&0) {
+        result.pop();
+    }
+    result
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() { return vec![]; }
+
+    let mut result = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let product = result[i + j] + x as u64 * y as u64 + carry;
+            result[i + j] = product % BASE;
+            carry = product / BASE;
+        }
+        result[i + b.len()] += carry;
+    }
+
+    let mut result: Vec<u32> = result.into_iter().map(|x| x as u32).collect();
+    while result.last() == Some(&0) {
+        result.pop();
+    }
+    result
+}
+
+// schoolbook long division, every digit of the quotient is found with a binary search
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder: Vec<u32> = vec![];
+
+    for i in (0..a.len()).rev() {
+        // remainder = remainder * BASE + a[i]
+        remainder.insert(0, a[i]);
+        while remainder.last() == Some(&0) {
+            remainder.pop();
+        }
+
+        let (mut lo, mut hi) = (0, BASE - 1);
+        while lo < hi {
+            let mid = (lo + hi + 1) / 2;
+            if cmp_magnitude(&mul_magnitude(b, &[mid as u32]), &remainder) == Ordering::Greater {
+                hi = mid - 1;
+            } else {
+                lo = mid;
+            }
+        }
+
+        quotient[i] = lo as u32;
+        remainder = sub_magnitude(&remainder, &mul_magnitude(b, &[lo as u32]));
+    }
+
+    (quotient, remainder)
+}
@@ -0,0 +1,150 @@
+use ::value::Value;
+use ::string_interner::StringInterner;
+use itertools::Itertools;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ConditionKind {
+    // raised by error and most natives
+    Error,
+    // a procedure was called with the wrong number of arguments
+    Arity,
+    // a native got an argument of the wrong type
+    Type,
+    // raise was called with something that isn't a condition
+    Raise,
+    // a continuation was called, it's caught by its call/cc and can't be handled otherwise
+    Escape,
+    // the fuel set by Interpreter::set_fuel ran out, with-exception-handler doesn't catch it
+    Fuel,
+    // the memory limit set by Interpreter::set_memory_limit was exceeded, not caught either
+    Memory,
+}
+
+impl ConditionKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ConditionKind::Error => "error",
+            ConditionKind::Arity => "arity",
+            ConditionKind::Type => "type",
+            ConditionKind::Raise => "raise",
+            ConditionKind::Escape => "escape",
+            ConditionKind::Fuel => "fuel",
+            ConditionKind::Memory => "memory",
+        }
+    }
+}
+
+// A condition is what gets returned in the Err case of every evaluation.
+// It's propagated up the call chain until somebody (usually the REPL or with-exception-handler) handles it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition {
+    kind: ConditionKind,
+    message: String,
+    irritants: Vec<Value>,
+    // what raise was called with, or what was passed to the continuation
+    payload: Option<Value>,
+    // the continuation that was called, only for ConditionKind::Escape
+    continuation: Option<Value>,
+    // the procedure calls that were active when it was raised, innermost first
+    backtrace: Option<Vec<String>>,
+}
+
+impl Condition {
+    pub fn new<T: Into<String>>(kind: ConditionKind, message: T) -> Self {
+        Condition {
+            kind: kind,
+            message: message.into(),
+            irritants: vec![],
+            payload: None,
+            continuation: None,
+            backtrace: None,
+        }
+    }
+
+    // (error message irritants*)
+    pub fn error<T: Into<String>>(message: T, irritants: Vec<Value>) -> Self {
+        Condition { irritants: irritants, ..Condition::new(ConditionKind::Error, message) }
+    }
+
+    // shown after the message, like the irritants of error
+    pub fn with_irritants(self, irritants: Vec<Value>) -> Self {
+        Condition { irritants: irritants, ..self }
+    }
+
+    // raising a condition object raises the condition itself again
+    pub fn raise(payload: Value) -> Self {
+        match payload.get_condition() {
+            Some(condition) => condition.clone(),
+            None => Condition { payload: Some(payload.clone()), ..Condition::new(ConditionKind::Raise, "") },
+        }
+    }
+
+    // returns value from the call/cc that created continuation
+    pub fn escape(continuation: Value, value: Value) -> Self {
+        Condition {
+            payload: Some(value),
+            continuation: Some(continuation),
+            ..Condition::new(ConditionKind::Escape, "")
+        }
+    }
+
+    pub fn continuation(&self) -> Option<&Value> {
+        self.continuation.as_ref()
+    }
+
+    pub fn kind(&self) -> ConditionKind {
+        self.kind
+    }
+
+    // escapes and exceeded limits can't be handled by with-exception-handler
+    pub fn is_catchable(&self) -> bool {
+        match self.kind {
+            ConditionKind::Escape | ConditionKind::Fuel | ConditionKind::Memory => false,
+            _ => true,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn irritants(&self) -> &[Value] {
+        &self.irritants
+    }
+
+    // what a handler gets, the raised value itself or the condition as object
+    pub fn to_value(&self) -> Value {
+        match self.payload {
+            Some(ref payload) => payload.clone(),
+            None => Value::new_condition(self.clone()),
+        }
+    }
+
+    pub fn backtrace(&self) -> Option<&[String]> {
+        self.backtrace.as_ref().map(|x| &**x)
+    }
+
+    pub fn set_backtrace(&mut self, backtrace: Vec<String>) {
+        self.backtrace = Some(backtrace);
+    }
+
+    // the message followed by the irritants, written like by write
+    pub fn to_string(&self, interner: &StringInterner) -> String {
+        match self.payload {
+            Some(_) if self.kind == ConditionKind::Escape => "continuation called outside of call/cc".into(),
+            Some(ref payload) => format!("uncaught raise: {}", payload.to_string(interner)),
+            None if self.irritants.is_empty() => self.message.clone(),
+            None => format!("{} {}", self.message, self.irritants.iter().map(|x| x.to_string(interner)).join(" ")),
+        }
+    }
+
+    // the message followed by one line per frame of the backtrace
+    pub fn to_string_with_backtrace(&self, interner: &StringInterner) -> String {
+        let mut s = self.to_string(interner);
+        for frame in self.backtrace().unwrap_or(&[]) {
+            s.push_str("\n  ");
+            s.push_str(frame);
+        }
+        s
+    }
+}
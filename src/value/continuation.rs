@@ -0,0 +1,41 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use ::value::Value;
+
+// An escaping continuation created by call/cc.
+// Calling it raises an escape condition, which is caught by the call/cc that created it,
+// so it can only be used while that call/cc hasn't returned yet.
+#[derive(Debug)]
+pub struct Continuation {
+    active: AtomicBool,
+}
+
+impl Continuation {
+    pub fn new() -> Self {
+        Continuation { active: AtomicBool::new(true) }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn deactivate(&self) {
+        self.active.store(false, Ordering::Relaxed);
+    }
+}
+
+impl PartialEq for Continuation {
+    fn eq(&self, other: &Continuation) -> bool {
+        self.is_active() == other.is_active()
+    }
+}
+
+// A continuation captured by shift, it's delimited by the enclosing reset.
+// Calling it with x evaluates (func args*) again in a new reset,
+// where the shifts return the values of log and then x instead of escaping.
+// So side effects before the shift are repeated every time it's called.
+#[derive(Debug, PartialEq)]
+pub struct ComposableContinuation {
+    pub func: Value,
+    pub args: Vec<Value>,
+    pub log: Vec<Value>,
+}
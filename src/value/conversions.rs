@@ -0,0 +1,118 @@
+// Conversions between rust types and values, for applications embedding the interpreter.
+// Converting back fails with the value itself if it has another type.
+
+use std::convert::TryFrom;
+use ::value::{Value, HashTable, HashKey};
+use ::string_interner::StringInterner;
+
+impl From<i64> for Value {
+    fn from(x: i64) -> Self { Value::new_integer(x) }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self { Value::new_float(x) }
+}
+
+impl From<bool> for Value {
+    fn from(x: bool) -> Self { Value::new_bool(x) }
+}
+
+impl From<char> for Value {
+    fn from(x: char) -> Self { Value::new_char(x) }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(x: &'a str) -> Self { Value::new_string(x) }
+}
+
+impl From<String> for Value {
+    fn from(x: String) -> Self { Value::new_string(x) }
+}
+
+// a list, vectors have to be created with Value::new_vector
+impl From<Vec<Value>> for Value {
+    fn from(x: Vec<Value>) -> Self { Value::new_list(&x) }
+}
+
+macro_rules! try_from_value {
+    ($t:ty, $convert:expr) => (
+        impl TryFrom<Value> for $t {
+            type Error = Value;
+
+            fn try_from(value: Value) -> Result<$t, Value> {
+                match $convert(&value) {
+                    Some(x) => Ok(x),
+                    None => Err(value),
+                }
+            }
+        }
+    );
+}
+
+try_from_value!(i64, Value::as_i64);
+try_from_value!(f64, Value::as_f64);
+try_from_value!(bool, Value::as_bool);
+try_from_value!(char, Value::get_char);
+try_from_value!(String, |x: &Value| x.as_str().map(String::from));
+// lists and vectors
+try_from_value!(Vec<Value>, Value::as_vec);
+
+// A struct that's converted to an alist with a symbol per field, like ((x . 1) (y . 2)), or to a hash table
+// with the same keys. impl_record! implements it for structs whose fields can be converted to values and back.
+pub trait Record: Sized {
+    fn fields(&self) -> Vec<(&'static str, Value)>;
+
+    // get returns the value of a field by its name, None if it's missing
+    fn from_fields<F: Fn(&str) -> Option<Value>>(get: F) -> Option<Self>;
+
+    fn to_alist(&self, interner: &mut StringInterner) -> Value {
+        let entries: Vec<Value> = self.fields().into_iter()
+        .map(|(name, value)| Value::new_pair(Value::new_symbol(interner.intern(name)), value))
+        .collect();
+        Value::new_list(&entries)
+    }
+
+    // None if value isn't an alist or a field is missing or has the wrong type
+    fn from_alist(value: &Value, interner: &StringInterner) -> Option<Self> {
+        let entries = value.get_list()?;
+        Self::from_fields(|name| {
+            entries.iter().filter_map(Value::get_pair)
+            .find(|pair| pair.car().get_symbol().and_then(|id| interner.lookup(id)) == Some(name))
+            .map(|pair| pair.cdr())
+        })
+    }
+
+    fn to_hash_table(&self, interner: &mut StringInterner) -> Value {
+        let mut table = HashTable::new();
+        for (name, value) in self.fields() {
+            let id = interner.intern(name);
+            table.insert(HashKey::Symbol(id), Value::new_symbol(id), value);
+        }
+        Value::new_hash_table(table)
+    }
+
+    fn from_hash_table(value: &Value, interner: &StringInterner) -> Option<Self> {
+        let table = value.get_hash_table()?.read().unwrap();
+        Self::from_fields(|name| {
+            table.entries()
+            .find(|&(key, _)| key.get_symbol().and_then(|id| interner.lookup(id)) == Some(name))
+            .map(|(_, value)| value.clone())
+        })
+    }
+}
+
+// impl_record!(Point { x, y }) implements Record for struct Point with the fields x and y
+#[macro_export]
+macro_rules! impl_record {
+    ($name:ident { $($field:ident),* }) => (
+        impl $crate::value::Record for $name {
+            fn fields(&self) -> Vec<(&'static str, $crate::value::Value)> {
+                vec![$((stringify!($field), self.$field.clone().into())),*]
+            }
+
+            fn from_fields<F: Fn(&str) -> Option<$crate::value::Value>>(get: F) -> Option<Self> {
+                Some($name { $($field: ::std::convert::TryFrom::try_from(get(stringify!($field))?).ok()?),* })
+            }
+        }
+    );
+}
@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet};
+use ::value::Value;
+use ::string_interner::StringInterner;
+
+// Pairs and vectors that are reached more than once are written with datum labels: the first time
+// as #0=(...), every time after that only as #0#. write labels only the ones that contain themselves,
+// so data without cycles is written like before, write-shared labels everything that's shared.
+
+impl Value {
+    // like to_string, but every pair or vector that occurs more than once is written only once
+    pub fn to_shared_string(&self, interner: &StringInterner) -> String {
+        print_labeled(self, interner, false, true).unwrap_or_else(|| self.to_string(interner))
+    }
+
+    // true if following cars, cdrs and vector elements leads back to a pair or vector already on the way
+    pub fn is_cyclic(&self) -> bool {
+        !find_labels(self, false).is_empty()
+    }
+}
+
+// None if value needs no labels, then it's written like print_unlabeled does
+pub fn print_labeled(value: &Value, interner: &StringInterner, display: bool, shared: bool) -> Option<String> {
+    let labels = find_labels(value, shared);
+    if labels.is_empty() { return None; }
+    let mut printer = Printer { interner: interner, display: display, labels: labels, numbers: HashMap::new(), s: String::new() };
+    printer.write(value);
+    Some(printer.s)
+}
+
+enum Step {
+    Enter(Value),
+    // all the children of the pair or vector with this id are done
+    Exit(usize),
+}
+
+// The ids of the pairs and vectors that need a label. The ones that are being walked are active,
+// reaching one of them again is a cycle. Without shared a pair that's done isn't walked again.
+fn find_labels(value: &Value, shared: bool) -> HashSet<usize> {
+    let mut labels = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut active = HashSet::new();
+    // an explicit stack, recursing would overflow for long lists
+    let mut pending = vec![Step::Enter(value.clone())];
+    while let Some(step) = pending.pop() {
+        let x = match step {
+            Step::Enter(x) => x,
+            Step::Exit(id) => { active.remove(&id); continue; },
+        };
+        let children = if let Some(pair) = x.get_pair() {
+            vec![pair.car(), pair.cdr()]
+        } else if let Some(elements) = x.get_vector() {
+            elements.read().unwrap().clone()
+        } else {
+            continue;
+        };
+
+        let id = x.id();
+        if active.contains(&id) || (shared && visited.contains(&id)) {
+            labels.insert(id);
+            continue;
+        }
+        if !visited.insert(id) { continue; }
+        active.insert(id);
+        pending.push(Step::Exit(id));
+        pending.extend(children.into_iter().rev().map(Step::Enter));
+    }
+    labels
+}
+
+struct Printer<'a> {
+    interner: &'a StringInterner,
+    display: bool,
+    labels: HashSet<usize>,
+    // the labels that were written already, numbered in the order they're written
+    numbers: HashMap<usize, usize>,
+    s: String,
+}
+
+impl<'a> Printer<'a> {
+    fn write(&mut self, x: &Value) {
+        if x.get_pair().is_none() && x.get_vector().is_none() {
+            self.s.push_str(&x.print(self.interner, self.display));
+            return;
+        }
+
+        let id = x.id();
+        if self.labels.contains(&id) {
+            if let Some(n) = self.numbers.get(&id) {
+                self.s.push_str(&format!("#{}#", n));
+                return;
+            }
+            let n = self.numbers.len();
+            self.numbers.insert(id, n);
+            self.s.push_str(&format!("#{}=", n));
+        }
+
+        if let Some(elements) = x.get_vector() {
+            self.s.push_str("#(");
+            for (i, element) in elements.read().unwrap().clone().iter().enumerate() {
+                if i > 0 { self.s.push(' '); }
+                self.write(element);
+            }
+            self.s.push(')');
+            return;
+        }
+
+        // iterative for the cdrs, so long lists don't overflow the stack
+        self.s.push('(');
+        let mut current = x.clone();
+        loop {
+            // safe because only pairs get here
+            let (car, cdr) = { let pair = current.get_pair().unwrap(); (pair.car(), pair.cdr()) };
+            self.write(&car);
+            if cdr.get_empty_list().is_some() { break; }
+            // a labeled cdr has to be written on its own, which is only possible after a dot
+            if cdr.get_pair().is_some() && !self.labels.contains(&cdr.id()) {
+                self.s.push(' ');
+                current = cdr;
+                continue;
+            }
+            self.s.push_str(" . ");
+            self.write(&cdr);
+            break;
+        }
+        self.s.push(')');
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::mem;
+use ::value::{Value, BigInt, Rational};
+use ::gc::{self, Heap};
+
+// an estimate of the bytes an entry takes, for the memory limit
+const ENTRY_SIZE: usize = mem::size_of::<HashKey>() + 2 * mem::size_of::<Value>();
+
+// Everything that can be used as key in a hash table.
+// Keys are compared by value, like eqv? for atoms and structurally for strings and lists.
+// Floats can't be keys, because they aren't Eq.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Bool(bool),
+    Char(char),
+    Integer(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    Symbol(u64),
+    Keyword(u64),
+    String(String),
+    List(Vec<HashKey>),
+}
+
+impl HashKey {
+    // None if value or one of its elements isn't hashable
+    pub fn from_value(value: &Value) -> Option<Self> {
+        if let Some(x) = value.get_bool() { return Some(HashKey::Bool(x)); }
+        if let Some(x) = value.get_char() { return Some(HashKey::Char(x)); }
+        if let Some(x) = value.get_integer() { return Some(HashKey::Integer(x)); }
+        if let Some(x) = value.get_bigint() { return Some(HashKey::BigInt(x.clone())); }
+        if let Some(x) = value.get_rational() { return Some(HashKey::Rational(x.clone())); }
+        if let Some(x) = value.get_symbol() { return Some(HashKey::Symbol(x)); }
+        if let Some(x) = value.get_keyword() { return Some(HashKey::Keyword(x)); }
+        if let Some(x) = value.get_string() { return Some(HashKey::String(x.into())); }
+        if let Some(list) = value.get_list() {
+            return list.iter().map(HashKey::from_value).collect::<Option<_>>().map(HashKey::List);
+        }
+        None
+    }
+}
+
+// The original key is stored alongside the value, so hash-keys can return it.
+// The entries are tracked for the memory limit, in the heap of the interpreter that created the table.
+#[derive(Debug)]
+pub struct HashTable {
+    entries: HashMap<HashKey, (Value, Value)>,
+    heap: Option<Heap>,
+}
+
+impl HashTable {
+    pub fn new() -> Self {
+        HashTable { entries: HashMap::new(), heap: gc::running() }
+    }
+
+    pub fn get(&self, key: &HashKey) -> Option<&Value> {
+        self.entries.get(key).map(|&(_, ref value)| value)
+    }
+
+    pub fn insert(&mut self, key: HashKey, original_key: Value, value: Value) {
+        if self.entries.insert(key, (original_key, value)).is_none() {
+            self.heap.as_ref().map(|heap| heap.track_allocation(ENTRY_SIZE));
+        }
+    }
+
+    pub fn remove(&mut self, key: &HashKey) {
+        if self.entries.remove(key).is_some() {
+            self.heap.as_ref().map(|heap| heap.track_free(ENTRY_SIZE));
+        }
+    }
+
+    // in no particular order
+    pub fn entries<'a>(&'a self) -> impl Iterator<Item=(&'a Value, &'a Value)> + 'a {
+        self.entries.values().map(|&(ref key, ref value)| (key, value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl Clone for HashTable {
+    fn clone(&self) -> Self {
+        let heap = gc::running();
+        heap.as_ref().map(|heap| heap.track_allocation(self.len() * ENTRY_SIZE));
+        HashTable { entries: self.entries.clone(), heap: heap }
+    }
+}
+
+impl Drop for HashTable {
+    fn drop(&mut self) {
+        self.heap.as_ref().map(|heap| heap.track_free(self.len() * ENTRY_SIZE));
+    }
+}
+
+// the heap doesn't matter, tables with the same entries are equal
+impl PartialEq for HashTable {
+    fn eq(&self, other: &HashTable) -> bool {
+        self.entries == other.entries
+    }
+}
@@ -0,0 +1,111 @@
+use std::any::Any;
+use std::fmt;
+use ::value::{Value, Condition, ConditionKind, Userdata};
+use ::interpreter::Interpreter;
+
+pub type HostFn = dyn Fn(&mut Interpreter, Args) -> Result<Value, Condition> + Send + Sync;
+
+// A procedure written in rust by an application embedding the interpreter, see Interpreter::register_fn.
+// Unlike natives it gets its args evaluated, like procedures written in flip.
+pub struct HostProc {
+    name: String,
+    f: Box<HostFn>,
+}
+
+impl HostProc {
+    pub fn new(name: String, f: Box<HostFn>) -> Self {
+        HostProc { name: name, f: f }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, Condition> {
+        (self.f)(interpreter, Args { name: &self.name, values: args })
+    }
+}
+
+// closures can't be compared, so host procedures are only equal to themselves
+impl PartialEq for HostProc {
+    fn eq(&self, other: &HostProc) -> bool {
+        self as *const HostProc == other as *const HostProc
+    }
+}
+
+impl fmt::Debug for HostProc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HostProc {{ name: {:?} }}", self.name)
+    }
+}
+
+// The args of a host procedure. The getters raise the same conditions natives do
+// if an arg is missing or has the wrong type, so they can be used with ?.
+#[derive(Clone, Copy)]
+pub struct Args<'a> {
+    name: &'a str,
+    values: &'a [Value],
+}
+
+impl<'a> Args<'a> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &'a [Value] {
+        self.values
+    }
+
+    pub fn get(&self, i: usize) -> Result<&'a Value, Condition> {
+        self.values.get(i).ok_or_else(|| {
+            Condition::new(ConditionKind::Arity, format!("arity mismatch for {}: expected: {}.., got: {}", self.name, i + 1, self.len()))
+        })
+    }
+
+    pub fn get_i64(&self, i: usize) -> Result<i64, Condition> {
+        self.get_as(i, "integer", Value::get_integer)
+    }
+
+    // integers are converted
+    pub fn get_f64(&self, i: usize) -> Result<f64, Condition> {
+        self.get_as(i, "number", |x| x.get_float().or_else(|| x.get_integer().map(|x| x as f64)))
+    }
+
+    pub fn get_bool(&self, i: usize) -> Result<bool, Condition> {
+        self.get_as(i, "bool", Value::get_bool)
+    }
+
+    pub fn get_char(&self, i: usize) -> Result<char, Condition> {
+        self.get_as(i, "char", Value::get_char)
+    }
+
+    pub fn get_str(&self, i: usize) -> Result<&'a str, Condition> {
+        self.get_as(i, "string", Value::get_string)
+    }
+
+    pub fn get_symbol(&self, i: usize) -> Result<u64, Condition> {
+        self.get_as(i, "symbol", Value::get_symbol)
+    }
+
+    pub fn get_list(&self, i: usize) -> Result<Vec<Value>, Condition> {
+        self.get_as(i, "list", Value::get_list)
+    }
+
+    // the object of userdata, if it's a T
+    pub fn get_userdata<T: Any>(&self, i: usize) -> Result<&'a T, Condition> {
+        self.get_as(i, "userdata", |x| x.get_userdata().and_then(Userdata::downcast_ref))
+    }
+
+    fn get_as<T, F>(&self, i: usize, type_name: &str, unwrap: F) -> Result<T, Condition>
+    where F: FnOnce(&'a Value) -> Option<T> {
+        let value = self.get(i)?;
+        unwrap(value).ok_or_else(|| {
+            let message = format!("{} expected {} as argument {}, got:", self.name, type_name, i + 1);
+            Condition::new(ConditionKind::Type, message).with_irritants(vec![value.clone()])
+        })
+    }
+}
@@ -0,0 +1,307 @@
+// Conversions between values and JSON, for exchanging data with other programs.
+//
+// Lists and vectors are arrays, hash tables with string or symbol keys are objects and the symbol null is null.
+// Arrays are read back as lists and object keys as strings, numbers without a fraction or exponent as integers.
+
+use std::str::Chars;
+use std::iter::Peekable;
+use std::char;
+use std::collections::HashSet;
+use ::value::{Value, BigInt, HashTable, HashKey};
+use ::string_interner::StringInterner;
+
+// arrays and objects nested deeper are rejected both ways, like serde_json does, so neither recursion overflows the stack,
+// e.g. for a vector that contains itself
+const MAX_DEPTH: usize = 128;
+
+// The message names the first value that has no JSON representation, like a procedure or a float that isn't finite.
+// Hash tables can't have a symbol and a string key with the same name, they would be the same key in JSON.
+pub fn to_json(value: &Value, interner: &StringInterner) -> Result<String, String> {
+    let mut s = String::new();
+    write_json(value, interner, &mut s, 0)?;
+    Ok(s)
+}
+
+fn unconvertible<T>(value: &Value, interner: &StringInterner) -> Result<T, String> {
+    Err(format!("can't convert: {}", value.to_string(interner)))
+}
+
+// depth is the number of arrays and objects value is in
+fn write_json(value: &Value, interner: &StringInterner, s: &mut String, depth: usize) -> Result<(), String> {
+    if depth > MAX_DEPTH { return Err(format!("can't convert, nested deeper than {} levels", MAX_DEPTH)); }
+    if let Some(x) = value.get_bool() {
+        s.push_str(if x { "true" } else { "false" });
+    } else if let Some(x) = value.get_integer() {
+        s.push_str(&x.to_string());
+    } else if let Some(x) = value.get_bigint() {
+        s.push_str(&x.to_string());
+    } else if let Some(x) = value.get_float() {
+        if !x.is_finite() { return unconvertible(value, interner); }
+        // debug formatting always prints a decimal point or an exponent, so it's read back as float
+        s.push_str(&format!("{:?}", x));
+    } else if let Some(x) = value.get_string() {
+        write_string(x, s);
+    } else if let Some(id) = value.get_symbol() {
+        match interner.lookup(id) {
+            Some("null") => s.push_str("null"),
+            _ => return unconvertible(value, interner),
+        }
+    } else if let Some(elements) = value.get_list() {
+        write_array(&elements, interner, s, depth)?;
+    } else if let Some(elements) = value.get_vector() {
+        write_array(&elements.read().unwrap(), interner, s, depth)?;
+    } else if let Some(table) = value.get_hash_table() {
+        let mut entries = vec![];
+        let mut keys = HashSet::new();
+        for (key, x) in table.read().unwrap().entries() {
+            let key = match (key.get_string(), key.get_symbol().and_then(|id| interner.lookup(id))) {
+                (Some(key), _) | (None, Some(key)) => key.to_string(),
+                _ => return unconvertible(key, interner),
+            };
+            if !keys.insert(key.clone()) { return Err(format!("can't convert, duplicate key: {:?}", key)); }
+            let mut entry = String::new();
+            write_string(&key, &mut entry);
+            entry.push(':');
+            write_json(x, interner, &mut entry, depth + 1)?;
+            entries.push(entry);
+        }
+        // sorted, so equal tables are always written the same
+        entries.sort();
+        s.push('{');
+        s.push_str(&entries.join(","));
+        s.push('}');
+    } else {
+        return unconvertible(value, interner);
+    }
+    Ok(())
+}
+
+fn write_array(elements: &[Value], interner: &StringInterner, s: &mut String, depth: usize) -> Result<(), String> {
+    s.push('[');
+    for (i, x) in elements.iter().enumerate() {
+        if i > 0 { s.push(','); }
+        write_json(x, interner, s, depth + 1)?;
+    }
+    s.push(']');
+    Ok(())
+}
+
+fn write_string(x: &str, s: &mut String) {
+    s.push('"');
+    for c in x.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => s.push_str(&format!("\\u{:04x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+}
+
+// the message says what was expected where, the input has to be a single JSON value
+pub fn from_json(input: &str, interner: &mut StringInterner) -> Result<Value, String> {
+    let mut reader = Reader { chars: input.chars().peekable(), position: 0, depth: 0, interner: interner };
+    let value = reader.read_value()?;
+    reader.skip_whitespace();
+    match reader.chars.peek() {
+        Some(_) => reader.error("end of input"),
+        None => Ok(value),
+    }
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    // in chars, for error messages
+    position: usize,
+    // the number of arrays and objects being read
+    depth: usize,
+    interner: &'a mut StringInterner,
+}
+
+impl<'a> Reader<'a> {
+    fn error<T>(&mut self, expected: &str) -> Result<T, String> {
+        match self.chars.peek() {
+            Some(c) => Err(format!("expected {} at {}, got: {:?}", expected, self.position, c)),
+            None => Err(format!("expected {} at {}, got end of input", expected, self.position)),
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.position += 1;
+        self.chars.next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().map_or(false, |&c| c == ' ' || c == '\t' || c == '\n' || c == '\r') {
+            self.next();
+        }
+    }
+
+    // consumes c if it comes next
+    fn eat(&mut self, c: char) -> bool {
+        if self.chars.peek() == Some(&c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) { Ok(()) } else { self.error(&format!("{:?}", c)) }
+    }
+
+    fn read_value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.peek().cloned() {
+            Some('{') => self.read_object(),
+            Some('[') => self.read_array(),
+            Some('"') => self.read_string().map(Value::new_string),
+            Some(c) if c == '-' || c.is_digit(10) => self.read_number(),
+            Some(c) if c.is_alphabetic() => {
+                let start = self.position;
+                let mut word = String::new();
+                while let Some(c) = self.chars.peek().cloned() {
+                    if !c.is_alphabetic() { break; }
+                    word.push(c);
+                    self.next();
+                }
+                match &*word {
+                    "true" => Ok(Value::new_bool(true)),
+                    "false" => Ok(Value::new_bool(false)),
+                    "null" => Ok(Value::new_symbol(self.interner.intern("null"))),
+                    _ => Err(format!("expected value at {}, got: {}", start, word)),
+                }
+            },
+            _ => self.error("value"),
+        }
+    }
+
+    // called when an array or object starts, it has to decrement depth again when it ends
+    fn nest(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH { return Err(format!("nested deeper than {} levels at {}", MAX_DEPTH, self.position)); }
+        Ok(())
+    }
+
+    fn read_array(&mut self) -> Result<Value, String> {
+        self.nest()?;
+        self.expect('[')?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if !self.eat(']') {
+            loop {
+                elements.push(self.read_value()?);
+                self.skip_whitespace();
+                if self.eat(']') { break; }
+                self.expect(',')?;
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::new_list(&elements))
+    }
+
+    fn read_object(&mut self) -> Result<Value, String> {
+        self.nest()?;
+        self.expect('{')?;
+        let mut table = HashTable::new();
+        self.skip_whitespace();
+        if !self.eat('}') {
+            loop {
+                self.skip_whitespace();
+                if self.chars.peek() != Some(&'"') { return self.error("string"); }
+                let key = self.read_string()?;
+                self.skip_whitespace();
+                self.expect(':')?;
+                let value = self.read_value()?;
+                table.insert(HashKey::String(key.clone()), Value::new_string(key), value);
+                self.skip_whitespace();
+                if self.eat('}') { break; }
+                self.expect(',')?;
+            }
+        }
+        self.depth -= 1;
+        Ok(Value::new_hash_table(table))
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.read_escaped_char()?,
+                        _ => return self.error("escape sequence"),
+                    };
+                    s.push(c);
+                },
+                Some(c) => s.push(c),
+                None => return self.error("\"\\\"\""),
+            }
+        }
+    }
+
+    // after \u, chars outside of the basic plane are written as two escaped surrogates
+    fn read_escaped_char(&mut self) -> Result<char, String> {
+        let high = self.read_hex()?;
+        if high < 0xd800 || high > 0xdbff {
+            return char::from_u32(high).map_or_else(|| self.error("char"), Ok);
+        }
+        if !(self.eat('\\') && self.eat('u')) { return self.error("low surrogate"); }
+        let low = self.read_hex()?;
+        if low < 0xdc00 || low > 0xdfff { return self.error("low surrogate"); }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)).map_or_else(|| self.error("char"), Ok)
+    }
+
+    fn read_hex(&mut self) -> Result<u32, String> {
+        let mut x = 0;
+        for _ in 0..4 {
+            match self.chars.peek().and_then(|c| c.to_digit(16)) {
+                Some(digit) => x = x * 16 + digit,
+                None => return self.error("hex digit"),
+            }
+            self.next();
+        }
+        Ok(x)
+    }
+
+    fn read_number(&mut self) -> Result<Value, String> {
+        let mut s = String::new();
+        let mut float = false;
+        while let Some(c) = self.chars.peek().cloned() {
+            match c {
+                c if c.is_digit(10) || c == '-' || c == '+' => (),
+                '.' | 'e' | 'E' => float = true,
+                _ => break,
+            }
+            s.push(c);
+            self.next();
+        }
+
+        if float {
+            match s.parse::<f64>() {
+                Ok(x) => Ok(Value::new_float(x)),
+                Err(_) => Err(format!("expected number at {}, got: {}", self.position - s.len(), s)),
+            }
+        } else {
+            match BigInt::parse(&s) {
+                Some(x) => Ok(Value::new_bigint(x)),
+                None => Err(format!("expected number at {}, got: {}", self.position - s.len(), s)),
+            }
+        }
+    }
+}
@@ -1,9 +1,55 @@
 mod value;
 pub use self::value::*;
 
+mod condition;
+pub use self::condition::*;
+
+mod bigint;
+pub use self::bigint::*;
+
+mod rational;
+pub use self::rational::*;
+
+mod pair;
+pub use self::pair::*;
+
+mod hash_table;
+pub use self::hash_table::*;
+
+mod port;
+pub use self::port::*;
+
 mod procedure;
 pub use self::procedure::*;
 
+mod continuation;
+pub use self::continuation::*;
+
+mod promise;
+pub use self::promise::*;
+
+mod record;
+pub use self::record::*;
+
+mod parameter;
+pub use self::parameter::*;
+
+mod host_proc;
+pub use self::host_proc::*;
+
+mod userdata;
+pub use self::userdata::*;
+
+#[macro_use]
+mod conversions;
+pub use self::conversions::*;
+
+mod json;
+pub use self::json::*;
+
+mod pretty;
+
+mod datum_labels;
 
 mod special_forms;
 pub use self::special_forms::*;
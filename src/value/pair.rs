@@ -0,0 +1,71 @@
+use std::sync::RwLock;
+use std::mem;
+use ::value::Value;
+
+// A cons cell, lists are chains of pairs ending with ().
+// Both halves can be changed with set-car! and set-cdr!.
+#[derive(Debug)]
+pub struct Pair {
+    car: RwLock<Value>,
+    cdr: RwLock<Value>,
+}
+
+impl Pair {
+    pub fn new(car: Value, cdr: Value) -> Self {
+        Pair {
+            car: RwLock::new(car),
+            cdr: RwLock::new(cdr),
+        }
+    }
+
+    pub fn car(&self) -> Value {
+        self.car.read().unwrap().clone()
+    }
+
+    pub fn cdr(&self) -> Value {
+        self.cdr.read().unwrap().clone()
+    }
+
+    pub fn set_car(&self, value: Value) {
+        *self.car.write().unwrap() = value;
+    }
+
+    pub fn set_cdr(&self, value: Value) {
+        *self.cdr.write().unwrap() = value;
+    }
+
+    // the car and the cdr, None if one of them is being set right now, see gc
+    pub fn try_elements(&self) -> Option<(Value, Value)> {
+        match (self.car.try_read(), self.cdr.try_read()) {
+            (Ok(car), Ok(cdr)) => Some((car.clone(), cdr.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl Clone for Pair {
+    fn clone(&self) -> Self {
+        Pair::new(self.car(), self.cdr())
+    }
+}
+
+// Lists are compared element by element without recursing, see Value::all_eq.
+// Deriving this would recurse once per element, overflow the stack for long lists and never end for cyclic ones.
+impl PartialEq for Pair {
+    fn eq(&self, other: &Pair) -> bool {
+        Value::all_eq(vec![(self.cdr(), other.cdr()), (self.car(), other.car())])
+    }
+}
+
+// same reasoning as for PartialEq, the rest of the list is dropped iteratively
+impl Drop for Pair {
+    fn drop(&mut self) {
+        if self.cdr.get_mut().unwrap().get_pair().is_none() { return; }
+
+        let mut next = mem::replace(self.cdr.get_mut().unwrap(), Value::empty_list());
+        while let Some(mut pair) = next.try_unwrap_pair() {
+            if pair.cdr.get_mut().unwrap().get_pair().is_none() { break; }
+            next = mem::replace(pair.cdr.get_mut().unwrap(), Value::empty_list());
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use ::value::Value;
+
+// A parameter object created by make-parameter, calling it returns the value it's bound to.
+// parameterize binds new values for the dynamic extent of its body, see Interpreter::parameter_value,
+// the value given to make-parameter is used outside of all parameterize forms.
+#[derive(Debug, PartialEq)]
+pub struct Parameter {
+    value: Value,
+    // a procedure called with the values given to make-parameter and parameterize, it returns the value that is bound
+    converter: Option<Value>,
+}
+
+impl Parameter {
+    // value has to be converted already
+    pub fn new(value: Value, converter: Option<Value>) -> Self {
+        Parameter { value: value, converter: converter }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn converter(&self) -> Option<&Value> {
+        self.converter.as_ref()
+    }
+}
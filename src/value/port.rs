@@ -0,0 +1,262 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::Mutex;
+use ::value::Value;
+use ::gc::{self, Heap};
+use ::grammar;
+use ::string_interner::StringInterner;
+
+enum Stream {
+    Input(BufReader<File>),
+    InputString(Cursor<Vec<u8>>),
+    Stdin,
+    Stdout,
+    Output(BufWriter<File>),
+    OutputString(String),
+    // a connection is read and written, writes aren't buffered so the other side sees them right away
+    Tcp(BufReader<TcpStream>, TcpStream),
+    // only for accepting connections
+    Listener(TcpListener),
+    Closed,
+}
+
+impl Stream {
+    // the bytes held by a string port, for the memory limit
+    fn buffer_size(&self) -> usize {
+        match self {
+            &Stream::InputString(ref reader) => reader.get_ref().capacity(),
+            &Stream::OutputString(ref output) => output.capacity(),
+            _ => 0,
+        }
+    }
+}
+
+// A file opened for either reading or writing, a string, stdin or a tcp connection.
+// All io errors are returned as strings, so they can be raised as conditions.
+pub struct Port {
+    name: String,
+    stream: Mutex<Stream>,
+    // read reads whole lines, data after the first datum are returned by the next reads
+    reader: Mutex<grammar::Reader>,
+    // string ports count their buffer in the heap of the interpreter that opened them, like hash tables their entries
+    heap: Option<Heap>,
+}
+
+impl Port {
+    pub fn open_input(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("could not open {:?}: {}", path, err))?;
+        Ok(Port::new(path, Stream::Input(BufReader::new(file))))
+    }
+
+    // creates the file or truncates it if it already exists
+    pub fn open_output(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("could not open {:?}: {}", path, err))?;
+        Ok(Port::new(path, Stream::Output(BufWriter::new(file))))
+    }
+
+    // reads s like the contents of a file
+    pub fn open_input_string(s: &str) -> Self {
+        Port::new("string", Stream::InputString(Cursor::new(s.as_bytes().to_vec())))
+    }
+
+    // collects the output, see output_string
+    pub fn open_output_string() -> Self {
+        Port::new("string", Stream::OutputString(String::new()))
+    }
+
+    // what was written to a port created by open_output_string
+    pub fn output_string(&self) -> Result<String, String> {
+        match *self.stream.lock().unwrap() {
+            Stream::OutputString(ref s) => Ok(s.clone()),
+            _ => Err(format!("port {} is not an open output string port", self.name)),
+        }
+    }
+
+    pub fn connect(host: &str, port: u16) -> Result<Self, String> {
+        let address = format!("{}:{}", host, port);
+        let stream = TcpStream::connect((host, port)).map_err(|err| format!("could not connect to {}: {}", address, err))?;
+        Port::tcp(&address, stream)
+    }
+
+    // port 0 picks a free port, see local_port
+    pub fn listen(host: &str, port: u16) -> Result<Self, String> {
+        let address = format!("{}:{}", host, port);
+        let listener = TcpListener::bind((host, port)).map_err(|err| format!("could not listen on {}: {}", address, err))?;
+        Ok(Port::new(&address, Stream::Listener(listener)))
+    }
+
+    // waits for the next connection to a port created by listen
+    pub fn accept(&self) -> Result<Self, String> {
+        let (stream, address) = match *self.stream.lock().unwrap() {
+            Stream::Listener(ref listener) => listener.accept().map_err(io_error)?,
+            _ => return Err(format!("port {} is not listening", self.name)),
+        };
+        Port::tcp(&address.to_string(), stream)
+    }
+
+    // the port number a listener is bound to
+    pub fn local_port(&self) -> Result<u16, String> {
+        match *self.stream.lock().unwrap() {
+            Stream::Listener(ref listener) => listener.local_addr().map(|address| address.port()).map_err(io_error),
+            _ => Err(format!("port {} is not listening", self.name)),
+        }
+    }
+
+    fn tcp(name: &str, stream: TcpStream) -> Result<Self, String> {
+        let reader = BufReader::new(stream.try_clone().map_err(io_error)?);
+        Ok(Port::new(name, Stream::Tcp(reader, stream)))
+    }
+
+    pub fn stdin() -> Self {
+        Port::new("stdin", Stream::Stdin)
+    }
+
+    pub fn stdout() -> Self {
+        Port::new("stdout", Stream::Stdout)
+    }
+
+    fn new(name: &str, stream: Stream) -> Self {
+        let heap = gc::running();
+        heap.as_ref().map(|heap| heap.track_allocation(stream.buffer_size()));
+        Port {
+            name: name.into(),
+            stream: Mutex::new(stream),
+            reader: Mutex::new(grammar::Reader::new()),
+            heap: heap,
+        }
+    }
+
+    // None at end of file, the line ending is removed
+    pub fn read_line(&self) -> Result<Option<String>, String> {
+        let mut line = String::new();
+        let read = match *self.stream.lock().unwrap() {
+            Stream::Input(ref mut reader) => reader.read_line(&mut line).map_err(io_error)?,
+            Stream::InputString(ref mut reader) => reader.read_line(&mut line).map_err(io_error)?,
+            Stream::Tcp(ref mut reader, _) => reader.read_line(&mut line).map_err(io_error)?,
+            Stream::Stdin => io::stdin().read_line(&mut line).map_err(io_error)?,
+            _ => return Err(self.not_open("input")),
+        };
+
+        if read == 0 { return Ok(None); }
+        if line.ends_with('\n') { line.pop(); }
+        if line.ends_with('\r') { line.pop(); }
+        Ok(Some(line))
+    }
+
+    // None at end of file
+    pub fn read_char(&self) -> Result<Option<char>, String> {
+        match *self.stream.lock().unwrap() {
+            Stream::Input(ref mut reader) => read_utf8_char(reader),
+            Stream::InputString(ref mut reader) => read_utf8_char(reader),
+            Stream::Tcp(ref mut reader, _) => read_utf8_char(reader),
+            Stream::Stdin => read_utf8_char(&mut io::stdin()),
+            _ => Err(self.not_open("input")),
+        }
+    }
+
+    // the next datum, None at end of file
+    pub fn read_datum(&self, interner: &mut StringInterner) -> Result<Option<Value>, String> {
+        let mut reader = self.reader.lock().unwrap();
+        // a datum can span multiple lines, so read until it's complete
+        loop {
+            if let Some(x) = reader.next(interner)? { return Ok(Some(x)); }
+            match self.read_line()? {
+                Some(line) => { reader.feed(&line); reader.feed("\n"); },
+                None => {
+                    reader.finish();
+                    return reader.next(interner);
+                },
+            }
+        }
+    }
+
+    pub fn write_str(&self, s: &str) -> Result<(), String> {
+        match *self.stream.lock().unwrap() {
+            Stream::Output(ref mut writer) => writer.write_all(s.as_bytes()).map_err(io_error),
+            Stream::OutputString(ref mut output) => {
+                let capacity = output.capacity();
+                output.push_str(s);
+                self.heap.as_ref().map(|heap| heap.track_allocation(output.capacity() - capacity));
+                Ok(())
+            },
+            Stream::Tcp(_, ref mut writer) => writer.write_all(s.as_bytes()).map_err(io_error),
+            // flushed right away, so output without a newline shows up in the repl
+            Stream::Stdout => {
+                let mut stdout = io::stdout();
+                stdout.write_all(s.as_bytes()).and_then(|_| stdout.flush()).map_err(io_error)
+            },
+            _ => Err(self.not_open("output")),
+        }
+    }
+
+    // flushes pending output, closing a closed port does nothing
+    pub fn close(&self) -> Result<(), String> {
+        self.reader.lock().unwrap().clear();
+        let stream = ::std::mem::replace(&mut *self.stream.lock().unwrap(), Stream::Closed);
+        self.heap.as_ref().map(|heap| heap.track_free(stream.buffer_size()));
+        match stream {
+            Stream::Output(mut writer) => writer.flush().map_err(io_error),
+            // the other side may have closed the connection already
+            Stream::Tcp(_, writer) => match writer.shutdown(Shutdown::Both) {
+                Err(ref err) if err.kind() == io::ErrorKind::NotConnected => Ok(()),
+                res => res.map_err(io_error),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match *self.stream.lock().unwrap() {
+            Stream::Input(_) | Stream::InputString(_) | Stream::Stdin => format!("[INPUT_PORT: {}]", self.name),
+            Stream::Output(_) | Stream::OutputString(_) | Stream::Stdout => format!("[OUTPUT_PORT: {}]", self.name),
+            Stream::Tcp(..) => format!("[TCP_PORT: {}]", self.name),
+            Stream::Listener(_) => format!("[TCP_LISTENER: {}]", self.name),
+            Stream::Closed => format!("[CLOSED_PORT: {}]", self.name),
+        }
+    }
+
+    // must not lock the stream, it's still locked by the caller
+    fn not_open(&self, direction: &str) -> String {
+        format!("port {} is not open for {}", self.name, direction)
+    }
+}
+
+fn read_utf8_char<R: Read>(reader: &mut R) -> Result<Option<char>, String> {
+    let mut bytes = [0; 4];
+    if reader.read(&mut bytes[..1]).map_err(io_error)? == 0 { return Ok(None); }
+
+    // the first byte tells how many bytes follow
+    let len = if bytes[0] < 0x80 { 1 } else if bytes[0] < 0xE0 { 2 } else if bytes[0] < 0xF0 { 3 } else { 4 };
+    reader.read_exact(&mut bytes[1..len]).map_err(io_error)?;
+    match ::std::str::from_utf8(&bytes[..len]) {
+        Ok(s) => Ok(s.chars().next()),
+        Err(_) => Err("stream did not contain valid UTF-8".into()),
+    }
+}
+
+fn io_error(err: io::Error) -> String {
+    format!("io error: {}", err)
+}
+
+// Ports are compared by identity, two ports for the same file are still different.
+impl PartialEq for Port {
+    fn eq(&self, other: &Port) -> bool {
+        self as *const Port == other as *const Port
+    }
+}
+
+impl Drop for Port {
+    fn drop(&mut self) {
+        if let (Some(heap), Ok(stream)) = (self.heap.as_ref(), self.stream.get_mut()) {
+            heap.track_free(stream.buffer_size());
+        }
+    }
+}
+
+impl fmt::Debug for Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string())
+    }
+}
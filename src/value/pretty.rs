@@ -0,0 +1,56 @@
+use ::value::Value;
+use ::string_interner::StringInterner;
+
+impl Value {
+    // Like to_string, but lists and vectors that don't fit into width are broken into lines.
+    // The elements are indented below the first one, forms starting with a symbol like (define x ...)
+    // keep their first argument on the same line and indent the rest by two.
+    pub fn pretty_string(&self, interner: &StringInterner, width: usize) -> String {
+        // data with labels is written on one line
+        if self.is_cyclic() { return self.to_string(interner); }
+        let mut s = String::new();
+        pretty(self, interner, width, 0, &mut s);
+        s
+    }
+}
+
+// column is where value starts, s already holds everything before it
+fn pretty(value: &Value, interner: &StringInterner, width: usize, column: usize, s: &mut String) {
+    let flat = value.to_string(interner);
+    let (open, elements) = match (value.get_list(), value.get_vector()) {
+        (Some(elements), _) => ("(", elements),
+        (_, Some(elements)) => ("#(", elements.read().unwrap().clone()),
+        _ => ("", vec![]),
+    };
+    if column + flat.chars().count() <= width || elements.len() < 2 {
+        s.push_str(&flat);
+        return;
+    }
+
+    s.push_str(open);
+    let column = column + open.len();
+    // safe because there are at least two elements
+    let (first, rest) = elements.split_first().unwrap();
+    pretty(first, interner, width, column, s);
+
+    let (rest, indent) = if open == "(" && first.get_symbol().is_some() {
+        s.push(' ');
+        let first_arg_column = column + first.to_string(interner).chars().count() + 1;
+        pretty(&rest[0], interner, width, first_arg_column, s);
+        (&rest[1..], column + 1)
+    } else {
+        (rest, column)
+    };
+    for x in rest {
+        newline(indent, s);
+        pretty(x, interner, width, indent, s);
+    }
+    s.push(')');
+}
+
+fn newline(column: usize, s: &mut String) {
+    s.push('\n');
+    for _ in 0..column {
+        s.push(' ');
+    }
+}
@@ -1,6 +1,7 @@
-use ::value::{Value, LetLoop};
+use ::value::{Value, Condition, RecursionPoint};
 use ::scope::Scope;
-use ::interpreter::Interpreter;
+use ::interpreter::{Interpreter, Trampoline, CompiledBody, Chunk, vm};
+use std::sync::Arc;
 use ::string_interner::StringInterner;
 use itertools::Itertools;
 
@@ -9,38 +10,102 @@ pub struct Proc {
     name: Option<String>,
     parent_scope: Scope,
     bindings: Vec<u64>,
+    // binds all args after bindings as list, if any
+    rest: Option<u64>,
     code: Vec<Value>,
+    // shared with the lambda that created the procedure, see Interpreter::use_vm
+    compiled: CompiledBody,
 }
 
 impl Proc {
-    pub fn new(name: Option<String>, parent_scope: Scope, bindings: Vec<u64>, code: Vec<Value>) -> Self {
+    pub fn new(name: Option<String>, parent_scope: Scope, bindings: Vec<u64>, rest: Option<u64>, code: Vec<Value>, compiled: CompiledBody) -> Self {
         Proc {
             name: name,
             parent_scope: parent_scope,
             bindings: bindings,
+            rest: rest,
             code: code,
+            compiled: compiled,
         }
     }
 
-    pub fn evaluate(&self, interpreter: &mut Interpreter, args: &[Value]) -> Value {
-        // every function's body is enclosed in an implicit loop
-        let bindings: Vec<(u64, Value)> = self.bindings.iter().cloned().zip(args.iter().cloned()).collect();
-        let implicit_loop = LetLoop::new(bindings, self.code.clone());
-        implicit_loop.evaluate_loop(interpreter)
+    pub fn parent_scope(&self) -> &Scope {
+        &self.parent_scope
     }
 
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|x| &**x)
+    }
+
+    // the number of parameters before the rest parameter and if there is one
+    pub fn arity(&self) -> (usize, bool) {
+        (self.bindings.len(), self.rest.is_some())
+    }
+
+    // args have to be evaluated already
+    pub fn evaluate(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Trampoline, Condition> {
+        interpreter.current_scope = self.bind(args)?;
+        if interpreter.use_vm {
+            if let Some(chunk) = self.compiled() { return vm::run(interpreter, chunk); }
+        }
+
+        // every function's body is enclosed in an implicit loop,
+        // recur has to supply the rest args as list
+        let mut bindings = self.bindings.clone();
+        bindings.extend(self.rest);
+        let implicit_loop = RecursionPoint::new(bindings, self.code.clone(), self.parent_scope.clone());
+        Ok(Trampoline::RecursionPoint(implicit_loop))
+    }
+
+    // the scope the body is evaluated in, a child of the scope the procedure was defined in
+    pub fn bind(&self, mut args: Vec<Value>) -> Result<Scope, Condition> {
+        let name = self.name.as_ref().map(|x| &**x).unwrap_or("lambda");
+        match self.rest {
+            Some(_) => {
+                check_arity!(name, args.len(), min => self.bindings.len() as u32);
+                let rest_args = args.split_off(self.bindings.len());
+                args.push(Value::new_list(&rest_args));
+            },
+            None => check_arity!(name, args.len(), self.bindings.len() as u32),
+        }
+
+        let mut scope = self.parent_scope.new_child();
+        for (&binding_name, binding_value) in self.bindings.iter().chain(self.rest.iter()).zip(args.into_iter()) {
+            scope.bind(binding_name, binding_value);
+        }
+        Ok(scope)
+    }
+
+    // the body as bytecode, None if it can't be compiled
+    pub fn compiled(&self) -> Option<Arc<Chunk>> {
+        let mut params = self.bindings.clone();
+        params.extend(self.rest);
+        self.compiled.get(&self.code, params)
+    }
 
     pub fn to_string(&self, interner: &StringInterner) -> String {
         let name = self.name.as_ref().map(|x| &**x).unwrap_or("lambda");
+        format!("({} {} {})", name, self.parameters(interner), self.code(interner))
+    }
 
-        let bindings = self.bindings.iter()
-        .flat_map(|&b| interner.lookup(b))
-        .join(" ");
+    // the lambda expression that creates it, the body as it was compiled, see Interpreter::dump_scope
+    pub fn source(&self, interner: &StringInterner) -> String {
+        let name = self.name.as_ref().map(|x| format!(" {}", x)).unwrap_or_default();
+        format!("(lambda{} {} {})", name, self.parameters(interner), self.code(interner))
+    }
 
-        let code = self.code.iter()
-        .map(|x| x.to_string(interner))
-        .join(" ");
+    fn parameters(&self, interner: &StringInterner) -> String {
+        let bindings: Vec<Value> = self.bindings.iter().map(|&b| Value::new_symbol(b)).collect();
+        let bindings = match self.rest {
+            Some(rest) => Value::new_dotted_list(bindings, Value::new_symbol(rest)),
+            None => Value::new_list(&bindings),
+        };
+        bindings.to_string(interner)
+    }
 
-        format!("({} ({}) {})", name, bindings, code)
+    fn code(&self, interner: &StringInterner) -> String {
+        self.code.iter()
+        .map(|x| x.to_string(interner))
+        .join(" ")
     }
 }
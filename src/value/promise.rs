@@ -0,0 +1,67 @@
+use std::sync::RwLock;
+use ::value::Value;
+
+#[derive(Debug, PartialEq)]
+enum State {
+    // a procedure without arguments, which computes the value
+    Delayed(Value),
+    Forced(Value),
+}
+
+// A value which is computed when it's needed, created by delay or make-promise.
+// The value is remembered, so the procedure of a promise is called at most once by force.
+#[derive(Debug)]
+pub struct Promise {
+    state: RwLock<State>,
+}
+
+impl Promise {
+    pub fn delayed(thunk: Value) -> Self {
+        Promise { state: RwLock::new(State::Delayed(thunk)) }
+    }
+
+    pub fn forced(value: Value) -> Self {
+        Promise { state: RwLock::new(State::Forced(value)) }
+    }
+
+    // the value, if the promise has been forced
+    pub fn value(&self) -> Option<Value> {
+        match *self.state.read().unwrap() {
+            State::Forced(ref x) => Some(x.clone()),
+            State::Delayed(_) => None,
+        }
+    }
+
+    // the procedure, if the promise hasn't been forced yet
+    pub fn thunk(&self) -> Option<Value> {
+        match *self.state.read().unwrap() {
+            State::Delayed(ref x) => Some(x.clone()),
+            State::Forced(_) => None,
+        }
+    }
+
+    // the value or the procedure, None if the promise is being fulfilled right now, see gc
+    pub fn try_references(&self) -> Option<Vec<Value>> {
+        match self.state.try_read() {
+            Ok(state) => match *state {
+                State::Delayed(ref x) | State::Forced(ref x) => Some(vec![x.clone()]),
+            },
+            Err(_) => None,
+        }
+    }
+
+    // remembers value and returns the value of the promise,
+    // the procedure can force its own promise, then the value of the inner force is kept
+    pub fn fulfill(&self, value: Value) -> Value {
+        if let Some(x) = self.value() { return x; }
+        *self.state.write().unwrap() = State::Forced(value.clone());
+        value
+    }
+}
+
+// like the derived version, comparing a promise with itself doesn't lock it twice
+impl PartialEq for Promise {
+    fn eq(&self, other: &Promise) -> bool {
+        self as *const Promise == other as *const Promise || *self.state.read().unwrap() == *other.state.read().unwrap()
+    }
+}
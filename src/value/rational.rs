@@ -0,0 +1,116 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Sub, Mul};
+use std::fmt;
+use ::value::BigInt;
+
+// Exact fraction, always reduced and with a positive denominator.
+// Values with a denominator of 1 are stored as integers, see Value::new_rational.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+impl Rational {
+    // None if denominator is zero
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Option<Self> {
+        if denominator.is_zero() { return None; }
+
+        let divisor = numerator.clone().gcd(denominator.clone());
+        // safe because the gcd of anything and a non zero number isn't zero
+        let (mut numerator, _) = numerator.div_rem(&divisor).unwrap();
+        let (mut denominator, _) = denominator.div_rem(&divisor).unwrap();
+        if denominator.is_negative() {
+            numerator = -numerator;
+            denominator = -denominator;
+        }
+
+        Some(Rational {
+            numerator: numerator,
+            denominator: denominator,
+        })
+    }
+
+    // parses numerator/denominator, e.g. -1/3
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.splitn(2, '/');
+        let numerator = parts.next().and_then(BigInt::parse)?;
+        let denominator = parts.next().and_then(BigInt::parse)?;
+        Rational::new(numerator, denominator)
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator.to_f64() / self.denominator.to_f64()
+    }
+
+    // None if other is zero
+    pub fn checked_div(self, other: Rational) -> Option<Rational> {
+        Rational::new(self.numerator * other.denominator, self.denominator * other.numerator)
+    }
+}
+
+impl From<BigInt> for Rational {
+    fn from(x: BigInt) -> Self {
+        Rational {
+            numerator: x,
+            denominator: BigInt::from(1),
+        }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        // denominators are positive, so a/b < c/d <=> a*d < c*b
+        let a = self.numerator.clone() * other.denominator.clone();
+        let b = other.numerator.clone() * self.denominator.clone();
+        a.cmp(&b)
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// unwraps are safe, because the product of 2 non zero denominators isn't zero
+
+impl Add for Rational {
+    type Output = Rational;
+
+    fn add(self, other: Rational) -> Rational {
+        let numerator = self.numerator * other.denominator.clone() + other.numerator * self.denominator.clone();
+        Rational::new(numerator, self.denominator * other.denominator).unwrap()
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, other: Rational) -> Rational {
+        let numerator = self.numerator * other.denominator.clone() - other.numerator * self.denominator.clone();
+        Rational::new(numerator, self.denominator * other.denominator).unwrap()
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+
+    fn mul(self, other: Rational) -> Rational {
+        Rational::new(self.numerator * other.numerator, self.denominator * other.denominator).unwrap()
+    }
+}
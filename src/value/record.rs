@@ -0,0 +1,53 @@
+use std::sync::RwLock;
+use ::value::Value;
+
+// The type of the records created by define-record-type, with the names of the type and its fields.
+#[derive(Debug, PartialEq)]
+pub struct RecordType {
+    name: u64,
+    fields: Vec<u64>,
+}
+
+impl RecordType {
+    pub fn new(name: u64, fields: Vec<u64>) -> Self {
+        RecordType { name: name, fields: fields }
+    }
+
+    pub fn name(&self) -> u64 {
+        self.name
+    }
+
+    pub fn fields(&self) -> &[u64] {
+        &self.fields
+    }
+}
+
+// An instance of a record type, the fields are in the order of RecordType::fields and can be changed by mutators.
+#[derive(Debug)]
+pub struct RecordInstance {
+    // always a record type value
+    record_type: Value,
+    fields: RwLock<Vec<Value>>,
+}
+
+impl RecordInstance {
+    pub fn new(record_type: Value, fields: Vec<Value>) -> Self {
+        RecordInstance { record_type: record_type, fields: RwLock::new(fields) }
+    }
+
+    pub fn record_type(&self) -> &Value {
+        &self.record_type
+    }
+
+    pub fn fields(&self) -> &RwLock<Vec<Value>> {
+        &self.fields
+    }
+}
+
+// like the derived version, comparing a record with itself doesn't lock it twice
+impl PartialEq for RecordInstance {
+    fn eq(&self, other: &RecordInstance) -> bool {
+        self as *const RecordInstance == other as *const RecordInstance ||
+        (self.record_type == other.record_type && *self.fields.read().unwrap() == *other.fields.read().unwrap())
+    }
+}
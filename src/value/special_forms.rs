@@ -1,31 +1,115 @@
-use ::value::Value;
-use ::interpreter::Interpreter;
+use ::value::{Value, Condition};
+use ::interpreter::{Interpreter, Trampoline, Compiler, CompiledBody, Op, Unsupported};
+use ::scope::Scope;
+use ::string_interner::StringInterner;
+use itertools::Itertools;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum SpecialForm {
     Begin(Begin),
     Define(Define),
+    Set(Set),
     If(If),
+    And(Connective),
+    Or(Connective),
     Lambda(Lambda),
     Let(LetLoop),
+    LetStar(LetLoop),
+    Letrec(LetLoop),
     Loop(LetLoop),
     RecurForm(RecurForm),
     Quote(Quote),
+    Quasiquote(Quasiquote),
+    Cond(Cond),
+    Case(Case),
 }
 
 impl SpecialForm {
-    pub fn evaluate(&self, interpreter: &mut Interpreter) -> Value {
+    pub fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
         match self {
             &SpecialForm::Begin(ref x) => x.evaluate(interpreter),
             &SpecialForm::Define(ref x) => x.evaluate(interpreter),
+            &SpecialForm::Set(ref x) => x.evaluate(interpreter),
             &SpecialForm::If(ref x) => x.evaluate(interpreter),
+            &SpecialForm::And(ref x) => x.evaluate(interpreter, "and", false),
+            &SpecialForm::Or(ref x) => x.evaluate(interpreter, "or", true),
             &SpecialForm::Lambda(ref x) => x.evaluate(interpreter),
             &SpecialForm::Let(ref x) => x.evaluate_let(interpreter),
+            &SpecialForm::LetStar(ref x) => x.evaluate_let_star(interpreter),
+            &SpecialForm::Letrec(ref x) => x.evaluate_letrec(interpreter),
             &SpecialForm::Loop(ref x) => x.evaluate_loop(interpreter),
             &SpecialForm::RecurForm(ref x) => x.evaluate(interpreter),
             &SpecialForm::Quote(ref x) => x.evaluate(interpreter),
+            &SpecialForm::Quasiquote(ref x) => x.evaluate(interpreter),
+            &SpecialForm::Cond(ref x) => x.evaluate(interpreter),
+            &SpecialForm::Case(ref x) => x.evaluate(interpreter),
         }
     }
+
+    // emits the instructions for value, which contains this special form
+    pub fn compile(&self, compiler: &mut Compiler, value: &Value, tail: bool) -> Result<(), Unsupported> {
+        match self {
+            &SpecialForm::Begin(ref x) => compiler.compile_body(&x.code, tail),
+            &SpecialForm::Define(ref x) => {
+                compiler.compile(&x.expression, false)?;
+                compiler.emit(Op::Define(x.symbol_id));
+                Ok(compiler.finish(tail))
+            },
+            &SpecialForm::Set(ref x) => {
+                compiler.compile(&x.expression, false)?;
+                compiler.set(x.symbol_id);
+                Ok(compiler.finish(tail))
+            },
+            &SpecialForm::If(ref x) => x.compile(compiler, tail),
+            &SpecialForm::And(ref x) => x.compile(compiler, tail, "and", false),
+            &SpecialForm::Or(ref x) => x.compile(compiler, tail, "or", true),
+            &SpecialForm::Let(ref x) => x.compile_let(compiler, tail),
+            &SpecialForm::LetStar(ref x) => x.compile_let_star(compiler, tail),
+            &SpecialForm::Letrec(ref x) => x.compile_letrec(compiler, tail),
+            // recur refers to the recursion point of the procedure, which the vm doesn't have
+            &SpecialForm::RecurForm(_) => Err(Unsupported),
+            &SpecialForm::Quote(ref x) => {
+                compiler.push_constant(x.expression.clone());
+                Ok(compiler.finish(tail))
+            },
+            &SpecialForm::Cond(ref x) => x.compile(compiler, tail),
+            &SpecialForm::Case(ref x) => x.compile(compiler, tail),
+            // the body is compiled when the procedure is called, in the scopes it's created in here
+            &SpecialForm::Lambda(ref x) => {
+                x.compiled.set_env(compiler.scopes());
+                Ok(compiler.fallback(value, tail))
+            },
+            // loops establish recursion points, quasiquotes gain nothing from being compiled
+            &SpecialForm::Loop(_) | &SpecialForm::Quasiquote(_) => Ok(compiler.fallback(value, tail)),
+        }
+    }
+
+    // prints the special form as it would be written
+    pub fn to_string(&self, interner: &StringInterner) -> String {
+        match self {
+            &SpecialForm::Begin(ref x) => x.to_string(interner),
+            &SpecialForm::Define(ref x) => x.to_string(interner),
+            &SpecialForm::Set(ref x) => x.to_string(interner),
+            &SpecialForm::If(ref x) => x.to_string(interner),
+            &SpecialForm::And(ref x) => x.to_string("and", interner),
+            &SpecialForm::Or(ref x) => x.to_string("or", interner),
+            &SpecialForm::Lambda(ref x) => x.to_string(interner),
+            &SpecialForm::Let(ref x) => x.to_string("let", interner),
+            &SpecialForm::LetStar(ref x) => x.to_string("let*", interner),
+            &SpecialForm::Letrec(ref x) => x.to_string("letrec", interner),
+            &SpecialForm::Loop(ref x) => x.to_string("loop", interner),
+            &SpecialForm::RecurForm(ref x) => x.to_string(interner),
+            &SpecialForm::Quote(ref x) => x.to_string(interner),
+            &SpecialForm::Quasiquote(ref x) => x.to_string(interner),
+            &SpecialForm::Cond(ref x) => x.to_string(interner),
+            &SpecialForm::Case(ref x) => x.to_string(interner),
+        }
+    }
+}
+
+// prints code separated by spaces, prefixed with a space if there is any code
+fn code_to_string(code: &[Value], interner: &StringInterner) -> String {
+    code.iter().map(|x| format!(" {}", x.to_string(interner))).join("")
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -44,13 +128,90 @@ impl If {
         }
     }
 
-    fn evaluate(&self, interpreter: &mut Interpreter) -> Value {
-        let condition = interpreter.evaluate(&self.condition);
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        let condition = interpreter.evaluate(&self.condition)?;
         match condition.get_bool() {
-            Some(true)  => interpreter.evaluate(&self.then),
-            Some(false) => interpreter.evaluate(&self.or_else),
-            None => new_condition!(format!("if expected bool, found: {}", condition.to_string(&interpreter.interner)))
+            Some(true)  => Ok(Trampoline::Eval(self.then.clone())),
+            Some(false) => Ok(Trampoline::Eval(self.or_else.clone())),
+            None => raise_condition!(format!("if expected bool, found: {}", condition.to_string(&interpreter.interner)))
+        }
+    }
+
+    fn compile(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        compiler.compile(&self.condition, false)?;
+        let or_else = compiler.emit(Op::JumpUnless(0, "if"));
+        compiler.compile(&self.then, tail)?;
+        let end = compiler.emit(Op::Jump(0));
+        let position = compiler.position();
+        compiler.patch(or_else, position);
+        compiler.compile(&self.or_else, tail)?;
+        let position = compiler.position();
+        compiler.patch(end, position);
+        Ok(())
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        format!("(if {} {} {})", self.condition.to_string(interner), self.then.to_string(interner), self.or_else.to_string(interner))
+    }
+}
+
+// and & or
+#[derive(Debug, PartialEq, Clone)]
+pub struct Connective {
+    operands: Vec<Value>,
+}
+
+impl Connective {
+    pub fn new(operands: Vec<Value>) -> Self {
+        Connective {
+            operands: operands,
+        }
+    }
+
+    // Operands are evaluated until one of them is short_circuit, which is returned.
+    // Otherwise the last operand is evaluated in tail position, it doesn't have to be a bool.
+    fn evaluate(&self, interpreter: &mut Interpreter, keyword: &str, short_circuit: bool) -> Result<Trampoline, Condition> {
+        let (last, init) = match self.operands.split_last() {
+            Some(x) => x,
+            None => return Ok(Trampoline::Return(Value::new_bool(!short_circuit))),
+        };
+
+        for x in init {
+            let value = interpreter.evaluate(x)?;
+            match value.get_bool() {
+                Some(b) if b == short_circuit => return Ok(Trampoline::Return(value)),
+                Some(_) => continue,
+                None => raise_condition!(format!("{} expected bool, found: {}", keyword, value.to_string(&interpreter.interner))),
+            }
         }
+        Ok(Trampoline::Eval(last.clone()))
+    }
+
+    // short circuits jump behind the last operand, which returns itself in tail position
+    fn compile(&self, compiler: &mut Compiler, tail: bool, keyword: &'static str, short_circuit: bool) -> Result<(), Unsupported> {
+        let (last, init) = match self.operands.split_last() {
+            Some(x) => x,
+            None => {
+                compiler.push_constant(Value::new_bool(!short_circuit));
+                return Ok(compiler.finish(tail));
+            },
+        };
+
+        let mut jumps = vec![];
+        for x in init {
+            compiler.compile(x, false)?;
+            jumps.push(compiler.emit(Op::ShortCircuit(short_circuit, 0, keyword)));
+        }
+        compiler.compile(last, tail)?;
+        let end = compiler.position();
+        for jump in jumps {
+            compiler.patch(jump, end);
+        }
+        Ok(compiler.finish(tail))
+    }
+
+    fn to_string(&self, keyword: &str, interner: &StringInterner) -> String {
+        format!("({}{})", keyword, code_to_string(&self.operands, interner))
     }
 }
 
@@ -68,10 +229,44 @@ impl Define {
         }
     }
 
-    fn evaluate(&self, interpreter: &mut Interpreter) -> Value {
-        let expr = interpreter.evaluate(&self.expression);
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        let expr = interpreter.evaluate(&self.expression)?;
         interpreter.current_scope.add_symbol(self.symbol_id, expr);
-        Value::new_symbol(self.symbol_id)
+        Ok(Trampoline::Return(Value::new_symbol(self.symbol_id)))
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        format!("(define {} {})", Value::new_symbol(self.symbol_id).to_string(interner), self.expression.to_string(interner))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Set {
+    symbol_id: u64,
+    expression: Value,
+}
+
+impl Set {
+    pub fn new(symbol_id: u64, expression: Value) -> Self {
+        Set {
+            symbol_id: symbol_id,
+            expression: expression,
+        }
+    }
+
+    // unlike define this never creates a binding, the nearest existing one is changed
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        let expr = interpreter.evaluate(&self.expression)?;
+        interpreter.report_set(self.symbol_id, &expr)?;
+        if !interpreter.current_scope.set_symbol(self.symbol_id, expr) {
+            let name = Value::new_symbol(self.symbol_id).to_string(&interpreter.interner);
+            raise_condition!(format!("set! undefined ident: {}", name));
+        }
+        Ok(Trampoline::Return(Value::new_symbol(self.symbol_id)))
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        format!("(set! {} {})", Value::new_symbol(self.symbol_id).to_string(interner), self.expression.to_string(interner))
     }
 }
 
@@ -87,8 +282,81 @@ impl Quote {
         }
     }
 
-    fn evaluate(&self, _: &mut Interpreter) -> Value {
-        self.expression.clone()
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        if interpreter.coverage.is_some() { interpreter.cover_quoted(&self.expression); }
+        Ok(Trampoline::Return(self.expression.clone()))
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        format!("(quote {})", self.expression.to_string(interner))
+    }
+}
+
+// The parts of a quasiquoted expression, created by the syntax pass.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Template {
+    // contains no unquotes, returned as is
+    Datum(Value),
+    // ,x
+    Unquote(Value),
+    // ,@x, only valid inside of a list
+    UnquoteSplicing(Value),
+    List(Vec<Template>),
+}
+
+impl Template {
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Value, Condition> {
+        match self {
+            &Template::Datum(ref datum) => Ok(datum.clone()),
+            &Template::Unquote(ref code) => interpreter.evaluate(code),
+            // rejected by the syntax pass
+            &Template::UnquoteSplicing(_) => unreachable!(),
+            &Template::List(ref templates) => {
+                let mut values = vec![];
+                for template in templates {
+                    if let &Template::UnquoteSplicing(ref code) = template {
+                        let spliced = interpreter.evaluate(code)?;
+                        match spliced.get_list() {
+                            Some(list) => values.extend(list),
+                            None => raise_condition!(format!("unquote-splicing expected list, got: {}", spliced.to_string(&interpreter.interner))),
+                        }
+                    } else {
+                        values.push(template.evaluate(interpreter)?);
+                    }
+                }
+                Ok(Value::new_list(&values))
+            },
+        }
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        match self {
+            &Template::Datum(ref datum) => datum.to_string(interner),
+            &Template::Unquote(ref code) => format!("(unquote {})", code.to_string(interner)),
+            &Template::UnquoteSplicing(ref code) => format!("(unquote-splicing {})", code.to_string(interner)),
+            &Template::List(ref templates) => format!("({})", templates.iter().map(|x| x.to_string(interner)).join(" ")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Quasiquote {
+    template: Template,
+}
+
+impl Quasiquote {
+    pub fn new(template: Template) -> Self {
+        Quasiquote {
+            template: template,
+        }
+    }
+
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        self.template.evaluate(interpreter).map(Trampoline::Return)
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        format!("(quasiquote {})", self.template.to_string(interner))
     }
 }
 
@@ -96,20 +364,37 @@ impl Quote {
 pub struct Lambda {
     name: Option<String>,
     bindings: Vec<u64>,
+    rest: Option<u64>,
     code: Vec<Value>,
+    compiled: CompiledBody,
 }
 
 impl Lambda {
-    pub fn new(name: Option<String>, bindings: Vec<u64>, code: Vec<Value>) -> Self {
+    pub fn new(name: Option<String>, bindings: Vec<u64>, rest: Option<u64>, code: Vec<Value>) -> Self {
         Lambda {
             name: name,
             bindings: bindings,
+            rest: rest,
             code: code,
+            compiled: CompiledBody::new(),
         }
     }
 
-    fn evaluate(&self, interpreter: &mut Interpreter) -> Value {
-        Value::new_proc(self.name.clone(), interpreter.current_scope.clone(), self.bindings.clone(), self.code.clone())
+    // the procedure captures the scope, it might be part of a cycle from now on
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        interpreter.heap.register_scope(&interpreter.current_scope);
+        let procedure = Value::new_proc(self.name.clone(), interpreter.current_scope.clone(), self.bindings.clone(), self.rest, self.code.clone(), self.compiled.clone());
+        Ok(Trampoline::Return(procedure))
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        let name = self.name.as_ref().map(|x| format!(" {}", x)).unwrap_or("".into());
+        let bindings: Vec<Value> = self.bindings.iter().map(|&b| Value::new_symbol(b)).collect();
+        let bindings = match self.rest {
+            Some(rest) => Value::new_dotted_list(bindings, Value::new_symbol(rest)),
+            None => Value::new_list(&bindings),
+        };
+        format!("(lambda{} {}{})", name, bindings.to_string(interner), code_to_string(&self.code, interner))
     }
 }
 
@@ -127,67 +412,155 @@ impl LetLoop {
         }
     }
 
-    fn evaluate_let(&self, interpreter: &mut Interpreter) -> Value {
-        // replace interpreter scope with fresh child scope
-        let parent_scope = interpreter.current_scope.clone();
-        interpreter.current_scope = parent_scope.new_child();
+    // The following functions replace the interpreter scope with a fresh child scope.
+    // The interpreter restores the old scope once the body is evaluated.
 
-        // evaluate bindings sequentially in fresh scope
-        for &(binding_name, ref binding_value) in &self.bindings {
-            let binding_value = interpreter.evaluate(binding_value);
-            interpreter.current_scope.add_symbol(binding_name, binding_value);
+    // bindings are evaluated in the outer scope, so they can't refer to each other
+    fn evaluate_let(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        let mut values = Vec::with_capacity(self.bindings.len());
+        for &(_, ref binding_value) in &self.bindings {
+            values.push(interpreter.evaluate(binding_value)?);
+        }
+
+        interpreter.current_scope = interpreter.current_scope.new_child();
+        for (&(binding_name, _), binding_value) in self.bindings.iter().zip(values.into_iter()) {
+            interpreter.current_scope.bind(binding_name, binding_value);
         }
 
         // evaluate body with new scope and bindings
-        let mut res = Value::empty_list();
-        for body in &self.code {
-            res = interpreter.evaluate(body);
+        evaluate_body(interpreter, &self.code)
+    }
+
+    // each binding gets its own scope, so it can only refer to the bindings before it
+    fn evaluate_let_star(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        for &(binding_name, ref binding_value) in &self.bindings {
+            let binding_value = interpreter.evaluate(binding_value)?;
+            interpreter.current_scope = interpreter.current_scope.new_child();
+            interpreter.current_scope.bind(binding_name, binding_value);
         }
 
-        // restore old scope
-        interpreter.current_scope = parent_scope;
+        // the body gets a scope of its own, so defines don't leak when there are no bindings
+        interpreter.current_scope = interpreter.current_scope.new_child();
+        evaluate_body(interpreter, &self.code)
+    }
 
-        res
+    // bindings are evaluated in the new scope, so procedures can refer to each other
+    fn evaluate_letrec(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        interpreter.current_scope = interpreter.current_scope.new_child();
+        self.evaluate_bindings(interpreter)?;
+        evaluate_body(interpreter, &self.code)
     }
 
-    // pub because this is also used for procs
-    pub fn evaluate_loop(&self, interpreter: &mut Interpreter) -> Value {
-        // replace interpreter scope with fresh child scope
+    fn evaluate_loop(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
         let parent_scope = interpreter.current_scope.clone();
         interpreter.current_scope = parent_scope.new_child();
+        self.evaluate_bindings(interpreter)?;
 
-        // evaluate bindings sequentially in fresh scope
+        let names = self.bindings.iter().map(|&(name, _)| name).collect();
+        let recursion_point = RecursionPoint::new(names, self.code.clone(), parent_scope);
+        Ok(Trampoline::RecursionPoint(recursion_point))
+    }
+
+    // Like the evaluation, the compiled versions create child scopes.
+    // The old scope is only restored if the let isn't in tail position, returning restores it otherwise.
+
+    fn compile_let(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        for &(_, ref binding_value) in &self.bindings {
+            compiler.compile(binding_value, false)?;
+        }
+        compiler.save_scope();
+        compiler.new_scope();
+        // the last value is on top of the stack, it wins if a name is bound twice
+        let mut bound = vec![];
+        for &(binding_name, _) in self.bindings.iter().rev() {
+            if bound.contains(&binding_name) {
+                compiler.emit(Op::Pop);
+            } else {
+                compiler.bind(binding_name);
+                bound.push(binding_name);
+            }
+        }
+        self.compile_body(compiler, tail)
+    }
+
+    fn compile_let_star(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        compiler.save_scope();
         for &(binding_name, ref binding_value) in &self.bindings {
-            let binding_value = interpreter.evaluate(binding_value);
-            interpreter.current_scope.add_symbol(binding_name, binding_value);
+            compiler.compile(binding_value, false)?;
+            compiler.new_scope();
+            compiler.bind(binding_name);
         }
+        compiler.new_scope();
+        self.compile_body(compiler, tail)
+    }
 
-        let mut res = Value::empty_list();
-        loop {
-            // evaluate body with new scope and bindings
-            for body in &self.code {
-                res = interpreter.evaluate(body);
-            }
+    fn compile_letrec(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        compiler.save_scope();
+        compiler.new_scope();
+        for &(binding_name, ref binding_value) in &self.bindings {
+            compiler.compile(binding_value, false)?;
+            compiler.bind(binding_name);
+        }
+        self.compile_body(compiler, tail)
+    }
 
-            // check for recursion
-            if let Some(args) = res.get_recur() {
-                check_arity!("loop", args.len(), self.bindings.len() as u32);
+    fn compile_body(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        compiler.compile_body(&self.code, tail)?;
+        compiler.restore_scope(tail);
+        Ok(())
+    }
 
-                // recreate a new scope
-                interpreter.current_scope = parent_scope.new_child();
+    // evaluate bindings sequentially in current scope
+    fn evaluate_bindings(&self, interpreter: &mut Interpreter) -> Result<(), Condition> {
+        for &(binding_name, ref binding_value) in &self.bindings {
+            let binding_value = interpreter.evaluate(binding_value)?;
+            interpreter.current_scope.bind(binding_name, binding_value);
+        }
+        Ok(())
+    }
 
-                // bind values from recur
-                for (binding_name, binding_value) in self.bindings.iter().map(|&(name, _)| name).zip(args.iter()) {
-                    interpreter.current_scope.add_symbol(binding_name, binding_value.clone());
-                }
+    fn to_string(&self, keyword: &str, interner: &StringInterner) -> String {
+        let bindings = self.bindings.iter()
+        .map(|&(name, ref value)| format!("{} {}", Value::new_symbol(name).to_string(interner), value.to_string(interner)))
+        .join(" ");
+        format!("({} ({}){})", keyword, bindings, code_to_string(&self.code, interner))
+    }
+}
 
-                continue;
-            }
+// A recursion point is established by loop and by calling a procedure.
+// When recur is evaluated in tail position, the bindings are rebound in a fresh
+// child scope of parent_scope and code is evaluated again.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecursionPoint {
+    bindings: Vec<u64>,
+    code: Vec<Value>,
+    parent_scope: Scope,
+}
 
-            // restore old scope
-            interpreter.current_scope = parent_scope;
-            return res;
+impl RecursionPoint {
+    pub fn new(bindings: Vec<u64>, code: Vec<Value>, parent_scope: Scope) -> Self {
+        RecursionPoint {
+            bindings: bindings,
+            code: code,
+            parent_scope: parent_scope,
+        }
+    }
+
+    // evaluates the body, expects the bindings to be already bound in the current scope
+    pub fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        evaluate_body(interpreter, &self.code)
+    }
+
+    pub fn recur(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Trampoline, Condition> {
+        check_arity!("recur", args.len(), self.bindings.len() as u32);
+
+        // recreate a new scope and bind values from recur
+        interpreter.current_scope = self.parent_scope.new_child();
+        for (&binding_name, binding_value) in self.bindings.iter().zip(args.into_iter()) {
+            interpreter.current_scope.bind(binding_name, binding_value);
         }
+
+        self.evaluate(interpreter)
     }
 }
 
@@ -203,9 +576,26 @@ impl RecurForm {
         }
     }
 
-    fn evaluate(&self, interpreter: &mut Interpreter) -> Value {
-        let evaluated_bindings = self.bindings.iter().map(|b| interpreter.evaluate(b)).collect();
-        Value::new_recur(evaluated_bindings)
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        let evaluated_bindings = self.bindings.iter().map(|b| interpreter.evaluate(b)).collect::<Result<_, _>>()?;
+        Ok(Trampoline::Recur(evaluated_bindings))
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        format!("(recur{})", code_to_string(&self.bindings, interner))
+    }
+}
+
+// evaluates all but the last expression, the last one is left to the interpreter as tail call
+pub fn evaluate_body(interpreter: &mut Interpreter, code: &[Value]) -> Result<Trampoline, Condition> {
+    match code.split_last() {
+        Some((last, init)) => {
+            for x in init {
+                interpreter.evaluate(x)?;
+            }
+            Ok(Trampoline::Eval(last.clone()))
+        },
+        None => Ok(Trampoline::Return(Value::empty_list())),
     }
 }
 
@@ -221,8 +611,253 @@ impl Begin {
         }
     }
 
-    fn evaluate(&self, interpreter: &mut Interpreter) -> Value {
-        let evaluated_code = self.code.iter().map(|b| interpreter.evaluate(b)).last();
-        evaluated_code.unwrap_or(Value::empty_list())
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        evaluate_body(interpreter, &self.code)
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        format!("(begin{})", code_to_string(&self.code, interner))
+    }
+}
+
+// What happens when a cond or case clause is selected.
+// The value is the result of the test (cond) or the key (case).
+#[derive(Debug, PartialEq, Clone)]
+pub enum ClauseBody {
+    // (test code*), for an empty body the value itself is returned
+    Code(Vec<Value>),
+    // (test => receiver), receiver is called with the value
+    Receiver(Value),
+}
+
+impl ClauseBody {
+    fn evaluate(&self, interpreter: &mut Interpreter, value: Value) -> Result<Trampoline, Condition> {
+        match self {
+            &ClauseBody::Code(ref code) if code.is_empty() => Ok(Trampoline::Return(value)),
+            &ClauseBody::Code(ref code) => evaluate_body(interpreter, code),
+            &ClauseBody::Receiver(ref receiver) => {
+                let receiver = interpreter.evaluate(receiver)?;
+                interpreter.apply(&receiver, vec![value])
+            },
+        }
+    }
+
+    // expects the value on top of the stack
+    fn compile(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        match self {
+            &ClauseBody::Code(ref code) if code.is_empty() => Ok(compiler.finish(tail)),
+            &ClauseBody::Code(ref code) => {
+                compiler.emit(Op::Pop);
+                compiler.compile_body(code, tail)
+            },
+            &ClauseBody::Receiver(ref receiver) => {
+                compiler.compile(receiver, false)?;
+                compiler.emit(Op::Swap);
+                let site = compiler.constant(receiver.clone());
+                compiler.emit(if tail { Op::TailCall(1, site) } else { Op::Call(1, site) });
+                Ok(())
+            },
+        }
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        match self {
+            &ClauseBody::Code(ref code) => code_to_string(code, interner),
+            &ClauseBody::Receiver(ref receiver) => format!(" => {}", receiver.to_string(interner)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CondClause {
+    // None for else
+    test: Option<Value>,
+    body: ClauseBody,
+}
+
+impl CondClause {
+    pub fn new(test: Option<Value>, body: ClauseBody) -> Self {
+        CondClause {
+            test: test,
+            body: body,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cond {
+    clauses: Vec<CondClause>,
+}
+
+impl Cond {
+    pub fn new(clauses: Vec<CondClause>) -> Self {
+        Cond {
+            clauses: clauses,
+        }
+    }
+
+    // Tests are evaluated in order until one of them is true, the rest is skipped.
+    // Like if, tests have to evaluate to bools. Only clauses with receivers accept
+    // anything but false, as the receiver wouldn't make much sense otherwise.
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        for clause in &self.clauses {
+            let test = match clause.test {
+                Some(ref test) => interpreter.evaluate(test)?,
+                None => return clause.body.evaluate(interpreter, Value::new_bool(true)),
+            };
+
+            let selected = match (&clause.body, test.get_bool()) {
+                (&ClauseBody::Receiver(_), b) => b != Some(false),
+                (_, Some(b)) => b,
+                (_, None) => raise_condition!(format!("cond expected bool, found: {}", test.to_string(&interpreter.interner))),
+            };
+
+            if selected {
+                return clause.body.evaluate(interpreter, test);
+            }
+        }
+        Ok(Trampoline::Return(Value::empty_list()))
+    }
+
+    // the test stays on the stack for the clause body, unless it's a bool that wasn't selected
+    fn compile(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        let mut ends = vec![];
+        for clause in &self.clauses {
+            let next = match clause.test {
+                Some(ref test) => {
+                    compiler.compile(test, false)?;
+                    match clause.body {
+                        ClauseBody::Receiver(_) => Some(compiler.emit(Op::JumpIfFalse(0))),
+                        ClauseBody::Code(_) => {
+                            let next = compiler.emit(Op::JumpUnless(0, "cond"));
+                            compiler.push_constant(Value::new_bool(true));
+                            Some(next)
+                        },
+                    }
+                },
+                None => {
+                    compiler.push_constant(Value::new_bool(true));
+                    None
+                },
+            };
+
+            clause.body.compile(compiler, tail)?;
+            ends.push(compiler.emit(Op::Jump(0)));
+            match next {
+                Some(next) => {
+                    let position = compiler.position();
+                    compiler.patch(next, position);
+                },
+                // the clauses after else are never selected
+                None => break,
+            }
+        }
+
+        compiler.push_constant(Value::empty_list());
+        compiler.finish(tail);
+        let end = compiler.position();
+        for jump in ends {
+            compiler.patch(jump, end);
+        }
+        Ok(())
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        let clauses = self.clauses.iter().map(|clause| {
+            let test = clause.test.as_ref().map(|x| x.to_string(interner)).unwrap_or("else".into());
+            format!(" ({}{})", test, clause.body.to_string(interner))
+        }).join("");
+        format!("(cond{})", clauses)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CaseClause {
+    // None for else
+    data: Option<Vec<Value>>,
+    body: ClauseBody,
+}
+
+impl CaseClause {
+    pub fn new(data: Option<Vec<Value>>, body: ClauseBody) -> Self {
+        CaseClause {
+            data: data,
+            body: body,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Case {
+    key: Value,
+    clauses: Vec<CaseClause>,
+}
+
+impl Case {
+    pub fn new(key: Value, clauses: Vec<CaseClause>) -> Self {
+        Case {
+            key: key,
+            clauses: clauses,
+        }
+    }
+
+    // selects the first clause with a datum that is eqv to the key
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<Trampoline, Condition> {
+        let key = interpreter.evaluate(&self.key)?;
+        for clause in &self.clauses {
+            let selected = match clause.data {
+                Some(ref data) => data.iter().any(|datum| datum.eqv(&key)),
+                None => true,
+            };
+
+            if selected {
+                return clause.body.evaluate(interpreter, key);
+            }
+        }
+        Ok(Trampoline::Return(Value::empty_list()))
+    }
+
+    // the key stays on the stack for the clause body
+    fn compile(&self, compiler: &mut Compiler, tail: bool) -> Result<(), Unsupported> {
+        compiler.compile(&self.key, false)?;
+        let mut ends = vec![];
+        for clause in &self.clauses {
+            let next = match clause.data {
+                Some(ref data) => {
+                    let data = compiler.constant(Value::new_list(data));
+                    Some(compiler.emit(Op::JumpUnlessCase(data, 0)))
+                },
+                None => None,
+            };
+
+            clause.body.compile(compiler, tail)?;
+            ends.push(compiler.emit(Op::Jump(0)));
+            match next {
+                Some(next) => {
+                    let position = compiler.position();
+                    compiler.patch(next, position);
+                },
+                None => break,
+            }
+        }
+
+        compiler.emit(Op::Pop);
+        compiler.push_constant(Value::empty_list());
+        compiler.finish(tail);
+        let end = compiler.position();
+        for jump in ends {
+            compiler.patch(jump, end);
+        }
+        Ok(())
+    }
+
+    fn to_string(&self, interner: &StringInterner) -> String {
+        let clauses = self.clauses.iter().map(|clause| {
+            let data = clause.data.as_ref()
+            .map(|data| Value::new_list(data).to_string(interner))
+            .unwrap_or("else".into());
+            format!(" ({}{})", data, clause.body.to_string(interner))
+        }).join("");
+        format!("(case {}{})", self.key.to_string(interner), clauses)
     }
 }
@@ -1,4 +1,5 @@
-use ::value::Value;
+use ::value::{self, Value, BigInt, Record, HashTable, HashKey};
+use std::convert::TryFrom;
 use ::string_interner::StringInterner;
 
 #[test]
@@ -18,3 +19,172 @@ fn list_format() {
     assert_eq!(c.to_string(interner), "(2 3 4)");
     assert_eq!(d.to_string(interner), "(1 2 3 4)");
 }
+
+#[test]
+fn bigint() {
+    fn big(s: &str) -> BigInt { BigInt::parse(s).unwrap() }
+
+    assert_eq!(big("0").to_string(), "0");
+    assert_eq!(big("-0").to_string(), "0");
+    assert_eq!(big("000123").to_string(), "123");
+    assert_eq!(big("-1000000000000000000000").to_string(), "-1000000000000000000000");
+    assert_eq!(BigInt::from(::std::i64::MIN).to_string(), "-9223372036854775808");
+    assert_eq!(BigInt::parse("1a"), None);
+    assert_eq!(BigInt::parse("-"), None);
+
+    assert_eq!(big("9223372036854775807").to_i64(), Some(::std::i64::MAX));
+    assert_eq!(big("-9223372036854775808").to_i64(), Some(::std::i64::MIN));
+    assert_eq!(big("9223372036854775808").to_i64(), None);
+
+    assert_eq!((big("999999999999999999") + big("1")).to_string(), "1000000000000000000");
+    assert_eq!((big("1") - big("1000000000000000000000")).to_string(), "-999999999999999999999");
+    assert_eq!((big("-5") + big("5")).to_string(), "0");
+    assert_eq!((big("123456789123456789") * big("-987654321987654321")).to_string(), "-121932631356500531347203169112635269");
+
+    let (q, r) = big("121932631356500531347203169112635270").div_rem(&big("123456789123456789")).unwrap();
+    assert_eq!((q.to_string(), r.to_string()), ("987654321987654321".into(), "1".into()));
+    let (q, r) = big("-7").div_rem(&big("2")).unwrap();
+    assert_eq!((q.to_string(), r.to_string()), ("-3".into(), "-1".into()));
+    assert_eq!(big("1").div_rem(&big("0")), None);
+
+    assert!(big("-10000000000000000000") < big("-1"));
+    assert!(big("10000000000000000000") > big("9999999999999999999"));
+
+    assert_eq!(BigInt::parse_radix("ff", 16), Some(big("255")));
+    assert_eq!(BigInt::parse_radix("-101", 2), Some(big("-5")));
+    assert_eq!(BigInt::parse_radix("ffffffffffffffffffff", 16), Some(big("1208925819614629174706175")));
+    assert_eq!(BigInt::parse_radix("8", 8), None);
+    assert_eq!(BigInt::parse_radix("-", 16), None);
+    assert_eq!(big("255").to_string_radix(16), "ff");
+    assert_eq!(big("-5").to_string_radix(2), "-101");
+    assert_eq!(big("0").to_string_radix(8), "0");
+    assert_eq!(big("1208925819614629174706175").to_string_radix(16), "ffffffffffffffffffff");
+}
+
+#[test]
+fn immediates() {
+    let interner = &mut StringInterner::new();
+    let id = interner.intern("x");
+
+    // symbols from the parser are on the heap, but they're still the same symbol
+    let located = Value::new_located_symbol(id);
+    assert_eq!(located, Value::new_symbol(id));
+    assert!(located.eqv(&Value::new_symbol(id)));
+    assert_eq!(located.get_symbol(), Some(id));
+    assert!(located.downgrade().is_some());
+
+    assert_eq!(Value::new_integer(1), Value::new_integer(1));
+    assert!(Value::new_char('a').eqv(&Value::new_char('a')));
+    assert!(Value::new_bool(true) != Value::new_integer(1));
+    assert!(Value::new_integer(1).downgrade().is_none());
+    assert_eq!(Value::empty_list().to_string(interner), "()");
+}
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: i64,
+    label: String,
+}
+
+impl_record!(Point { x, label });
+
+#[test]
+fn conversions() {
+    let interner = &mut StringInterner::new();
+
+    assert_eq!(Value::from(1), Value::new_integer(1));
+    assert_eq!(Value::from("a").to_string(interner), "\"a\"");
+    assert_eq!(Value::from(vec![Value::from(true), Value::from('c')]).to_string(interner), "(true #\\c)");
+    assert_eq!(i64::try_from(Value::from(2)), Ok(2));
+    assert_eq!(f64::try_from(Value::from(2)), Ok(2.0));
+    assert_eq!(String::try_from(Value::from("s")), Ok("s".to_string()));
+    assert_eq!(bool::try_from(Value::from(1)), Err(Value::from(1)));
+    assert_eq!(Vec::<Value>::try_from(Value::new_vector(vec![Value::from(1)])), Ok(vec![Value::from(1)]));
+
+    let point = Point { x: 1, label: "origin".into() };
+    let alist = point.to_alist(interner);
+    assert_eq!(alist.to_string(interner), "((x . 1) (label . \"origin\"))");
+    assert_eq!(Point::from_alist(&alist, interner), Some(Point { x: 1, label: "origin".into() }));
+    let table = point.to_hash_table(interner);
+    assert_eq!(Point::from_hash_table(&table, interner), Some(point));
+
+    // missing fields and wrong types
+    let x = interner.intern("x");
+    let wrong = Value::new_list(&[Value::new_pair(Value::new_symbol(x), Value::from("one"))]);
+    assert_eq!(Point::from_alist(&wrong, interner), None);
+}
+
+#[test]
+fn json() {
+    let interner = &mut StringInterner::new();
+    let null = Value::new_symbol(interner.intern("null"));
+
+    let list = Value::new_list(&[Value::from(1), Value::from("a\"b\n"), Value::from(true), null.clone(), Value::from(2.5)]);
+    assert_eq!(value::to_json(&list, interner), Ok("[1,\"a\\\"b\\n\",true,null,2.5]".into()));
+    assert_eq!(value::to_json(&Value::new_vector(vec![Value::empty_list()]), interner), Ok("[[]]".into()));
+    let mut table = HashTable::new();
+    let a = interner.intern("a");
+    table.insert(HashKey::Symbol(a), Value::new_symbol(a), Value::from(1));
+    table.insert(HashKey::String("b".into()), Value::from("b"), Value::from(1.0));
+    assert_eq!(value::to_json(&Value::new_hash_table(table), interner), Ok("{\"a\":1,\"b\":1.0}".into()));
+    assert_eq!(value::to_json(&Value::from('c'), interner), Err("can't convert: #\\c".into()));
+    assert_eq!(value::to_json(&Value::from(::std::f64::NAN), interner).is_err(), true);
+
+    // a symbol and a string key with the same name would both be written as "a"
+    let mut table = HashTable::new();
+    table.insert(HashKey::Symbol(a), Value::new_symbol(a), Value::from(1));
+    table.insert(HashKey::String("a".into()), Value::from("a"), Value::from(2));
+    assert_eq!(value::to_json(&Value::new_hash_table(table), interner), Err("can't convert, duplicate key: \"a\"".into()));
+
+    // the nesting is limited, e.g. for a vector that contains itself
+    let vector = Value::new_vector(vec![]);
+    vector.get_vector().unwrap().write().unwrap().push(vector.clone());
+    assert_eq!(value::to_json(&vector, interner), Err("can't convert, nested deeper than 128 levels".into()));
+    vector.get_vector().unwrap().write().unwrap().clear();
+
+    let parsed = value::from_json(" {\"x\": [1, -2.0, 3e2, 123456789012345678901234567890], \"s\": \"\\u00e9\\ud83d\\ude00\\n\", \"n\": null} ", interner).unwrap();
+    assert_eq!(parsed.to_string(interner), "#hash((\"n\" null) (\"s\" \"\u{e9}\u{1f600}\\n\") (\"x\" (1 -2.0 300.0 123456789012345678901234567890)))");
+    assert_eq!(value::from_json(&value::to_json(&list, interner).unwrap(), interner), Ok(list));
+
+    assert_eq!(value::from_json("[1,", interner), Err("expected value at 3, got end of input".into()));
+    assert_eq!(value::from_json("[1] 2", interner), Err("expected end of input at 4, got: '2'".into()));
+    assert_eq!(value::from_json("nope", interner), Err("expected value at 0, got: nope".into()));
+    let nested = format!("{}{}", "[".repeat(128), "]".repeat(128));
+    assert!(value::from_json(&nested, interner).is_ok());
+    let nested = format!("{}{}", "[".repeat(129), "]".repeat(129));
+    assert_eq!(value::from_json(&nested, interner), Err("nested deeper than 128 levels at 128".into()));
+}
+
+#[test]
+fn pretty_string() {
+    let interner = &mut StringInterner::new();
+    let parse = |s: &str, interner: &mut StringInterner| ::grammar::parse_data(s, interner).unwrap().remove(0);
+
+    let short = parse("(1 (2 3))", interner);
+    assert_eq!(short.pretty_string(interner, 80), "(1 (2 3))");
+
+    let code = parse("(define (f x) (if (< x 10) (list x x) (f (- x 1))))", interner);
+    assert_eq!(code.pretty_string(interner, 30), "(define (f x)\n  (if (< x 10)\n    (list x x)\n    (f (- x 1))))");
+    let data = parse("((1 2) #(3 4 5) \"six\")", interner);
+    assert_eq!(data.pretty_string(interner, 8), "((1 2)\n #(3\n   4\n   5)\n \"six\")");
+    // atoms are never broken
+    assert_eq!(parse("\"a long string\"", interner).pretty_string(interner, 4), "\"a long string\"");
+}
+
+#[test]
+fn equal() {
+    let a = Value::new_list(&[Value::from(1), Value::new_vector(vec![Value::from("x")])]);
+    let b = Value::new_list(&[Value::from(1), Value::new_vector(vec![Value::from("x")])]);
+    assert!(a.equal(&b) && !a.eqv(&b));
+    assert!(!a.equal(&Value::new_list(&[Value::from(1)])));
+
+    // (1 1 1 ...) compared with itself and with a longer cycle of ones
+    let one = Value::new_pair(Value::from(1), Value::empty_list());
+    one.get_pair().unwrap().set_cdr(one.clone());
+    let two = Value::new_list(&[Value::from(1), Value::from(1)]);
+    two.get_pair().unwrap().cdr().get_pair().unwrap().set_cdr(two.clone());
+    assert!(one.equal(&two) && two.equal(&one));
+    // break the cycles so the pairs are freed
+    one.get_pair().unwrap().set_cdr(Value::empty_list());
+    two.get_pair().unwrap().set_cdr(Value::empty_list());
+}
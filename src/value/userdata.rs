@@ -0,0 +1,66 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use ::value::{Value, Condition, HostProc, Args};
+use ::interpreter::Interpreter;
+
+// A rust object handed to flip code by an application embedding the interpreter.
+// Flip code can only pass it around and call its methods with call-method,
+// the application gets the object back with downcast_ref.
+#[derive(Clone)]
+pub struct Userdata {
+    object: Arc<dyn Any + Send + Sync>,
+    type_name: Option<String>,
+    // procedures that get the userdata as first arg, by name
+    methods: Arc<HashMap<String, Value>>,
+}
+
+impl Userdata {
+    pub fn new<T: Any + Send + Sync>(object: T) -> Self {
+        Userdata { object: Arc::new(object), type_name: None, methods: Arc::new(HashMap::new()) }
+    }
+
+    // shown when it's printed and by userdata-type
+    pub fn with_type_name(self, type_name: &str) -> Self {
+        Userdata { type_name: Some(type_name.into()), ..self }
+    }
+
+    pub fn with_method<F>(mut self, name: &str, f: F) -> Self
+    where F: Fn(&mut Interpreter, Args) -> Result<Value, Condition> + Send + Sync + 'static {
+        let full_name = format!("{}.{}", self.type_name(), name);
+        let method = Value::new_host_proc(HostProc::new(full_name, Box::new(f)));
+        Arc::make_mut(&mut self.methods).insert(name.into(), method);
+        self
+    }
+
+    pub fn type_name(&self) -> &str {
+        self.type_name.as_ref().map_or("userdata", |x| &**x)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&Value> {
+        self.methods.get(name)
+    }
+
+    pub fn object(&self) -> &Arc<dyn Any + Send + Sync> {
+        &self.object
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        let object: &dyn Any = &*self.object;
+        object.downcast_ref()
+    }
+}
+
+// the object can't be compared, so userdata is only equal to userdata with the same object
+impl PartialEq for Userdata {
+    fn eq(&self, other: &Userdata) -> bool {
+        Arc::ptr_eq(&self.object, &other.object)
+    }
+}
+
+impl fmt::Debug for Userdata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Userdata {{ type_name: {:?} }}", self.type_name())
+    }
+}
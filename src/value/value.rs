@@ -1,55 +1,167 @@
-use std::rc::Rc;
+use std::sync::{Arc, Weak, RwLock};
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::mem;
 use std::char;
+use std::fmt;
 use super::value_data::*;
 use ::value::*;
-use ::interpreter::Interpreter;
+use ::interpreter::{Interpreter, CompiledBody};
 use ::scope::Scope;
+use ::gc::{self, Heap, Object};
 use ::string_interner::StringInterner;
 
-#[derive(Debug, Clone, PartialEq)]
+// Bools, chars, integers that fit into an i64, symbols, keywords and () are stored in the value itself,
+// everything else on the heap behind an Arc. Cloning never copies heap data.
+#[derive(Clone)]
 pub struct Value {
-    val_ptr: Rc<ValueData>
+    repr: Repr,
+}
+
+#[derive(Clone)]
+enum Repr {
+    Bool(bool),
+    Char(char),
+    Integer(i64),
+    Symbol(u64),
+    Keyword(u64),
+    EmptyList,
+    Heap(Arc<Allocation>),
+}
+
+// The data of a value on the heap and the heap of the interpreter that allocated it, see gc.
+// The bytes are counted there until the data is dropped, whichever thread drops it.
+struct Allocation {
+    data: ValueData,
+    // None for values allocated while no interpreter was running
+    heap: Option<Heap>,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        if let Some(ref heap) = self.heap {
+            heap.track_free(self.data.heap_size());
+        }
+    }
+}
+
+// Refers to a value without keeping it alive, see Locations.
+pub struct WeakValue {
+    val_ptr: Weak<Allocation>
+}
+
+impl WeakValue {
+    // true if value is the very value this was created from
+    pub fn refers_to(&self, value: &Value) -> bool {
+        match (self.val_ptr.upgrade(), &value.repr) {
+            (Some(ref ptr), &Repr::Heap(ref other)) => Arc::ptr_eq(ptr, other),
+            _ => false,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.val_ptr.upgrade().is_some()
+    }
+
+    pub fn upgrade(&self) -> Option<Value> {
+        self.val_ptr.upgrade().map(|ptr| Value { repr: Repr::Heap(ptr) })
+    }
 }
 
 impl Value {
     fn new_with(data: ValueData) -> Self {
-        Value { val_ptr: Rc::new(data) }
+        let heap = gc::running();
+        if let Some(ref heap) = heap { heap.track_allocation(data.heap_size()); }
+        Value { repr: Repr::Heap(Arc::new(Allocation { data: data, heap: heap })) }
     }
 
-    pub fn new_bool(x: bool) -> Self { Self::new_with(ValueData::Bool(x)) }
-    pub fn new_char(x: char) -> Self { Self::new_with(ValueData::Char(x)) }
-    pub fn new_integer(x: i64) -> Self { Self::new_with(ValueData::Integer(x)) }
-    pub fn new_symbol(id: u64) -> Self { Self::new_with(ValueData::Symbol(id)) }
+    pub fn new_bool(x: bool) -> Self { Value { repr: Repr::Bool(x) } }
+    pub fn new_char(x: char) -> Self { Value { repr: Repr::Char(x) } }
+    pub fn new_integer(x: i64) -> Self { Value { repr: Repr::Integer(x) } }
+    // integers that fit into an i64 are stored as such
+    pub fn new_bigint(x: BigInt) -> Self {
+        match x.to_i64() {
+            Some(i) => Self::new_integer(i),
+            None => Self::new_with(ValueData::BigInt(x)),
+        }
+    }
+    // rationals with a denominator of 1 are stored as integers
+    pub fn new_rational(x: Rational) -> Self {
+        if x.denominator() == &BigInt::from(1) {
+            Self::new_bigint(x.numerator().clone())
+        } else {
+            Self::new_with(ValueData::Rational(x))
+        }
+    }
+    pub fn new_float(x: f64) -> Self { Self::new_with(ValueData::Float(x)) }
+    pub fn new_symbol(id: u64) -> Self { Value { repr: Repr::Symbol(id) } }
+    pub fn new_keyword(id: u64) -> Self { Value { repr: Repr::Keyword(id) } }
+    // symbols written in code are on the heap, so they can be told apart for their locations
+    pub fn new_located_symbol(id: u64) -> Self { Self::new_with(ValueData::Symbol(id)) }
     pub fn new_string<'a, T: 'a + Into<Cow<'a, str>>>(x: T) -> Self { Self::new_with(ValueData::String(x.into().into_owned())) }
-    pub fn new_condition(x: Value) -> Self { Self::new_with(ValueData::Condition(x)) }
-    pub fn empty_list() -> Self { Self::new_with(ValueData::EmptyList) }
-    pub fn new_native_proc(f: fn(&mut Interpreter, &mut [Value]) -> Value) -> Self {
-        let raw: *const () = f as *const ();
-        Self::new_with(ValueData::NativeProc(raw))
-    }
-    pub fn new_proc(name: Option<String>, parent_scope: Scope, bindings: Vec<u64>, code: Vec<Value>) -> Self {
-        let procedure = Proc::new(name, parent_scope, bindings, code);
+    pub fn empty_list() -> Self { Value { repr: Repr::EmptyList } }
+    pub fn new_vector(x: Vec<Value>) -> Self { Self::new_with(ValueData::Vector(RwLock::new(x))) }
+    pub fn new_hash_table(x: HashTable) -> Self { Self::new_with(ValueData::HashTable(RwLock::new(x))) }
+    pub fn new_port(x: Port) -> Self { Self::new_with(ValueData::Port(x)) }
+    pub fn new_eof() -> Self { Self::new_with(ValueData::Eof) }
+    pub fn new_environment(x: Scope) -> Self {
+        let value = Self::new_with(ValueData::Environment(x.clone()));
+        if let Some(heap) = value.heap() { heap.register_scope(&x); }
+        value
+    }
+    pub fn new_condition(x: Condition) -> Self { Self::new_with(ValueData::Condition(x)) }
+    pub fn new_continuation() -> Self { Self::new_with(ValueData::Continuation(Continuation::new())) }
+    pub fn new_composable_continuation(func: Value, args: Vec<Value>, log: Vec<Value>) -> Self {
+        Self::new_with(ValueData::ComposableContinuation(ComposableContinuation { func: func, args: args, log: log }))
+    }
+    pub fn new_promise(x: Promise) -> Self { Self::new_with(ValueData::Promise(x)) }
+    // a single value is returned as it is
+    pub fn new_values(mut x: Vec<Value>) -> Self {
+        if x.len() == 1 { x.pop().unwrap() } else { Self::new_with(ValueData::Values(x)) }
+    }
+    pub fn new_parameter(x: Parameter) -> Self { Self::new_with(ValueData::Parameter(x)) }
+    pub fn new_record_type(x: RecordType) -> Self { Self::new_with(ValueData::RecordType(x)) }
+    pub fn new_record(x: RecordInstance) -> Self { Self::new_with(ValueData::Record(x)) }
+    pub fn new_native_proc(f: fn(&mut Interpreter, &mut [Value]) -> Result<Value, Condition>) -> Self {
+        Self::new_with(ValueData::NativeProc(f as usize))
+    }
+    pub fn new_host_proc(x: HostProc) -> Self { Self::new_with(ValueData::HostProc(x)) }
+    pub fn new_userdata(x: Userdata) -> Self { Self::new_with(ValueData::Userdata(x)) }
+    pub fn new_proc(name: Option<String>, parent_scope: Scope, bindings: Vec<u64>, rest: Option<u64>, code: Vec<Value>, compiled: CompiledBody) -> Self {
+        let procedure = Proc::new(name, parent_scope, bindings, rest, code, compiled);
         Self::new_with(ValueData::Proc(procedure))
     }
 
-    pub fn new_recur(args: Vec<Value>) -> Self { Self::new_with(ValueData::Recur(args)) }
     pub fn new_begin(code: Vec<Value>) -> Self {
         Self::new_with(ValueData::SpecialForm(SpecialForm::Begin(Begin::new(code))))
     }
+    pub fn new_and(operands: Vec<Value>) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::And(Connective::new(operands))))
+    }
+    pub fn new_or(operands: Vec<Value>) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::Or(Connective::new(operands))))
+    }
     pub fn new_define(symbol_id: u64, expression: Value) -> Self {
         Self::new_with(ValueData::SpecialForm(SpecialForm::Define(Define::new(symbol_id, expression))))
     }
+    pub fn new_set(symbol_id: u64, expression: Value) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::Set(Set::new(symbol_id, expression))))
+    }
     pub fn new_if(condition: Value, then: Value, or_else: Value) -> Self {
         Self::new_with(ValueData::SpecialForm(SpecialForm::If(If::new(condition, then, or_else))))
     }
-    pub fn new_lambda(name: Option<String>, bindings: Vec<u64>, code: Vec<Value>) -> Self {
-        Self::new_with(ValueData::SpecialForm(SpecialForm::Lambda(Lambda::new(name, bindings, code))))
+    pub fn new_lambda(name: Option<String>, bindings: Vec<u64>, rest: Option<u64>, code: Vec<Value>) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::Lambda(Lambda::new(name, bindings, rest, code))))
     }
     pub fn new_let(bindings: Vec<(u64, Value)>, code: Vec<Value>) -> Self {
         Self::new_with(ValueData::SpecialForm(SpecialForm::Let(LetLoop::new(bindings, code))))
     }
+    pub fn new_let_star(bindings: Vec<(u64, Value)>, code: Vec<Value>) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::LetStar(LetLoop::new(bindings, code))))
+    }
+    pub fn new_letrec(bindings: Vec<(u64, Value)>, code: Vec<Value>) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::Letrec(LetLoop::new(bindings, code))))
+    }
     pub fn new_loop(bindings: Vec<(u64, Value)>, code: Vec<Value>) -> Self {
         Self::new_with(ValueData::SpecialForm(SpecialForm::Loop(LetLoop::new(bindings, code))))
     }
@@ -59,100 +171,585 @@ impl Value {
     pub fn new_quote(expression: Value) -> Self {
         Self::new_with(ValueData::SpecialForm(SpecialForm::Quote(Quote::new(expression))))
     }
+    pub fn new_quasiquote(template: Template) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::Quasiquote(Quasiquote::new(template))))
+    }
+    pub fn new_cond(clauses: Vec<CondClause>) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::Cond(Cond::new(clauses))))
+    }
+    pub fn new_case(key: Value, clauses: Vec<CaseClause>) -> Self {
+        Self::new_with(ValueData::SpecialForm(SpecialForm::Case(Case::new(key, clauses))))
+    }
+
+    // Unique while the value is alive, unlike == this distinguishes equal values.
+    // Values that aren't on the heap have no identity, they're 0.
+    pub fn id(&self) -> usize {
+        match self.repr {
+            Repr::Heap(ref ptr) => &**ptr as *const Allocation as usize,
+            _ => 0,
+        }
+    }
+
+    // None for values that aren't on the heap
+    pub fn downgrade(&self) -> Option<WeakValue> {
+        match self.repr {
+            Repr::Heap(ref ptr) => Some(WeakValue { val_ptr: Arc::downgrade(ptr) }),
+            _ => None,
+        }
+    }
+
+    // the heap of the interpreter that allocated the value, None for values that aren't on the heap
+    // or were allocated while no interpreter was running
+    pub fn heap(&self) -> Option<&Heap> {
+        match self.repr {
+            Repr::Heap(ref ptr) => ptr.heap.as_ref(),
+            _ => None,
+        }
+    }
 
-    fn data(&self) -> &ValueData {
-        &*self.val_ptr
+    // The following functions are used by the cycle collector, see gc.
+    // Only mutable values, procedures, environments and promises can be part of cycles,
+    // the references other values hold aren't needed.
+
+    pub fn strong_count(&self) -> usize {
+        match self.repr {
+            Repr::Heap(ref ptr) => Arc::strong_count(ptr),
+            _ => 1,
+        }
+    }
+
+    // None if the value is locked for writing
+    pub fn references(&self) -> Option<Vec<Object>> {
+        let values = match self.data() {
+            None => vec![],
+            Some(&ValueData::Pair(ref pair)) => match pair.try_elements() {
+                Some((car, cdr)) => vec![car, cdr],
+                None => return None,
+            },
+            Some(&ValueData::Vector(ref vector)) => match vector.try_read() {
+                Ok(vector) => vector.clone(),
+                Err(_) => return None,
+            },
+            Some(&ValueData::HashTable(ref table)) => match table.try_read() {
+                Ok(table) => table.entries().flat_map(|(key, value)| vec![key.clone(), value.clone()]).collect(),
+                Err(_) => return None,
+            },
+            Some(&ValueData::Promise(ref promise)) => match promise.try_references() {
+                Some(values) => values,
+                None => return None,
+            },
+            Some(&ValueData::Values(ref values)) => values.clone(),
+            Some(&ValueData::Parameter(ref parameter)) => Some(parameter.value().clone()).into_iter().chain(parameter.converter().cloned()).collect(),
+            Some(&ValueData::Record(ref record)) => match record.fields().try_read() {
+                Ok(fields) => fields.clone(),
+                Err(_) => return None,
+            },
+            Some(&ValueData::Proc(ref procedure)) => return Some(vec![Object::Scope(procedure.parent_scope().clone())]),
+            Some(&ValueData::Environment(ref scope)) => return Some(vec![Object::Scope(scope.clone())]),
+            _ => vec![],
+        };
+        // values that aren't on the heap hold no references and can't be freed
+        Some(values.into_iter().filter(|x| x.data().is_some()).map(Object::Value).collect())
+    }
+
+    // drops the values this holds, only called for garbage
+    pub fn clear_references(&self) {
+        match self.data() {
+            Some(&ValueData::Pair(ref pair)) => {
+                pair.set_car(Value::empty_list());
+                pair.set_cdr(Value::empty_list());
+            },
+            Some(&ValueData::Vector(ref vector)) => if let Ok(mut vector) = vector.try_write() { vector.clear(); },
+            Some(&ValueData::HashTable(ref table)) => if let Ok(mut table) = table.try_write() { *table = HashTable::new(); },
+            Some(&ValueData::Record(ref record)) => if let Ok(mut fields) = record.fields().try_write() { fields.clear(); },
+            _ => (),
+        }
+    }
+
+    // None for values that aren't on the heap
+    fn data(&self) -> Option<&ValueData> {
+        match self.repr {
+            Repr::Heap(ref ptr) => Some(&ptr.data),
+            _ => None,
+        }
     }
 
     pub fn get_empty_list(&self) -> Option<()> {
-        if let &ValueData::EmptyList = self.data() { Some(()) } else { None }
+        if let Repr::EmptyList = self.repr { Some(()) } else { None }
     }
 
-    pub fn get_list(&self) -> Option<Vec<Value>> {
+    pub fn get_pair(&self) -> Option<&Pair> {
         match self.data() {
-            &ValueData::List(ref values) => {
-                Some(values.clone())
+            Some(&ValueData::Pair(ref pair)) => Some(pair),
+            _ => None,
+        }
+    }
+
+    // used by Pair::drop, returns the pair if this was the last reference to it
+    pub fn try_unwrap_pair(self) -> Option<Pair> {
+        match self.repr {
+            // the pair is replaced, because the data can't be moved out of, see Allocation::drop
+            Repr::Heap(ptr) => match Arc::try_unwrap(ptr) {
+                Ok(mut allocation) => match allocation.data {
+                    ValueData::Pair(ref mut pair) => Some(mem::replace(pair, Pair::new(Value::empty_list(), Value::empty_list()))),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Follows the cdrs, returns the cars and the first cdr that isn't a pair.
+    // None if the list is circular, which is detected by a second value going half as fast.
+    fn walk_pairs(&self) -> Option<(Vec<Value>, Value)> {
+        let mut elements = vec![];
+        let mut current = self.clone();
+        let mut slow = self.clone();
+        loop {
+            let next = match current.get_pair() {
+                Some(pair) => {
+                    elements.push(pair.car());
+                    pair.cdr()
+                },
+                None => return Some((elements, current)),
+            };
+            current = next;
+
+            if elements.len() % 2 == 0 {
+                // safe because current already went past slow
+                let next_slow = slow.get_pair().unwrap().cdr();
+                slow = next_slow;
+                if current.id() == slow.id() { return None; }
+            }
+        }
+    }
+
+    // the elements of a proper list, ending with ()
+    pub fn get_list(&self) -> Option<Vec<Value>> {
+        match self.walk_pairs() {
+            Some((elements, ref tail)) if tail.get_empty_list().is_some() => Some(elements),
+            _ => None,
+        }
+    }
+
+    // the elements and the tail of an improper list like (a b . c)
+    pub fn get_dotted_list(&self) -> Option<(Vec<Value>, Value)> {
+        match self.walk_pairs() {
+            Some((elements, tail)) => {
+                if elements.is_empty() || tail.get_empty_list().is_some() { None } else { Some((elements, tail)) }
             },
-            &ValueData::EmptyList => Some(vec![]),
-            _ => None
+            None => None,
         }
     }
 
     pub fn get_symbol(&self) -> Option<u64> {
-        match self.data() {
-            &ValueData::Symbol(id) => Some(id),
+        match self.repr {
+            Repr::Symbol(id) => Some(id),
+            Repr::Heap(ref ptr) => match ptr.data {
+                ValueData::Symbol(id) => Some(id),
+                _ => None,
+            },
             _ => None,
         }
     }
 
+    // the id of the name, without #:
+    pub fn get_keyword(&self) -> Option<u64> {
+        if let Repr::Keyword(id) = self.repr { Some(id) } else { None }
+    }
+
     pub fn get_bool(&self) -> Option<bool> {
-        match self.data() {
-            &ValueData::Bool(b) => Some(b),
+        match self.repr {
+            Repr::Bool(b) => Some(b),
             _ => None,
         }
     }
 
     pub fn get_integer(&self) -> Option<i64> {
+        match self.repr {
+            Repr::Integer(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn get_bigint(&self) -> Option<&BigInt> {
+        match self.data() {
+            Some(&ValueData::BigInt(ref x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn get_rational(&self) -> Option<&Rational> {
+        match self.data() {
+            Some(&ValueData::Rational(ref x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn get_float(&self) -> Option<f64> {
         match self.data() {
-            &ValueData::Integer(i) => Some(i),
+            Some(&ValueData::Float(f)) => Some(f),
             _ => None,
         }
     }
 
     pub fn get_char(&self) -> Option<char> {
+        match self.repr {
+            Repr::Char(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub fn get_vector(&self) -> Option<&RwLock<Vec<Value>>> {
+        match self.data() {
+            Some(&ValueData::Vector(ref values)) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn get_hash_table(&self) -> Option<&RwLock<HashTable>> {
         match self.data() {
-            &ValueData::Char(c) => Some(c),
+            Some(&ValueData::HashTable(ref table)) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn get_port(&self) -> Option<&Port> {
+        match self.data() {
+            Some(&ValueData::Port(ref port)) => Some(port),
+            _ => None,
+        }
+    }
+
+    pub fn get_eof(&self) -> Option<()> {
+        if let Some(&ValueData::Eof) = self.data() { Some(()) } else { None }
+    }
+
+    pub fn get_environment(&self) -> Option<&Scope> {
+        match self.data() {
+            Some(&ValueData::Environment(ref scope)) => Some(scope),
+            _ => None,
+        }
+    }
+
+    pub fn get_condition(&self) -> Option<&Condition> {
+        match self.data() {
+            Some(&ValueData::Condition(ref condition)) => Some(condition),
+            _ => None,
+        }
+    }
+
+    pub fn get_continuation(&self) -> Option<&Continuation> {
+        match self.data() {
+            Some(&ValueData::Continuation(ref k)) => Some(k),
+            _ => None,
+        }
+    }
+
+    pub fn get_composable_continuation(&self) -> Option<&ComposableContinuation> {
+        match self.data() {
+            Some(&ValueData::ComposableContinuation(ref k)) => Some(k),
+            _ => None,
+        }
+    }
+
+    pub fn get_promise(&self) -> Option<&Promise> {
+        match self.data() {
+            Some(&ValueData::Promise(ref x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn get_values(&self) -> Option<&[Value]> {
+        match self.data() {
+            Some(&ValueData::Values(ref x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn get_parameter(&self) -> Option<&Parameter> {
+        match self.data() {
+            Some(&ValueData::Parameter(ref x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn get_record_type(&self) -> Option<&RecordType> {
+        match self.data() {
+            Some(&ValueData::RecordType(ref x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn get_record(&self) -> Option<&RecordInstance> {
+        match self.data() {
+            Some(&ValueData::Record(ref x)) => Some(x),
             _ => None,
         }
     }
 
     pub fn get_string(&self) -> Option<&str> {
         match self.data() {
-            &ValueData::String(ref s) => Some(s.as_str()),
+            Some(&ValueData::String(ref s)) => Some(s.as_str()),
             _ => None,
         }
     }
 
-    pub fn get_condition(&self) -> Option<&Value> {
+    pub fn get_native_fn_ptr(&self) -> Option<fn(&mut Interpreter, &mut [Value]) -> Result<Value, Condition>> {
         match self.data() {
-            &ValueData::Condition(ref x) => Some(x),
+            Some(&ValueData::NativeProc(f)) => Some(unsafe { mem::transmute(f) }),
             _ => None,
         }
     }
 
-    pub fn get_native_fn_ptr(&self) -> Option<fn(&mut Interpreter, &mut [Value]) -> Value> {
+    // The following conversions are for applications embedding the interpreter, see Interpreter::eval_str.
+    // Unlike the getters they convert between types where that's lossless.
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.get_integer()
+    }
+
+    // integers are converted
+    pub fn as_f64(&self) -> Option<f64> {
+        self.get_float().or_else(|| self.get_integer().map(|x| x as f64))
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        self.get_bool()
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.get_string()
+    }
+
+    // the elements of lists and vectors
+    pub fn as_vec(&self) -> Option<Vec<Value>> {
+        self.get_list().or_else(|| self.get_vector().map(|x| x.read().unwrap().clone()))
+    }
+
+    pub fn get_userdata(&self) -> Option<&Userdata> {
         match self.data() {
-            &ValueData::NativeProc(f) => Some(unsafe { mem::transmute(f) }),
+            Some(&ValueData::Userdata(ref x)) => Some(x),
             _ => None,
         }
     }
 
-    pub fn get_proc(&self) -> Option<&Proc> {
+    pub fn get_host_proc(&self) -> Option<&HostProc> {
         match self.data() {
-            &ValueData::Proc(ref p) => Some(p),
+            Some(&ValueData::HostProc(ref p)) => Some(p),
             _ => None,
         }
     }
 
-    pub fn get_special_form(&self) -> Option<&SpecialForm> {
+    // true for everything that can be called, see procedure?
+    pub fn is_procedure(&self) -> bool {
+        match self.data() {
+            Some(&ValueData::NativeProc(_)) | Some(&ValueData::HostProc(_)) | Some(&ValueData::Proc(_)) |
+            Some(&ValueData::Continuation(_)) | Some(&ValueData::ComposableContinuation(_)) | Some(&ValueData::Parameter(_)) => true,
+            _ => false,
+        }
+    }
+
+    pub fn get_proc(&self) -> Option<&Proc> {
         match self.data() {
-            &ValueData::SpecialForm(ref s) => Some(s),
+            Some(&ValueData::Proc(ref p)) => Some(p),
             _ => None,
         }
     }
 
-    pub fn get_recur(&self) -> Option<&[Value]> {
+    pub fn get_special_form(&self) -> Option<&SpecialForm> {
         match self.data() {
-            &ValueData::Recur(ref args) => Some(&*args),
+            Some(&ValueData::SpecialForm(ref s)) => Some(s),
             _ => None,
         }
     }
+
+    pub fn new_pair(car: Value, cdr: Value) -> Value {
+        Value::new_with(ValueData::Pair(Pair::new(car, cdr)))
+    }
+
     pub fn new_list(elements: &[Value]) -> Value {
-        if elements.len() == 0 { return Value::empty_list(); }
-        Value::new_with(ValueData::List(elements.iter().cloned().collect()))
+        Value::new_dotted_list(elements.to_vec(), Value::empty_list())
+    }
+
+    // the elements followed by tail, (a b . (c d)) is the same as (a b c d)
+    pub fn new_dotted_list(elements: Vec<Value>, tail: Value) -> Value {
+        elements.into_iter().rev().fold(tail, |cdr, car| Value::new_pair(car, cdr))
+    }
+
+    // Equality for bools, chars, numbers, symbols and (), identity for everything else.
+    // Integers and floats are never eqv.
+    pub fn eqv(&self, other: &Value) -> bool {
+        if let (Some(a), Some(b)) = (self.get_symbol(), other.get_symbol()) { return a == b; }
+        match (&self.repr, &other.repr) {
+            (&Repr::Heap(ref a), &Repr::Heap(ref b)) => match (&a.data, &b.data) {
+                (&ValueData::BigInt(ref a), &ValueData::BigInt(ref b)) => a == b,
+                (&ValueData::Rational(ref a), &ValueData::Rational(ref b)) => a == b,
+                (&ValueData::Float(a), &ValueData::Float(b)) => a == b,
+                _ => Arc::ptr_eq(a, b),
+            },
+            (a, b) => Immediate::from(a) == Immediate::from(b),
+        }
+    }
+
+    // Pairs, vectors and strings are compared by their contents, everything else with eqv.
+    // Like all_eq this terminates for cyclic structures: two pairs or vectors that are already being
+    // compared are assumed to be equal when they are reached again, so cycles are equal if walking them
+    // never finds a difference.
+    pub fn equal(&self, other: &Value) -> bool {
+        let mut visited = HashSet::new();
+        // an explicit stack, recursing would overflow for long lists
+        let mut pending = vec![(self.clone(), other.clone())];
+        while let Some((a, b)) = pending.pop() {
+            if a.eqv(&b) { continue; }
+            if let (Some(x), Some(y)) = (a.get_string(), b.get_string()) {
+                if x != y { return false; }
+                continue;
+            }
+
+            if let (Some(x), Some(y)) = (a.get_pair(), b.get_pair()) {
+                if !visited.insert((a.id(), b.id())) { continue; }
+                pending.push((x.cdr(), y.cdr()));
+                pending.push((x.car(), y.car()));
+            } else if let (Some(x), Some(y)) = (a.get_vector(), b.get_vector()) {
+                if !visited.insert((a.id(), b.id())) { continue; }
+                let (x, y) = (x.read().unwrap(), y.read().unwrap());
+                if x.len() != y.len() { return false; }
+                pending.extend(x.iter().cloned().zip(y.iter().cloned()).rev());
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+
+    // PartialEq for every pair of values in pending. Pairs and vectors are compared by their contents
+    // the way equal does it, so lists that contain themselves like #0=(a . #0#) are compared too.
+    pub fn all_eq(mut pending: Vec<(Value, Value)>) -> bool {
+        let mut visited = HashSet::new();
+        // an explicit stack, recursing would overflow for long lists
+        while let Some((a, b)) = pending.pop() {
+            if let (Some(x), Some(y)) = (a.get_pair(), b.get_pair()) {
+                if a.id() == b.id() || !visited.insert((a.id(), b.id())) { continue; }
+                pending.push((x.cdr(), y.cdr()));
+                pending.push((x.car(), y.car()));
+            } else if let (Some(x), Some(y)) = (a.get_vector(), b.get_vector()) {
+                if a.id() == b.id() || !visited.insert((a.id(), b.id())) { continue; }
+                let (x, y) = (x.read().unwrap(), y.read().unwrap());
+                if x.len() != y.len() { return false; }
+                pending.extend(x.iter().cloned().zip(y.iter().cloned()).rev());
+            } else if a != b {
+                return false;
+            }
+        }
+        true
     }
 
+    // the representation used by write and the repl, strings are quoted and escaped
     pub fn to_string(&self, interner: &StringInterner) -> String {
-        self.data().to_string(interner)
+        self.print(interner, false)
+    }
+
+    // the representation used by display, strings and chars are printed without quotes
+    pub fn to_display_string(&self, interner: &StringInterner) -> String {
+        self.print(interner, true)
+    }
+
+    // what kind of value it is, see ValueData::type_name
+    pub fn type_name(&self) -> &'static str {
+        match self.repr {
+            Repr::Bool(_) => "boolean",
+            Repr::Char(_) => "char",
+            Repr::Integer(_) => "integer",
+            Repr::Symbol(_) => "symbol",
+            Repr::Keyword(_) => "keyword",
+            Repr::EmptyList => "empty list",
+            Repr::Heap(ref ptr) => ptr.type_name(),
+        }
+    }
+
+    // data that contains itself is written with datum labels, see datum_labels
+    pub fn print(&self, interner: &StringInterner, display: bool) -> String {
+        match self.repr {
+            Repr::Heap(_) => super::datum_labels::print_labeled(self, interner, display, false)
+                             .unwrap_or_else(|| self.print_unlabeled(interner, display)),
+            _ => self.print_unlabeled(interner, display),
+        }
+    }
+
+    // like print, but loops forever for data that contains itself
+    pub fn print_unlabeled(&self, interner: &StringInterner, display: bool) -> String {
+        match self.repr {
+            Repr::Bool(x) => ValueData::Bool(x).print(interner, display),
+            Repr::Char(x) => ValueData::Char(x).print(interner, display),
+            Repr::Integer(x) => ValueData::Integer(x).print(interner, display),
+            Repr::Symbol(x) => ValueData::Symbol(x).print(interner, display),
+            Repr::Keyword(x) => ValueData::Keyword(x).print(interner, display),
+            Repr::EmptyList => ValueData::EmptyList.print(interner, display),
+            Repr::Heap(ref ptr) => ptr.data.print(interner, display),
+        }
+    }
+}
+
+// The values that aren't on the heap, for comparisons. Located symbols are the same as other symbols.
+#[derive(PartialEq)]
+enum Immediate {
+    Bool(bool),
+    Char(char),
+    Integer(i64),
+    Symbol(u64),
+    Keyword(u64),
+    EmptyList,
+    None,
+}
+
+impl<'a> From<&'a Repr> for Immediate {
+    fn from(repr: &'a Repr) -> Self {
+        match repr {
+            &Repr::Bool(x) => Immediate::Bool(x),
+            &Repr::Char(x) => Immediate::Char(x),
+            &Repr::Integer(x) => Immediate::Integer(x),
+            &Repr::Symbol(x) => Immediate::Symbol(x),
+            &Repr::Keyword(x) => Immediate::Keyword(x),
+            &Repr::EmptyList => Immediate::EmptyList,
+            &Repr::Heap(ref ptr) => match ptr.data {
+                ValueData::Symbol(x) => Immediate::Symbol(x),
+                _ => Immediate::None,
+            },
+        }
+    }
+}
+
+// structural, like the derived version from before values were split into immediates and heap data
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (&self.repr, &other.repr) {
+            (&Repr::Heap(ref a), &Repr::Heap(ref b)) => match (&a.data, &b.data) {
+                (&ValueData::Symbol(a), &ValueData::Symbol(b)) => a == b,
+                (&ValueData::Pair(_), &ValueData::Pair(_)) | (&ValueData::Vector(_), &ValueData::Vector(_)) => {
+                    Value::all_eq(vec![(self.clone(), other.clone())])
+                },
+                (a, b) => a == b,
+            },
+            (a, b) => {
+                let (a, b) = (Immediate::from(a), Immediate::from(b));
+                a != Immediate::None && a == b
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.repr {
+            Repr::Bool(x) => write!(f, "Bool({:?})", x),
+            Repr::Char(x) => write!(f, "Char({:?})", x),
+            Repr::Integer(x) => write!(f, "Integer({:?})", x),
+            Repr::Symbol(x) => write!(f, "Symbol({:?})", x),
+            Repr::Keyword(x) => write!(f, "Keyword({:?})", x),
+            Repr::EmptyList => write!(f, "EmptyList"),
+            Repr::Heap(ref ptr) => write!(f, "{:?}", ptr.data),
+        }
     }
 }
@@ -1,45 +1,230 @@
-use ::value::{Value, Proc, SpecialForm};
+use std::sync::RwLock;
+use std::mem;
+use ::value::{Value, Proc, SpecialForm, BigInt, Rational, HashTable, Pair, Port, Condition, Continuation, ComposableContinuation, Promise, RecordType, RecordInstance, Parameter, HostProc, Userdata};
 use ::string_interner::StringInterner;
-use grammar::escape_char;
+use ::scope::Scope;
+use grammar::{escape_char, escape_string, escape_symbol};
 use itertools::Itertools;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug)]
 pub enum ValueData {
     Bool(bool),
     Char(char),
     Integer(i64),
+    // only used if the integer doesn't fit into an i64
+    BigInt(BigInt),
+    // never has a denominator of 1
+    Rational(Rational),
+    Float(f64),
     Symbol(u64),
+    Keyword(u64),
     String(String),
     EmptyList,
-    List(Vec<Value>),
-    Condition(Value),
-    NativeProc(*const ()),
+    Pair(Pair),
+    // the only mutable type, see vector-set!
+    Vector(RwLock<Vec<Value>>),
+    HashTable(RwLock<HashTable>),
+    Port(Port),
+    // returned by read-line and read-char at the end of a file
+    Eof,
+    // a scope, see eval and interaction-environment
+    Environment(Scope),
+    // error objects, as caught by with-exception-handler
+    Condition(Condition),
+    Continuation(Continuation),
+    ComposableContinuation(ComposableContinuation),
+    Promise(Promise),
+    // more or less than one value returned by values, see call-with-values
+    Values(Vec<Value>),
+    Parameter(Parameter),
+    RecordType(RecordType),
+    Record(RecordInstance),
+    Userdata(Userdata),
+    // the address of the fn, see Value::get_native_fn_ptr
+    NativeProc(usize),
+    HostProc(HostProc),
     Proc(Proc),
-    Recur(Vec<Value>),
     SpecialForm(SpecialForm),
 }
 
 impl ValueData {
-    pub fn to_string(&self, interner: &StringInterner) -> String {
+    // An estimate of the bytes this takes, for the memory limit. Only vectors and strings have
+    // a size that isn't fixed, entries of hash tables and buffers of string ports are tracked by HashTable and Port.
+    // The capacity is used, so clearing a vector doesn't change the size.
+    pub fn heap_size(&self) -> usize {
+        mem::size_of::<ValueData>() + match self {
+            &ValueData::String(ref x) => x.capacity(),
+            &ValueData::Vector(ref values) => values.read().map(|x| x.capacity()).unwrap_or(0) * mem::size_of::<Value>(),
+            &ValueData::Values(ref values) => values.capacity() * mem::size_of::<Value>(),
+            &ValueData::Record(ref record) => record.fields().read().map(|x| x.capacity()).unwrap_or(0) * mem::size_of::<Value>(),
+            _ => 0,
+        }
+    }
+
+    // what kind of value it is, e.g. for describe
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            &ValueData::Bool(_) => "boolean",
+            &ValueData::Char(_) => "char",
+            &ValueData::Integer(_) | &ValueData::BigInt(_) => "integer",
+            &ValueData::Rational(_) => "rational",
+            &ValueData::Float(_) => "float",
+            &ValueData::Symbol(_) => "symbol",
+            &ValueData::Keyword(_) => "keyword",
+            &ValueData::String(_) => "string",
+            &ValueData::EmptyList => "empty list",
+            &ValueData::Pair(_) => "pair",
+            &ValueData::Vector(_) => "vector",
+            &ValueData::HashTable(_) => "hash table",
+            &ValueData::Port(_) => "port",
+            &ValueData::Eof => "eof object",
+            &ValueData::Environment(_) => "environment",
+            &ValueData::Condition(_) => "condition",
+            &ValueData::Continuation(_) => "continuation",
+            &ValueData::ComposableContinuation(_) => "composable continuation",
+            &ValueData::Promise(_) => "promise",
+            &ValueData::Values(_) => "values",
+            &ValueData::Parameter(_) => "parameter",
+            &ValueData::RecordType(_) => "record type",
+            &ValueData::Record(_) => "record",
+            &ValueData::Userdata(_) => "userdata",
+            &ValueData::NativeProc(_) => "native procedure",
+            &ValueData::HostProc(_) => "host procedure",
+            &ValueData::Proc(_) => "procedure",
+            &ValueData::SpecialForm(_) => "special form",
+        }
+    }
+
+    // the external representation, see Value::to_string and Value::to_display_string
+    pub fn print(&self, interner: &StringInterner, display: bool) -> String {
+        // the value being printed already made sure its elements need no labels
+        let print = |x: &Value| x.print_unlabeled(interner, display);
         match self {
             &ValueData::Bool(x) => format!("{}", x),
+            &ValueData::Char(x) if display => x.to_string(),
             &ValueData::Char(x) => {
                 if let Some(c) = escape_char(x) {
                     format!("#\\\\{}", c)
+                } else if x.is_whitespace() || x.is_control() {
+                    // everything else can be written directly, e.g. #\λ
+                    format!("#\\x{:x}", x as u32)
                 } else {
                     format!("#\\{}", x)
                 }
             },
             &ValueData::Integer(x) => format!("{}", x),
-            &ValueData::Symbol(id) => format!("{}", interner.lookup(id).unwrap_or(&format!("[SYMBOL: {}]", id.to_string()))),
-            &ValueData::String(ref x) => format!("\"{}\"", x),
-            &ValueData::Condition(ref x) => format!("[CONDITION: {:?}]", x),
+            &ValueData::BigInt(ref x) => format!("{}", x),
+            &ValueData::Rational(ref x) => format!("{}", x),
+            // debug formatting always prints a decimal point or an exponent, infinities and nan are written like in scheme
+            &ValueData::Float(x) if x.is_nan() => format!("+nan.0"),
+            &ValueData::Float(x) if x.is_infinite() => format!("{}inf.0", if x > 0.0 { "+" } else { "-" }),
+            &ValueData::Float(x) => format!("{:?}", x),
+            &ValueData::Symbol(id) => match interner.lookup(id) {
+                Some(name) if display => name.into(),
+                // written in pipes if it couldn't be read back otherwise, e.g. |hello world|
+                Some(name) => escape_symbol(name),
+                None => format!("[SYMBOL: {}]", id.to_string()),
+            },
+            &ValueData::Keyword(id) => format!("#:{}", interner.lookup(id).unwrap_or("?")),
+            &ValueData::String(ref x) if display => x.clone(),
+            &ValueData::String(ref x) => format!("\"{}\"", escape_string(x)),
             &ValueData::EmptyList => format!("()"),
-            &ValueData::List(ref values) => format!("({})", values.iter().map(|v| v.to_string(interner)).join(" ")),
-            &ValueData::NativeProc(x) => format!("[NATIVE_PROC: {:?}]", x),
+            &ValueData::Pair(ref pair) => {
+                // iterative, so long lists don't overflow the stack
+                let mut elements = vec![print(&pair.car())];
+                let mut tail = pair.cdr();
+                loop {
+                    let next = match tail.get_pair() {
+                        Some(pair) => {
+                            elements.push(print(&pair.car()));
+                            pair.cdr()
+                        },
+                        None => break,
+                    };
+                    tail = next;
+                }
+
+                if tail.get_empty_list().is_some() {
+                    format!("({})", elements.join(" "))
+                } else {
+                    format!("({} . {})", elements.join(" "), print(&tail))
+                }
+            },
+            &ValueData::Vector(ref values) => format!("#({})", values.read().unwrap().iter().map(print).join(" ")),
+            &ValueData::HashTable(ref table) => {
+                // sorted, so equal tables are always printed the same
+                let mut entries: Vec<String> = table.read().unwrap().entries()
+                .map(|(k, v)| format!("({} {})", k.print(interner, display), v.print(interner, display)))
+                .collect();
+                entries.sort();
+                format!("#hash({})", entries.iter().join(" "))
+            },
+            &ValueData::Port(ref port) => port.to_string(),
+            &ValueData::Eof => format!("[EOF]"),
+            &ValueData::Environment(_) => format!("[ENVIRONMENT]"),
+            &ValueData::Continuation(_) => format!("[CONTINUATION]"),
+            &ValueData::ComposableContinuation(_) => format!("[COMPOSABLE_CONTINUATION]"),
+            &ValueData::Promise(_) => format!("[PROMISE]"),
+            &ValueData::Values(ref values) => values.iter().map(|x| x.print(interner, display)).join(" "),
+            &ValueData::Parameter(_) => format!("[PARAMETER]"),
+            &ValueData::RecordType(ref t) => format!("[RECORD_TYPE: {}]", interner.lookup(t.name()).unwrap_or("?")),
+            &ValueData::Record(ref record) => {
+                // safe because records are only created with a record type
+                let record_type = record.record_type().get_record_type().unwrap();
+                let fields = record_type.fields().iter().zip(record.fields().read().unwrap().iter())
+                .map(|(&name, value)| format!(" {}: {}", interner.lookup(name).unwrap_or("?"), value.print(interner, display)))
+                .join("");
+                format!("[RECORD {}{}]", interner.lookup(record_type.name()).unwrap_or("?"), fields)
+            },
+            &ValueData::Condition(ref c) => format!("[{}: {}]", c.kind().name().to_uppercase(), c.to_string(interner)),
+            &ValueData::NativeProc(x) => format!("[NATIVE_PROC: {:#x}]", x),
+            &ValueData::HostProc(ref p) => format!("[HOST_PROC: {}]", p.name()),
+            &ValueData::Userdata(ref x) => format!("[USERDATA: {}]", x.type_name()),
             &ValueData::Proc(ref p) => format!("[PROC: {}]", p.to_string(interner)),
-            &ValueData::Recur(ref p) => format!("[RECUR: {}]", Value::new_list(&p).to_string(interner)),
-            &ValueData::SpecialForm(_) => unimplemented!(),
+            &ValueData::SpecialForm(ref s) => s.to_string(interner),
+        }
+    }
+}
+
+// structural, locked vectors and hash tables compare their contents
+impl PartialEq for ValueData {
+    fn eq(&self, other: &ValueData) -> bool {
+        match (self, other) {
+            (&ValueData::Bool(a), &ValueData::Bool(b)) => a == b,
+            (&ValueData::Char(a), &ValueData::Char(b)) => a == b,
+            (&ValueData::Integer(a), &ValueData::Integer(b)) => a == b,
+            (&ValueData::BigInt(ref a), &ValueData::BigInt(ref b)) => a == b,
+            (&ValueData::Rational(ref a), &ValueData::Rational(ref b)) => a == b,
+            (&ValueData::Float(a), &ValueData::Float(b)) => a == b,
+            (&ValueData::Symbol(a), &ValueData::Symbol(b)) => a == b,
+            (&ValueData::Keyword(a), &ValueData::Keyword(b)) => a == b,
+            (&ValueData::String(ref a), &ValueData::String(ref b)) => a == b,
+            (&ValueData::EmptyList, &ValueData::EmptyList) => true,
+            (&ValueData::Pair(ref a), &ValueData::Pair(ref b)) => a == b,
+            (&ValueData::Vector(ref a), &ValueData::Vector(ref b)) => {
+                // comparing a vector with itself would lock it twice
+                a as *const RwLock<Vec<Value>> == b as *const RwLock<Vec<Value>> || *a.read().unwrap() == *b.read().unwrap()
+            },
+            (&ValueData::HashTable(ref a), &ValueData::HashTable(ref b)) => {
+                a as *const RwLock<HashTable> == b as *const RwLock<HashTable> || *a.read().unwrap() == *b.read().unwrap()
+            },
+            (&ValueData::Port(ref a), &ValueData::Port(ref b)) => a == b,
+            (&ValueData::Eof, &ValueData::Eof) => true,
+            (&ValueData::Environment(ref a), &ValueData::Environment(ref b)) => a == b,
+            (&ValueData::Condition(ref a), &ValueData::Condition(ref b)) => a == b,
+            (&ValueData::Continuation(ref a), &ValueData::Continuation(ref b)) => a == b,
+            (&ValueData::ComposableContinuation(ref a), &ValueData::ComposableContinuation(ref b)) => a == b,
+            (&ValueData::Promise(ref a), &ValueData::Promise(ref b)) => a == b,
+            (&ValueData::Values(ref a), &ValueData::Values(ref b)) => a == b,
+            (&ValueData::Parameter(ref a), &ValueData::Parameter(ref b)) => a == b,
+            (&ValueData::RecordType(ref a), &ValueData::RecordType(ref b)) => a == b,
+            (&ValueData::Record(ref a), &ValueData::Record(ref b)) => a == b,
+            (&ValueData::Userdata(ref a), &ValueData::Userdata(ref b)) => a == b,
+            (&ValueData::NativeProc(a), &ValueData::NativeProc(b)) => a == b,
+            (&ValueData::HostProc(ref a), &ValueData::HostProc(ref b)) => a == b,
+            (&ValueData::Proc(ref a), &ValueData::Proc(ref b)) => a == b,
+            (&ValueData::SpecialForm(ref a), &ValueData::SpecialForm(ref b)) => a == b,
+            _ => false,
         }
     }
 }